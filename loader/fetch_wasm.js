@@ -0,0 +1,40 @@
+// Fetch of wasm module reporting download progress to the page as `wasm_loading` messages,
+// shared by loader scripts and bootstrap scripts host module and xtask put together.
+// Body goes through a stream counting bytes and is handed to wasm-bindgen as a response,
+// so module is still compiled while it downloads.
+// Content length is the compressed size when response is compressed, so total is unknown then.
+self.bevyFetchWasm = (url, report) =>
+  fetch(url).then((response) => {
+    if (!response.ok || !response.body) {
+      return response;
+    }
+
+    const length = Number(response.headers.get("content-length"));
+    const total = length && !response.headers.get("content-encoding") ? length : null;
+    const reader = response.body.getReader();
+    let received = 0;
+    let postedAt = 0;
+    const post = () => report({ kind: "wasm_loading", received, total });
+
+    const body = new ReadableStream({
+      pull(controller) {
+        return reader.read().then(({ done, value }) => {
+          if (done) {
+            post();
+            controller.close();
+            return;
+          }
+
+          received += value.length;
+          const now = performance.now();
+          if (now - postedAt > 100) {
+            postedAt = now;
+            post();
+          }
+          controller.enqueue(value);
+        });
+      },
+    });
+
+    return new Response(body, { headers: { "content-type": "application/wasm" } });
+  });
@@ -0,0 +1,28 @@
+// Worker bootstrap served as a static file, for sites which forbid `blob:` scripts.
+// Spawn it as a classic worker with artifact locations passed in query string:
+// `new Worker("worker_loader.js?js=bevy_worker.js&wasm=bevy_worker_bg.wasm&init=wasm_bindgen")`.
+// Expects `fetch_wasm.js` next to it.
+importScripts("fetch_wasm.js");
+
+const params = new URL(self.location.href).searchParams;
+const init = params.get("init") ?? "wasm_bindgen";
+
+// Picked up by the worker to spawn threads, see `worker::threads`.
+self.bevyWorkerArtifacts = { js: params.get("js"), init, module: false };
+
+// Shared worker has no page of its own: tabs connect over ports, which are kept for the wasm to take over,
+// see `worker::shared`. Until then, messages tabs post wait in their ports.
+const shared = typeof SharedWorkerGlobalScope !== "undefined" && self instanceof SharedWorkerGlobalScope;
+if (shared) {
+  self.bevySharedPorts = [];
+  self.onconnect = (event) => self.bevySharedPorts.push(event.ports[0]);
+}
+const postToPage = (msg) =>
+  shared ? self.bevySharedPorts.forEach((port) => port.postMessage(msg)) : postMessage(msg);
+
+importScripts(params.get("js"));
+
+// Default wasm-bindgen global is introduced by `let`, so it is not a property of `self`.
+// Custom globals are looked up on `self`, since `eval` is off limits under strict CSP.
+const initFn = init === "wasm_bindgen" ? wasm_bindgen : self[init];
+initFn(bevyFetchWasm(params.get("wasm"), postToPage)).catch((e) => postToPage({ kind: "error", message: String(e) }));
@@ -0,0 +1,25 @@
+// Worker bootstrap served as a static file, for sites which forbid `blob:` scripts.
+// Spawn it as a module worker with artifact locations passed in query string:
+// `new Worker("worker_loader_module.js?js=bevy_worker.js&wasm=bevy_worker_bg.wasm", { type: "module" })`.
+// Expects `fetch_wasm.js` next to it.
+import "./fetch_wasm.js";
+
+const params = new URL(self.location.href).searchParams;
+const init = params.get("init") ?? "default";
+
+// Picked up by the worker to spawn threads, see `worker::threads`.
+self.bevyWorkerArtifacts = { js: params.get("js"), init, module: true };
+
+// Shared worker has no page of its own: tabs connect over ports, which are kept for the wasm to take over,
+// see `worker::shared`. Until then, messages tabs post wait in their ports.
+const shared = typeof SharedWorkerGlobalScope !== "undefined" && self instanceof SharedWorkerGlobalScope;
+if (shared) {
+  self.bevySharedPorts = [];
+  self.onconnect = (event) => self.bevySharedPorts.push(event.ports[0]);
+}
+const postToPage = (msg) =>
+  shared ? self.bevySharedPorts.forEach((port) => port.postMessage(msg)) : postMessage(msg);
+
+import(params.get("js"))
+  .then((m) => m[init](bevyFetchWasm(params.get("wasm"), postToPage)))
+  .catch((e) => postToPage({ kind: "error", message: String(e) }));
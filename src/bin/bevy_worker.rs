@@ -1,104 +1,65 @@
-use bevy::app::PluginGroupBuilder;
 use bevy::prelude::*;
-use bevy::window::WebElement;
-use web_sys::OffscreenCanvas;
-
-/// Query primary window and set up the handle to it so rendering can pick it up.
-///
-/// Normally this job is done by WinitPlugin, however it is hopelessly broken for web workers.
-/// We definitely don't do everything that we need to, but this is enough to get us rendering.
-///
-/// Notably it doesn't properly communicate viewport size to bevy.
-/// Currently it works because both sides use hardcoded 1280x720.
-#[derive(Default)]
-pub struct RegisterPrimaryWindow;
-
-impl Plugin for RegisterPrimaryWindow {
-    fn build(&self, app: &mut App) {
-        use bevy::ecs::system::SystemState;
-        use bevy::window::{AbstractHandleWrapper, PrimaryWindow};
-
-        #[allow(clippy::type_complexity)]
-        let mut system_state: SystemState<(
-            Commands,
-            Query<(Entity, &Window, With<PrimaryWindow>)>,
-        )> = SystemState::from_world(&mut app.world);
-        let (mut commands, query) = system_state.get_mut(&mut app.world);
-
-        let (entity, window, _) = query.get_single().unwrap();
-
-        let handle: AbstractHandleWrapper = {
-            use bevy::window::WebHandle;
-
-            let web_handle = match &window.web_element {
-                WebElement::OffscreenCanvas(canvas) => WebHandle::OffscreenCanvas(canvas.clone()),
-                // Ignore other options.
-                _ => unreachable!(),
-            };
-
-            AbstractHandleWrapper::WebHandle(web_handle)
-        };
+use bevy_webworker_test::worker::asset_cache::AssetCacheSettings;
+use bevy_webworker_test::worker::config::ConfigPlugin;
+use bevy_webworker_test::worker::console::ConsoleCommands;
+use bevy_webworker_test::worker::gestures::{GesturePlugin, Pinch, Rotate};
+use bevy_webworker_test::worker::inmem::InMemoryAssetPlugin;
+use bevy_webworker_test::worker::latency::LatencyTestPlugin;
+use bevy_webworker_test::worker::media::MediaPreferences;
+use bevy_webworker_test::worker::settings::SettingsPlugin;
+use bevy_webworker_test::worker::{CanvasPlugins, DefaultPlugins};
+use serde::{Deserialize, Serialize};
+
+/// Tuning values from `shapes` section of config file.
+#[derive(Resource, Deserialize)]
+struct ShapesConfig {
+    /// Radians per second.
+    spin_speed: f32,
+}
 
-        commands.entity(entity).insert(handle);
-        system_state.apply(&mut app.world);
+impl Default for ShapesConfig {
+    fn default() -> Self {
+        ShapesConfig { spin_speed: 0.5 }
     }
 }
 
-/// Refreshed version of Bevy's default plugins, now with web-worker flavor.
-///
-/// Note: it isn't a faithful recreation of `DefaultPlugins` with all configs, it just works here.
-pub struct DefaultPlugins {
-    primary_window: WebElement,
+#[derive(Component)]
+struct Spin;
+
+/// Colors of a shape for light and dark color scheme.
+#[derive(Component)]
+struct Themed {
+    light: Color,
+    dark: Color,
 }
 
-impl PluginGroup for DefaultPlugins {
-    fn build(self) -> PluginGroupBuilder {
-        use bevy::a11y::AccessibilityPlugin;
-        use bevy::app::ScheduleRunnerPlugin;
-        use bevy::core_pipeline::CorePipelinePlugin;
-        use bevy::diagnostic::DiagnosticsPlugin;
-        use bevy::input::InputPlugin;
-        use bevy::log::LogPlugin;
-        use bevy::render::RenderPlugin;
-        use bevy::sprite::SpritePlugin;
-        use bevy::time::TimePlugin;
-
-        let window_plugin = {
-            let primary_window = Window {
-                web_element: self.primary_window,
-                ..Window::default()
-            };
-
-            let primary_window = Some(primary_window);
-
-            WindowPlugin {
-                primary_window,
-                ..WindowPlugin::default()
-            }
-        };
+impl Themed {
+    fn new(light: Color, dark: Color) -> Self {
+        Themed { light, dark }
+    }
+}
 
-        PluginGroupBuilder::start::<Self>()
-            .add(LogPlugin::default())
-            .add(TaskPoolPlugin::default())
-            .add(TypeRegistrationPlugin::default())
-            .add(TimePlugin::default())
-            .add(FrameCountPlugin::default())
-            .add(TransformPlugin::default())
-            .add(HierarchyPlugin::default())
-            .add(DiagnosticsPlugin::default())
-            .add(InputPlugin::default())
-            .add(window_plugin)
-            .add(AccessibilityPlugin)
-            .add(RegisterPrimaryWindow::default())
-            .add(AssetPlugin::default())
-            .add(RenderPlugin::default())
-            .add(ImagePlugin::default())
-            .add(CorePipelinePlugin)
-            .add(SpritePlugin::default())
-            .add(ScheduleRunnerPlugin::default())
+/// How much camera still shakes after button press, fades out over a fraction of a second.
+#[derive(Resource, Default)]
+struct CameraShake(f32);
+
+/// Which way shapes spin, flipped by the button and remembered across visits.
+#[derive(Resource, Serialize, Deserialize)]
+struct SpinDirection(f32);
+
+impl Default for SpinDirection {
+    fn default() -> Self {
+        SpinDirection(1.0)
     }
 }
 
+const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
+const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
+
+const LIGHT_BACKGROUND: Color = Color::rgb(0.92, 0.92, 0.95);
+const DARK_BACKGROUND: Color = Color::rgb(0.08, 0.08, 0.1);
+
 fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -109,75 +70,241 @@ fn setup(
     commands.spawn(Camera2dBundle::default());
 
     // Circle
-    commands.spawn(MaterialMesh2dBundle {
-        mesh: meshes.add(shape::Circle::new(50.).into()).into(),
-        material: materials.add(ColorMaterial::from(Color::PURPLE)),
-        transform: Transform::from_translation(Vec3::new(-150., 0., 0.)),
-        ..default()
-    });
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(shape::Circle::new(50.).into()).into(),
+            material: materials.add(ColorMaterial::from(Color::PURPLE)),
+            transform: Transform::from_translation(Vec3::new(-150., 0., 0.)),
+            ..default()
+        },
+        Spin,
+        Themed::new(Color::PURPLE, Color::rgb(0.75, 0.45, 0.95)),
+    ));
 
     // Rectangle
-    commands.spawn(SpriteBundle {
-        sprite: Sprite {
-            color: Color::rgb(0.25, 0.25, 0.75),
-            custom_size: Some(Vec2::new(50.0, 100.0)),
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgb(0.25, 0.25, 0.75),
+                custom_size: Some(Vec2::new(50.0, 100.0)),
+                ..default()
+            },
+            transform: Transform::from_translation(Vec3::new(-50., 0., 0.)),
             ..default()
         },
-        transform: Transform::from_translation(Vec3::new(-50., 0., 0.)),
-        ..default()
-    });
+        Spin,
+        Themed::new(Color::rgb(0.25, 0.25, 0.75), Color::rgb(0.45, 0.55, 1.0)),
+    ));
 
     // Quad
-    commands.spawn(MaterialMesh2dBundle {
-        mesh: meshes
-            .add(shape::Quad::new(Vec2::new(50., 100.)).into())
-            .into(),
-        material: materials.add(ColorMaterial::from(Color::LIME_GREEN)),
-        transform: Transform::from_translation(Vec3::new(50., 0., 0.)),
-        ..default()
-    });
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes
+                .add(shape::Quad::new(Vec2::new(50., 100.)).into())
+                .into(),
+            material: materials.add(ColorMaterial::from(Color::LIME_GREEN)),
+            transform: Transform::from_translation(Vec3::new(50., 0., 0.)),
+            ..default()
+        },
+        Spin,
+        Themed::new(Color::rgb(0.1, 0.6, 0.1), Color::LIME_GREEN),
+    ));
 
     // Hexagon
-    commands.spawn(MaterialMesh2dBundle {
-        mesh: meshes.add(shape::RegularPolygon::new(50., 6).into()).into(),
-        material: materials.add(ColorMaterial::from(Color::TURQUOISE)),
-        transform: Transform::from_translation(Vec3::new(150., 0., 0.)),
-        ..default()
-    });
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(shape::RegularPolygon::new(50., 6).into()).into(),
+            material: materials.add(ColorMaterial::from(Color::TURQUOISE)),
+            transform: Transform::from_translation(Vec3::new(150., 0., 0.)),
+            ..default()
+        },
+        Spin,
+        Themed::new(Color::rgb(0.0, 0.55, 0.55), Color::TURQUOISE),
+    ));
 }
 
-fn single_pass(canvas: OffscreenCanvas) {
-    use bevy::app::ScheduleRunnerSettings;
-
-    App::new()
-        .insert_resource(ScheduleRunnerSettings::run_once())
-        .add_plugins(DefaultPlugins {
-            primary_window: WebElement::OffscreenCanvas(canvas),
+fn setup_ui(mut commands: Commands) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                padding: UiRect::all(Val::Px(16.0)),
+                ..default()
+            },
+            ..default()
         })
-        .add_systems(Startup, setup)
-        .run();
+        .with_children(|parent| {
+            parent
+                .spawn(ButtonBundle {
+                    style: Style {
+                        padding: UiRect::axes(Val::Px(16.0), Val::Px(8.0)),
+                        ..default()
+                    },
+                    background_color: NORMAL_BUTTON.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Reverse",
+                        TextStyle {
+                            font_size: 24.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                });
+        });
+}
+
+fn button_system(
+    mut buttons: Query<(&Interaction, &mut BackgroundColor), (Changed<Interaction>, With<Button>)>,
+    mut direction: ResMut<SpinDirection>,
+    mut shake: ResMut<CameraShake>,
+) {
+    for (interaction, mut color) in &mut buttons {
+        *color = match interaction {
+            Interaction::Pressed => {
+                direction.0 = -direction.0;
+                shake.0 = 1.0;
+                PRESSED_BUTTON.into()
+            }
+            Interaction::Hovered => HOVERED_BUTTON.into(),
+            Interaction::None => NORMAL_BUTTON.into(),
+        };
+    }
+}
+
+fn spin(
+    mut shapes: Query<&mut Transform, With<Spin>>,
+    config: Res<ShapesConfig>,
+    direction: Res<SpinDirection>,
+    time: Res<Time>,
+) {
+    for mut transform in &mut shapes {
+        transform.rotate_z(direction.0 * config.spin_speed * time.delta_seconds());
+    }
+}
+
+/// Switch palette to match user's color scheme.
+fn apply_theme(
+    preferences: Res<MediaPreferences>,
+    mut clear_color: ResMut<ClearColor>,
+    mut shapes: Query<(&Themed, Option<&Handle<ColorMaterial>>, Option<&mut Sprite>)>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    if !preferences.is_changed() {
+        return;
+    }
+
+    let pick = |themed: &Themed| {
+        if preferences.dark {
+            themed.dark
+        } else {
+            themed.light
+        }
+    };
+    clear_color.0 = if preferences.dark {
+        DARK_BACKGROUND
+    } else {
+        LIGHT_BACKGROUND
+    };
+
+    for (themed, material, sprite) in &mut shapes {
+        if let Some(material) = material.and_then(|material| materials.get_mut(material)) {
+            material.color = pick(themed);
+        }
+        if let Some(mut sprite) = sprite {
+            sprite.color = pick(themed);
+        }
+    }
+}
+
+/// Jolt the camera on button press, unless user asked for reduced motion.
+fn shake_camera(
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+    mut shake: ResMut<CameraShake>,
+    preferences: Res<MediaPreferences>,
+    time: Res<Time>,
+    // Offset applied last frame, taken back before the next one so camera keeps its own position.
+    mut applied: Local<Vec2>,
+) {
+    if preferences.reduced_motion {
+        shake.0 = 0.0;
+    }
+
+    shake.0 = (shake.0 - 3.0 * time.delta_seconds()).max(0.0);
+    let t = time.elapsed_seconds();
+    let amplitude = 12.0 * shake.0 * shake.0;
+    let offset = Vec2::new((t * 83.0).sin(), (t * 67.0).cos()) * amplitude;
+
+    if offset == *applied {
+        return;
+    }
+
+    for mut transform in &mut cameras {
+        transform.translation += (offset - *applied).extend(0.0);
+    }
+    *applied = offset;
+}
+
+/// Zoom and turn the camera with two fingers.
+fn pinch_camera(
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<Camera2d>>,
+    mut pinches: EventReader<Pinch>,
+    mut rotations: EventReader<Rotate>,
+) {
+    let scale: f32 = pinches.iter().map(|pinch| pinch.scale).product();
+    let angle: f32 = rotations.iter().map(|rotate| rotate.angle).sum();
+
+    for (mut transform, mut projection) in &mut cameras {
+        // Spreading fingers brings the scene closer.
+        projection.scale = (projection.scale / scale).clamp(0.25, 4.0);
+        // Scene follows the fingers, so camera turns the other way.
+        transform.rotate_z(-angle);
+    }
+}
+
+fn register_commands(mut console: ResMut<ConsoleCommands>) {
+    console.register(
+        "spin",
+        "spin [speed]: show or set spin speed in radians per second",
+        |world, args| {
+            let mut config = world.resource_mut::<ShapesConfig>();
+            if let Some(speed) = args.optional(0, "speed")? {
+                config.spin_speed = speed;
+            }
+
+            Ok(format!("spin speed is {}", config.spin_speed))
+        },
+    );
 }
 
-// Adapted from https://github.com/thedodd/trunk/blob/master/examples/webworker/src/bin/worker.rs
 fn main() {
-    use js_sys::Array;
-    use wasm_bindgen::prelude::{Closure, JsCast, JsValue};
-    use web_sys::{DedicatedWorkerGlobalScope, MessageEvent};
-
-    let scope = DedicatedWorkerGlobalScope::from(JsValue::from(js_sys::global()));
-
-    let onmessage = Closure::wrap(Box::new(move |msg: MessageEvent| {
-        let offscreen_canvas = msg
-            .data()
-            .dyn_into::<OffscreenCanvas>()
-            .expect("message must be an OffscreenCanvas");
-        single_pass(offscreen_canvas);
-    }) as Box<dyn Fn(MessageEvent)>);
-    scope.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
-    onmessage.forget();
-
-    // The worker must send a message to indicate that it's ready to receive messages.
-    scope
-        .post_message(&Array::new().into())
-        .expect("posting ready message succeeds");
+    // App is built while page is still busy, only rendering waits for the canvas.
+    bevy_webworker_test::worker::start_warm(
+        |_| {
+            let mut app = App::new();
+
+            app.add_plugins(DefaultPlugins::headless().set(InMemoryAssetPlugin {
+                cache: Some(AssetCacheSettings::default()),
+                ..default()
+            }))
+            .add_plugins(ConfigPlugin::default().section::<ShapesConfig>("shapes"))
+            .add_plugins(SettingsPlugin::default().settings::<SpinDirection>("spin_direction"))
+            .init_resource::<CameraShake>()
+            .add_systems(Startup, (setup, setup_ui, register_commands))
+            .add_systems(Update, (button_system, spin));
+
+            app
+        },
+        |_, mut app, canvas| {
+            // Colors and camera only exist once there is something to render to.
+            app.add_plugins(CanvasPlugins::new(canvas))
+                .add_plugins((LatencyTestPlugin, GesturePlugin::default()))
+                .add_systems(Update, (apply_theme, shake_camera, pinch_camera));
+
+            app.run();
+        },
+    );
 }
@@ -0,0 +1,258 @@
+//! Debug dashboard, shown instead of the app when page is opened at `/debug`.
+//!
+//! Watches every worker of this origin through the channel `worker::dashboard` publishes on,
+//! so it can sit in a separate tab next to the app.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use bevy_webworker_test::host::SpawnError;
+use js_sys::{Object, Reflect};
+use serde_json::Value;
+use wasm_bindgen::prelude::{Closure, JsCast, JsValue};
+use web_sys::{BroadcastChannel, Document, HtmlElement, MessageEvent};
+
+const CHANNEL: &str = "bevy-worker-dashboard";
+/// Workers have to be reminded someone is watching, see `SUBSCRIPTION_MS` on worker side.
+const PING_MS: i32 = 1000;
+/// Worker is considered gone after not publishing for this long.
+const STALE_MS: f64 = 5000.0;
+
+const PANEL_STYLE: &str = "margin: 8px; padding: 8px; background: #1e1e1e; color: #ddd; \
+    font: 12px monospace; white-space: pre;";
+
+/// Whether page was opened as the dashboard.
+///
+/// Servers without SPA fallback don't route `/debug` to the page, `?debug` works there instead.
+pub fn is_requested() -> bool {
+    let Some(location) = web_sys::window().map(|window| window.location()) else {
+        return false;
+    };
+
+    let path = location.pathname().unwrap_or_default();
+    let search = location.search().unwrap_or_default();
+
+    path.trim_end_matches('/').ends_with("/debug")
+        || search
+            .trim_start_matches('?')
+            .split('&')
+            .any(|param| param == "debug")
+}
+
+/// Show live stats of every worker publishing on the dashboard channel.
+pub fn run() -> Result<(), SpawnError> {
+    let window = web_sys::window().ok_or(SpawnError::NoWindow)?;
+    let document = window.document().ok_or(SpawnError::NoWindow)?;
+    let body = document.body().ok_or(SpawnError::NoWindow)?;
+
+    let status = create_panel(&document)?;
+    status.set_text_content(Some("Waiting for workers..."));
+    body.append_child(&status).map_err(SpawnError::Dom)?;
+
+    let channel = BroadcastChannel::new(CHANNEL).map_err(SpawnError::Dom)?;
+    let dashboard = Rc::new(Dashboard {
+        document,
+        channel: channel.clone(),
+        status,
+        workers: RefCell::new(BTreeMap::new()),
+    });
+
+    let onmessage = {
+        let dashboard = Rc::clone(&dashboard);
+
+        Closure::wrap(Box::new(move |event: MessageEvent| {
+            let message = event
+                .data()
+                .as_string()
+                .and_then(|data| serde_json::from_str(&data).ok());
+
+            if let Some(message) = message {
+                if let Err(err) = dashboard.receive(message) {
+                    web_sys::console::warn_1(&err);
+                }
+            }
+        }) as Box<dyn Fn(MessageEvent)>)
+    };
+    channel.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+    onmessage.forget();
+
+    let ping = {
+        let dashboard = Rc::clone(&dashboard);
+
+        Closure::wrap(Box::new(move || dashboard.ping()) as Box<dyn Fn()>)
+    };
+    dashboard.ping();
+    window
+        .set_interval_with_callback_and_timeout_and_arguments_0(
+            ping.as_ref().unchecked_ref(),
+            PING_MS,
+        )
+        .map_err(SpawnError::Dom)?;
+    ping.forget();
+
+    Ok(())
+}
+
+struct Dashboard {
+    document: Document,
+    channel: BroadcastChannel,
+    status: HtmlElement,
+    workers: RefCell<BTreeMap<String, WorkerPanel>>,
+}
+
+struct WorkerPanel {
+    element: HtmlElement,
+    seen_at: f64,
+    snapshot: Option<Value>,
+    schedules: Option<Value>,
+}
+
+impl Dashboard {
+    fn receive(&self, message: Value) -> Result<(), JsValue> {
+        let Some(id) = message["worker"].as_str().map(str::to_owned) else {
+            return Ok(());
+        };
+
+        let mut workers = self.workers.borrow_mut();
+
+        if !workers.contains_key(&id) {
+            let element =
+                create_panel(&self.document).map_err(|err| JsValue::from_str(&err.to_string()))?;
+            self.document
+                .body()
+                .ok_or("page has no body")?
+                .append_child(&element)?;
+
+            workers.insert(
+                id.clone(),
+                WorkerPanel {
+                    element,
+                    seen_at: 0.0,
+                    snapshot: None,
+                    schedules: None,
+                },
+            );
+
+            // Schedules rarely change, so they are only sent on request.
+            self.send("request_schedules")?;
+        }
+
+        let Some(panel) = workers.get_mut(&id) else {
+            return Ok(());
+        };
+        panel.seen_at = js_sys::Date::now();
+
+        match message["kind"].as_str() {
+            Some("snapshot") => panel.snapshot = Some(message),
+            Some("schedules") => panel.schedules = Some(message),
+            _ => return Ok(()),
+        }
+
+        panel.element.set_text_content(Some(&panel.render(&id)));
+        self.status
+            .set_text_content(Some(&format!("Watching {} worker(s)", workers.len())));
+
+        Ok(())
+    }
+
+    fn ping(&self) {
+        if let Err(err) = self.send("subscribe") {
+            web_sys::console::warn_1(&err);
+        }
+
+        let now = js_sys::Date::now();
+        for panel in self.workers.borrow().values() {
+            let stale = now - panel.seen_at > STALE_MS;
+            let _ = panel
+                .element
+                .style()
+                .set_property("opacity", if stale { "0.4" } else { "1" });
+        }
+    }
+
+    fn send(&self, kind: &str) -> Result<(), JsValue> {
+        let message = Object::new();
+        Reflect::set(&message, &"kind".into(), &kind.into())?;
+        self.channel.post_message(&message)
+    }
+}
+
+impl WorkerPanel {
+    fn render(&self, id: &str) -> String {
+        let mut text = String::new();
+
+        // Writing into a string cannot fail.
+        let _ = self.render_into(id, &mut text);
+        text
+    }
+
+    fn render_into(&self, id: &str, text: &mut String) -> std::fmt::Result {
+        writeln!(text, "worker {id}")?;
+
+        if let Some(snapshot) = &self.snapshot {
+            writeln!(
+                text,
+                "frame {}, entities {}, archetypes {}, components {}",
+                snapshot["frame"],
+                snapshot["entities"],
+                snapshot["archetypes"],
+                snapshot["components"],
+            )?;
+
+            writeln!(text, "\ndiagnostics")?;
+            for diagnostic in snapshot["diagnostics"].as_array().into_iter().flatten() {
+                writeln!(
+                    text,
+                    "  {:<40} {:>10} {}",
+                    diagnostic["name"].as_str().unwrap_or_default(),
+                    number(&diagnostic["average"]),
+                    diagnostic["suffix"].as_str().unwrap_or_default(),
+                )?;
+            }
+
+            writeln!(text, "\nbridge traffic, latest first")?;
+            let traffic = snapshot["traffic"].as_array().into_iter().flatten();
+            for record in traffic.rev().take(16) {
+                writeln!(
+                    text,
+                    "  {:<8} {:<24} #{}",
+                    record["direction"].as_str().unwrap_or_default(),
+                    record["kind"].as_str().unwrap_or_default(),
+                    record["correlation_id"],
+                )?;
+            }
+        }
+
+        if let Some(schedules) = &self.schedules {
+            writeln!(text, "\nschedules")?;
+            for schedule in schedules["schedules"].as_array().into_iter().flatten() {
+                writeln!(text, "  {}", schedule["name"].as_str().unwrap_or_default())?;
+                for system in schedule["systems"].as_array().into_iter().flatten() {
+                    writeln!(text, "    {}", system.as_str().unwrap_or_default())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn number(value: &Value) -> String {
+    match value.as_f64() {
+        Some(value) => format!("{value:.2}"),
+        None => "-".to_owned(),
+    }
+}
+
+fn create_panel(document: &Document) -> Result<HtmlElement, SpawnError> {
+    let element: HtmlElement = document
+        .create_element("div")
+        .map_err(SpawnError::Dom)?
+        .dyn_into()
+        .map_err(|element| SpawnError::Dom(element.into()))?;
+    element.style().set_css_text(PANEL_STYLE);
+
+    Ok(element)
+}
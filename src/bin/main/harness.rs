@@ -0,0 +1,247 @@
+//! Page scaffolding around the worker canvas.
+
+use std::cell::RefCell;
+use std::fmt::Display;
+
+use bevy_webworker_test::host::accessibility::AccessibilityMirror;
+use bevy_webworker_test::host::SpawnError;
+use wasm_bindgen::JsCast;
+use web_sys::{Document, Element, HtmlCanvasElement, HtmlElement};
+
+const CONTAINER_STYLE: &str = "position: relative; display: inline-block;";
+const LOADING_STYLE: &str = "position: absolute; inset: 0; display: flex; \
+    align-items: center; justify-content: center; color: white; font: 24px sans-serif;";
+const ERROR_STYLE: &str = "position: absolute; inset: 0; display: none; padding: 16px; \
+    background: rgba(64, 0, 0, 0.85); color: white; font: 16px monospace; white-space: pre-wrap;";
+const STATS_STYLE: &str = "position: absolute; top: 4px; left: 4px; padding: 4px; \
+    background: rgba(0, 0, 0, 0.5); color: white; font: 12px monospace; white-space: pre;";
+
+/// Description of the page hosting the worker.
+///
+/// Everything is laid over the canvas inside one container,
+/// so the page itself only needs a spot to mount it.
+pub struct PageHarness {
+    mount: Option<String>,
+    width: u32,
+    height: u32,
+    loading: Option<String>,
+    error_overlay: bool,
+    stats_panel: bool,
+}
+
+impl PageHarness {
+    pub fn new() -> Self {
+        PageHarness {
+            mount: None,
+            // Bevy expects viewport of this size.
+            width: 1280,
+            height: 720,
+            loading: None,
+            error_overlay: false,
+            stats_panel: false,
+        }
+    }
+
+    /// Mount into element matching CSS selector instead of document body.
+    pub fn mount(mut self, selector: &str) -> Self {
+        self.mount = Some(selector.to_owned());
+        self
+    }
+
+    /// Show text over the canvas, hand [`Page::loading`] to `WorkerBuilder::splash`
+    /// to have it fade out on the first frame.
+    pub fn loading(mut self, text: &str) -> Self {
+        self.loading = Some(text.to_owned());
+        self
+    }
+
+    /// Show errors over the canvas instead of logging them.
+    pub fn error_overlay(mut self) -> Self {
+        self.error_overlay = true;
+        self
+    }
+
+    /// Small text panel in the corner.
+    pub fn stats_panel(mut self) -> Self {
+        self.stats_panel = true;
+        self
+    }
+
+    /// Create page elements.
+    pub fn build(self) -> Result<Page, SpawnError> {
+        let PageHarness {
+            mount,
+            width,
+            height,
+            loading,
+            error_overlay,
+            stats_panel,
+        } = self;
+
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or(SpawnError::NoWindow)?;
+
+        let parent: Element = match mount {
+            Some(selector) => document
+                .query_selector(&selector)
+                .map_err(SpawnError::Dom)?
+                .ok_or_else(|| SpawnError::Dom(format!("no element matches {selector}").into()))?,
+            None => document.body().ok_or(SpawnError::NoWindow)?.into(),
+        };
+
+        let container = create_element(&document, "div", CONTAINER_STYLE)?;
+        parent.append_child(&container).map_err(SpawnError::Dom)?;
+
+        let canvas = create_canvas(&document, width, height)?;
+        container.append_child(&canvas).map_err(SpawnError::Dom)?;
+
+        let loading = loading
+            .map(|text| {
+                let loading = create_element(&document, "div", LOADING_STYLE)?;
+                loading.set_text_content(Some(&text));
+                container.append_child(&loading).map_err(SpawnError::Dom)?;
+
+                Ok::<_, SpawnError>(loading)
+            })
+            .transpose()?;
+
+        let error = error_overlay
+            .then(|| {
+                let error = create_element(&document, "div", ERROR_STYLE)?;
+                container.append_child(&error).map_err(SpawnError::Dom)?;
+
+                Ok::<_, SpawnError>(error)
+            })
+            .transpose()?;
+
+        let stats = stats_panel
+            .then(|| {
+                let stats = create_element(&document, "div", STATS_STYLE)?;
+                container.append_child(&stats).map_err(SpawnError::Dom)?;
+
+                Ok::<_, SpawnError>(stats)
+            })
+            .transpose()?;
+
+        Ok(Page {
+            document,
+            width,
+            height,
+            container,
+            canvas: RefCell::new(canvas),
+            loading,
+            error,
+            stats,
+        })
+    }
+}
+
+impl Default for PageHarness {
+    fn default() -> Self {
+        PageHarness::new()
+    }
+}
+
+/// Page elements built by [`PageHarness`].
+pub struct Page {
+    document: Document,
+    width: u32,
+    height: u32,
+    container: HtmlElement,
+    canvas: RefCell<HtmlCanvasElement>,
+    loading: Option<HtmlElement>,
+    error: Option<HtmlElement>,
+    stats: Option<HtmlElement>,
+}
+
+impl Page {
+    /// Element holding canvas and everything laid over it.
+    pub fn container(&self) -> &HtmlElement {
+        &self.container
+    }
+
+    pub fn canvas(&self) -> HtmlCanvasElement {
+        self.canvas.borrow().clone()
+    }
+
+    /// Swap canvas for a fresh one in the same spot.
+    ///
+    /// Canvas which lost its context is of no use, and it cannot be transferred to worker twice anyway.
+    pub fn replace_canvas(&self) -> Result<HtmlCanvasElement, SpawnError> {
+        let canvas = create_canvas(&self.document, self.width, self.height)?;
+        self.canvas
+            .borrow()
+            .replace_with_with_node_1(&canvas)
+            .map_err(SpawnError::Dom)?;
+        *self.canvas.borrow_mut() = canvas.clone();
+
+        Ok(canvas)
+    }
+
+    /// Mirror accessibility tree on top of the canvas.
+    pub fn accessibility_mirror(&self) -> Result<AccessibilityMirror, SpawnError> {
+        AccessibilityMirror::new(&self.container)
+    }
+
+    /// Loading text element, if harness has one.
+    pub fn loading(&self) -> Option<&HtmlElement> {
+        self.loading.as_ref()
+    }
+
+    /// Replace loading text.
+    pub fn set_loading(&self, text: &str) {
+        if let Some(loading) = &self.loading {
+            loading.set_text_content(Some(text));
+        }
+    }
+
+    /// Show error over the canvas.
+    ///
+    /// Falls back to console when error overlay is disabled.
+    pub fn show_error(&self, err: &dyn Display) {
+        let message = err.to_string();
+
+        match &self.error {
+            Some(error) => {
+                error.set_text_content(Some(&message));
+                let _ = error.style().set_property("display", "block");
+            }
+            None => web_sys::console::error_1(&message.into()),
+        }
+    }
+
+    /// Replace contents of stats panel.
+    pub fn set_stats(&self, text: &str) {
+        if let Some(stats) = &self.stats {
+            stats.set_text_content(Some(text));
+        }
+    }
+}
+
+fn create_element(document: &Document, tag: &str, style: &str) -> Result<HtmlElement, SpawnError> {
+    let element: HtmlElement = document
+        .create_element(tag)
+        .map_err(SpawnError::Dom)?
+        .dyn_into()
+        .map_err(|element| SpawnError::Dom(element.into()))?;
+    element.style().set_css_text(style);
+
+    Ok(element)
+}
+
+fn create_canvas(
+    document: &Document,
+    width: u32,
+    height: u32,
+) -> Result<HtmlCanvasElement, SpawnError> {
+    let element = document.create_element("canvas").map_err(SpawnError::Dom)?;
+    let canvas: HtmlCanvasElement = element
+        .dyn_into()
+        .map_err(|element| SpawnError::Dom(element.into()))?;
+    canvas.set_width(width);
+    canvas.set_height(height);
+    canvas.style().set_css_text("display: block;");
+
+    Ok(canvas)
+}
@@ -0,0 +1,105 @@
+use std::rc::Rc;
+
+use bevy_webworker_test::host::splash::SplashOptions;
+use bevy_webworker_test::host::{SpawnError, WorkerBuilder};
+
+mod dashboard;
+mod harness;
+mod smoke;
+
+use harness::PageHarness;
+
+fn run() -> Result<(), SpawnError> {
+    let page = PageHarness::new()
+        .mount("#app")
+        .loading("Loading...")
+        .error_overlay()
+        .stats_panel()
+        .build()?;
+    let page = Rc::new(page);
+
+    // Loading text stays up until canvas shows the first frame.
+    let mut builder = WorkerBuilder::new("bevy_worker")?;
+    if let Some(loading) = page.loading() {
+        builder = builder.splash(loading, SplashOptions::default());
+    }
+
+    let worker = builder
+        .accessibility_mirror(page.accessibility_mirror()?)
+        .ui_scale_from_preferences()?
+        .boot_flags_from_query()?
+        .on_wasm_loading({
+            let page = Rc::clone(&page);
+            move |received, total| {
+                let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+                let text = match total {
+                    Some(total) => {
+                        format!("Loading... {:.1} / {:.1} MiB", mib(received), mib(total))
+                    }
+                    None => format!("Loading... {:.1} MiB", mib(received)),
+                };
+                page.set_loading(&text);
+            }
+        })
+        .on_ready({
+            let page = Rc::clone(&page);
+            move |_| {
+                page.set_stats("worker: running");
+            }
+        })
+        .on_error({
+            let page = Rc::clone(&page);
+            move |err| page.show_error(err)
+        })
+        .on_device_lost({
+            let page = Rc::clone(&page);
+            move |worker| {
+                page.set_stats("worker: restarting after device loss");
+
+                let result = page
+                    .replace_canvas()
+                    .and_then(|canvas| worker.attach(&canvas));
+
+                if let Err(err) = result {
+                    page.show_error(&err);
+                }
+            }
+        })
+        .spawn()?;
+    worker.attach(&page.canvas())?;
+    worker.expose_to_console("bevy")?;
+    worker.show_frame_stats(page.container())?;
+
+    Ok(())
+}
+
+/// Let user know that something went wrong.
+///
+/// Used when there is no harness to show the error in.
+/// Falls back to console when there is no page to show the message on.
+fn show_error(err: &SpawnError) {
+    let message = format!("Failed to start: {err}");
+
+    let body = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.body());
+
+    match body {
+        Some(body) => body.set_text_content(Some(&message)),
+        None => web_sys::console::error_1(&message.into()),
+    }
+}
+
+fn main() {
+    let result = if dashboard::is_requested() {
+        dashboard::run()
+    } else if smoke::is_requested() {
+        smoke::run()
+    } else {
+        run()
+    };
+
+    if let Err(err) = result {
+        show_error(&err);
+    }
+}
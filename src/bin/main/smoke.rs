@@ -0,0 +1,208 @@
+//! Smoke test of the whole bootstrap, run instead of the app when page is opened with `?smoke`.
+//!
+//! Probes what the browser supports, spawns the worker, attaches a canvas and waits for the first frame
+//! to be presented and for a frame to handle page's input. Outcome is posted as JSON to `/report` on the serving origin,
+//! which is what `cargo xtask smoke` listens for, and shown on the page as well.
+//!
+//! Browsers without `OffscreenCanvas` (Safari before 16.4) can't run the worker at all,
+//! they are reported as taking the fallback path rather than failing.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use bevy_webworker_test::host::{SpawnError, WorkerBuilder, WorkerHandle};
+use bevy_webworker_test::protocol::{Capabilities, HostMessage, ViewId};
+use js_sys::Reflect;
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::{Closure, JsCast, JsValue};
+use web_sys::{HtmlCanvasElement, RequestInit};
+
+/// Time the worker gets to boot and render, wasm compilation included.
+const TIMEOUT_MS: f64 = 30_000.0;
+const POLL_MS: i32 = 100;
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+
+/// Whether page was opened as the smoke test.
+pub fn is_requested() -> bool {
+    web_sys::window()
+        .and_then(|window| window.location().search().ok())
+        .map_or(false, |search| {
+            search
+                .trim_start_matches('?')
+                .split('&')
+                .any(|param| param == "smoke")
+        })
+}
+
+fn user_agent() -> Option<String> {
+    let global = JsValue::from(js_sys::global());
+    let navigator = Reflect::get(&global, &"navigator".into()).ok()?;
+    Reflect::get(&navigator, &"userAgent".into())
+        .ok()?
+        .as_string()
+}
+
+fn capabilities_json(capabilities: &Capabilities) -> Value {
+    capabilities
+        .entries()
+        .into_iter()
+        .map(|(name, supported)| (name.to_owned(), Value::Bool(supported)))
+        .collect()
+}
+
+/// Run the smoke test, failures are reported rather than returned.
+pub fn run() -> Result<(), SpawnError> {
+    let window = web_sys::window().ok_or(SpawnError::NoWindow)?;
+    let document = window.document().ok_or(SpawnError::NoWindow)?;
+    let body = document.body().ok_or(SpawnError::NoWindow)?;
+
+    let canvas: HtmlCanvasElement = document
+        .create_element("canvas")
+        .map_err(SpawnError::Dom)?
+        .unchecked_into();
+    canvas.set_width(WIDTH);
+    canvas.set_height(HEIGHT);
+    body.append_child(&canvas).map_err(SpawnError::Dom)?;
+
+    let page = Capabilities::probe();
+    let probe = Rc::new(json!({
+        "user_agent": user_agent(),
+        "page": capabilities_json(&page),
+    }));
+    if !page.offscreen_canvas {
+        report("fallback", &probe, json!({}));
+        return Ok(());
+    }
+
+    let started = js_sys::Date::now();
+    let ready_at = Rc::new(Cell::new(None));
+    let first_frame_at = Rc::new(Cell::new(None));
+    let failed = Rc::new(Cell::new(false));
+
+    let spawned = WorkerBuilder::new("bevy_worker").and_then(|builder| {
+        builder
+            .on_ready({
+                let ready_at = Rc::clone(&ready_at);
+                move |_| ready_at.set(Some(js_sys::Date::now() - started))
+            })
+            .on_first_frame({
+                let first_frame_at = Rc::clone(&first_frame_at);
+                move |_| first_frame_at.set(Some(js_sys::Date::now() - started))
+            })
+            .on_error({
+                let probe = Rc::clone(&probe);
+                let failed = Rc::clone(&failed);
+                move |err| {
+                    if !failed.replace(true) {
+                        report("fail", &probe, json!({ "error": err.to_string() }));
+                    }
+                }
+            })
+            .spawn()
+    });
+    let worker = match spawned {
+        Ok(worker) => worker,
+        Err(err) => {
+            report("fail", &probe, json!({ "error": err.to_string() }));
+            return Ok(());
+        }
+    };
+
+    worker.set_latency_measurement(true);
+    if let Err(err) = worker.attach(&canvas) {
+        report("fail", &probe, json!({ "error": err.to_string() }));
+        return Ok(());
+    }
+
+    poll(worker, probe, started, ready_at, first_frame_at, failed);
+    Ok(())
+}
+
+fn poll(
+    worker: WorkerHandle,
+    probe: Rc<Value>,
+    started: f64,
+    ready_at: Rc<Cell<Option<f64>>>,
+    first_frame_at: Rc<Cell<Option<f64>>>,
+    failed: Rc<Cell<bool>>,
+) {
+    if failed.get() {
+        return;
+    }
+
+    // Latency of page's input is only known once a frame handled it.
+    let elapsed = js_sys::Date::now() - started;
+    if first_frame_at.get().is_some() && worker.latency_report().is_some() {
+        let details = json!({
+            "ready_ms": ready_at.get(),
+            "first_frame_ms": first_frame_at.get(),
+            "input_ms": elapsed,
+            "threads": worker.threads(),
+            "worker_capabilities": worker.capabilities().map(|report| capabilities_json(&report.worker)),
+        });
+        report("pass", &probe, details);
+        return;
+    }
+
+    if elapsed > TIMEOUT_MS {
+        let stage = match (ready_at.get(), first_frame_at.get()) {
+            (None, _) => "no handshake",
+            (Some(_), None) => "no frame after handshake",
+            (Some(_), Some(_)) => "no input handled after first frame",
+        };
+        report(
+            "fail",
+            &probe,
+            json!({ "error": format!("timed out: {stage}") }),
+        );
+        return;
+    }
+
+    // Resize to the same size is harmless, and keeps coming in case probing wasn't enabled yet for the first one.
+    worker.send(HostMessage::Resize {
+        view: ViewId::PRIMARY,
+        width: WIDTH,
+        height: HEIGHT,
+    });
+
+    let next = Closure::once_into_js(move || {
+        poll(worker, probe, started, ready_at, first_frame_at, failed)
+    });
+    let scheduled = web_sys::window().map(|window| {
+        window.set_timeout_with_callback_and_timeout_and_arguments_0(next.unchecked_ref(), POLL_MS)
+    });
+    if !matches!(scheduled, Some(Ok(_))) {
+        web_sys::console::error_1(&"failed to schedule smoke test poll".into());
+    }
+}
+
+/// Show outcome on the page and post it to the server.
+fn report(status: &str, capabilities: &Value, details: Value) {
+    let report = json!({
+        "status": status,
+        "capabilities": capabilities,
+        "details": details,
+    })
+    .to_string();
+
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+
+    if let Some(document) = window.document() {
+        document.set_title(&format!("smoke: {status}"));
+        if let Some(body) = document.body() {
+            if let Ok(pre) = document.create_element("pre") {
+                pre.set_id("smoke-report");
+                pre.set_text_content(Some(&report));
+                let _ = body.append_child(&pre);
+            }
+        }
+    }
+
+    let mut init = RequestInit::new();
+    init.method("POST").body(Some(&report.into()));
+    // Page opened by hand has nobody listening, which is fine.
+    let _ = window.fetch_with_str_and_init("/report", &init);
+}
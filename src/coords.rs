@@ -0,0 +1,135 @@
+//! Coordinate spaces pointer positions and sizes pass through on their way from the page.
+//!
+//! * [`ClientPx`] are CSS pixels of the canvas element, as reported by DOM events.
+//!   Canvas may be stretched by CSS, so they don't have to match its drawing buffer.
+//! * [`PhysicalPx`] are pixels of canvas drawing buffer, which is what goes over the bridge.
+//! * [`LogicalPx`] are logical pixels of Bevy window, physical ones divided by window scale factor.
+//! * [`WorldUnits`] are coordinates in the world as seen by a 2D camera.
+//!
+//! All positions have origin at top-left corner and Y pointing down, except for world space.
+//! Mixing them up is easy to miss on a desktop, but shows up on any device with pixel ratio other than 1.
+
+use bevy::math::{UVec2, Vec2};
+use bevy::prelude::{Camera, GlobalTransform};
+
+/// CSS pixels of canvas element, relative to its top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ClientPx(pub Vec2);
+
+/// Pixels of canvas drawing buffer, relative to its top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PhysicalPx(pub Vec2);
+
+/// Logical pixels of Bevy window, relative to its top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LogicalPx(pub Vec2);
+
+/// Position in world space, Y pointing up.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WorldUnits(pub Vec2);
+
+impl ClientPx {
+    /// Position in drawing buffer of canvas with given CSS size and buffer size.
+    pub fn to_physical(self, client_size: Vec2, buffer_size: UVec2) -> PhysicalPx {
+        PhysicalPx(self.0 * buffer_size.as_vec2() / client_size.max(Vec2::ONE))
+    }
+
+    /// Drawing buffer size matching canvas of this CSS size on a screen with given pixel ratio.
+    pub fn buffer_size(self, pixel_ratio: f64) -> UVec2 {
+        (self.0.as_dvec2() * pixel_ratio).round().as_uvec2()
+    }
+}
+
+impl PhysicalPx {
+    pub fn to_client(self, client_size: Vec2, buffer_size: UVec2) -> ClientPx {
+        ClientPx(self.0 * client_size / buffer_size.max(UVec2::ONE).as_vec2())
+    }
+
+    pub fn to_logical(self, scale_factor: f64) -> LogicalPx {
+        LogicalPx(self.0 / scale_factor as f32)
+    }
+}
+
+impl LogicalPx {
+    pub fn to_physical(self, scale_factor: f64) -> PhysicalPx {
+        PhysicalPx(self.0 * scale_factor as f32)
+    }
+
+    /// Same position with origin at bottom-left corner of a window of given height, Y pointing up.
+    ///
+    /// Applying it twice gets the original position back.
+    pub fn flip_y(self, height: f32) -> LogicalPx {
+        LogicalPx(Vec2::new(self.0.x, height - self.0.y))
+    }
+
+    /// Point in the world under this position of camera's window.
+    ///
+    /// Returns `None` if camera has no viewport yet or its projection can't be inverted.
+    pub fn to_world(self, camera: &Camera, transform: &GlobalTransform) -> Option<WorldUnits> {
+        camera
+            .viewport_to_world_2d(transform, self.0)
+            .map(WorldUnits)
+    }
+}
+
+impl WorldUnits {
+    /// Position of this point on camera's window.
+    ///
+    /// Returns `None` if the point is outside of camera's view.
+    pub fn to_logical(self, camera: &Camera, transform: &GlobalTransform) -> Option<LogicalPx> {
+        camera
+            .world_to_viewport(transform, self.0.extend(0.0))
+            .map(LogicalPx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_to_physical_and_back() {
+        // Canvas of 400x300 CSS pixels with a buffer twice as large.
+        let (client_size, buffer_size) = (Vec2::new(400.0, 300.0), UVec2::new(800, 600));
+
+        let physical = ClientPx(Vec2::new(100.0, 150.0)).to_physical(client_size, buffer_size);
+        assert_eq!(physical, PhysicalPx(Vec2::new(200.0, 300.0)));
+        assert_eq!(
+            physical.to_client(client_size, buffer_size),
+            ClientPx(Vec2::new(100.0, 150.0))
+        );
+    }
+
+    #[test]
+    fn zero_sizes_do_not_divide_by_zero() {
+        let physical = ClientPx(Vec2::new(10.0, 10.0)).to_physical(Vec2::ZERO, UVec2::new(8, 8));
+        assert!(physical.0.is_finite());
+
+        let client = PhysicalPx(Vec2::new(10.0, 10.0)).to_client(Vec2::ONE, UVec2::ZERO);
+        assert!(client.0.is_finite());
+    }
+
+    #[test]
+    fn buffer_size_rounds() {
+        let size = ClientPx(Vec2::new(101.0, 50.0)).buffer_size(1.5);
+        assert_eq!(size, UVec2::new(152, 75));
+    }
+
+    #[test]
+    fn physical_to_logical_and_back() {
+        let logical = PhysicalPx(Vec2::new(300.0, 150.0)).to_logical(3.0);
+        assert_eq!(logical, LogicalPx(Vec2::new(100.0, 50.0)));
+        assert_eq!(
+            logical.to_physical(3.0),
+            PhysicalPx(Vec2::new(300.0, 150.0))
+        );
+    }
+
+    #[test]
+    fn flip_y_is_its_own_inverse() {
+        let position = LogicalPx(Vec2::new(10.0, 30.0));
+
+        assert_eq!(position.flip_y(100.0), LogicalPx(Vec2::new(10.0, 70.0)));
+        assert_eq!(position.flip_y(100.0).flip_y(100.0), position);
+    }
+}
@@ -0,0 +1,4608 @@
+//! Page side of the bridge.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::rc::{Rc, Weak};
+
+use bevy::log::info_span;
+use bevy::math::{UVec2, Vec2};
+use js_sys::ArrayBuffer;
+use wasm_bindgen::JsValue;
+use web_sys::{
+    Element, HtmlCanvasElement, HtmlElement, HtmlTextAreaElement, ImageBitmap, MessagePort,
+    RtcDataChannel, SharedWorker, Worker,
+};
+
+use crate::coords::{ClientPx, PhysicalPx};
+use crate::protocol::{
+    validate_transfer, AppId, BootFlags, BridgeError, BridgeStats, Capabilities, CapabilityReport,
+    CorrelationId, DebugShape, Envelope, FrameStats, GraphicsBackend, GraphicsOptions, HostMessage,
+    ImeAction, InspectQuery, MemoryWarning, PointerAction, Port, QualityPreset, RenderStats,
+    SurfacePresentMode, TonemappingMethod, TouchAction, TrafficDirection, TrafficLog, Transferable,
+    UpdateMode, ViewId, WheelMode, WorkerMessage,
+};
+
+pub mod accessibility;
+mod inspector;
+pub mod manager;
+pub mod mesh;
+pub mod splash;
+mod stats;
+
+use accessibility::AccessibilityMirror;
+use inspector::InspectorPanel;
+use splash::{Splash, SplashOptions};
+use stats::StatsOverlay;
+
+/// Reasons worker could not be spawned or handed a canvas.
+#[derive(Debug, Clone)]
+pub enum SpawnError {
+    /// Not running on a page, `window` or `document` is missing.
+    NoWindow,
+    /// Page origin could not be determined.
+    Origin(JsValue),
+    /// Bootstrap script could not be turned into a blob URL.
+    BlobUrl(JsValue),
+    /// Browser refused to create the worker, e.g. because of CSP.
+    Worker(JsValue),
+    /// Page DOM could not be set up.
+    Dom(JsValue),
+    /// Canvas could not be transferred to the worker.
+    Transfer(JsValue),
+    /// Requested feature doesn't work with shared workers, see [`WorkerBuilder::shared`].
+    SharedUnsupported(&'static str),
+}
+
+impl Display for SpawnError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpawnError::NoWindow => write!(f, "not running on a page"),
+            SpawnError::Origin(err) => {
+                write!(f, "failed to determine page origin: {}", describe(err))
+            }
+            SpawnError::BlobUrl(err) => {
+                write!(f, "failed to create bootstrap script: {}", describe(err))
+            }
+            SpawnError::Worker(err) => write!(f, "failed to spawn worker: {}", describe(err)),
+            SpawnError::Dom(err) => write!(f, "failed to set up page: {}", describe(err)),
+            SpawnError::Transfer(err) => {
+                write!(f, "failed to transfer canvas to worker: {}", describe(err))
+            }
+            SpawnError::SharedUnsupported(what) => {
+                write!(f, "{what} is not supported for shared workers")
+            }
+        }
+    }
+}
+
+impl Error for SpawnError {}
+
+fn describe(value: &JsValue) -> String {
+    use wasm_bindgen::JsCast;
+
+    match value.dyn_ref::<js_sys::Error>() {
+        Some(err) => err.message().into(),
+        None => value.as_string().unwrap_or_else(|| format!("{value:?}")),
+    }
+}
+
+/// Where to find worker's JS glue and wasm module.
+///
+/// Different toolchains name and place their output differently, presets cover the common ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkerArtifacts {
+    /// URL of JS glue emitted by wasm-bindgen.
+    pub js_url: String,
+    /// URL of wasm module.
+    pub wasm_url: String,
+    /// Function initializing wasm module.
+    ///
+    /// For classic workers it is the name of global set by JS glue (`wasm_bindgen` unless changed by `--no-modules-global`),
+    /// for module workers it is the name of export (usually `default`).
+    /// `None` picks the usual one for worker flavor.
+    pub init: Option<String>,
+}
+
+impl WorkerArtifacts {
+    /// Trunk worker output: `{name}.js` and `{name}_bg.wasm` at the root of the site.
+    pub fn trunk(name: &str) -> Result<Self, SpawnError> {
+        let origin = origin()?;
+
+        Ok(WorkerArtifacts {
+            js_url: format!("{origin}/{name}.js"),
+            wasm_url: format!("{origin}/{name}_bg.wasm"),
+            init: None,
+        })
+    }
+
+    /// wasm-pack output: `{crate_name}.js` and `{crate_name}_bg.wasm` inside `pkg_url` directory.
+    pub fn wasm_pack(pkg_url: &str, crate_name: &str) -> Self {
+        let pkg_url = pkg_url.trim_end_matches('/');
+
+        WorkerArtifacts {
+            js_url: format!("{pkg_url}/{crate_name}.js"),
+            wasm_url: format!("{pkg_url}/{crate_name}_bg.wasm"),
+            init: None,
+        }
+    }
+
+    /// Output of bundlers like vite, which hash file names and hand out URLs to them.
+    ///
+    /// Those emit ES modules, so it should be paired with [`WorkerFlavor::Module`].
+    pub fn vite(js_url: &str, wasm_url: &str) -> Self {
+        WorkerArtifacts {
+            js_url: js_url.to_owned(),
+            wasm_url: wasm_url.to_owned(),
+            init: Some("default".to_owned()),
+        }
+    }
+
+    fn init(&self, flavor: WorkerFlavor) -> &str {
+        match (&self.init, flavor) {
+            (Some(init), _) => init,
+            (None, WorkerFlavor::Classic) => "wasm_bindgen",
+            (None, WorkerFlavor::Module) => "default",
+        }
+    }
+}
+
+/// Fetch of wasm module reporting download progress to the page, shared with loader scripts.
+///
+/// Defines `bevyFetchWasm(url, report)`, where `report` posts progress messages.
+pub const FETCH_WASM_SCRIPT: &str = include_str!("../loader/fetch_wasm.js");
+
+// Copied from https://github.com/thedodd/trunk/blob/master/examples/webworker/src/bin/app.rs
+pub fn worker_new(artifacts: &WorkerArtifacts) -> Result<Worker, SpawnError> {
+    let js = js_string(&artifacts.js_url);
+    let wasm = js_string(&artifacts.wasm_url);
+    let init = artifacts.init(WorkerFlavor::Classic);
+
+    // Failure to load wasm module happens inside a promise and never reaches `onerror`,
+    // so bootstrap script has to report it by itself.
+    // Artifacts are left behind for spawning threads, see `worker::threads`.
+    let bootstrap = format!(
+        r#"{FETCH_WASM_SCRIPT}self.bevyWorkerArtifacts={{js:{js},init:{init_str},module:false}};importScripts({js});{init}(bevyFetchWasm({wasm},msg=>postMessage(msg))).catch(e=>postMessage({{kind:"error",message:String(e)}}));"#,
+        init_str = js_string(init),
+    );
+
+    Worker::new(&script_url(&bootstrap)?).map_err(SpawnError::Worker)
+}
+
+/// Spawn worker as ES module.
+///
+/// Expects artifacts produced by wasm-bindgen with `--target web`,
+/// where module export initializes wasm.
+/// Unlike [`worker_new`] this works with bundlers emitting ES modules
+/// and doesn't rely on `importScripts`.
+///
+/// Worker is started from `loader_url`, a same-origin copy of [`MODULE_LOADER_SCRIPT`],
+/// never from a `blob:` URL, so it runs under `script-src 'self'`.
+pub fn worker_new_module(
+    loader_url: &str,
+    artifacts: &WorkerArtifacts,
+) -> Result<Worker, SpawnError> {
+    worker_new_static(loader_url, artifacts, WorkerFlavor::Module)
+}
+
+/// Where [`WorkerFlavor::Module`] workers look for [`MODULE_LOADER_SCRIPT`] unless told otherwise,
+/// relative to the page.
+pub const MODULE_LOADER_URL: &str = "worker_loader_module.js";
+
+/// Loader script for [`WorkerFlavor::Classic`] workers, see [`worker_new_static`].
+///
+/// Loads [`FETCH_WASM_SCRIPT`] from `fetch_wasm.js` next to it.
+pub const LOADER_SCRIPT: &str = include_str!("../loader/worker_loader.js");
+
+/// Loader script for [`WorkerFlavor::Module`] workers, see [`worker_new_static`].
+///
+/// Imports [`FETCH_WASM_SCRIPT`] from `fetch_wasm.js` next to it.
+pub const MODULE_LOADER_SCRIPT: &str = include_str!("../loader/worker_loader_module.js");
+
+/// Spawn worker through a loader script served as a static file.
+///
+/// Blob URLs are not involved, so this works under `script-src 'self'`.
+/// `loader_url` should point to a copy of [`LOADER_SCRIPT`] or [`MODULE_LOADER_SCRIPT`] (matching the flavor).
+/// Artifact locations are passed to it in query string.
+pub fn worker_new_static(
+    loader_url: &str,
+    artifacts: &WorkerArtifacts,
+    flavor: WorkerFlavor,
+) -> Result<Worker, SpawnError> {
+    use web_sys::UrlSearchParams;
+
+    let query = UrlSearchParams::new().map_err(SpawnError::Worker)?;
+    query.append("js", &artifacts.js_url);
+    query.append("wasm", &artifacts.wasm_url);
+    query.append("init", artifacts.init(flavor));
+
+    let url = format!("{loader_url}?{}", String::from(query.to_string()));
+
+    worker_new_hosted(&url, flavor)
+}
+
+/// Connect to shared worker with given name, spawning it through a loader script if there is none yet.
+///
+/// Tabs of the same origin connecting with the same loader URL, artifacts and name share one worker,
+/// so unlike dedicated workers this can't go through a blob URL, which is unique to every page.
+/// See [`worker_new_static`] for the loader.
+pub fn shared_worker_new_static(
+    loader_url: &str,
+    artifacts: &WorkerArtifacts,
+    flavor: WorkerFlavor,
+    name: &str,
+) -> Result<SharedWorker, SpawnError> {
+    use web_sys::{UrlSearchParams, WorkerOptions, WorkerType};
+
+    let query = UrlSearchParams::new().map_err(SpawnError::Worker)?;
+    query.append("js", &artifacts.js_url);
+    query.append("wasm", &artifacts.wasm_url);
+    query.append("init", artifacts.init(flavor));
+
+    let url = format!("{loader_url}?{}", String::from(query.to_string()));
+
+    let mut options = WorkerOptions::new();
+    options.name(name);
+    options.type_(match flavor {
+        WorkerFlavor::Classic => WorkerType::Classic,
+        WorkerFlavor::Module => WorkerType::Module,
+    });
+
+    SharedWorker::new_with_worker_options(&url, &options).map_err(SpawnError::Worker)
+}
+
+/// Spawn worker from bootstrap script with artifact locations baked in.
+///
+/// Such script is generated by `cargo xtask bootstrap`.
+/// Nothing is created dynamically, so this works even for sites that forbid dynamic code altogether,
+/// and script's integrity hash can be pinned in CSP.
+pub fn worker_new_hosted(url: &str, flavor: WorkerFlavor) -> Result<Worker, SpawnError> {
+    use web_sys::{WorkerOptions, WorkerType};
+
+    let mut options = WorkerOptions::new();
+    options.type_(match flavor {
+        WorkerFlavor::Classic => WorkerType::Classic,
+        WorkerFlavor::Module => WorkerType::Module,
+    });
+
+    Worker::new_with_options(url, &options).map_err(SpawnError::Worker)
+}
+
+fn origin() -> Result<String, SpawnError> {
+    web_sys::window()
+        .ok_or(SpawnError::NoWindow)?
+        .location()
+        .origin()
+        .map_err(SpawnError::Origin)
+}
+
+/// Quote string as JS literal.
+fn js_string(s: &str) -> String {
+    js_sys::JSON::stringify(&s.into())
+        .map(String::from)
+        .unwrap_or_else(|_| format!("{s:?}"))
+}
+
+fn script_url(script: &str) -> Result<String, SpawnError> {
+    use js_sys::Array;
+    use web_sys::{Blob, BlobPropertyBag, Url};
+
+    let parts = Array::new();
+    parts.push(&script.into());
+
+    let blob = Blob::new_with_str_sequence_and_options(
+        &parts,
+        BlobPropertyBag::new().type_("text/javascript"),
+    )
+    .map_err(SpawnError::BlobUrl)?;
+
+    Url::create_object_url_with_blob(&blob).map_err(SpawnError::BlobUrl)
+}
+
+/// How worker script gets loaded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WorkerFlavor {
+    /// Classic worker pulling in wasm-bindgen's `--target no-modules` output via `importScripts`.
+    #[default]
+    Classic,
+    /// Module worker pulling in wasm-bindgen's `--target web` output via dynamic `import()`.
+    Module,
+}
+
+impl WorkerFlavor {
+    fn spawn(
+        self,
+        artifacts: &WorkerArtifacts,
+        bootstrap: &Bootstrap,
+    ) -> Result<Worker, SpawnError> {
+        match (self, bootstrap) {
+            (flavor, Bootstrap::Hosted(url)) => worker_new_hosted(url, flavor),
+            (flavor, Bootstrap::Loader(url)) => worker_new_static(url, artifacts, flavor),
+            (WorkerFlavor::Classic, Bootstrap::Blob) => worker_new(artifacts),
+            (WorkerFlavor::Module, Bootstrap::Blob) => {
+                worker_new_module(MODULE_LOADER_URL, artifacts)
+            }
+        }
+    }
+}
+
+/// Where worker bootstrap script comes from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Bootstrap {
+    /// Generated at runtime and served from a `blob:` URL.
+    ///
+    /// Only classic workers are spawned this way, module workers load [`MODULE_LOADER_URL`] instead.
+    #[default]
+    Blob,
+    /// Generic loader served as a static file, see [`worker_new_static`].
+    Loader(String),
+    /// Script with artifact locations baked in, see [`worker_new_hosted`].
+    Hosted(String),
+}
+
+/// Errors reported by the worker.
+#[derive(Debug, Clone)]
+pub enum WorkerError {
+    /// Worker script threw an uncaught exception or failed to load.
+    Script {
+        message: String,
+        filename: String,
+        lineno: u32,
+    },
+    /// Wasm module failed to load or instantiate.
+    Init(String),
+    /// Message could not be sent to or received from worker.
+    Bridge(BridgeError),
+    /// Worker could not be respawned.
+    Respawn(SpawnError),
+    /// Worker lost graphics context and waits for a fresh canvas.
+    DeviceLost,
+    /// App didn't present a frame within the time given to [`WorkerBuilder::splash`].
+    FirstFrameTimeout { timeout_ms: u32 },
+    /// App didn't answer with its snapshot in time, so [`WorkerHandle::upgrade`] was abandoned.
+    SnapshotTimeout { timeout_ms: u32 },
+}
+
+impl Display for WorkerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorkerError::Script {
+                message,
+                filename,
+                lineno,
+            } => write!(f, "worker script failed at {filename}:{lineno}: {message}"),
+            WorkerError::Init(message) => write!(f, "worker failed to initialize: {message}"),
+            WorkerError::Bridge(err) => write!(f, "{err}"),
+            WorkerError::Respawn(err) => write!(f, "failed to restart worker: {err}"),
+            WorkerError::DeviceLost => write!(f, "worker lost graphics context"),
+            WorkerError::FirstFrameTimeout { timeout_ms } => {
+                write!(f, "app didn't render anything in {timeout_ms} ms")
+            }
+            WorkerError::SnapshotTimeout { timeout_ms } => {
+                write!(
+                    f,
+                    "app didn't hand its state over in {timeout_ms} ms, upgrade is abandoned"
+                )
+            }
+        }
+    }
+}
+
+impl Error for WorkerError {}
+
+/// How to respawn worker after it fails.
+///
+/// Delay between attempts doubles every time, starting from `initial_delay_ms` and capped at `max_delay_ms`.
+/// Attempt counter is reset once worker reports readiness.
+///
+/// Note that canvases already delivered to a failed worker are gone with it,
+/// only messages which were still queued get replayed to the new one.
+#[derive(Debug, Clone, Copy)]
+pub struct RestartPolicy {
+    pub max_attempts: u32,
+    pub initial_delay_ms: i32,
+    pub max_delay_ms: i32,
+}
+
+impl RestartPolicy {
+    fn delay_ms(&self, attempt: u32) -> i32 {
+        self.initial_delay_ms
+            .saturating_mul(1 << attempt.min(30))
+            .min(self.max_delay_ms)
+    }
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy {
+            max_attempts: 5,
+            initial_delay_ms: 500,
+            max_delay_ms: 30_000,
+        }
+    }
+}
+
+/// UI scale matching user's preferred font size.
+///
+/// Browsers don't expose OS text scaling directly, but it is reflected in font size of the root element,
+/// relative to the default of 16px. Page zoom is already part of device pixel ratio and isn't included.
+pub fn preferred_ui_scale() -> Result<f64, SpawnError> {
+    const DEFAULT_FONT_SIZE: f64 = 16.0;
+
+    let window = web_sys::window().ok_or(SpawnError::NoWindow)?;
+    let root = window
+        .document()
+        .and_then(|document| document.document_element())
+        .ok_or(SpawnError::NoWindow)?;
+
+    let font_size = window
+        .get_computed_style(&root)
+        .map_err(SpawnError::Dom)?
+        .and_then(|style| style.get_property_value("font-size").ok())
+        .and_then(|size| size.strip_suffix("px")?.parse::<f64>().ok());
+
+    Ok(font_size.map_or(1.0, |size| size / DEFAULT_FONT_SIZE))
+}
+
+/// Configure worker before spawning it.
+pub struct WorkerBuilder {
+    artifacts: WorkerArtifacts,
+    flavor: WorkerFlavor,
+    bootstrap: Bootstrap,
+    restart: Option<RestartPolicy>,
+    on_error: Option<Box<dyn Fn(&WorkerError)>>,
+    on_device_lost: Option<Box<dyn Fn(&WorkerHandle)>>,
+    on_ready: Option<Box<dyn Fn(&WorkerHandle)>>,
+    on_memory_warning: Option<Box<dyn Fn(&WorkerHandle, MemoryWarning)>>,
+    on_first_frame: Option<Box<dyn Fn(&WorkerHandle)>>,
+    on_wasm_loading: Option<Box<dyn Fn(u64, Option<u64>)>>,
+    splash: Option<Splash>,
+    target_fps: Option<u32>,
+    update_mode: Option<UpdateMode>,
+    ui_scale: Option<f64>,
+    features: Vec<(String, bool)>,
+    boot_flags: BootFlags,
+    accessibility: Option<AccessibilityMirror>,
+    warm_spare: bool,
+    shared: Option<String>,
+    settings_prefix: String,
+    capture_right_click: bool,
+    responsive_canvas: bool,
+}
+
+impl WorkerBuilder {
+    /// Worker from trunk artifacts with given name.
+    pub fn new(name: &str) -> Result<Self, SpawnError> {
+        Ok(WorkerBuilder::with_artifacts(WorkerArtifacts::trunk(name)?))
+    }
+
+    /// Worker from artifacts at arbitrary location.
+    pub fn with_artifacts(artifacts: WorkerArtifacts) -> Self {
+        WorkerBuilder {
+            artifacts,
+            flavor: WorkerFlavor::default(),
+            bootstrap: Bootstrap::default(),
+            restart: None,
+            on_error: None,
+            on_device_lost: None,
+            on_ready: None,
+            on_memory_warning: None,
+            on_first_frame: None,
+            on_wasm_loading: None,
+            splash: None,
+            target_fps: None,
+            update_mode: None,
+            ui_scale: None,
+            features: Vec::new(),
+            boot_flags: BootFlags::default(),
+            accessibility: None,
+            warm_spare: false,
+            shared: None,
+            settings_prefix: "bevy-worker-settings:".to_owned(),
+            capture_right_click: false,
+            responsive_canvas: false,
+        }
+    }
+
+    /// How to load worker script.
+    pub fn flavor(mut self, flavor: WorkerFlavor) -> Self {
+        self.flavor = flavor;
+        self
+    }
+
+    /// Spawn worker through static loader script instead of a blob.
+    ///
+    /// See [`worker_new_static`].
+    pub fn static_loader(mut self, url: &str) -> Self {
+        self.bootstrap = Bootstrap::Loader(url.to_owned());
+        self
+    }
+
+    /// Spawn worker from pre-generated bootstrap script.
+    ///
+    /// Artifacts passed to the builder are ignored in this case, see [`worker_new_hosted`].
+    pub fn hosted_bootstrap(mut self, url: &str) -> Self {
+        self.bootstrap = Bootstrap::Hosted(url.to_owned());
+        self
+    }
+
+    /// Respawn worker when it fails.
+    pub fn restart(mut self, policy: RestartPolicy) -> Self {
+        self.restart = Some(policy);
+        self
+    }
+
+    /// Keep a spare worker loaded and waiting, so restarts take milliseconds instead of a cold boot.
+    ///
+    /// Spare is spawned once current worker is ready and takes over on failure
+    /// or [`WorkerHandle::restart`]. It costs a second copy of wasm module in memory.
+    pub fn warm_spare(mut self) -> Self {
+        self.warm_spare = true;
+        self
+    }
+
+    /// Keep browser's context menu from popping up over canvases, so app can use right click.
+    ///
+    /// Right button presses reach the worker either way.
+    pub fn capture_right_click(mut self) -> Self {
+        self.capture_right_click = true;
+        self
+    }
+
+    /// Size drawing buffers after canvases as laid out on the page, times device pixel ratio,
+    /// following window resizes and screen rotations.
+    ///
+    /// By default canvas keeps size of its `width` and `height` attributes and is stretched by CSS,
+    /// except in fullscreen. How the picture fits the new size is up to `ViewportPolicy` in the worker.
+    pub fn responsive_canvas(mut self) -> Self {
+        self.responsive_canvas = true;
+        self
+    }
+
+    /// Connect to a `SharedWorker` with given name, so every tab of the site runs the same app.
+    ///
+    /// Worker has to be started with `worker::start_shared` and loaded through [`static_loader`](Self::static_loader).
+    /// Primary view of this tab becomes another window of the shared app, unless this tab is the first one.
+    /// Shutting down only disconnects this tab, app keeps running for others until the last one leaves.
+    /// Restarting reconnects to the same worker, and there is no warm spare or upgrade.
+    pub fn shared(mut self, name: &str) -> Self {
+        self.shared = Some(name.to_owned());
+        self
+    }
+
+    /// Prefix of `localStorage` keys worker settings are stored under.
+    ///
+    /// Pages hosting several apps should give each one its own.
+    pub fn settings_prefix(mut self, prefix: &str) -> Self {
+        self.settings_prefix = prefix.to_owned();
+        self
+    }
+
+    /// Callback invoked on every worker error.
+    ///
+    /// By default errors are logged to console.
+    pub fn on_error(mut self, f: impl Fn(&WorkerError) + 'static) -> Self {
+        self.on_error = Some(Box::new(f));
+        self
+    }
+
+    /// Callback invoked when worker loses graphics context.
+    ///
+    /// Covers both WebGL2 context loss and WebGPU device loss.
+    /// Worker tears down the app and rebuilds it from scratch once primary view is attached again,
+    /// so this is the place to create a fresh canvas and [`attach`](WorkerHandle::attach) it.
+    /// Only entities app marked [`Persistent`](crate::worker::upgrade::Persistent) are carried over.
+    /// Without the callback loss is reported as an error.
+    pub fn on_device_lost(mut self, f: impl Fn(&WorkerHandle) + 'static) -> Self {
+        self.on_device_lost = Some(Box::new(f));
+        self
+    }
+
+    /// Callback invoked every time worker finished loading, including after restarts.
+    pub fn on_ready(mut self, f: impl Fn(&WorkerHandle) + 'static) -> Self {
+        self.on_ready = Some(Box::new(f));
+        self
+    }
+
+    /// Callback invoked when app presents its first frame, including after restarts.
+    pub fn on_first_frame(mut self, f: impl Fn(&WorkerHandle) + 'static) -> Self {
+        self.on_first_frame = Some(Box::new(f));
+        self
+    }
+
+    /// Callback invoked as wasm module downloads, with bytes received so far and total size if known.
+    ///
+    /// Comes from bootstrap script before the app even starts, including after restarts.
+    /// Called at most every 100 ms or so, and once more when download is complete.
+    pub fn on_wasm_loading(mut self, f: impl Fn(u64, Option<u64>) + 'static) -> Self {
+        self.on_wasm_loading = Some(Box::new(f));
+        self
+    }
+
+    /// Keep element, e.g. a loading screen over the canvas, visible until app presents its first frame,
+    /// then fade it out.
+    ///
+    /// Page gets [`WorkerError::FirstFrameTimeout`] if frame doesn't come in time,
+    /// counting from when the first view is attached.
+    pub fn splash(mut self, element: &HtmlElement, options: SplashOptions) -> Self {
+        self.splash = Some(Splash::new(element.clone(), options));
+        self
+    }
+
+    /// Callback invoked when app's wasm memory grows past the threshold.
+    ///
+    /// Threshold is set in [`MemoryDiagnosticsPlugin`](crate::worker::memory::MemoryDiagnosticsPlugin).
+    /// By default warnings are logged to console.
+    pub fn on_memory_warning(mut self, f: impl Fn(&WorkerHandle, MemoryWarning) + 'static) -> Self {
+        self.on_memory_warning = Some(Box::new(f));
+        self
+    }
+
+    /// Keep DOM mirror of app's accessibility tree, so screen readers can see worker-rendered UI.
+    pub fn accessibility_mirror(mut self, mirror: AccessibilityMirror) -> Self {
+        self.accessibility = Some(mirror);
+        self
+    }
+
+    /// Cap app at given frame rate from the very start.
+    ///
+    /// It can be changed later with [`WorkerHandle::set_target_fps`].
+    pub fn target_fps(mut self, fps: u32) -> Self {
+        self.target_fps = Some(fps);
+        self
+    }
+
+    /// Pick when worker updates the app from the very start.
+    ///
+    /// It can be changed later with [`WorkerHandle::set_update_mode`].
+    pub fn update_mode(mut self, mode: UpdateMode) -> Self {
+        self.update_mode = Some(mode);
+        self
+    }
+
+    /// Scale UI and text from the very start.
+    ///
+    /// It can be changed later with [`WorkerHandle::set_ui_scale`].
+    pub fn ui_scale(mut self, scale: f64) -> Self {
+        self.ui_scale = Some(scale);
+        self
+    }
+
+    /// Scale UI and text according to user's preferred font size, see [`preferred_ui_scale`].
+    pub fn ui_scale_from_preferences(mut self) -> Result<Self, SpawnError> {
+        self.ui_scale = Some(preferred_ui_scale()?);
+        Ok(self)
+    }
+
+    /// Set feature toggle from the very start.
+    pub fn feature(mut self, name: &str, enabled: bool) -> Self {
+        self.features.push((name.to_owned(), enabled));
+        self
+    }
+
+    /// Set feature toggles listed in `features` query parameter of page URL.
+    ///
+    /// Parameter is a comma separated list of names, names prefixed with `-` are disabled:
+    /// `?features=diagnostics,-overlays`.
+    pub fn features_from_query(mut self) -> Result<Self, SpawnError> {
+        use web_sys::UrlSearchParams;
+
+        let search = web_sys::window()
+            .ok_or(SpawnError::NoWindow)?
+            .location()
+            .search()
+            .map_err(SpawnError::Origin)?;
+        let params = UrlSearchParams::new_with_str(&search).map_err(SpawnError::Dom)?;
+
+        if let Some(features) = params.get("features") {
+            for name in features.split(',').filter(|name| !name.is_empty()) {
+                match name.strip_prefix('-') {
+                    Some(name) => self.features.push((name.to_owned(), false)),
+                    None => self.features.push((name.to_owned(), true)),
+                }
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Configure worker with given boot flags.
+    pub fn boot_flags(mut self, flags: BootFlags) -> Self {
+        self.boot_flags = flags;
+        self
+    }
+
+    /// Configure worker with boot flags from query parameters and hash of page URL.
+    ///
+    /// Recognized parameters are `log` (`error` through `trace`), `scene`, `quality` (`low`, `medium` or `high`),
+    /// `seed`, `debug` (comma separated list of toggles), `fps`, `backend` (`webgpu` or `webgl2`),
+    /// `msaa` (sample count), `hdr` (`true` or `false`) and `tonemapping` (e.g. `tony_mc_mapface`):
+    /// `?log=debug&scene=forest&seed=42&debug=colliders,paths`.
+    /// Hash is read the same way and wins over query, e.g. `#fps=30&backend=webgl2`,
+    /// so it can be tweaked without reloading the page from the server.
+    /// Other parameters go to [`BootFlags::extra`], except for `features`, see [`features_from_query`](Self::features_from_query).
+    /// Invalid values are reported to console and ignored.
+    pub fn boot_flags_from_query(mut self) -> Result<Self, SpawnError> {
+        use web_sys::UrlSearchParams;
+
+        const KNOWN: [&str; 11] = [
+            "log",
+            "scene",
+            "quality",
+            "seed",
+            "debug",
+            "fps",
+            "backend",
+            "msaa",
+            "hdr",
+            "tonemapping",
+            "features",
+        ];
+
+        let location = web_sys::window().ok_or(SpawnError::NoWindow)?.location();
+        let search = location.search().map_err(SpawnError::Origin)?;
+        let hash = location.hash().map_err(SpawnError::Origin)?;
+
+        // Parameters from hash replace ones from query.
+        let params = UrlSearchParams::new_with_str(&search).map_err(SpawnError::Dom)?;
+        let from_hash =
+            UrlSearchParams::new_with_str(hash.trim_start_matches('#')).map_err(SpawnError::Dom)?;
+        for entry in js_sys::try_iter(&from_hash)
+            .map_err(SpawnError::Dom)?
+            .into_iter()
+            .flatten()
+        {
+            let entry: js_sys::Array = entry.map_err(SpawnError::Dom)?.into();
+            if let (Some(key), Some(value)) = (entry.get(0).as_string(), entry.get(1).as_string()) {
+                params.set(&key, &value);
+            }
+        }
+
+        fn parse<T>(
+            params: &UrlSearchParams,
+            name: &str,
+            f: impl FnOnce(&str) -> Option<T>,
+        ) -> Option<T> {
+            let value = params.get(name)?;
+            let parsed = f(&value);
+            if parsed.is_none() {
+                web_sys::console::warn_1(
+                    &format!("invalid value of `{name}` query parameter: `{value}`").into(),
+                );
+            }
+            parsed
+        }
+
+        let flags = &mut self.boot_flags;
+        if let Some(level) = parse(&params, "log", |level| level.parse().ok()) {
+            flags.log_level = Some(level);
+        }
+        if let Some(scene) = params.get("scene").filter(|scene| !scene.is_empty()) {
+            flags.scene = Some(scene);
+        }
+        if let Some(quality) = parse(&params, "quality", QualityPreset::from_name) {
+            flags.quality = Some(quality);
+        }
+        if let Some(seed) = parse(&params, "seed", |seed| seed.parse().ok()) {
+            flags.seed = Some(seed);
+        }
+        if let Some(debug) = params.get("debug") {
+            flags.debug.extend(
+                debug
+                    .split(',')
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_owned),
+            );
+        }
+        if let Some(fps) = parse(&params, "fps", |fps| {
+            fps.parse().ok().filter(|&fps| fps > 0)
+        }) {
+            flags.fps = Some(fps);
+        }
+        if let Some(backend) = parse(&params, "backend", GraphicsBackend::from_name) {
+            flags.backend = Some(backend);
+        }
+        if let Some(msaa) = parse(&params, "msaa", |msaa| msaa.parse().ok()) {
+            flags.graphics.msaa = Some(msaa);
+        }
+        if let Some(hdr) = parse(&params, "hdr", |hdr| hdr.parse().ok()) {
+            flags.graphics.hdr = Some(hdr);
+        }
+        if let Some(tonemapping) = parse(&params, "tonemapping", TonemappingMethod::from_name) {
+            flags.graphics.tonemapping = Some(tonemapping);
+        }
+
+        for entry in js_sys::try_iter(&params)
+            .map_err(SpawnError::Dom)?
+            .into_iter()
+            .flatten()
+        {
+            let entry: js_sys::Array = entry.map_err(SpawnError::Dom)?.into();
+            if let (Some(key), Some(value)) = (entry.get(0).as_string(), entry.get(1).as_string()) {
+                if !KNOWN.contains(&key.as_str()) {
+                    flags.extra.insert(key, value);
+                }
+            }
+        }
+
+        Ok(self)
+    }
+
+    pub fn spawn(self) -> Result<WorkerHandle, SpawnError> {
+        let WorkerBuilder {
+            artifacts,
+            flavor,
+            bootstrap,
+            restart,
+            on_error,
+            on_device_lost,
+            on_ready,
+            on_memory_warning,
+            on_first_frame,
+            on_wasm_loading,
+            splash,
+            target_fps,
+            update_mode,
+            ui_scale,
+            features,
+            boot_flags,
+            accessibility,
+            warm_spare,
+            shared,
+            settings_prefix,
+            capture_right_click,
+            responsive_canvas,
+        } = self;
+
+        let inner = Rc::new(Inner {
+            worker: RefCell::new(Endpoint::spawn(
+                flavor,
+                &artifacts,
+                &bootstrap,
+                shared.as_deref(),
+            )?),
+            artifacts: RefCell::new(artifacts),
+            flavor,
+            bootstrap,
+            restart,
+            on_error,
+            on_device_lost,
+            on_ready,
+            on_memory_warning,
+            on_first_frame,
+            on_wasm_loading,
+            splash: RefCell::new(splash),
+            attempts: Cell::new(0),
+            pending: RefCell::new(Some(Vec::new())),
+            coalescing: RefCell::new(Vec::new()),
+            coalesce_scheduled: Cell::new(false),
+            coalesced: Cell::new(0),
+            ports: RefCell::new(HashMap::new()),
+            frame_clock: RefCell::new(None),
+            frame_clock_apps: RefCell::new(BTreeSet::new()),
+            frame_clock_running: Cell::new(false),
+            shutting_down: Cell::new(false),
+            features: RefCell::new(BTreeMap::new()),
+            boot_flags,
+            threads: Cell::new(1),
+            capabilities: Cell::new(None),
+            next_id: Cell::new(0),
+            accessibility,
+            inspector: RefCell::new(None),
+            anomalies: RefCell::new(VecDeque::new()),
+            warm_spare,
+            capture_right_click,
+            responsive_canvas,
+            shared,
+            spare: RefCell::new(None),
+            upgrade: RefCell::new(None),
+            next_preview: Cell::new(0),
+            previews: RefCell::new(BTreeMap::new()),
+            settings_prefix,
+            apps: RefCell::new(BTreeSet::from([AppId::DEFAULT])),
+            canvases: RefCell::new(HashMap::new()),
+            buffer_sizes: RefCell::new(HashMap::new()),
+            text_inputs: RefCell::new(HashMap::new()),
+            dom_anchors: RefCell::new(HashMap::new()),
+            keyboard_layout: RefCell::new(None),
+            motion_sensors: Cell::new(false),
+            traffic: RefCell::new(None),
+            traffic_export: RefCell::new(None),
+            scene_exports: RefCell::new(HashMap::new()),
+            recordings: RefCell::new(HashMap::new()),
+            bridge_stats: RefCell::new(HashMap::new()),
+            frame_stats: RefCell::new(HashMap::new()),
+            render_stats: RefCell::new(HashMap::new()),
+            graphics: RefCell::new(HashMap::new()),
+            present_modes: RefCell::new(HashMap::new()),
+            stats_overlay: RefCell::new(None),
+            latency_probe: RefCell::new(None),
+            click_audio: RefCell::new(None),
+            paused: RefCell::new(HashSet::new()),
+            intersection_hooks: RefCell::new(Vec::new()),
+        });
+
+        inner.listen();
+
+        let handle = WorkerHandle {
+            inner,
+            app: AppId::DEFAULT,
+        };
+        handle.forward_visibility()?;
+        handle.forward_media_preferences()?;
+        handle.forward_focus()?;
+        handle.forward_keyboard()?;
+
+        if let Some(fps) = target_fps {
+            handle.set_target_fps(fps);
+        }
+
+        if let Some(mode) = update_mode {
+            handle.set_update_mode(mode);
+        }
+
+        if let Some(scale) = ui_scale {
+            handle.set_ui_scale(scale);
+        }
+
+        for (name, enabled) in features {
+            handle.set_feature(&name, enabled);
+        }
+
+        Ok(handle)
+    }
+}
+
+/// Handle to running worker.
+///
+/// Messages sent before worker reports readiness are queued and delivered once it does.
+/// Cloning produces another handle to the same worker.
+#[derive(Clone)]
+pub struct WorkerHandle {
+    inner: Rc<Inner>,
+    app: AppId,
+}
+
+struct Inner {
+    // Replaced when worker is upgraded to a new build.
+    artifacts: RefCell<WorkerArtifacts>,
+    flavor: WorkerFlavor,
+    bootstrap: Bootstrap,
+    restart: Option<RestartPolicy>,
+    on_error: Option<Box<dyn Fn(&WorkerError)>>,
+    on_device_lost: Option<Box<dyn Fn(&WorkerHandle)>>,
+    on_ready: Option<Box<dyn Fn(&WorkerHandle)>>,
+    on_memory_warning: Option<Box<dyn Fn(&WorkerHandle, MemoryWarning)>>,
+    on_first_frame: Option<Box<dyn Fn(&WorkerHandle)>>,
+    on_wasm_loading: Option<Box<dyn Fn(u64, Option<u64>)>>,
+    // Taken down on the first frame.
+    splash: RefCell<Option<Splash>>,
+    attempts: Cell<u32>,
+    worker: RefCell<Endpoint>,
+    // `None` once worker is ready.
+    pending: RefCell<Option<Vec<Queued>>>,
+    // High-frequency input waiting for the next animation frame, see `send_coalesced`.
+    coalescing: RefCell<Vec<Queued>>,
+    coalesce_scheduled: Cell<bool>,
+    coalesced: Cell<u64>,
+    // Page's ends of subsystem channels, empty until worker is ready.
+    ports: RefCell<HashMap<Port, MessagePort>>,
+    // Page's end of the channel animation frames are ticked over, `None` until worker is ready.
+    frame_clock: RefCell<Option<MessagePort>>,
+    // Apps updating on animation frames, clock only ticks while there are any.
+    frame_clock_apps: RefCell<BTreeSet<AppId>>,
+    frame_clock_running: Cell<bool>,
+    shutting_down: Cell<bool>,
+    // Last state of feature toggles reported by worker.
+    features: RefCell<BTreeMap<String, bool>>,
+    boot_flags: BootFlags,
+    // Reported by worker in its ready message.
+    threads: Cell<u32>,
+    // `None` until worker is ready.
+    capabilities: Cell<Option<CapabilityReport>>,
+    next_id: Cell<u32>,
+    accessibility: Option<AccessibilityMirror>,
+    // App being inspected and the panel showing it, `None` while inspector is closed.
+    inspector: RefCell<Option<(AppId, InspectorPanel)>>,
+    // Reports waiting to be downloaded, oldest first.
+    anomalies: RefCell<VecDeque<String>>,
+    warm_spare: bool,
+    capture_right_click: bool,
+    responsive_canvas: bool,
+    // Name of shared worker, `None` for dedicated ones.
+    shared: Option<String>,
+    spare: RefCell<Option<Spare>>,
+    // `None` unless worker is being upgraded.
+    upgrade: RefCell<Option<Upgrade>>,
+    next_preview: Cell<u32>,
+    // Callbacks waiting for asset previews, keyed by request.
+    previews: RefCell<BTreeMap<u32, PreviewCallback>>,
+    settings_prefix: String,
+    // Apps page attached canvases for, they all get page-wide messages.
+    apps: RefCell<BTreeSet<AppId>>,
+    // Canvas elements of attached views, pointer lock has to be requested on them.
+    canvases: RefCell<HashMap<(AppId, ViewId), HtmlCanvasElement>>,
+    // Drawing buffer sizes of attached views, elements keep reporting the original one.
+    buffer_sizes: RefCell<HashMap<(AppId, ViewId), Rc<Cell<(u32, u32)>>>>,
+    // Hidden text elements of attached views, IME only works on a focused one.
+    text_inputs: RefCell<HashMap<(AppId, ViewId), HtmlTextAreaElement>>,
+    // Elements placed over canvases and latest positions of their anchors, by anchor id.
+    dom_anchors: RefCell<HashMap<(AppId, ViewId), HashMap<String, DomAnchor>>>,
+    // Labels of keys by `KeyboardEvent.code`, `None` until browser reports them, if it ever does.
+    keyboard_layout: RefCell<Option<Vec<(String, String)>>>,
+    // Orientation and motion events are forwarded.
+    motion_sensors: Cell<bool>,
+    // `None` while traffic logging is off.
+    traffic: RefCell<Option<TrafficLog>>,
+    // Latest bridge diagnostics forwarded by apps.
+    bridge_stats: RefCell<HashMap<AppId, BridgeStats>>,
+    // Latest render statistics forwarded by apps.
+    render_stats: RefCell<HashMap<AppId, RenderStats>>,
+    // Graphics settings each app reported to be using.
+    graphics: RefCell<HashMap<AppId, GraphicsOptions>>,
+    // Whether each app presents in sync with display refresh, once it reported it.
+    present_modes: RefCell<HashMap<AppId, SurfacePresentMode>>,
+    // Latest frame stats of apps which were asked for them.
+    frame_stats: RefCell<HashMap<AppId, FrameStats>>,
+    // App shown in the stats overlay, `None` while overlay is hidden.
+    stats_overlay: RefCell<Option<(AppId, StatsOverlay)>>,
+    // `None` while input latency isn't measured.
+    latency_probe: RefCell<Option<LatencyProbe>>,
+    // Plays clicks of latency test scene, created with the first one.
+    click_audio: RefCell<Option<web_sys::AudioContext>>,
+    // File name for traffic log export waiting on worker's half of the log.
+    traffic_export: RefCell<Option<String>>,
+    // File names for scene exports waiting on their app.
+    scene_exports: RefCell<HashMap<AppId, String>>,
+    // Callbacks waiting for recordings of their app.
+    recordings: RefCell<HashMap<AppId, RecordingCallback>>,
+    // Apps paused with `WorkerHandle::pause`, as opposed to ones manager paused while out of sight.
+    paused: RefCell<HashSet<AppId>>,
+    // Called whenever a canvas scrolls in or out of sight.
+    intersection_hooks: RefCell<Vec<IntersectionHook>>,
+}
+
+/// Element placed over a canvas, see [`WorkerHandle::anchor`].
+#[derive(Default)]
+struct DomAnchor {
+    element: Option<HtmlElement>,
+    // In pixels of canvas drawing buffer, `None` while anchor is out of sight.
+    position: Option<Vec2>,
+}
+
+impl DomAnchor {
+    /// Forget anchors which are out of sight and have no element,
+    /// worker reports them as such once their entities are gone.
+    fn prune(anchors: &mut HashMap<String, DomAnchor>) {
+        anchors.retain(|_, anchor| anchor.element.is_some() || anchor.position.is_some());
+    }
+}
+
+/// Message waiting to be posted to the worker.
+struct Queued {
+    app: AppId,
+    msg: HostMessage,
+    /// When page came up with it, by [`precise_now`].
+    captured_at: f64,
+}
+
+/// Messages kept in page's half of traffic log, worker keeps as many by default.
+const TRAFFIC_LOG_CAPACITY: usize = 1024;
+
+/// Input messages sent to the worker and when they were captured, waiting for their frame to be submitted.
+#[derive(Default)]
+struct LatencyProbe {
+    sent: VecDeque<(AppId, CorrelationId, f64)>,
+    samples: VecDeque<f64>,
+}
+
+impl LatencyProbe {
+    // Input which never makes it into a frame, e.g. because app ignores it, shouldn't pile up.
+    const MAX_SENT: usize = 1024;
+    const MAX_SAMPLES: usize = 4096;
+
+    fn sent(&mut self, app: AppId, id: CorrelationId, captured_at: f64) {
+        if self.sent.len() == Self::MAX_SENT {
+            self.sent.pop_front();
+        }
+        self.sent.push_back((app, id, captured_at));
+    }
+
+    fn presented(&mut self, app: AppId, ids: &[CorrelationId]) {
+        let now = precise_now();
+        let Self { sent, samples } = self;
+
+        sent.retain(|&(sent_app, id, at)| {
+            if sent_app != app || !ids.contains(&id) {
+                return true;
+            }
+
+            if samples.len() == Self::MAX_SAMPLES {
+                samples.pop_front();
+            }
+            samples.push_back(now - at);
+            false
+        });
+    }
+
+    fn report(&self) -> Option<LatencyReport> {
+        let mut samples: Vec<_> = self.samples.iter().copied().collect();
+        samples.sort_by(f64::total_cmp);
+
+        let max_ms = *samples.last()?;
+        Some(LatencyReport {
+            count: samples.len(),
+            p50_ms: percentile(&samples, 0.50),
+            p95_ms: percentile(&samples, 0.95),
+            p99_ms: percentile(&samples, 0.99),
+            max_ms,
+        })
+    }
+}
+
+/// Nearest-rank percentile of sorted samples, which must not be empty.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Time from page capturing input for the worker to the frame handling it being submitted.
+///
+/// Frame still has to reach the compositor, which isn't observable from either side,
+/// so actual latency is higher by a frame or two.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencyReport {
+    /// Number of measured messages, only the most recent few thousands are kept.
+    pub count: usize,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+type PreviewCallback = Box<dyn FnOnce(Result<ImageBitmap, String>)>;
+
+type RecordingCallback = Box<dyn FnOnce(ArrayBuffer)>;
+
+type IntersectionHook = Rc<dyn Fn(&WorkerHandle, ViewId, bool)>;
+
+/// Worker page talks to.
+#[derive(Clone)]
+enum Endpoint {
+    Dedicated(Worker),
+    /// Only this tab's connection belongs to the page, worker itself may be shared by other tabs.
+    Shared(SharedWorker),
+}
+
+impl Endpoint {
+    fn spawn(
+        flavor: WorkerFlavor,
+        artifacts: &WorkerArtifacts,
+        bootstrap: &Bootstrap,
+        shared: Option<&str>,
+    ) -> Result<Self, SpawnError> {
+        match (shared, bootstrap) {
+            (None, bootstrap) => flavor.spawn(artifacts, bootstrap).map(Endpoint::Dedicated),
+            (Some(name), Bootstrap::Loader(url)) => {
+                shared_worker_new_static(url, artifacts, flavor, name).map(Endpoint::Shared)
+            }
+            (Some(_), _) => Err(SpawnError::SharedUnsupported(
+                "bootstrap other than static loader",
+            )),
+        }
+    }
+
+    fn post_message_with_transfer(&self, msg: &JsValue, transfer: &JsValue) -> Result<(), JsValue> {
+        match self {
+            Endpoint::Dedicated(worker) => worker.post_message_with_transfer(msg, transfer),
+            Endpoint::Shared(worker) => worker.port().post_message_with_transferable(msg, transfer),
+        }
+    }
+
+    /// Terminate dedicated worker, or disconnect from shared one.
+    fn terminate(&self) {
+        match self {
+            Endpoint::Dedicated(worker) => worker.terminate(),
+            Endpoint::Shared(worker) => worker.port().close(),
+        }
+    }
+
+    fn set_onmessage(&self, f: Option<&js_sys::Function>) {
+        match self {
+            Endpoint::Dedicated(worker) => worker.set_onmessage(f),
+            Endpoint::Shared(worker) => worker.port().set_onmessage(f),
+        }
+    }
+
+    fn set_onmessageerror(&self, f: Option<&js_sys::Function>) {
+        match self {
+            Endpoint::Dedicated(worker) => worker.set_onmessageerror(f),
+            Endpoint::Shared(worker) => worker.port().set_onmessageerror(f),
+        }
+    }
+
+    fn set_onerror(&self, f: Option<&js_sys::Function>) {
+        match self {
+            Endpoint::Dedicated(worker) => worker.set_onerror(f),
+            Endpoint::Shared(worker) => worker.set_onerror(f),
+        }
+    }
+
+    fn onmessage(&self) -> Option<js_sys::Function> {
+        match self {
+            Endpoint::Dedicated(worker) => worker.onmessage(),
+            Endpoint::Shared(worker) => worker.port().onmessage(),
+        }
+    }
+
+    fn onmessageerror(&self) -> Option<js_sys::Function> {
+        match self {
+            Endpoint::Dedicated(worker) => worker.onmessageerror(),
+            Endpoint::Shared(worker) => worker.port().onmessageerror(),
+        }
+    }
+}
+
+/// Worker spawned ahead of time, waiting to replace the current one.
+struct Spare {
+    worker: Worker,
+    // Ready message is consumed before the spare is promoted, so it has to be remembered.
+    // Holds thread count and capabilities it reported.
+    ready: Rc<Cell<Option<(u32, Capabilities)>>>,
+}
+
+/// Worker running a new build, waiting for the current one to hand its state over.
+struct Upgrade {
+    worker: Worker,
+    artifacts: WorkerArtifacts,
+    app: AppId,
+    canvas: HtmlCanvasElement,
+    // Thread count and capabilities new worker reported, `None` until it is ready.
+    ready: Option<(u32, Capabilities)>,
+    // `None` until current app answers.
+    snapshot: Option<Result<String, String>>,
+}
+
+/// How long worker being replaced gets to release GPU resources before it is terminated anyway.
+const RETIRE_TIMEOUT_MS: i32 = 2000;
+
+/// How long app gets to answer with its snapshot before upgrade is abandoned.
+const SNAPSHOT_TIMEOUT_MS: u32 = 10_000;
+
+impl WorkerHandle {
+    /// Spawn worker from trunk artifacts with given name.
+    pub fn spawn(name: &str) -> Result<Self, SpawnError> {
+        WorkerBuilder::new(name)?.spawn()
+    }
+
+    /// Underlying worker object.
+    ///
+    /// It changes every time worker is restarted.
+    ///
+    /// # Panics
+    ///
+    /// When worker is shared, see [`shared_worker`](Self::shared_worker).
+    pub fn worker(&self) -> Worker {
+        match &*self.inner.worker.borrow() {
+            Endpoint::Dedicated(worker) => worker.clone(),
+            Endpoint::Shared(_) => panic!("worker is shared, use `shared_worker` instead"),
+        }
+    }
+
+    /// Underlying shared worker object, `None` unless spawned with [`WorkerBuilder::shared`].
+    pub fn shared_worker(&self) -> Option<SharedWorker> {
+        match &*self.inner.worker.borrow() {
+            Endpoint::Dedicated(_) => None,
+            Endpoint::Shared(worker) => Some(worker.clone()),
+        }
+    }
+
+    /// Handle to another app running in the same worker.
+    ///
+    /// Worker builds the app once its primary view is attached,
+    /// see `worker::start_apps`. Apps don't share views or messages,
+    /// but feature toggles, anomaly reports and accessibility mirror belong to the whole worker.
+    pub fn app(&self, app: AppId) -> WorkerHandle {
+        WorkerHandle {
+            inner: Rc::clone(&self.inner),
+            app,
+        }
+    }
+
+    /// App this handle talks to.
+    pub fn app_id(&self) -> AppId {
+        self.app
+    }
+
+    /// Send message to worker.
+    pub fn send(&self, msg: HostMessage) {
+        self.inner.send(self.app, msg);
+    }
+
+    /// Send message to worker on the next animation frame, merging it with ones sent before.
+    ///
+    /// Meant for input firing hundreds of times per second, like pointer moves.
+    /// Messages which can't be merged go out as usual, see [`HostMessage::coalesce`].
+    /// Sending anything through [`send`](Self::send) flushes waiting messages first, so order is kept.
+    pub fn send_coalesced(&self, msg: HostMessage) {
+        self.inner.send_coalesced(self.app, msg);
+    }
+
+    /// Latest bridge diagnostics of the app.
+    ///
+    /// Only available while app runs `BridgeDiagnosticsPlugin` with forwarding to the page enabled.
+    pub fn bridge_stats(&self) -> Option<BridgeStats> {
+        self.inner.bridge_stats.borrow().get(&self.app).copied()
+    }
+
+    /// Latest render statistics of the app.
+    ///
+    /// Only available while app runs `RenderStatsPlugin` with forwarding to the page enabled.
+    pub fn render_stats(&self) -> Option<RenderStats> {
+        self.inner.render_stats.borrow().get(&self.app).copied()
+    }
+
+    /// Graphics settings app is using, as of its latest report.
+    ///
+    /// Settings device can't do are replaced by ones it can, so this may differ from what was asked for.
+    pub fn graphics(&self) -> Option<GraphicsOptions> {
+        self.inner.graphics.borrow().get(&self.app).copied()
+    }
+
+    /// Latest frame rate and entity count of the app.
+    ///
+    /// Only available while stats are turned on, see [`set_frame_stats`](Self::set_frame_stats).
+    pub fn frame_stats(&self) -> Option<FrameStats> {
+        self.inner.frame_stats.borrow().get(&self.app).copied()
+    }
+
+    /// Start or stop receiving frame stats from the app.
+    ///
+    /// Stats come from `FrameTimeDiagnosticsPlugin` a few times per second.
+    /// Setting isn't kept over restarts.
+    pub fn set_frame_stats(&self, enabled: bool) {
+        if !enabled {
+            self.inner.frame_stats.borrow_mut().remove(&self.app);
+        }
+        self.send(HostMessage::SetFrameStats(enabled));
+    }
+
+    /// Show app's frame rate and entity count in the corner of `container`.
+    ///
+    /// Container should be positioned, overlay is placed absolutely inside it.
+    /// Only one app is shown at a time, showing stats again replaces the previous overlay.
+    pub fn show_frame_stats(&self, container: &Element) -> Result<(), SpawnError> {
+        self.hide_frame_stats();
+
+        let overlay = StatsOverlay::new(container)?;
+        *self.inner.stats_overlay.borrow_mut() = Some((self.app, overlay));
+        self.set_frame_stats(true);
+
+        Ok(())
+    }
+
+    /// Remove the stats overlay and stop receiving stats for it.
+    pub fn hide_frame_stats(&self) {
+        let previous = self.inner.stats_overlay.borrow_mut().take();
+        if let Some((app, overlay)) = previous {
+            drop(overlay);
+            self.inner.frame_stats.borrow_mut().remove(&app);
+            self.inner.send(app, HostMessage::SetFrameStats(false));
+        }
+    }
+
+    /// Start or stop measuring latency of input sent to the app.
+    ///
+    /// Starting discards previous measurements, see [`latency_report`](Self::latency_report).
+    pub fn set_latency_measurement(&self, enabled: bool) {
+        *self.inner.latency_probe.borrow_mut() = enabled.then(LatencyProbe::default);
+
+        for app in self.inner.apps.borrow().iter() {
+            self.inner.send(*app, HostMessage::SetLatencyProbe(enabled));
+        }
+    }
+
+    /// Input latency measured so far, `None` if measurement is off or nothing was measured yet.
+    ///
+    /// Covers input sent to all apps of the worker.
+    pub fn latency_report(&self) -> Option<LatencyReport> {
+        self.inner.latency_probe.borrow().as_ref()?.report()
+    }
+
+    /// Threads worker runs on, its own included.
+    ///
+    /// Worker only gets more than one when it is built with `atomics` target feature
+    /// and page is cross-origin isolated. Known once worker is ready, `1` until then.
+    pub fn threads(&self) -> u32 {
+        self.inner.threads.get()
+    }
+
+    /// What page and worker support, `None` until worker is ready.
+    ///
+    /// Worker gets the same report as [`CapabilityReport`] resource.
+    pub fn capabilities(&self) -> Option<CapabilityReport> {
+        self.inner.capabilities.get()
+    }
+
+    /// Show app's entities in a collapsible tree inside `container`.
+    ///
+    /// Tree follows the worker at [`RemoteInspectorPlugin`](crate::worker::inspector::RemoteInspectorPlugin)'s pace,
+    /// components are shown as RON and can be edited in place.
+    /// Only one app is inspected at a time, opening inspector again replaces the previous one.
+    /// Query isn't kept over restarts, open inspector again once worker is ready.
+    pub fn open_inspector(
+        &self,
+        container: &Element,
+        query: InspectQuery,
+    ) -> Result<(), SpawnError> {
+        self.close_inspector();
+
+        let on_edit = {
+            let inner = Rc::downgrade(&self.inner);
+            let app = self.app;
+
+            move |entity, component, value| {
+                if let Some(inner) = Weak::upgrade(&inner) {
+                    inner.send(
+                        app,
+                        HostMessage::InspectorEdit {
+                            entity,
+                            component,
+                            value,
+                        },
+                    );
+                }
+            }
+        };
+        let panel = InspectorPanel::new(container, on_edit)?;
+
+        *self.inner.inspector.borrow_mut() = Some((self.app, panel));
+        self.send(HostMessage::SetInspector(Some(query)));
+
+        Ok(())
+    }
+
+    /// Stop inspection and remove the panel.
+    pub fn close_inspector(&self) {
+        let previous = self.inner.inspector.borrow_mut().take();
+        if let Some((app, panel)) = previous {
+            drop(panel);
+            self.inner.send(app, HostMessage::SetInspector(None));
+        }
+    }
+
+    /// Number of messages merged into the one before them instead of being sent.
+    pub fn coalesced_messages(&self) -> u64 {
+        self.inner.coalesced.get()
+    }
+
+    /// Transfer control over canvas to the worker and make primary view render there.
+    ///
+    /// Canvas element can only give up control once,
+    /// so moving the view around requires a fresh element each time.
+    pub fn attach(&self, canvas: &HtmlCanvasElement) -> Result<(), SpawnError> {
+        self.attach_view(ViewId::PRIMARY, canvas)
+    }
+
+    /// Make worker drop rendering surface of primary view.
+    ///
+    /// Simulation keeps running, call [`attach`](Self::attach) to get the picture back.
+    pub fn detach(&self) {
+        self.detach_view(ViewId::PRIMARY);
+    }
+
+    /// Transfer control over canvas to the worker and make given view render there.
+    ///
+    /// Views other than primary get their own window entity the first time they are attached.
+    ///
+    /// Pointer events and files dropped on the canvas are forwarded to the worker from now on.
+    pub fn attach_view(&self, view: ViewId, canvas: &HtmlCanvasElement) -> Result<(), SpawnError> {
+        // Element keeps its original size attributes once control is transferred,
+        // so size of the drawing buffer has to be tracked separately.
+        let size = Rc::new(Cell::new((canvas.width(), canvas.height())));
+
+        self.forward_pointer(view, canvas, Rc::clone(&size))?;
+        self.forward_wheel(view, canvas, Rc::clone(&size))?;
+        self.forward_pointer_lock(view, canvas)?;
+        self.forward_fullscreen(view, canvas, Rc::clone(&size))?;
+        self.forward_drops(view, canvas)?;
+        if self.inner.capture_right_click {
+            self.suppress_context_menu(canvas)?;
+        }
+        self.forward_intersection(view, canvas)?;
+
+        let text_input = self.forward_ime(view)?;
+        if let Some(previous) = self
+            .inner
+            .text_inputs
+            .borrow_mut()
+            .insert((self.app, view), text_input)
+        {
+            previous.remove();
+        }
+
+        self.inner
+            .buffer_sizes
+            .borrow_mut()
+            .insert((self.app, view), size);
+        let previous = self
+            .inner
+            .canvases
+            .borrow_mut()
+            .insert((self.app, view), canvas.clone());
+
+        if self.inner.apps.borrow_mut().insert(self.app) {
+            self.inner.introduce(self.app);
+        }
+
+        // Worker only sends cursor when it changes, fresh canvas has to pick it up from the old one.
+        if let Some(previous) = previous {
+            if let Ok(cursor) = previous.style().get_property_value("cursor") {
+                let _ = canvas.style().set_property("cursor", &cursor);
+            }
+        }
+
+        // We cannot pass canvas element to worker directly, instead we have to convert it to OffscreenCanvas.
+        let canvas = canvas
+            .transfer_control_to_offscreen()
+            .map_err(SpawnError::Transfer)?;
+
+        self.send(HostMessage::Attach {
+            view,
+            canvas: Transferable::new(canvas),
+        });
+        // App can't present anything before it has a canvas, pages may attach long after spawning.
+        self.inner.arm_splash_timeout();
+        Ok(())
+    }
+
+    /// Make worker drop rendering surface of given view.
+    pub fn detach_view(&self, view: ViewId) {
+        self.send(HostMessage::Detach { view });
+    }
+
+    /// Keep element over the entity with [`DomAnchor`](crate::worker::dom_anchor::DomAnchor) of given id.
+    ///
+    /// Element is absolutely positioned relative to canvas' offset parent,
+    /// so it should share one with the canvas, e.g. be its sibling in a positioned container.
+    /// Its top-left corner lands on the anchor, use CSS `transform` to center it.
+    /// Element is hidden while anchor is out of sight.
+    pub fn anchor(&self, view: ViewId, id: &str, element: &HtmlElement) {
+        let style = element.style();
+        let _ = style.set_property("position", "absolute");
+        let _ = style.set_property("pointer-events", "none");
+
+        let mut anchors = self.inner.dom_anchors.borrow_mut();
+        let anchor = anchors
+            .entry((self.app, view))
+            .or_default()
+            .entry(id.to_owned())
+            .or_default();
+        anchor.element = Some(element.clone());
+
+        // Worker only sends positions when they change, element has to catch up with the last one.
+        self.inner.place_anchor(self.app, view, anchor);
+    }
+
+    /// Stop moving element previously passed to [`anchor`](Self::anchor).
+    pub fn unanchor(&self, view: ViewId, id: &str) {
+        let mut anchors = self.inner.dom_anchors.borrow_mut();
+        if let Some(view_anchors) = anchors.get_mut(&(self.app, view)) {
+            if let Some(anchor) = view_anchors.get_mut(id) {
+                anchor.element = None;
+            }
+            DomAnchor::prune(view_anchors);
+            if view_anchors.is_empty() {
+                anchors.remove(&(self.app, view));
+            }
+        }
+    }
+
+    /// Forward device orientation and motion to the worker, `f` is told whether user allowed it.
+    ///
+    /// Safari asks user for permission first, and only when called from an input handler such as a click,
+    /// other browsers allow it right away.
+    /// Readings go to every app of the page from then on.
+    pub fn enable_motion_sensors(&self, f: impl FnOnce(bool) + 'static) {
+        use js_sys::{Function, Promise, Reflect};
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::JsFuture;
+
+        let Some(window) = web_sys::window() else {
+            f(false);
+            return;
+        };
+
+        // Permission covers both kinds of events, but only exists where it has to be asked for.
+        let request = Reflect::get(&window, &"DeviceOrientationEvent".into())
+            .ok()
+            .and_then(|class| {
+                let function = Reflect::get(&class, &"requestPermission".into()).ok()?;
+                function.dyn_into::<Function>().ok()?.call0(&class).ok()
+            })
+            .and_then(|promise| promise.dyn_into::<Promise>().ok());
+
+        let inner = Rc::clone(&self.inner);
+        wasm_bindgen_futures::spawn_local(async move {
+            let granted = match request {
+                Some(request) => match JsFuture::from(request).await {
+                    Ok(state) => state.as_string().as_deref() == Some("granted"),
+                    Err(err) => {
+                        web_sys::console::warn_1(&err);
+                        false
+                    }
+                },
+                None => true,
+            };
+
+            if granted && !inner.motion_sensors.replace(true) {
+                if let Err(err) = inner.forward_motion_sensors(&window) {
+                    web_sys::console::warn_1(&format!("{err}").into());
+                }
+            }
+            f(granted);
+        });
+    }
+
+    /// Cap app at given frame rate.
+    pub fn set_target_fps(&self, fps: u32) {
+        self.send(HostMessage::SetTargetFps(fps));
+    }
+
+    /// Present frames in sync with display refresh or as soon as they are ready, e.g. for benchmarks.
+    ///
+    /// Surface may not support turning vsync off, see [`present_mode`](Self::present_mode) for what was applied.
+    pub fn set_vsync(&self, vsync: bool) {
+        self.send(HostMessage::SetVsync(vsync));
+    }
+
+    /// Present mode app's surface settled on, `None` until page asked to change it.
+    pub fn present_mode(&self) -> Option<SurfacePresentMode> {
+        self.inner.present_modes.borrow().get(&self.app).copied()
+    }
+
+    /// Whether app presents in sync with display refresh, `None` until page asked to change it.
+    pub fn vsync(&self) -> Option<bool> {
+        self.present_mode().map(|mode| mode.is_vsync())
+    }
+
+    /// Make app's time run slower or faster than real time, 1 is normal speed and 0 stops it.
+    ///
+    /// Unlike [`pause`](Self::pause) app keeps updating, only time stands still.
+    pub fn set_time_scale(&self, scale: f32) {
+        self.send(HostMessage::SetTimeScale(scale));
+    }
+
+    /// Switch between continuous and reactive updates.
+    pub fn set_update_mode(&self, mode: UpdateMode) {
+        {
+            let mut apps = self.inner.frame_clock_apps.borrow_mut();
+            if mode == UpdateMode::AnimationFrame {
+                apps.insert(self.app);
+            } else {
+                apps.remove(&self.app);
+            }
+        }
+
+        self.send(HostMessage::SetUpdateMode(mode));
+        self.inner.run_frame_clock();
+    }
+
+    /// Make reactive app update at least once.
+    ///
+    /// Any message sent to worker does the same,
+    /// so there is no need to call it after e.g. attaching a canvas.
+    pub fn request_redraw(&self) {
+        self.send(HostMessage::RequestRedraw);
+    }
+
+    /// Set share of worker time this app gets relative to other apps of the worker.
+    ///
+    /// All apps start with share of 1, give foreground view more to keep it smooth
+    /// while background widgets are busy.
+    pub fn set_budget_share(&self, share: f64) {
+        self.send(HostMessage::SetBudgetShare(share));
+    }
+
+    /// Freeze the app, it stops updating until [`resume`](Self::resume) while messages wait.
+    pub fn pause(&self) {
+        self.inner.paused.borrow_mut().insert(self.app);
+        self.send(HostMessage::Pause);
+    }
+
+    /// Continue updating paused app.
+    pub fn resume(&self) {
+        self.inner.paused.borrow_mut().remove(&self.app);
+        self.send(HostMessage::Resume);
+    }
+
+    /// Whether app was paused with [`pause`](Self::pause) and not resumed since.
+    fn paused_by_page(&self) -> bool {
+        self.inner.paused.borrow().contains(&self.app)
+    }
+
+    /// Call `f` with view and whether its canvas is in sight whenever that changes.
+    fn on_intersection(&self, f: impl Fn(&WorkerHandle, ViewId, bool) + 'static) {
+        self.inner.intersection_hooks.borrow_mut().push(Rc::new(f));
+    }
+
+    /// Advance paused app by given number of frames, each one frame of its pacing long.
+    pub fn step_frames(&self, frames: u32) {
+        self.send(HostMessage::StepFrames(frames));
+    }
+
+    /// Run a command registered in app's [`ConsoleCommands`](crate::worker::console::ConsoleCommands).
+    ///
+    /// Output is printed to browser's console once app answers.
+    pub fn console_command(&self, line: &str) {
+        self.send(HostMessage::ConsoleCommand(line.to_owned()));
+    }
+
+    /// Make debugging commands callable from browser's console as `window[name]`.
+    ///
+    /// E.g. with name `bevy`: `bevy.pause()`, `bevy.step(10)`, `bevy.resume()`, `bevy.timeScale(0.25)`
+    /// and `bevy.command("help")` for commands app registered itself.
+    pub fn expose_to_console(&self, name: &str) -> Result<(), SpawnError> {
+        use js_sys::{Object, Reflect};
+        use wasm_bindgen::prelude::Closure;
+
+        let commands: [(&str, fn(&WorkerHandle, JsValue)); 5] = [
+            ("pause", |worker, _| worker.pause()),
+            ("resume", |worker, _| worker.resume()),
+            ("step", |worker, frames| {
+                worker.step_frames(frames.as_f64().map_or(1, |frames| frames as u32))
+            }),
+            ("timeScale", |worker, scale| {
+                worker.set_time_scale(scale.as_f64().map_or(1.0, |scale| scale as f32))
+            }),
+            ("command", |worker, line| {
+                worker.console_command(&line.as_string().unwrap_or_default())
+            }),
+        ];
+
+        let window = web_sys::window().ok_or(SpawnError::NoWindow)?;
+        let object = Object::new();
+
+        for (command, f) in commands {
+            // Console outliving the worker shouldn't keep it alive.
+            let inner = Rc::downgrade(&self.inner);
+            let app = self.app;
+
+            let callback = Closure::wrap(Box::new(move |arg: JsValue| {
+                if let Some(inner) = inner.upgrade() {
+                    f(&WorkerHandle { inner, app }, arg);
+                }
+            }) as Box<dyn Fn(JsValue)>);
+            Reflect::set(&object, &command.into(), &callback.into_js_value())
+                .map_err(SpawnError::Dom)?;
+        }
+
+        Reflect::set(&window, &name.into(), &object).map_err(SpawnError::Dom)?;
+
+        Ok(())
+    }
+
+    /// Multiply size of UI and text, on top of device pixel ratio.
+    pub fn set_ui_scale(&self, scale: f64) {
+        self.send(HostMessage::SetUiScale(scale));
+    }
+
+    /// Change MSAA, HDR or tonemapping of the app, settings left `None` stay as they are.
+    ///
+    /// Worker checks them against what the GPU supports, see [`graphics`](Self::graphics) for the outcome.
+    pub fn set_graphics(&self, options: GraphicsOptions) {
+        self.send(HostMessage::SetGraphics(options));
+    }
+
+    /// Flip feature toggle inside the worker.
+    pub fn set_feature(&self, name: &str, enabled: bool) {
+        self.send(HostMessage::SetFeature {
+            name: name.to_owned(),
+            enabled,
+        });
+    }
+
+    /// Make worker load its config file again.
+    pub fn reload_config(&self) {
+        self.send(HostMessage::ReloadConfig);
+    }
+
+    /// Make file contents loadable by worker's asset server under `inmem://{path}`.
+    pub fn send_asset(&self, path: &str, bytes: &[u8]) {
+        let bytes = js_sys::Uint8Array::from(bytes).buffer();
+        self.send_asset_buffer(path, bytes);
+    }
+
+    /// Same as [`send_asset`](Self::send_asset), but transfers the buffer instead of copying it.
+    pub fn send_asset_buffer(&self, path: &str, bytes: ArrayBuffer) {
+        self.send(HostMessage::AssetBytes {
+            path: path.to_owned(),
+            bytes: Transferable::new(bytes),
+        });
+    }
+
+    /// Decode image with `createImageBitmap` and make it loadable by worker's asset server under `inmem://{path}`.
+    ///
+    /// Browser decodes images faster than wasm does, and off the worker's thread.
+    /// Path should end with `.bitmap`, e.g. `hero.png.bitmap`, so worker loads it as an already decoded image.
+    /// Images browser can't decode are reported to console.
+    pub fn send_image(&self, path: &str, image: &web_sys::Blob) {
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::JsFuture;
+        use web_sys::{ColorSpaceConversion, ImageBitmapOptions, PremultiplyAlpha};
+
+        // Keep pixels as they are in the file, like image loaders inside the worker do.
+        let mut options = ImageBitmapOptions::new();
+        options
+            .premultiply_alpha(PremultiplyAlpha::None)
+            .color_space_conversion(ColorSpaceConversion::None);
+
+        let promise = web_sys::window()
+            .ok_or(SpawnError::NoWindow)
+            .and_then(|window| {
+                window
+                    .create_image_bitmap_with_blob_and_image_bitmap_options(image, &options)
+                    .map_err(SpawnError::Dom)
+            });
+        let promise = match promise {
+            Ok(promise) => promise,
+            Err(err) => {
+                web_sys::console::warn_1(&format!("failed to decode `{path}`: {err}").into());
+                return;
+            }
+        };
+
+        let handle = self.clone();
+        let path = path.to_owned();
+        wasm_bindgen_futures::spawn_local(async move {
+            match JsFuture::from(promise).await {
+                Ok(bitmap) => handle.send_image_bitmap(&path, bitmap.unchecked_into()),
+                Err(err) => {
+                    web_sys::console::warn_2(&format!("failed to decode `{path}`:").into(), &err)
+                }
+            }
+        });
+    }
+
+    /// Same as [`send_image`](Self::send_image), but for an image page already decoded.
+    ///
+    /// Pixels are read on the page and sent as a `.bitmap` file, see
+    /// [`ImageBitmapLoader`](crate::worker::image_bitmap::ImageBitmapLoader) for its format.
+    /// Bitmap is closed afterwards, so it becomes unusable on page side.
+    pub fn send_image_bitmap(&self, path: &str, bitmap: ImageBitmap) {
+        let file = bitmap_file(&bitmap);
+        // Bitmap holds on to decoded pixels until closed.
+        bitmap.close();
+
+        match file {
+            Ok(buffer) => self.send_asset_buffer(path, buffer),
+            Err(err) => web_sys::console::warn_1(
+                &format!("failed to read pixels of `{path}`: {err}").into(),
+            ),
+        }
+    }
+
+    /// Get bitmap of image asset worker loaded from given path, e.g. to show it as a thumbnail.
+    ///
+    /// Worker loads the asset if it didn't already.
+    /// Callback receives an error if asset is not an image, or worker is restarted before answering.
+    pub fn request_asset_preview(
+        &self,
+        path: &str,
+        f: impl FnOnce(Result<ImageBitmap, String>) + 'static,
+    ) {
+        let request = self.inner.next_preview.get();
+        self.inner.next_preview.set(request.wrapping_add(1));
+        self.inner
+            .previews
+            .borrow_mut()
+            .insert(request, Box::new(f));
+
+        self.send(HostMessage::RequestAssetPreview {
+            request,
+            path: path.to_owned(),
+        });
+    }
+
+    /// Hand WebRTC data channel over to the worker, it shows up there under its label.
+    ///
+    /// Peer connection and signaling stay on the page, since workers can't create them,
+    /// but messages travel between the channel and the worker over a dedicated `MessagePort`
+    /// instead of the bridge.
+    /// Channel is handed over once it is open, closing it is reported to the worker as well.
+    pub fn attach_data_channel(&self, channel: &RtcDataChannel) -> Result<(), SpawnError> {
+        use js_sys::Array;
+        use wasm_bindgen::prelude::{Closure, JsCast};
+        use web_sys::{MessageChannel, MessageEvent, RtcDataChannelState, RtcDataChannelType};
+
+        channel.set_binary_type(RtcDataChannelType::Arraybuffer);
+
+        let ports = MessageChannel::new().map_err(SpawnError::Dom)?;
+        let label = channel.label();
+
+        // Worker -> channel.
+        let onport = {
+            let channel = channel.clone();
+
+            Closure::wrap(Box::new(move |event: MessageEvent| {
+                let data = event.data();
+
+                let result = if let Some(text) = data.as_string() {
+                    channel.send_with_str(&text)
+                } else if let Some(buffer) = data.dyn_ref::<ArrayBuffer>() {
+                    channel.send_with_array_buffer(buffer)
+                } else {
+                    return;
+                };
+
+                if let Err(err) = result {
+                    web_sys::console::warn_2(&"failed to send over data channel:".into(), &err);
+                }
+            }) as Box<dyn Fn(MessageEvent)>)
+        };
+        ports
+            .port1()
+            .set_onmessage(Some(onport.as_ref().unchecked_ref()));
+        onport.forget();
+
+        // Channel -> worker.
+        // Listeners are added rather than set, so page can keep its own handlers.
+        let onmessage = {
+            let port = ports.port1();
+
+            Closure::wrap(Box::new(move |event: MessageEvent| {
+                let data = event.data();
+
+                let result = if data.is_instance_of::<ArrayBuffer>() {
+                    port.post_message_with_transferable(&data, &Array::of1(&data))
+                } else {
+                    port.post_message(&data)
+                };
+
+                if let Err(err) = result {
+                    web_sys::console::warn_2(&"failed to relay data channel message:".into(), &err);
+                }
+            }) as Box<dyn Fn(MessageEvent)>)
+        };
+        channel
+            .add_event_listener_with_callback("message", onmessage.as_ref().unchecked_ref())
+            .map_err(SpawnError::Dom)?;
+        onmessage.forget();
+
+        let onclose = {
+            let handle = self.clone();
+            let label = label.clone();
+            let port = ports.port1();
+
+            Closure::wrap(Box::new(move || {
+                port.close();
+                handle.send(HostMessage::DataChannelClosed {
+                    label: label.clone(),
+                });
+            }) as Box<dyn Fn()>)
+        };
+        channel
+            .add_event_listener_with_callback("close", onclose.as_ref().unchecked_ref())
+            .map_err(SpawnError::Dom)?;
+        onclose.forget();
+
+        let handle = self.clone();
+        let port = ports.port2();
+        let attach = move || {
+            handle.send(HostMessage::DataChannel {
+                label,
+                port: Transferable::new(port),
+            });
+        };
+
+        if channel.ready_state() == RtcDataChannelState::Open {
+            attach();
+        } else {
+            let onopen = Closure::once(attach);
+            channel
+                .add_event_listener_with_callback("open", onopen.as_ref().unchecked_ref())
+                .map_err(SpawnError::Dom)?;
+            onopen.forget();
+        }
+
+        Ok(())
+    }
+
+    /// Drop every asset worker keeps in its persistent cache.
+    pub fn clear_asset_cache(&self) {
+        self.send(HostMessage::ClearAssetCache);
+    }
+
+    /// Draw debug geometry over the scene for given time, zero means a single frame.
+    pub fn debug_draw(&self, shapes: Vec<DebugShape>, duration_ms: u32) {
+        self.send(HostMessage::DebugDraw {
+            shapes,
+            duration_ms,
+        });
+    }
+
+    /// Take slow frame reports worker sent so far, each one is a JSON document.
+    ///
+    /// Worker only captures them while `anomaly_capture` feature is enabled.
+    /// Only the latest few reports are kept.
+    pub fn take_anomaly_reports(&self) -> Vec<String> {
+        self.inner.anomalies.borrow_mut().drain(..).collect()
+    }
+
+    /// Save queued slow frame reports as a JSON file through browser download.
+    ///
+    /// Returns `false` if there was nothing to save.
+    pub fn download_anomaly_reports(&self, filename: &str) -> Result<bool, SpawnError> {
+        let reports = self.take_anomaly_reports();
+        if reports.is_empty() {
+            return Ok(false);
+        }
+
+        download_json(&format!("[{}]", reports.join(",")), filename)?;
+
+        Ok(true)
+    }
+
+    /// Start or stop logging every message going through the bridge, on both sides.
+    ///
+    /// Stopping discards what was logged so far.
+    pub fn set_traffic_log(&self, enabled: bool) {
+        {
+            let mut traffic = self.inner.traffic.borrow_mut();
+
+            match (enabled, traffic.is_some()) {
+                (true, false) => *traffic = Some(TrafficLog::new(TRAFFIC_LOG_CAPACITY)),
+                (false, true) => *traffic = None,
+                _ => (),
+            }
+        }
+
+        self.send(HostMessage::SetTrafficLog(enabled));
+    }
+
+    /// Page's half of traffic log as JSON, `None` when logging is off.
+    pub fn traffic_log(&self) -> Option<String> {
+        self.inner
+            .traffic
+            .borrow()
+            .as_ref()
+            .map(TrafficLog::to_json)
+    }
+
+    /// Save traffic log of both sides as a JSON file through browser download.
+    ///
+    /// Worker's half has to be asked for, so download starts once it arrives.
+    pub fn export_traffic_log(&self, filename: &str) {
+        *self.inner.traffic_export.borrow_mut() = Some(filename.to_owned());
+        self.send(HostMessage::RequestTrafficLog);
+    }
+
+    /// Save app's entities as a `bevy_scene` file through browser download.
+    ///
+    /// With a list of component names, only those are saved, otherwise every reflected component is.
+    /// Download starts once the app answers.
+    pub fn export_scene(&self, filename: &str, components: Option<&[&str]>) {
+        self.inner
+            .scene_exports
+            .borrow_mut()
+            .insert(self.app, filename.to_owned());
+        self.send(HostMessage::ExportScene {
+            components: components
+                .map(|components| components.iter().map(|&name| name.to_owned()).collect()),
+        });
+    }
+
+    /// Spawn entities of a `bevy_scene` file in RON format into the app.
+    pub fn import_scene(&self, scene: &[u8]) {
+        let scene = js_sys::Uint8Array::from(scene).buffer();
+        self.import_scene_buffer(scene);
+    }
+
+    /// Same as [`import_scene`](Self::import_scene), but transfers the buffer instead of copying it.
+    pub fn import_scene_buffer(&self, scene: ArrayBuffer) {
+        self.send(HostMessage::ImportScene(Transferable::new(scene)));
+    }
+
+    /// Start or stop recording input and other messages driving the app, see [`HostMessage::is_replayable`].
+    ///
+    /// Stopping discards what was recorded so far.
+    pub fn set_recording(&self, enabled: bool) {
+        self.send(HostMessage::SetRecording(enabled));
+    }
+
+    /// Ask app for its recording, `f` gets it as a JSON document once it arrives.
+    ///
+    /// Asking again before the answer arrives replaces `f`.
+    pub fn take_recording(&self, f: impl FnOnce(ArrayBuffer) + 'static) {
+        self.inner
+            .recordings
+            .borrow_mut()
+            .insert(self.app, Box::new(f));
+        self.send(HostMessage::RequestRecording);
+    }
+
+    /// Save app's recording as a JSON file through browser download.
+    pub fn export_recording(&self, filename: &str) {
+        let filename = filename.to_owned();
+
+        self.take_recording(move |recording| {
+            if let Err(err) = download(&recording, "application/json", &filename) {
+                web_sys::console::warn_1(&format!("failed to export recording: {err}").into());
+            }
+        });
+    }
+
+    /// Feed recording back into the app, one simulation step (or frame, for apps without one) per frame.
+    ///
+    /// App should update continuously, so replay doesn't stall waiting for input it no longer gets.
+    pub fn replay(&self, recording: &[u8]) {
+        let recording = js_sys::Uint8Array::from(recording).buffer();
+        self.replay_buffer(recording);
+    }
+
+    /// Same as [`replay`](Self::replay), but transfers the buffer instead of copying it.
+    pub fn replay_buffer(&self, recording: ArrayBuffer) {
+        self.send(HostMessage::Replay(Transferable::new(recording)));
+    }
+
+    /// State of feature toggle as last reported by worker.
+    pub fn feature(&self, name: &str) -> Option<bool> {
+        self.inner.features.borrow().get(name).copied()
+    }
+
+    /// Ask worker to stop and release GPU resources, then terminate it.
+    ///
+    /// Terminating worker mid-frame can leak graphics context,
+    /// so this waits for worker to confirm it is done before pulling the plug.
+    /// Worker is not restarted after this, regardless of restart policy.
+    pub fn shutdown(&self) {
+        self.inner.shutting_down.set(true);
+
+        // Spare never got a canvas, nothing to release there.
+        if let Some(spare) = self.inner.spare.borrow_mut().take() {
+            spare.worker.terminate();
+        }
+        if let Some(upgrade) = self.inner.upgrade.borrow_mut().take() {
+            upgrade.worker.terminate();
+        }
+
+        self.send(HostMessage::Shutdown);
+    }
+
+    /// Terminate worker right away, without waiting for it to release anything.
+    ///
+    /// Only safe for workers which never got a canvas.
+    fn terminate(&self) {
+        self.inner.shutting_down.set(true);
+
+        if let Some(spare) = self.inner.spare.borrow_mut().take() {
+            spare.worker.terminate();
+        }
+        self.inner.worker.borrow().terminate();
+    }
+
+    /// Replace worker with one running a new build, keeping app's persistent entities.
+    ///
+    /// New worker boots in background while the current one keeps running.
+    /// Once it is ready, app writes its entities marked `worker::upgrade::Persistent` into a snapshot,
+    /// new worker gets the snapshot together with `canvas` for primary view,
+    /// and the old one is shut down.
+    /// Canvas can't be taken back from a worker, so it has to be a fresh element,
+    /// swap it in for the old one e.g. from [`WorkerBuilder::on_first_frame`].
+    ///
+    /// Only this app is carried over, other apps of the worker have to be attached again same as after restart.
+    /// If new worker fails to boot or app doesn't answer with its snapshot in time,
+    /// current one keeps going and error is reported.
+    pub fn upgrade(
+        &self,
+        artifacts: WorkerArtifacts,
+        canvas: &HtmlCanvasElement,
+    ) -> Result<(), SpawnError> {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+        use web_sys::{Event, MessageEvent};
+
+        let inner = &self.inner;
+        if inner.shared.is_some() {
+            return Err(SpawnError::SharedUnsupported("upgrade"));
+        }
+        let worker = inner.flavor.spawn(&artifacts, &inner.bootstrap)?;
+
+        // Only the latest upgrade goes through.
+        if let Some(previous) = inner.upgrade.borrow_mut().take() {
+            previous.worker.terminate();
+        }
+
+        let onmessage = {
+            let inner = Rc::downgrade(inner);
+            let worker = worker.clone();
+
+            Closure::wrap(Box::new(move |event: MessageEvent| {
+                let Some(inner) = inner.upgrade() else {
+                    return;
+                };
+
+                match WorkerMessage::decode(&event.data()) {
+                    Some(WorkerMessage::Ready {
+                        threads,
+                        capabilities,
+                    }) => {
+                        if let Some(upgrade) = &mut *inner.upgrade.borrow_mut() {
+                            upgrade.ready = Some((threads, capabilities));
+                        }
+                        inner.finish_upgrade();
+                    }
+                    Some(WorkerMessage::Error(message)) => {
+                        inner.abandon_upgrade(&worker, WorkerError::Init(message))
+                    }
+                    _ => (),
+                }
+            }) as Box<dyn Fn(MessageEvent)>)
+        };
+
+        let onerror = {
+            let inner = Rc::downgrade(inner);
+            let worker = worker.clone();
+
+            Closure::wrap(Box::new(move |_: Event| {
+                if let Some(inner) = inner.upgrade() {
+                    inner.abandon_upgrade(
+                        &worker,
+                        WorkerError::Script {
+                            message: "upgraded worker failed".to_owned(),
+                            filename: String::new(),
+                            lineno: 0,
+                        },
+                    );
+                }
+            }) as Box<dyn Fn(Event)>)
+        };
+
+        worker.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        worker.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+        onmessage.forget();
+        onerror.forget();
+
+        // Paused or stuck app would otherwise keep the new worker waiting forever.
+        let timeout = {
+            let inner = Rc::downgrade(inner);
+            let worker = worker.clone();
+
+            Closure::once_into_js(move || {
+                let Some(inner) = inner.upgrade() else {
+                    return;
+                };
+                let answered = inner.upgrade.borrow().as_ref().map_or(true, |upgrade| {
+                    upgrade.worker != worker || upgrade.snapshot.is_some()
+                });
+                if !answered {
+                    inner.abandon_upgrade(
+                        &worker,
+                        WorkerError::SnapshotTimeout {
+                            timeout_ms: SNAPSHOT_TIMEOUT_MS,
+                        },
+                    );
+                }
+            })
+        };
+        if let Some(window) = web_sys::window() {
+            let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(
+                timeout.unchecked_ref(),
+                SNAPSHOT_TIMEOUT_MS as i32,
+            );
+        }
+
+        *inner.upgrade.borrow_mut() = Some(Upgrade {
+            worker,
+            artifacts,
+            app: self.app,
+            canvas: canvas.clone(),
+            ready: None,
+            snapshot: None,
+        });
+
+        self.send(HostMessage::TakeSnapshot);
+        Ok(())
+    }
+
+    /// Replace worker with a fresh one.
+    ///
+    /// Warm spare takes over if there is one, otherwise worker boots from scratch.
+    /// Note that spare was loaded ahead of time, so it doesn't pick up a rebuilt app.
+    ///
+    /// Canvases are gone together with the old worker, attach fresh ones once new worker is ready.
+    pub fn restart(&self) {
+        let inner = &self.inner;
+
+        inner.worker.borrow().terminate();
+        inner.pending.borrow_mut().get_or_insert_with(Vec::new);
+        inner.abandon_previews();
+        // Apps start over running.
+        inner.paused.borrow_mut().clear();
+        inner.canvases.borrow_mut().clear();
+        inner.buffer_sizes.borrow_mut().clear();
+        for (_, text_input) in inner.text_inputs.borrow_mut().drain() {
+            text_input.remove();
+        }
+
+        if inner.promote_spare() {
+            return;
+        }
+
+        match inner.spawn_endpoint() {
+            Ok(worker) => {
+                *inner.worker.borrow_mut() = worker;
+                inner.listen();
+            }
+            Err(err) => inner.fail(WorkerError::Respawn(err)),
+        }
+    }
+
+    fn forward_pointer(
+        &self,
+        view: ViewId,
+        canvas: &HtmlCanvasElement,
+        size: Rc<Cell<(u32, u32)>>,
+    ) -> Result<(), SpawnError> {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+        use web_sys::PointerEvent;
+
+        const EVENTS: [(&str, PointerAction, Option<TouchAction>); 5] = [
+            ("pointermove", PointerAction::Move, Some(TouchAction::Move)),
+            ("pointerdown", PointerAction::Down, Some(TouchAction::Start)),
+            ("pointerup", PointerAction::Up, Some(TouchAction::End)),
+            ("pointerleave", PointerAction::Leave, None),
+            (
+                "pointercancel",
+                PointerAction::Leave,
+                Some(TouchAction::Cancel),
+            ),
+        ];
+
+        // Otherwise browser pans and zooms the page instead of reporting touches past the first one.
+        let _ = canvas.style().set_property("touch-action", "none");
+
+        for (event, action, touch) in EVENTS {
+            let listener = {
+                let handle = self.clone();
+                let canvas = canvas.clone();
+                let size = Rc::clone(&size);
+
+                Closure::wrap(Box::new(move |event: PointerEvent| {
+                    // Locked pointer stays in place, only its movement matters.
+                    if action == PointerAction::Move && is_locked(&canvas) {
+                        handle.send_coalesced(HostMessage::PointerMotion {
+                            view,
+                            dx: event.movement_x() as f32,
+                            dy: event.movement_y() as f32,
+                        });
+                        return;
+                    }
+
+                    // Canvas may be stretched by CSS, worker wants coordinates in canvas pixels.
+                    let (width, height) = size.get();
+                    let client_size =
+                        Vec2::new(canvas.client_width() as f32, canvas.client_height() as f32);
+                    let position =
+                        ClientPx(Vec2::new(event.offset_x() as f32, event.offset_y() as f32))
+                            .to_physical(client_size, UVec2::new(width, height));
+
+                    if event.pointer_type() == "touch" {
+                        if let Some(touch) = touch {
+                            let msg = HostMessage::Touch {
+                                view,
+                                id: event.pointer_id(),
+                                action: touch,
+                                x: position.0.x,
+                                y: position.0.y,
+                            };
+
+                            if touch == TouchAction::Move {
+                                handle.send_coalesced(msg);
+                            } else {
+                                handle.send(msg);
+                            }
+                        }
+
+                        // Only the first finger stands in for mouse.
+                        if !event.is_primary() {
+                            return;
+                        }
+                    }
+
+                    let msg = HostMessage::Pointer {
+                        view,
+                        action,
+                        x: position.0.x,
+                        y: position.0.y,
+                        button: event.button(),
+                    };
+
+                    if action == PointerAction::Move {
+                        handle.send_coalesced(msg);
+                    } else {
+                        handle.send(msg);
+                    }
+                }) as Box<dyn Fn(PointerEvent)>)
+            };
+
+            canvas
+                .add_event_listener_with_callback(event, listener.as_ref().unchecked_ref())
+                .map_err(SpawnError::Dom)?;
+            listener.forget();
+        }
+
+        Ok(())
+    }
+
+    fn forward_wheel(
+        &self,
+        view: ViewId,
+        canvas: &HtmlCanvasElement,
+        size: Rc<Cell<(u32, u32)>>,
+    ) -> Result<(), SpawnError> {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+        use web_sys::{AddEventListenerOptions, WheelEvent};
+
+        let listener = {
+            let handle = self.clone();
+            let canvas = canvas.clone();
+
+            Closure::wrap(Box::new(move |event: WheelEvent| {
+                // Otherwise page scrolls along, or zooms in case of a pinch.
+                event.prevent_default();
+
+                let mode = WheelMode::from_dom(event.delta_mode());
+                let mut delta = Vec2::new(event.delta_x() as f32, event.delta_y() as f32);
+                if mode == WheelMode::Pixel {
+                    let (width, height) = size.get();
+                    let client_size =
+                        Vec2::new(canvas.client_width() as f32, canvas.client_height() as f32);
+                    delta = ClientPx(delta)
+                        .to_physical(client_size, UVec2::new(width, height))
+                        .0;
+                }
+
+                handle.send_coalesced(HostMessage::Wheel {
+                    view,
+                    dx: delta.x,
+                    dy: delta.y,
+                    mode,
+                    pinch: event.ctrl_key(),
+                });
+            }) as Box<dyn Fn(WheelEvent)>)
+        };
+
+        // Wheel listeners are passive by default in some browsers, which ignores `preventDefault`.
+        let mut options = AddEventListenerOptions::new();
+        options.passive(false);
+        canvas
+            .add_event_listener_with_callback_and_add_event_listener_options(
+                "wheel",
+                listener.as_ref().unchecked_ref(),
+                &options,
+            )
+            .map_err(SpawnError::Dom)?;
+        listener.forget();
+
+        Ok(())
+    }
+
+    fn suppress_context_menu(&self, canvas: &HtmlCanvasElement) -> Result<(), SpawnError> {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+        use web_sys::Event;
+
+        let listener = Closure::wrap(Box::new(|event: Event| {
+            event.prevent_default();
+        }) as Box<dyn Fn(Event)>);
+
+        canvas
+            .add_event_listener_with_callback("contextmenu", listener.as_ref().unchecked_ref())
+            .map_err(SpawnError::Dom)?;
+        listener.forget();
+
+        Ok(())
+    }
+
+    fn forward_pointer_lock(
+        &self,
+        view: ViewId,
+        canvas: &HtmlCanvasElement,
+    ) -> Result<(), SpawnError> {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+        use web_sys::Event;
+
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or(SpawnError::NoWindow)?;
+
+        let onpointerlockchange = {
+            let handle = self.clone();
+            let canvas = canvas.clone();
+            let locked = Cell::new(false);
+
+            // Event fires on document for every element, only report changes for this canvas.
+            Closure::wrap(Box::new(move |_: Event| {
+                let now = is_locked(&canvas);
+                if locked.replace(now) != now {
+                    handle.send(HostMessage::PointerLockChanged { view, locked: now });
+                }
+            }) as Box<dyn Fn(Event)>)
+        };
+
+        document
+            .add_event_listener_with_callback(
+                "pointerlockchange",
+                onpointerlockchange.as_ref().unchecked_ref(),
+            )
+            .map_err(SpawnError::Dom)?;
+        onpointerlockchange.forget();
+
+        Ok(())
+    }
+
+    fn forward_fullscreen(
+        &self,
+        view: ViewId,
+        canvas: &HtmlCanvasElement,
+        size: Rc<Cell<(u32, u32)>>,
+    ) -> Result<(), SpawnError> {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+        use web_sys::Event;
+
+        let window = web_sys::window().ok_or(SpawnError::NoWindow)?;
+        let document = window.document().ok_or(SpawnError::NoWindow)?;
+
+        let fullscreen = Rc::new(Cell::new(false));
+
+        // Fullscreen canvas fills the screen, its picture should be sharp there too.
+        // Outside of fullscreen canvas goes back to its original size, unless it is responsive.
+        let resize = {
+            let handle = self.clone();
+            let window = window.clone();
+            let canvas = canvas.clone();
+            let fullscreen = Rc::clone(&fullscreen);
+
+            move || {
+                let new_size = if fullscreen.get() || handle.inner.responsive_canvas {
+                    let client_size =
+                        Vec2::new(canvas.client_width() as f32, canvas.client_height() as f32);
+                    let size = ClientPx(client_size).buffer_size(window.device_pixel_ratio());
+                    (size.x, size.y)
+                } else {
+                    (canvas.width(), canvas.height())
+                };
+
+                if size.replace(new_size) != new_size {
+                    let (width, height) = new_size;
+                    handle.send_coalesced(HostMessage::Resize {
+                        view,
+                        width,
+                        height,
+                    });
+                }
+            }
+        };
+        let resize = Rc::new(resize);
+
+        let onfullscreenchange = {
+            let handle = self.clone();
+            let document = document.clone();
+            let canvas = canvas.clone();
+            let resize = Rc::clone(&resize);
+
+            // Event fires on document for every element, only report changes for this canvas.
+            Closure::wrap(Box::new(move |_: Event| {
+                let element = document.fullscreen_element();
+                let now = element.as_ref() == Some(canvas.as_ref());
+
+                if fullscreen.replace(now) != now {
+                    resize();
+                    handle.send(HostMessage::FullscreenChanged {
+                        view,
+                        fullscreen: now,
+                    });
+                }
+            }) as Box<dyn Fn(Event)>)
+        };
+
+        document
+            .add_event_listener_with_callback(
+                "fullscreenchange",
+                onfullscreenchange.as_ref().unchecked_ref(),
+            )
+            .map_err(SpawnError::Dom)?;
+        onfullscreenchange.forget();
+
+        // Screen may still change while in fullscreen, e.g. device rotating.
+        // Dragging window border fires a stream of events, only the size it settles on matters.
+        let onresize = {
+            let window = window.clone();
+            let timer = Cell::new(None);
+
+            Closure::wrap(Box::new(move |_: Event| {
+                const DEBOUNCE_MS: i32 = 100;
+
+                if let Some(timer) = timer.take() {
+                    window.clear_timeout_with_handle(timer);
+                }
+
+                let resize = Rc::clone(&resize);
+                let callback = Closure::once_into_js(move || resize());
+                timer.set(
+                    window
+                        .set_timeout_with_callback_and_timeout_and_arguments_0(
+                            callback.unchecked_ref(),
+                            DEBOUNCE_MS,
+                        )
+                        .ok(),
+                );
+            }) as Box<dyn Fn(Event)>)
+        };
+
+        // Mobile browsers may report rotation before they are done laying out the page for it.
+        for event in ["resize", "orientationchange"] {
+            window
+                .add_event_listener_with_callback(event, onresize.as_ref().unchecked_ref())
+                .map_err(SpawnError::Dom)?;
+        }
+        onresize.forget();
+
+        // Layout may already differ from size attributes.
+        if self.inner.responsive_canvas {
+            resize();
+        }
+
+        Ok(())
+    }
+
+    /// Let worker know when canvas scrolls out of sight, so it can throttle the app.
+    fn forward_intersection(
+        &self,
+        view: ViewId,
+        canvas: &HtmlCanvasElement,
+    ) -> Result<(), SpawnError> {
+        use js_sys::Array;
+        use wasm_bindgen::prelude::{Closure, JsCast, JsValue};
+        use web_sys::{IntersectionObserver, IntersectionObserverEntry, IntersectionObserverInit};
+
+        // Worker picks its render rate by how much of the canvas is visible, see `worker::offscreen`.
+        const THRESHOLDS: [f64; 5] = [0.0, 0.25, 0.5, 0.75, 1.0];
+
+        let onintersection = {
+            let handle = self.clone();
+            let canvas = canvas.clone();
+
+            Closure::wrap(Box::new(move |entries: Array| {
+                // Canvas replaced by a fresh one reports itself gone once removed from the page.
+                let current = handle
+                    .inner
+                    .canvases
+                    .borrow()
+                    .get(&(handle.app, view))
+                    .map_or(true, |current| current == &canvas);
+                if !current {
+                    return;
+                }
+
+                // Entries are in order, the last one is the latest state.
+                let Some(entry) = entries
+                    .iter()
+                    .filter_map(|entry| entry.dyn_into::<IntersectionObserverEntry>().ok())
+                    .last()
+                else {
+                    return;
+                };
+
+                let visible = entry.is_intersecting();
+                handle.send(HostMessage::ViewIntersection {
+                    view,
+                    visible,
+                    ratio: entry.intersection_ratio() as f32,
+                });
+
+                // Hooks may add more hooks.
+                let hooks = handle.inner.intersection_hooks.borrow().clone();
+                for hook in hooks {
+                    hook(&handle, view, visible);
+                }
+            }) as Box<dyn Fn(Array)>)
+        };
+
+        let thresholds: Array = THRESHOLDS.iter().copied().map(JsValue::from).collect();
+        let mut options = IntersectionObserverInit::new();
+        options.threshold(&thresholds);
+
+        let observer = IntersectionObserver::new_with_options(
+            onintersection.as_ref().unchecked_ref(),
+            &options,
+        )
+        .map_err(SpawnError::Dom)?;
+        observer.observe(canvas);
+        onintersection.forget();
+
+        Ok(())
+    }
+
+    /// Create hidden text element forwarding text typed into it, IME composition included.
+    fn forward_ime(&self, view: ViewId) -> Result<HtmlTextAreaElement, SpawnError> {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+        use web_sys::{CompositionEvent, InputEvent};
+
+        const STYLE: &str = "position: fixed; left: 0; top: 0; width: 1px; height: 1px; \
+            padding: 0; border: 0; opacity: 0; resize: none; pointer-events: none;";
+
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or(SpawnError::NoWindow)?;
+
+        let text_input: HtmlTextAreaElement = document
+            .create_element("textarea")
+            .map_err(SpawnError::Dom)?
+            .unchecked_into();
+        text_input.style().set_css_text(STYLE);
+        for (name, value) in [
+            ("autocomplete", "off"),
+            ("autocapitalize", "off"),
+            ("spellcheck", "false"),
+            ("aria-hidden", "true"),
+            ("tabindex", "-1"),
+        ] {
+            text_input
+                .set_attribute(name, value)
+                .map_err(SpawnError::Dom)?;
+        }
+
+        let oncompositionupdate = {
+            let handle = self.clone();
+
+            Closure::wrap(Box::new(move |event: CompositionEvent| {
+                handle.send(HostMessage::Ime {
+                    view,
+                    action: ImeAction::Preedit,
+                    text: event.data().unwrap_or_default(),
+                });
+            }) as Box<dyn Fn(CompositionEvent)>)
+        };
+
+        let oncompositionend = {
+            let handle = self.clone();
+            let text_input = text_input.clone();
+
+            Closure::wrap(Box::new(move |event: CompositionEvent| {
+                // Some browsers fire `input` once more after this, it has to find nothing left to commit.
+                text_input.set_value("");
+
+                handle.send(HostMessage::Ime {
+                    view,
+                    action: ImeAction::Commit,
+                    text: event.data().unwrap_or_default(),
+                });
+            }) as Box<dyn Fn(CompositionEvent)>)
+        };
+
+        let oninput = {
+            let handle = self.clone();
+            let text_input = text_input.clone();
+
+            // Text typed without composition, or pasted.
+            Closure::wrap(Box::new(move |event: InputEvent| {
+                if event.is_composing() {
+                    return;
+                }
+
+                let text = text_input.value();
+                text_input.set_value("");
+
+                if !text.is_empty() {
+                    handle.send(HostMessage::Ime {
+                        view,
+                        action: ImeAction::Commit,
+                        text,
+                    });
+                }
+            }) as Box<dyn Fn(InputEvent)>)
+        };
+
+        for (name, listener) in [
+            ("compositionupdate", oncompositionupdate.as_ref()),
+            ("compositionend", oncompositionend.as_ref()),
+            ("input", oninput.as_ref()),
+        ] {
+            text_input
+                .add_event_listener_with_callback(name, listener.unchecked_ref())
+                .map_err(SpawnError::Dom)?;
+        }
+        oncompositionupdate.forget();
+        oncompositionend.forget();
+        oninput.forget();
+
+        document
+            .body()
+            .ok_or(SpawnError::NoWindow)?
+            .append_child(&text_input)
+            .map_err(SpawnError::Dom)?;
+
+        Ok(text_input)
+    }
+
+    fn forward_drops(&self, view: ViewId, canvas: &HtmlCanvasElement) -> Result<(), SpawnError> {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+        use web_sys::{DragEvent, File, FileReader};
+
+        // Canvas doesn't accept drops unless told so.
+        let ondragover = Closure::wrap(Box::new(|event: DragEvent| {
+            event.prevent_default();
+        }) as Box<dyn Fn(DragEvent)>);
+
+        canvas
+            .add_event_listener_with_callback("dragover", ondragover.as_ref().unchecked_ref())
+            .map_err(SpawnError::Dom)?;
+        ondragover.forget();
+
+        let ondrop = {
+            let handle = self.clone();
+
+            Closure::wrap(Box::new(move |event: DragEvent| {
+                // Otherwise browser navigates to the file.
+                event.prevent_default();
+
+                let Some(files) = event.data_transfer().and_then(|data| data.files()) else {
+                    return;
+                };
+
+                for file in (0..files.length()).filter_map(|i| files.get(i)) {
+                    let result = read_file(&file, {
+                        let handle = handle.clone();
+                        let name = file.name();
+
+                        move |bytes| {
+                            handle.send(HostMessage::FileDropped {
+                                view,
+                                name,
+                                bytes: Transferable::new(bytes),
+                            });
+                        }
+                    });
+
+                    if let Err(err) = result {
+                        web_sys::console::warn_1(&err);
+                    }
+                }
+            }) as Box<dyn Fn(DragEvent)>)
+        };
+
+        canvas
+            .add_event_listener_with_callback("drop", ondrop.as_ref().unchecked_ref())
+            .map_err(SpawnError::Dom)?;
+        ondrop.forget();
+
+        fn read_file(file: &File, f: impl FnOnce(ArrayBuffer) + 'static) -> Result<(), JsValue> {
+            let reader = FileReader::new()?;
+
+            let onload = {
+                let reader = reader.clone();
+
+                Closure::once_into_js(move || {
+                    if let Some(bytes) = reader
+                        .result()
+                        .ok()
+                        .and_then(|result| result.dyn_into().ok())
+                    {
+                        f(bytes);
+                    }
+                })
+            };
+
+            reader.set_onload(Some(onload.unchecked_ref()));
+            reader.read_as_array_buffer(file)
+        }
+
+        Ok(())
+    }
+
+    fn forward_visibility(&self) -> Result<(), SpawnError> {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+        use web_sys::Event;
+
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or(SpawnError::NoWindow)?;
+
+        self.send(HostMessage::Visibility {
+            visible: !document.hidden(),
+        });
+
+        let onvisibilitychange = {
+            let inner = Rc::clone(&self.inner);
+            let document = document.clone();
+
+            Closure::wrap(Box::new(move |_: Event| {
+                let apps = inner.apps.borrow().clone();
+
+                for app in apps {
+                    inner.send(
+                        app,
+                        HostMessage::Visibility {
+                            visible: !document.hidden(),
+                        },
+                    );
+                }
+            }) as Box<dyn Fn(Event)>)
+        };
+
+        document
+            .add_event_listener_with_callback(
+                "visibilitychange",
+                onvisibilitychange.as_ref().unchecked_ref(),
+            )
+            .map_err(SpawnError::Dom)?;
+        onvisibilitychange.forget();
+
+        Ok(())
+    }
+
+    /// Tell worker when page gains or loses focus, so it doesn't keep keys held through it.
+    fn forward_focus(&self) -> Result<(), SpawnError> {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+        use web_sys::Event;
+
+        let window = web_sys::window().ok_or(SpawnError::NoWindow)?;
+
+        if let Some(focus) = focus() {
+            self.send(focus);
+        }
+
+        for (event, focused) in [("focus", true), ("blur", false)] {
+            let listener = {
+                let inner = Rc::clone(&self.inner);
+
+                Closure::wrap(Box::new(move |_: Event| {
+                    let apps = inner.apps.borrow().clone();
+                    for app in apps {
+                        inner.send(app, HostMessage::Focus { focused });
+                    }
+                }) as Box<dyn Fn(Event)>)
+            };
+
+            window
+                .add_event_listener_with_callback(event, listener.as_ref().unchecked_ref())
+                .map_err(SpawnError::Dom)?;
+            listener.forget();
+        }
+
+        Ok(())
+    }
+
+    /// Forward keys pressed anywhere on the page, except in its own text fields.
+    fn forward_keyboard(&self) -> Result<(), SpawnError> {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+        use web_sys::KeyboardEvent;
+
+        let window = web_sys::window().ok_or(SpawnError::NoWindow)?;
+
+        for (event, pressed) in [("keydown", true), ("keyup", false)] {
+            let listener = {
+                let inner = Rc::clone(&self.inner);
+
+                Closure::wrap(Box::new(move |event: KeyboardEvent| {
+                    // Keys typed into IME go through its own messages.
+                    if event.is_composing() {
+                        return;
+                    }
+
+                    let editable = event
+                        .target()
+                        .and_then(|target| target.dyn_into::<Element>().ok())
+                        .and_then(|target| {
+                            target
+                                .closest("input, textarea, select, [contenteditable]")
+                                .ok()
+                                .flatten()
+                        });
+                    if let Some(editable) = editable {
+                        let ours = inner
+                            .text_inputs
+                            .borrow()
+                            .values()
+                            .any(|text_input| text_input.is_same_node(Some(&*editable)));
+                        if !ours {
+                            return;
+                        }
+                    }
+
+                    let plain = !(event.shift_key()
+                        || event.ctrl_key()
+                        || event.alt_key()
+                        || event.meta_key());
+                    let apps = inner.apps.borrow().clone();
+                    for app in apps {
+                        inner.send(
+                            app,
+                            HostMessage::Key {
+                                code: event.code(),
+                                key: event.key(),
+                                scan_code: event.key_code(),
+                                pressed,
+                                plain,
+                            },
+                        );
+                    }
+                }) as Box<dyn Fn(KeyboardEvent)>)
+            };
+
+            window
+                .add_event_listener_with_callback(event, listener.as_ref().unchecked_ref())
+                .map_err(SpawnError::Dom)?;
+            listener.forget();
+        }
+
+        self.inner.fetch_keyboard_layout();
+
+        // Only Chromium has layout map, and the event along with it.
+        if let Some(keyboard) = keyboard() {
+            let listener = {
+                let inner = Rc::clone(&self.inner);
+
+                Closure::wrap(Box::new(move || inner.fetch_keyboard_layout()) as Box<dyn Fn()>)
+            };
+
+            keyboard
+                .add_event_listener_with_callback("layoutchange", listener.as_ref().unchecked_ref())
+                .map_err(SpawnError::Dom)?;
+            listener.forget();
+        }
+
+        Ok(())
+    }
+
+    /// Keep worker up to date with user's color scheme and reduced motion preferences.
+    fn forward_media_preferences(&self) -> Result<(), SpawnError> {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+        use web_sys::Event;
+
+        let window = web_sys::window().ok_or(SpawnError::NoWindow)?;
+
+        if let Some(preferences) = media_preferences() {
+            self.send(preferences);
+        }
+
+        let onchange = {
+            let inner = Rc::clone(&self.inner);
+
+            Closure::wrap(Box::new(move |_: Event| {
+                let apps = inner.apps.borrow().clone();
+                for app in apps {
+                    if let Some(preferences) = media_preferences() {
+                        inner.send(app, preferences);
+                    }
+                }
+            }) as Box<dyn Fn(Event)>)
+        };
+
+        for query in [DARK_SCHEME_QUERY, REDUCED_MOTION_QUERY] {
+            // Browsers without media query support simply never report a change.
+            if let Ok(Some(list)) = window.match_media(query) {
+                list.add_event_listener_with_callback("change", onchange.as_ref().unchecked_ref())
+                    .map_err(SpawnError::Dom)?;
+            }
+        }
+        onchange.forget();
+
+        Ok(())
+    }
+}
+
+/// `navigator.keyboard`, which isn't covered by stable `web-sys`.
+fn keyboard() -> Option<web_sys::EventTarget> {
+    use js_sys::Reflect;
+    use wasm_bindgen::JsCast;
+
+    let navigator = Reflect::get(&web_sys::window()?, &"navigator".into()).ok()?;
+    Reflect::get(&navigator, &"keyboard".into())
+        .ok()?
+        .dyn_into()
+        .ok()
+}
+
+/// Whether page currently has focus, `None` if not running on a page.
+fn focus() -> Option<HostMessage> {
+    let document = web_sys::window()?.document()?;
+
+    Some(HostMessage::Focus {
+        focused: document.has_focus().ok()?,
+    })
+}
+
+const DARK_SCHEME_QUERY: &str = "(prefers-color-scheme: dark)";
+const REDUCED_MOTION_QUERY: &str = "(prefers-reduced-motion: reduce)";
+
+/// Current media preferences of the user, `None` if not running on a page.
+fn media_preferences() -> Option<HostMessage> {
+    let window = web_sys::window()?;
+    let matches = |query| {
+        window
+            .match_media(query)
+            .ok()
+            .flatten()
+            .map_or(false, |list| list.matches())
+    };
+
+    Some(HostMessage::MediaPreferences {
+        dark: matches(DARK_SCHEME_QUERY),
+        reduced_motion: matches(REDUCED_MOTION_QUERY),
+    })
+}
+
+impl Inner {
+    /// Play a short click, returns in how many ms it reaches audio output.
+    ///
+    /// Audio context is created on the first click, which follows a press on the page,
+    /// so browser lets it start.
+    fn play_click(&self) -> Result<f64, JsValue> {
+        use web_sys::AudioContext;
+
+        let mut audio = self.click_audio.borrow_mut();
+        let audio = match &mut *audio {
+            Some(audio) => audio,
+            audio => audio.insert(AudioContext::new()?),
+        };
+        if audio.state() == web_sys::AudioContextState::Suspended {
+            let _ = audio.resume()?;
+        }
+
+        let start = audio.current_time();
+        let oscillator = audio.create_oscillator()?;
+        oscillator.frequency().set_value(1000.0);
+        let gain = audio.create_gain()?;
+        gain.gain().set_value_at_time(1.0, start)?;
+        gain.gain()
+            .exponential_ramp_to_value_at_time(0.001, start + 0.02)?;
+
+        oscillator.connect_with_audio_node(&gain)?;
+        gain.connect_with_audio_node(&audio.destination())?;
+        oscillator.start()?;
+        oscillator.stop_with_when(start + 0.02)?;
+
+        // Latencies are missing from some browsers, what is there is a lower bound then.
+        let latency = |name: &str| {
+            js_sys::Reflect::get(audio, &name.into())
+                .ok()
+                .and_then(|latency| latency.as_f64())
+                .unwrap_or(0.0)
+        };
+        Ok((latency("baseLatency") + latency("outputLatency")) * 1000.0)
+    }
+
+    fn listen(self: &Rc<Self>) {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+        use web_sys::{ErrorEvent, Event, MessageEvent};
+
+        // Channels belong to the previous worker, new one gets its own once ready.
+        for (_, port) in self.ports.borrow_mut().drain() {
+            port.close();
+        }
+        if let Some(port) = self.frame_clock.borrow_mut().take() {
+            port.close();
+        }
+
+        let onmessage = {
+            let inner = Rc::clone(self);
+
+            Closure::wrap(Box::new(move |event: MessageEvent| {
+                let data = event.data();
+                let msg = WorkerMessage::decode(&data);
+                let app = AppId::read(&data);
+                if let Some(traffic) = &mut *inner.traffic.borrow_mut() {
+                    traffic.record(
+                        TrafficDirection::Received,
+                        msg.as_ref().map(WorkerMessage::kind),
+                        &data,
+                    );
+                }
+                let _span = info_span!(
+                    "bridge_receive",
+                    kind = msg.as_ref().map(WorkerMessage::kind),
+                    correlation_id = Envelope::read(&data).map(|envelope| envelope.id.0)
+                )
+                .entered();
+
+                match msg {
+                    Some(WorkerMessage::Ready {
+                        threads,
+                        capabilities,
+                    }) => inner.ready(threads, capabilities),
+                    Some(WorkerMessage::Error(message)) => inner.fail(WorkerError::Init(message)),
+                    Some(WorkerMessage::WasmLoading { received, total }) => {
+                        if let Some(on_wasm_loading) = &inner.on_wasm_loading {
+                            on_wasm_loading(received, total);
+                        }
+                    }
+                    Some(WorkerMessage::ShutdownComplete) => inner.worker.borrow().terminate(),
+                    Some(WorkerMessage::Features(features)) => {
+                        *inner.features.borrow_mut() = features.into_iter().collect();
+                    }
+                    Some(WorkerMessage::FirstFrameRendered) => {
+                        let splash = inner.splash.borrow_mut().take();
+                        if let Some(splash) = splash {
+                            splash.dismiss();
+                        }
+
+                        if let Some(on_first_frame) = &inner.on_first_frame {
+                            on_first_frame(&WorkerHandle {
+                                inner: Rc::clone(&inner),
+                                app,
+                            });
+                        }
+                    }
+                    Some(WorkerMessage::DeviceLost) => match &inner.on_device_lost {
+                        Some(on_device_lost) => on_device_lost(&WorkerHandle {
+                            inner: Rc::clone(&inner),
+                            app,
+                        }),
+                        None => inner.report(&WorkerError::DeviceLost),
+                    },
+                    Some(WorkerMessage::Accessibility(tree)) => {
+                        let result = match &inner.accessibility {
+                            Some(mirror) => mirror.update(&tree),
+                            None => Ok(()),
+                        };
+
+                        if let Err(err) = result {
+                            web_sys::console::warn_1(
+                                &format!("failed to update accessibility mirror: {err}").into(),
+                            );
+                        }
+                    }
+                    Some(WorkerMessage::RequestPointerLock { view }) => {
+                        match inner.canvases.borrow().get(&(app, view)) {
+                            Some(canvas) => canvas.request_pointer_lock(),
+                            None => web_sys::console::warn_1(
+                                &format!("pointer lock requested for unattached view {view:?}")
+                                    .into(),
+                            ),
+                        }
+                    }
+                    Some(WorkerMessage::ExitPointerLock) => {
+                        if let Some(document) =
+                            web_sys::window().and_then(|window| window.document())
+                        {
+                            document.exit_pointer_lock();
+                        }
+                    }
+                    Some(WorkerMessage::SetFullscreen { view, fullscreen }) => {
+                        let Some(canvas) = inner.canvases.borrow().get(&(app, view)).cloned()
+                        else {
+                            web_sys::console::warn_1(
+                                &format!("fullscreen requested for unattached view {view:?}")
+                                    .into(),
+                            );
+                            return;
+                        };
+                        let Some(document) = web_sys::window().and_then(|window| window.document())
+                        else {
+                            return;
+                        };
+
+                        let result = if fullscreen {
+                            canvas.request_fullscreen()
+                        } else if document.fullscreen_element().as_ref() == Some(canvas.as_ref()) {
+                            document.exit_fullscreen();
+                            Ok(())
+                        } else {
+                            Ok(())
+                        };
+
+                        if let Err(err) = result {
+                            web_sys::console::warn_1(&err);
+                        }
+                    }
+                    Some(WorkerMessage::SetCursor { view, cursor }) => {
+                        if let Some(canvas) = inner.canvases.borrow().get(&(app, view)) {
+                            if let Err(err) = canvas.style().set_property("cursor", &cursor) {
+                                web_sys::console::warn_1(&err);
+                            }
+                        }
+                    }
+                    Some(WorkerMessage::DomAnchors { view, anchors }) => {
+                        let mut dom_anchors = inner.dom_anchors.borrow_mut();
+                        let view_anchors = dom_anchors.entry((app, view)).or_default();
+                        for (id, position) in anchors {
+                            let anchor = view_anchors.entry(id).or_default();
+                            anchor.position = position.map(Vec2::from);
+                            inner.place_anchor(app, view, anchor);
+                        }
+
+                        DomAnchor::prune(view_anchors);
+                        if view_anchors.is_empty() {
+                            dom_anchors.remove(&(app, view));
+                        }
+                    }
+                    Some(WorkerMessage::SetIme {
+                        view,
+                        enabled,
+                        x,
+                        y,
+                    }) => {
+                        let text_inputs = inner.text_inputs.borrow();
+                        let canvases = inner.canvases.borrow();
+                        let (Some(text_input), Some(canvas)) =
+                            (text_inputs.get(&(app, view)), canvases.get(&(app, view)))
+                        else {
+                            return;
+                        };
+
+                        let result = if enabled {
+                            let (width, height) = inner
+                                .buffer_sizes
+                                .borrow()
+                                .get(&(app, view))
+                                .map_or((canvas.width(), canvas.height()), |size| size.get());
+                            let client_size = Vec2::new(
+                                canvas.client_width() as f32,
+                                canvas.client_height() as f32,
+                            );
+                            let position = PhysicalPx(Vec2::new(x, y))
+                                .to_client(client_size, UVec2::new(width, height));
+
+                            // IME shows its candidate window next to the focused element.
+                            let rect = canvas.get_bounding_client_rect();
+                            let style = text_input.style();
+                            let _ = style.set_property(
+                                "left",
+                                &format!("{}px", rect.left() + position.0.x as f64),
+                            );
+                            let _ = style.set_property(
+                                "top",
+                                &format!("{}px", rect.top() + position.0.y as f64),
+                            );
+
+                            text_input.focus()
+                        } else {
+                            text_input.set_value("");
+                            text_input.blur()
+                        };
+
+                        if let Err(err) = result {
+                            web_sys::console::warn_1(&err);
+                        }
+                    }
+                    Some(WorkerMessage::ClipboardCopy(text)) => {
+                        wasm_bindgen_futures::spawn_local(async move {
+                            if let Err(err) = Inner::clipboard("writeText", &[text.into()]).await {
+                                web_sys::console::warn_1(&err);
+                            }
+                        });
+                    }
+                    Some(WorkerMessage::ClipboardPasteRequest) => {
+                        let handle = WorkerHandle {
+                            inner: Rc::clone(&inner),
+                            app,
+                        };
+
+                        wasm_bindgen_futures::spawn_local(async move {
+                            let result = Inner::clipboard("readText", &[])
+                                .await
+                                .and_then(|text| {
+                                    text.as_string()
+                                        .ok_or_else(|| "clipboard has no text".into())
+                                })
+                                .map_err(|err| describe(&err));
+
+                            handle.send(HostMessage::ClipboardPaste(result));
+                        });
+                    }
+                    Some(WorkerMessage::TrafficLog(worker)) => {
+                        let Some(filename) = inner.traffic_export.borrow_mut().take() else {
+                            return;
+                        };
+                        let page = inner
+                            .traffic
+                            .borrow()
+                            .as_ref()
+                            .map_or_else(|| "[]".to_owned(), TrafficLog::to_json);
+
+                        let json = format!(r#"{{"page":{page},"worker":{worker}}}"#);
+                        if let Err(err) = download_json(&json, &filename) {
+                            web_sys::console::warn_1(
+                                &format!("failed to export traffic log: {err}").into(),
+                            );
+                        }
+                    }
+                    Some(WorkerMessage::Snapshot(result)) => {
+                        if let Some(upgrade) = &mut *inner.upgrade.borrow_mut() {
+                            if upgrade.app == app {
+                                upgrade.snapshot = Some(result);
+                            }
+                        }
+                        inner.finish_upgrade();
+                    }
+                    Some(WorkerMessage::SceneExported(result)) => {
+                        let Some(filename) = inner.scene_exports.borrow_mut().remove(&app) else {
+                            return;
+                        };
+
+                        let result = result.and_then(|scene| {
+                            download(&scene.into(), "application/ron", &filename)
+                                .map_err(|err| err.to_string())
+                        });
+                        if let Err(err) = result {
+                            web_sys::console::warn_1(
+                                &format!("failed to export scene: {err}").into(),
+                            );
+                        }
+                    }
+                    Some(WorkerMessage::Recording(recording)) => {
+                        let callback = inner.recordings.borrow_mut().remove(&app);
+                        if let Some(callback) = callback {
+                            callback(recording.into_inner());
+                        }
+                    }
+                    Some(WorkerMessage::Ping(seq)) => inner.send(
+                        app,
+                        HostMessage::Pong {
+                            seq,
+                            page_time: epoch_now(),
+                        },
+                    ),
+                    Some(WorkerMessage::BridgeStats(stats)) => {
+                        inner.bridge_stats.borrow_mut().insert(app, stats);
+                    }
+                    Some(WorkerMessage::ConsoleOutput { command, result }) => match result {
+                        Ok(output) if output.is_empty() => {
+                            web_sys::console::log_1(&format!("> {command}").into())
+                        }
+                        Ok(output) => {
+                            web_sys::console::log_1(&format!("> {command}\n{output}").into())
+                        }
+                        Err(error) => {
+                            web_sys::console::error_1(&format!("> {command}\n{error}").into())
+                        }
+                    },
+                    Some(WorkerMessage::RenderStats(stats)) => {
+                        inner.render_stats.borrow_mut().insert(app, stats);
+                    }
+                    Some(WorkerMessage::Graphics(options)) => {
+                        inner.graphics.borrow_mut().insert(app, options);
+                    }
+                    Some(WorkerMessage::PresentMode(mode)) => {
+                        inner.present_modes.borrow_mut().insert(app, mode);
+                    }
+                    Some(WorkerMessage::MemoryWarning(warning)) => match &inner.on_memory_warning {
+                        Some(on_memory_warning) => on_memory_warning(
+                            &WorkerHandle {
+                                inner: Rc::clone(&inner),
+                                app,
+                            },
+                            warning,
+                        ),
+                        None => web_sys::console::warn_1(
+                            &format!(
+                                "worker memory is at {} MiB, past {} MiB threshold",
+                                warning.bytes >> 20,
+                                warning.threshold >> 20
+                            )
+                            .into(),
+                        ),
+                    },
+                    Some(WorkerMessage::FrameStats(stats)) => {
+                        if let Some((shown, overlay)) = &*inner.stats_overlay.borrow() {
+                            if *shown == app {
+                                overlay.update(&stats);
+                            }
+                        }
+                        inner.frame_stats.borrow_mut().insert(app, stats);
+                    }
+                    Some(WorkerMessage::InputPresented(ids)) => {
+                        if let Some(probe) = &mut *inner.latency_probe.borrow_mut() {
+                            probe.presented(app, &ids);
+                        }
+                    }
+                    Some(WorkerMessage::LatencyClick { pressed_at }) => match inner.play_click() {
+                        Ok(audible_in_ms) => inner.send(
+                            app,
+                            HostMessage::ClickLatency(
+                                js_sys::Date::now() + audible_in_ms - pressed_at,
+                            ),
+                        ),
+                        Err(err) => web_sys::console::warn_2(&"failed to play click:".into(), &err),
+                    },
+                    Some(WorkerMessage::AnomalyReport(report)) => {
+                        const MAX_QUEUED: usize = 32;
+
+                        let mut anomalies = inner.anomalies.borrow_mut();
+                        if anomalies.len() == MAX_QUEUED {
+                            anomalies.pop_front();
+                        }
+                        anomalies.push_back(report);
+                    }
+                    Some(WorkerMessage::SaveSettings { key, value }) => {
+                        if let Err(err) = inner.save_settings(&key, &value) {
+                            web_sys::console::warn_1(
+                                &format!("failed to store settings `{key}`: {err}").into(),
+                            );
+                        }
+                    }
+                    Some(WorkerMessage::AssetPreview { request, result }) => {
+                        let callback = inner.previews.borrow_mut().remove(&request);
+                        if let Some(callback) = callback {
+                            callback(result.map(Transferable::into_inner));
+                        }
+                    }
+                    Some(WorkerMessage::InspectorSnapshot(entities)) => {
+                        let result = match &*inner.inspector.borrow() {
+                            Some((inspected, panel)) if *inspected == app => {
+                                panel.update(&entities)
+                            }
+                            _ => Ok(()),
+                        };
+
+                        if let Err(err) = result {
+                            web_sys::console::warn_1(
+                                &format!("failed to update inspector: {err}").into(),
+                            );
+                        }
+                    }
+                    None => (),
+                }
+            }) as Box<dyn Fn(MessageEvent)>)
+        };
+
+        let onerror = {
+            let inner = Rc::clone(self);
+
+            Closure::wrap(Box::new(move |event: Event| {
+                let error = match event.dyn_into::<ErrorEvent>() {
+                    Ok(event) => WorkerError::Script {
+                        message: event.message(),
+                        filename: event.filename(),
+                        lineno: event.lineno(),
+                    },
+                    // Failing to fetch worker script fires a plain event without any details.
+                    Err(_) => WorkerError::Script {
+                        message: "failed to load worker script".to_owned(),
+                        filename: String::new(),
+                        lineno: 0,
+                    },
+                };
+
+                inner.fail(error);
+            }) as Box<dyn Fn(Event)>)
+        };
+
+        let onmessageerror = {
+            let inner = Rc::clone(self);
+
+            Closure::wrap(Box::new(move |_: MessageEvent| {
+                inner.report(&WorkerError::Bridge(BridgeError::Serialization {
+                    kind: None,
+                    reason: "message from worker could not be deserialized".to_owned(),
+                }));
+            }) as Box<dyn Fn(MessageEvent)>)
+        };
+
+        let worker = self.worker.borrow();
+        worker.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        worker.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+        worker.set_onmessageerror(Some(onmessageerror.as_ref().unchecked_ref()));
+        onmessage.forget();
+        onerror.forget();
+        onmessageerror.forget();
+    }
+
+    fn ready(self: &Rc<Self>, threads: u32, worker_capabilities: Capabilities) {
+        self.attempts.set(0);
+        self.threads.set(threads);
+
+        let capabilities = CapabilityReport {
+            page: Capabilities::probe(),
+            worker: worker_capabilities,
+        };
+        self.capabilities.set(Some(capabilities));
+
+        // Shared worker tells tabs apart by their connections, everything has to go over them.
+        if self.shared.is_none() {
+            if let Err(err) = self.open_ports() {
+                web_sys::console::warn_1(&err);
+            }
+            if let Err(err) = self.open_frame_clock() {
+                web_sys::console::warn_1(&err);
+            }
+        }
+
+        // Goes ahead of queued messages, so flags and settings are in place by the time app starts.
+        self.post(
+            AppId::DEFAULT,
+            &HostMessage::BootFlags(self.boot_flags.clone()),
+        );
+        self.post(AppId::DEFAULT, &HostMessage::Capabilities(capabilities));
+
+        let settings = self.stored_settings().unwrap_or_else(|err| {
+            web_sys::console::warn_1(&format!("failed to read stored settings: {err}").into());
+            Vec::new()
+        });
+        let apps = self.apps.borrow().clone();
+        for app in apps {
+            self.post(app, &HostMessage::StoredSettings(settings.clone()));
+        }
+
+        self.flush();
+
+        if let Some(on_ready) = &self.on_ready {
+            on_ready(&WorkerHandle {
+                inner: Rc::clone(self),
+                app: AppId::DEFAULT,
+            });
+        }
+
+        // Spare is only spawned now, so it doesn't slow down the boot of current worker.
+        self.spawn_spare();
+    }
+
+    fn spawn_spare(self: &Rc<Self>) {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+        use web_sys::{Event, MessageEvent};
+
+        if !self.warm_spare
+            || self.shared.is_some()
+            || self.shutting_down.get()
+            || self.spare.borrow().is_some()
+        {
+            return;
+        }
+
+        let spawned = self.flavor.spawn(&self.artifacts.borrow(), &self.bootstrap);
+        let worker = match spawned {
+            Ok(worker) => worker,
+            Err(err) => {
+                self.report(&WorkerError::Respawn(err));
+                return;
+            }
+        };
+        let ready = Rc::new(Cell::new(None));
+
+        // Failed spare is dropped, it will be replaced next time current worker becomes ready.
+        let discard = {
+            let inner = Rc::clone(self);
+            let ready = Rc::clone(&ready);
+
+            move |error: WorkerError| {
+                let mut spare = inner.spare.borrow_mut();
+                if spare
+                    .as_ref()
+                    .map_or(false, |spare| Rc::ptr_eq(&spare.ready, &ready))
+                {
+                    if let Some(spare) = spare.take() {
+                        spare.worker.terminate();
+                    }
+                    drop(spare);
+                    inner.report(&error);
+                }
+            }
+        };
+
+        let onmessage = {
+            let ready = Rc::clone(&ready);
+            let discard = discard.clone();
+
+            Closure::wrap(Box::new(move |event: MessageEvent| {
+                match WorkerMessage::decode(&event.data()) {
+                    Some(WorkerMessage::Ready {
+                        threads,
+                        capabilities,
+                    }) => ready.set(Some((threads, capabilities))),
+                    Some(WorkerMessage::Error(message)) => discard(WorkerError::Init(message)),
+                    _ => (),
+                }
+            }) as Box<dyn Fn(MessageEvent)>)
+        };
+
+        let onerror = Closure::wrap(Box::new(move |_: Event| {
+            discard(WorkerError::Script {
+                message: "spare worker failed".to_owned(),
+                filename: String::new(),
+                lineno: 0,
+            });
+        }) as Box<dyn Fn(Event)>);
+
+        worker.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        worker.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+        onmessage.forget();
+        onerror.forget();
+
+        *self.spare.borrow_mut() = Some(Spare { worker, ready });
+    }
+
+    /// Create a channel per subsystem and hand worker's ends over.
+    ///
+    /// Handshake goes over the worker itself, so it arrives ahead of anything posted to the ports.
+    /// Everything keeps going over the worker if channels can't be created.
+    fn open_ports(&self) -> Result<(), JsValue> {
+        use web_sys::MessageChannel;
+
+        let worker = self.worker.borrow().clone();
+        let mut ours = HashMap::new();
+        let mut theirs = Vec::new();
+
+        for port in Port::ALL {
+            let channel = MessageChannel::new()?;
+
+            // Ports share handlers with the worker, messages carry everything needed to route them.
+            let local = channel.port1();
+            local.set_onmessage(worker.onmessage().as_ref());
+            local.set_onmessageerror(worker.onmessageerror().as_ref());
+
+            ours.insert(port, local);
+            theirs.push((port, Transferable::new(channel.port2())));
+        }
+
+        self.post(AppId::DEFAULT, &HostMessage::Ports(theirs));
+        *self.ports.borrow_mut() = ours;
+
+        Ok(())
+    }
+
+    fn open_frame_clock(self: &Rc<Self>) -> Result<(), JsValue> {
+        use web_sys::MessageChannel;
+
+        let channel = MessageChannel::new()?;
+        self.post(
+            AppId::DEFAULT,
+            &HostMessage::FrameClock(Transferable::new(channel.port2())),
+        );
+        *self.frame_clock.borrow_mut() = Some(channel.port1());
+        self.run_frame_clock();
+
+        Ok(())
+    }
+
+    /// Tick frame clock on every animation frame while any app updates on them.
+    fn run_frame_clock(self: &Rc<Self>) {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+
+        let wanted =
+            !self.frame_clock_apps.borrow().is_empty() && self.frame_clock.borrow().is_some();
+        if !wanted || self.frame_clock_running.replace(true) {
+            return;
+        }
+
+        let tick = {
+            let inner = Rc::clone(self);
+            Closure::once_into_js(move || {
+                inner.frame_clock_running.set(false);
+
+                if let Some(port) = &*inner.frame_clock.borrow() {
+                    if let Err(err) = port.post_message(&precise_now().into()) {
+                        web_sys::console::warn_1(&err);
+                    }
+                }
+
+                inner.run_frame_clock();
+            })
+        };
+
+        let scheduled = web_sys::window().map_or(false, |window| {
+            window.request_animation_frame(tick.unchecked_ref()).is_ok()
+        });
+        if !scheduled {
+            self.frame_clock_running.set(false);
+        }
+    }
+
+    /// Spawn replacement of the current worker, or reconnect to the shared one.
+    fn spawn_endpoint(&self) -> Result<Endpoint, SpawnError> {
+        Endpoint::spawn(
+            self.flavor,
+            &self.artifacts.borrow(),
+            &self.bootstrap,
+            self.shared.as_deref(),
+        )
+    }
+
+    /// Make spare the current worker, returns `false` if there is no spare.
+    ///
+    /// Current worker is expected to be terminated already.
+    fn promote_spare(self: &Rc<Self>) -> bool {
+        let Some(spare) = self.spare.borrow_mut().take() else {
+            return false;
+        };
+
+        *self.worker.borrow_mut() = Endpoint::Dedicated(spare.worker);
+        self.listen();
+
+        if let Some((threads, capabilities)) = spare.ready.get() {
+            self.ready(threads, capabilities);
+        }
+
+        true
+    }
+
+    /// Swap upgraded worker in once it is ready and current app handed its snapshot over.
+    fn finish_upgrade(self: &Rc<Self>) {
+        let upgrade = {
+            let mut upgrade = self.upgrade.borrow_mut();
+            let done = upgrade.as_ref().map_or(false, |upgrade| {
+                upgrade.ready.is_some() && upgrade.snapshot.is_some()
+            });
+            if !done {
+                return;
+            }
+            upgrade.take()
+        };
+        let Some(Upgrade {
+            worker,
+            artifacts,
+            app,
+            canvas,
+            ready: Some((threads, capabilities)),
+            snapshot: Some(snapshot),
+        }) = upgrade
+        else {
+            return;
+        };
+
+        // Old ports are kept out of `listen`, old worker confirms shutdown over them.
+        let old_ports: Vec<_> = self
+            .ports
+            .borrow_mut()
+            .drain()
+            .map(|(_, port)| port)
+            .collect();
+        let old_worker = self.worker.replace(Endpoint::Dedicated(worker));
+        self.retire(old_worker, old_ports);
+
+        // Spare runs the old build.
+        if let Some(spare) = self.spare.borrow_mut().take() {
+            spare.worker.terminate();
+        }
+
+        *self.artifacts.borrow_mut() = artifacts;
+        self.pending.borrow_mut().get_or_insert_with(Vec::new);
+        self.abandon_previews();
+        // Other apps keep their canvases until they are attached again.
+        self.canvases.borrow_mut().retain(|(id, _), _| *id != app);
+        self.buffer_sizes
+            .borrow_mut()
+            .retain(|(id, _), _| *id != app);
+        self.text_inputs.borrow_mut().retain(|(id, _), text_input| {
+            let other = *id != app;
+            if !other {
+                text_input.remove();
+            }
+            other
+        });
+
+        let handle = WorkerHandle {
+            inner: Rc::clone(self),
+            app,
+        };
+        match snapshot {
+            Ok(snapshot) => handle.send(HostMessage::RestoreSnapshot(snapshot)),
+            Err(err) => web_sys::console::warn_1(
+                &format!("app state is lost in upgrade, snapshot failed: {err}").into(),
+            ),
+        }
+        if let Err(err) = handle.attach(&canvas) {
+            self.report(&WorkerError::Respawn(err));
+        }
+
+        self.listen();
+        self.ready(threads, capabilities);
+    }
+
+    /// Drop upgrade to given worker if it is still pending.
+    fn abandon_upgrade(&self, worker: &Worker, error: WorkerError) {
+        let mut upgrade = self.upgrade.borrow_mut();
+        let pending = upgrade
+            .as_ref()
+            .map_or(false, |upgrade| upgrade.worker == *worker);
+        if !pending {
+            return;
+        }
+
+        if let Some(upgrade) = upgrade.take() {
+            upgrade.worker.terminate();
+        }
+        drop(upgrade);
+        self.report(&error);
+    }
+
+    /// Shut replaced worker down, terminating it once it is done or after a timeout.
+    fn retire(&self, worker: Endpoint, ports: Vec<MessagePort>) {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+        use web_sys::MessageEvent;
+
+        let terminate = {
+            let worker = worker.clone();
+            let ports = ports.clone();
+
+            move || {
+                worker.terminate();
+                for port in &ports {
+                    port.close();
+                }
+            }
+        };
+
+        let onmessage = {
+            let terminate = terminate.clone();
+
+            Closure::wrap(Box::new(move |event: MessageEvent| {
+                if let Some(WorkerMessage::ShutdownComplete) = WorkerMessage::decode(&event.data())
+                {
+                    terminate();
+                }
+            }) as Box<dyn Fn(MessageEvent)>)
+        };
+
+        worker.set_onerror(None);
+        worker.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        for port in &ports {
+            port.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+            port.set_onmessageerror(None);
+        }
+        onmessage.forget();
+
+        // Shutdown is posted straight to the worker, replies still come over the control port.
+        let posted = HostMessage::Shutdown.encode().map(|(msg, _)| {
+            AppId::DEFAULT.stamp(&msg);
+            worker.post_message(&msg)
+        });
+        if !matches!(posted, Ok(Ok(()))) {
+            terminate();
+            return;
+        }
+
+        let timeout = Closure::once_into_js(terminate);
+        let scheduled = web_sys::window().map_or(false, |window| {
+            window
+                .set_timeout_with_callback_and_timeout_and_arguments_0(
+                    timeout.unchecked_ref(),
+                    RETIRE_TIMEOUT_MS,
+                )
+                .is_ok()
+        });
+        if !scheduled {
+            worker.terminate();
+        }
+    }
+
+    /// Report timeout if splash is still up by then, unless timeout is running already.
+    fn arm_splash_timeout(self: &Rc<Self>) {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+
+        let timeout_ms = self
+            .splash
+            .borrow()
+            .as_ref()
+            .filter(|splash| !splash.is_armed())
+            .and_then(Splash::timeout_ms);
+        let Some(timeout_ms) = timeout_ms else {
+            return;
+        };
+
+        let inner = Rc::downgrade(self);
+        let fire = Closure::once_into_js(move || {
+            let Some(inner) = inner.upgrade() else {
+                return;
+            };
+
+            let timed_out = match &mut *inner.splash.borrow_mut() {
+                Some(splash) => {
+                    splash.clear_timer();
+                    true
+                }
+                None => false,
+            };
+            if timed_out {
+                inner.report(&WorkerError::FirstFrameTimeout { timeout_ms });
+            }
+        });
+
+        let timer = web_sys::window().and_then(|window| {
+            window
+                .set_timeout_with_callback_and_timeout_and_arguments_0(
+                    fire.unchecked_ref(),
+                    timeout_ms as i32,
+                )
+                .ok()
+        });
+        if let (Some(timer), Some(splash)) = (timer, &mut *self.splash.borrow_mut()) {
+            splash.set_timer(timer);
+        }
+    }
+
+    fn report(&self, error: &WorkerError) {
+        match &self.on_error {
+            Some(on_error) => on_error(error),
+            None => web_sys::console::error_1(&error.to_string().into()),
+        }
+    }
+
+    fn fail(self: &Rc<Self>, error: WorkerError) {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+
+        self.report(&error);
+
+        if self.shutting_down.get() {
+            return;
+        }
+
+        let Some(policy) = self.restart else {
+            return;
+        };
+
+        let attempt = self.attempts.get();
+        if attempt >= policy.max_attempts {
+            return;
+        }
+        self.attempts.set(attempt + 1);
+
+        self.worker.borrow().terminate();
+
+        // Whatever was posted to failed worker is lost, start queueing again.
+        self.pending.borrow_mut().get_or_insert_with(Vec::new);
+        self.abandon_previews();
+
+        if self.promote_spare() {
+            return;
+        }
+
+        let respawn = {
+            let inner = Rc::clone(self);
+
+            Closure::once_into_js(move || match inner.spawn_endpoint() {
+                Ok(worker) => {
+                    *inner.worker.borrow_mut() = worker;
+                    inner.listen();
+                }
+                Err(err) => inner.fail(WorkerError::Respawn(err)),
+            })
+        };
+
+        let scheduled = web_sys::window()
+            .ok_or(SpawnError::NoWindow)
+            .and_then(|window| {
+                window
+                    .set_timeout_with_callback_and_timeout_and_arguments_0(
+                        respawn.unchecked_ref(),
+                        policy.delay_ms(attempt),
+                    )
+                    .map_err(SpawnError::Dom)
+            });
+        // Restart is best effort, failure to schedule it is reported like a failed respawn.
+        if let Err(err) = scheduled {
+            self.report(&WorkerError::Respawn(err));
+        }
+    }
+
+    fn local_storage() -> Result<web_sys::Storage, SpawnError> {
+        web_sys::window()
+            .ok_or(SpawnError::NoWindow)?
+            .local_storage()
+            .map_err(SpawnError::Dom)?
+            .ok_or(SpawnError::NoWindow)
+    }
+
+    fn stored_settings(&self) -> Result<Vec<(String, String)>, SpawnError> {
+        let storage = Self::local_storage()?;
+        let len = storage.length().map_err(SpawnError::Dom)?;
+
+        let mut settings = Vec::new();
+        for i in 0..len {
+            let Some(name) = storage.key(i).map_err(SpawnError::Dom)? else {
+                continue;
+            };
+            let Some(key) = name.strip_prefix(&self.settings_prefix) else {
+                continue;
+            };
+
+            if let Some(value) = storage.get_item(&name).map_err(SpawnError::Dom)? {
+                settings.push((key.to_owned(), value));
+            }
+        }
+
+        Ok(settings)
+    }
+
+    fn save_settings(&self, key: &str, value: &str) -> Result<(), SpawnError> {
+        Self::local_storage()?
+            .set_item(&format!("{}{key}", self.settings_prefix), value)
+            .map_err(SpawnError::Dom)
+    }
+
+    /// Move anchored element over its position on the canvas, or hide it.
+    fn place_anchor(&self, app: AppId, view: ViewId, anchor: &DomAnchor) {
+        let Some(element) = &anchor.element else {
+            return;
+        };
+        let style = element.style();
+
+        let canvases = self.canvases.borrow();
+        let (Some(position), Some(canvas)) = (anchor.position, canvases.get(&(app, view))) else {
+            let _ = style.set_property("visibility", "hidden");
+            return;
+        };
+
+        let (width, height) = self
+            .buffer_sizes
+            .borrow()
+            .get(&(app, view))
+            .map_or((canvas.width(), canvas.height()), |size| size.get());
+        let client_size = Vec2::new(canvas.client_width() as f32, canvas.client_height() as f32);
+        let position = PhysicalPx(position).to_client(client_size, UVec2::new(width, height));
+
+        let _ = style.set_property(
+            "left",
+            &format!("{}px", canvas.offset_left() as f32 + position.0.x),
+        );
+        let _ = style.set_property(
+            "top",
+            &format!("{}px", canvas.offset_top() as f32 + position.0.y),
+        );
+        let _ = style.set_property("visibility", "visible");
+    }
+
+    /// Call method of `navigator.clipboard`, which isn't covered by stable `web-sys`.
+    async fn clipboard(method: &str, args: &[JsValue]) -> Result<JsValue, JsValue> {
+        use js_sys::{Array, Function, Promise, Reflect};
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::JsFuture;
+
+        let window = web_sys::window().ok_or("not running on a page")?;
+        let navigator = Reflect::get(&window, &"navigator".into())?;
+        let clipboard = Reflect::get(&navigator, &"clipboard".into())?;
+        if clipboard.is_undefined() {
+            return Err("clipboard is not available, page must be served over https".into());
+        }
+
+        let function: Function = Reflect::get(&clipboard, &method.into())?.dyn_into()?;
+        let promise: Promise = function
+            .apply(&clipboard, &args.iter().collect::<Array>())?
+            .dyn_into()?;
+
+        JsFuture::from(promise).await
+    }
+
+    /// Fail preview requests which old worker is never going to answer.
+    fn abandon_previews(&self) {
+        let previews = std::mem::take(&mut *self.previews.borrow_mut());
+
+        for callback in previews.into_values() {
+            callback(Err("worker was restarted".to_owned()));
+        }
+    }
+
+    fn flush(&self) {
+        let pending = self.pending.borrow_mut().take().unwrap_or_default();
+
+        for queued in pending {
+            self.post_captured(queued.app, &queued.msg, queued.captured_at);
+        }
+    }
+
+    fn send(&self, app: AppId, msg: HostMessage) {
+        let captured_at = precise_now();
+        self.flush_coalesced();
+        self.enqueue(Queued {
+            app,
+            msg,
+            captured_at,
+        });
+    }
+
+    fn send_coalesced(self: &Rc<Self>, app: AppId, msg: HostMessage) {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+
+        let captured_at = precise_now();
+        let mut coalescing = self.coalescing.borrow_mut();
+
+        // Only the latest message can take it, merging into earlier ones would reorder them.
+        let result = match coalescing.last_mut() {
+            Some(last) if last.app == app => last.msg.coalesce(msg).map(|()| {
+                // Merged message is as new as the latest input in it.
+                last.captured_at = captured_at;
+            }),
+            _ => Err(msg),
+        };
+
+        match result {
+            Ok(()) => self.coalesced.set(self.coalesced.get() + 1),
+            Err(msg) => coalescing.push(Queued {
+                app,
+                msg,
+                captured_at,
+            }),
+        }
+
+        if self.coalesce_scheduled.replace(true) {
+            return;
+        }
+
+        let flush = {
+            let inner = Rc::clone(self);
+            Closure::once_into_js(move || inner.flush_coalesced())
+        };
+
+        let scheduled = web_sys::window().map_or(false, |window| {
+            window
+                .request_animation_frame(flush.unchecked_ref())
+                .is_ok()
+        });
+
+        if !scheduled {
+            drop(coalescing);
+            self.flush_coalesced();
+        }
+    }
+
+    fn flush_coalesced(&self) {
+        self.coalesce_scheduled.set(false);
+        let coalescing = std::mem::take(&mut *self.coalescing.borrow_mut());
+
+        for queued in coalescing {
+            self.enqueue(queued);
+        }
+    }
+
+    fn enqueue(&self, queued: Queued) {
+        let mut pending = self.pending.borrow_mut();
+
+        match pending.as_mut() {
+            Some(queue) => queue.push(queued),
+            None => {
+                drop(pending);
+                self.post_captured(queued.app, &queued.msg, queued.captured_at);
+            }
+        }
+    }
+
+    /// Send page-wide state to app seen for the first time.
+    ///
+    /// Apps known before worker is ready get stored settings with the rest.
+    fn introduce(&self, app: AppId) {
+        if self.pending.borrow().is_none() {
+            match self.stored_settings() {
+                Ok(settings) => self.send(app, HostMessage::StoredSettings(settings)),
+                Err(err) => web_sys::console::warn_1(
+                    &format!("failed to read stored settings: {err}").into(),
+                ),
+            }
+        }
+
+        if let Some(document) = web_sys::window().and_then(|window| window.document()) {
+            self.send(
+                app,
+                HostMessage::Visibility {
+                    visible: !document.hidden(),
+                },
+            );
+        }
+
+        if let Some(preferences) = media_preferences() {
+            self.send(app, preferences);
+        }
+
+        if let Some(focus) = focus() {
+            self.send(app, focus);
+        }
+
+        if let Some(layout) = &*self.keyboard_layout.borrow() {
+            self.send(app, HostMessage::KeyboardLayout(layout.clone()));
+        }
+    }
+
+    fn forward_motion_sensors(self: &Rc<Self>, window: &web_sys::Window) -> Result<(), SpawnError> {
+        use wasm_bindgen::prelude::{Closure, JsCast};
+        use web_sys::{DeviceAcceleration, DeviceMotionEvent, DeviceOrientationEvent};
+
+        let onorientation = {
+            let inner = Rc::clone(self);
+
+            Closure::wrap(Box::new(move |event: DeviceOrientationEvent| {
+                // Devices without sensors report a single event with nothing in it.
+                let (Some(alpha), Some(beta), Some(gamma)) =
+                    (event.alpha(), event.beta(), event.gamma())
+                else {
+                    return;
+                };
+
+                let apps = inner.apps.borrow().clone();
+                for app in apps {
+                    inner.send_coalesced(
+                        app,
+                        HostMessage::DeviceOrientation {
+                            alpha: alpha as f32,
+                            beta: beta as f32,
+                            gamma: gamma as f32,
+                        },
+                    );
+                }
+            }) as Box<dyn Fn(DeviceOrientationEvent)>)
+        };
+
+        let onmotion = {
+            let inner = Rc::clone(self);
+
+            Closure::wrap(Box::new(move |event: DeviceMotionEvent| {
+                fn axes(acceleration: Option<DeviceAcceleration>) -> Option<[f32; 3]> {
+                    let acceleration = acceleration?;
+                    Some([
+                        acceleration.x()? as f32,
+                        acceleration.y()? as f32,
+                        acceleration.z()? as f32,
+                    ])
+                }
+
+                let Some(with_gravity) = axes(event.acceleration_including_gravity()) else {
+                    return;
+                };
+                let acceleration = axes(event.acceleration());
+
+                let apps = inner.apps.borrow().clone();
+                for app in apps {
+                    inner.send_coalesced(
+                        app,
+                        HostMessage::DeviceMotion {
+                            acceleration,
+                            with_gravity,
+                        },
+                    );
+                }
+            }) as Box<dyn Fn(DeviceMotionEvent)>)
+        };
+
+        window
+            .add_event_listener_with_callback(
+                "deviceorientation",
+                onorientation.as_ref().unchecked_ref(),
+            )
+            .map_err(SpawnError::Dom)?;
+        window
+            .add_event_listener_with_callback("devicemotion", onmotion.as_ref().unchecked_ref())
+            .map_err(SpawnError::Dom)?;
+        onorientation.forget();
+        onmotion.forget();
+
+        Ok(())
+    }
+
+    /// Read labels of keys from `navigator.keyboard.getLayoutMap()` and pass them to every app.
+    fn fetch_keyboard_layout(self: &Rc<Self>) {
+        use js_sys::{Array, Function, Promise, Reflect};
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::JsFuture;
+
+        let Some(keyboard) = keyboard() else {
+            return;
+        };
+        let Some(promise) = Reflect::get(&keyboard, &"getLayoutMap".into())
+            .ok()
+            .and_then(|function| function.dyn_into::<Function>().ok())
+            .and_then(|function| function.call0(&keyboard).ok())
+            .and_then(|promise| promise.dyn_into::<Promise>().ok())
+        else {
+            return;
+        };
+
+        let inner = Rc::clone(self);
+        wasm_bindgen_futures::spawn_local(async move {
+            // Browsers refuse inside of iframes unless page allows it.
+            let map = match JsFuture::from(promise).await {
+                Ok(map) => map,
+                Err(err) => {
+                    web_sys::console::warn_1(&err);
+                    return;
+                }
+            };
+            let Ok(Some(entries)) = js_sys::try_iter(&map) else {
+                return;
+            };
+
+            let layout: Vec<_> = entries
+                .filter_map(|entry| {
+                    let entry: Array = entry.ok()?.dyn_into().ok()?;
+                    Some((entry.get(0).as_string()?, entry.get(1).as_string()?))
+                })
+                .collect();
+
+            let apps = inner.apps.borrow().clone();
+            for app in apps {
+                inner.send(app, HostMessage::KeyboardLayout(layout.clone()));
+            }
+            *inner.keyboard_layout.borrow_mut() = Some(layout);
+        });
+    }
+
+    fn post(&self, app: AppId, msg: &HostMessage) {
+        self.post_captured(app, msg, precise_now());
+    }
+
+    /// Post message which page came up with at `captured_at` by [`precise_now`].
+    ///
+    /// Input may wait for the worker or the next animation frame before going out,
+    /// latency is measured from when it happened rather than from when it was posted.
+    fn post_captured(&self, app: AppId, msg: &HostMessage, captured_at: f64) {
+        let id = CorrelationId(self.next_id.replace(self.next_id.get().wrapping_add(1)));
+        let _span = info_span!("bridge_send", kind = msg.kind(), correlation_id = id.0).entered();
+
+        let kind = msg.kind();
+        let target_port = msg.port();
+        let port = self.ports.borrow().get(&target_port).cloned();
+        let (msg, transfer) = match msg.encode() {
+            Ok(encoded) => encoded,
+            Err(err) => {
+                self.report(&WorkerError::Bridge(err));
+                return;
+            }
+        };
+        // Envelope uses `Date.now()`, which worker shares.
+        Envelope {
+            id,
+            sent_at: js_sys::Date::now() - (precise_now() - captured_at),
+        }
+        .stamp(&msg);
+        app.stamp(&msg);
+
+        if let Some(probe) = &mut *self.latency_probe.borrow_mut() {
+            if target_port == Port::Input {
+                probe.sent(app, id, captured_at);
+            }
+        }
+
+        if let Err(reason) = validate_transfer(&transfer) {
+            self.report(&WorkerError::Bridge(BridgeError::Serialization {
+                kind: Some(kind),
+                reason,
+            }));
+            return;
+        }
+
+        // Transferred buffers are detached after posting, so their size has to be taken now.
+        if let Some(traffic) = &mut *self.traffic.borrow_mut() {
+            traffic.record(TrafficDirection::Sent, Some(kind), &msg);
+        }
+
+        // Transferable objects need to be passed twice:
+        // once as part of message, and other time inside transfer *array*.
+        // Otherwise JS runtime will panic.
+        let result = match port {
+            Some(port) => port.post_message_with_transferable(&msg, &transfer),
+            None => self
+                .worker
+                .borrow()
+                .post_message_with_transfer(&msg, &transfer),
+        };
+
+        if let Err(err) = result {
+            self.report(&WorkerError::Bridge(BridgeError::serialization(
+                Some(kind),
+                &err,
+            )));
+        }
+    }
+}
+
+/// Page's high resolution clock offset to match `Date.now()`, in ms.
+fn epoch_now() -> f64 {
+    web_sys::window()
+        .and_then(|window| window.performance())
+        .map_or_else(js_sys::Date::now, |performance| {
+            performance.time_origin() + performance.now()
+        })
+}
+
+/// Page's high resolution clock, in ms.
+fn precise_now() -> f64 {
+    web_sys::window()
+        .and_then(|window| window.performance())
+        .map_or_else(js_sys::Date::now, |performance| performance.now())
+}
+
+/// Whether pointer is currently locked to given canvas.
+fn is_locked(canvas: &HtmlCanvasElement) -> bool {
+    let element = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.pointer_lock_element());
+
+    element.as_ref() == Some(canvas.as_ref())
+}
+
+/// Save JSON document as a file through browser download.
+fn download_json(json: &str, filename: &str) -> Result<(), SpawnError> {
+    download(&json.into(), "application/json", filename)
+}
+
+/// Save string or buffer as a file of given MIME type through browser download.
+fn download(contents: &JsValue, mime: &str, filename: &str) -> Result<(), SpawnError> {
+    use js_sys::Array;
+    use wasm_bindgen::JsCast;
+    use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, Url};
+
+    let parts = Array::new();
+    parts.push(contents);
+
+    let blob = Blob::new_with_str_sequence_and_options(&parts, BlobPropertyBag::new().type_(mime))
+        .map_err(SpawnError::BlobUrl)?;
+    let url = Url::create_object_url_with_blob(&blob).map_err(SpawnError::BlobUrl)?;
+
+    let anchor: HtmlAnchorElement = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or(SpawnError::NoWindow)?
+        .create_element("a")
+        .map_err(SpawnError::Dom)?
+        .unchecked_into();
+    anchor.set_href(&url);
+    anchor.set_download(filename);
+    anchor.click();
+
+    Url::revoke_object_url(&url).map_err(SpawnError::BlobUrl)
+}
+
+/// Contents of `.bitmap` file with pixels of the bitmap.
+fn bitmap_file(bitmap: &ImageBitmap) -> Result<ArrayBuffer, SpawnError> {
+    use js_sys::Uint8Array;
+    use wasm_bindgen::JsCast;
+    use web_sys::CanvasRenderingContext2d;
+
+    let (width, height) = (bitmap.width(), bitmap.height());
+    let canvas: HtmlCanvasElement = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or(SpawnError::NoWindow)?
+        .create_element("canvas")
+        .map_err(SpawnError::Dom)?
+        .unchecked_into();
+    canvas.set_width(width);
+    canvas.set_height(height);
+
+    let context: CanvasRenderingContext2d = canvas
+        .get_context("2d")
+        .map_err(SpawnError::Dom)?
+        .ok_or_else(|| SpawnError::Dom("2d context is not available".into()))?
+        .unchecked_into();
+    context
+        .draw_image_with_image_bitmap(bitmap, 0.0, 0.0)
+        .map_err(SpawnError::Dom)?;
+    let pixels = context
+        .get_image_data(0.0, 0.0, width as f64, height as f64)
+        .map_err(SpawnError::Dom)?
+        .data();
+
+    let file = Uint8Array::new_with_length(8 + pixels.len() as u32);
+    file.subarray(0, 4).copy_from(&width.to_le_bytes());
+    file.subarray(4, 8).copy_from(&height.to_le_bytes());
+    file.subarray(8, file.length()).copy_from(&pixels);
+    Ok(file.buffer())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_rank_percentile() {
+        let samples: Vec<_> = (1..=100).map(f64::from).collect();
+
+        assert_eq!(percentile(&samples, 0.50), 50.0);
+        assert_eq!(percentile(&samples, 0.95), 95.0);
+        assert_eq!(percentile(&samples, 0.99), 99.0);
+        assert_eq!(percentile(&samples, 0.0), 1.0);
+        assert_eq!(percentile(&samples, 1.0), 100.0);
+        assert_eq!(percentile(&[7.0], 0.5), 7.0);
+    }
+
+    #[test]
+    fn latency_report() {
+        let probe = LatencyProbe {
+            samples: [4.0, 1.0, 3.0, 2.0].into(),
+            ..LatencyProbe::default()
+        };
+        let report = probe.report().unwrap();
+
+        assert_eq!(report.count, 4);
+        assert_eq!(report.p50_ms, 2.0);
+        assert_eq!(report.p99_ms, 4.0);
+        assert_eq!(report.max_ms, 4.0);
+        assert!(LatencyProbe::default().report().is_none());
+    }
+}
@@ -0,0 +1,133 @@
+//! Hidden DOM mirror of worker's accessibility tree.
+//!
+//! Screen readers cannot look inside a canvas, so every accessible node gets a transparent element
+//! laid over the canvas at the same spot.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Document, Element, HtmlElement};
+
+use super::SpawnError;
+use crate::protocol::{AccessNode, AccessTree};
+
+/// Layer of aria-labelled elements on top of the canvas.
+///
+/// Mirror fills its container, which should have the same size as the canvas
+/// and be positioned (e.g. `position: relative`), so that elements end up in the right place.
+pub struct AccessibilityMirror {
+    document: Document,
+    root: HtmlElement,
+    elements: RefCell<HashMap<u64, HtmlElement>>,
+}
+
+impl AccessibilityMirror {
+    /// Add mirror layer to container.
+    pub fn new(container: &Element) -> Result<Self, SpawnError> {
+        let document = container.owner_document().ok_or(SpawnError::NoWindow)?;
+        let root = create_element(&document, "div")?;
+
+        // Elements must stay in accessibility tree, so no `display: none` or `visibility: hidden`.
+        root.style()
+            .set_css_text("position: absolute; inset: 0; overflow: hidden; pointer-events: none;");
+        container.append_child(&root).map_err(SpawnError::Dom)?;
+
+        Ok(AccessibilityMirror {
+            document,
+            root,
+            elements: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// Bring mirror in sync with the tree.
+    ///
+    /// Elements are reused between updates, so screen readers don't lose their place.
+    pub fn update(&self, tree: &AccessTree) -> Result<(), SpawnError> {
+        let mut elements = self.elements.borrow_mut();
+        let mut stale = std::mem::take(&mut *elements);
+
+        for node in &tree.nodes {
+            let element = match stale.remove(&node.id) {
+                Some(element) => element,
+                None => create_element(&self.document, "div")?,
+            };
+
+            describe(&element, node, tree).map_err(SpawnError::Dom)?;
+
+            // Appending existing child moves it, this keeps DOM in reading order.
+            self.root.append_child(&element).map_err(SpawnError::Dom)?;
+            elements.insert(node.id, element);
+        }
+
+        for element in stale.into_values() {
+            element.remove();
+        }
+
+        match tree.focus.filter(|id| elements.contains_key(id)) {
+            Some(focus) => self
+                .root
+                .set_attribute("aria-activedescendant", &element_id(focus))
+                .map_err(SpawnError::Dom)?,
+            None => self
+                .root
+                .remove_attribute("aria-activedescendant")
+                .map_err(SpawnError::Dom)?,
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for AccessibilityMirror {
+    fn drop(&mut self) {
+        self.root.remove();
+    }
+}
+
+fn create_element(document: &Document, tag: &str) -> Result<HtmlElement, SpawnError> {
+    document
+        .create_element(tag)
+        .map_err(SpawnError::Dom)?
+        .dyn_into()
+        .map_err(|element| SpawnError::Dom(element.into()))
+}
+
+fn describe(element: &HtmlElement, node: &AccessNode, tree: &AccessTree) -> Result<(), JsValue> {
+    element.set_id(&element_id(node.id));
+
+    match &node.role {
+        Some(role) => {
+            element.set_attribute("role", role)?;
+            match &node.name {
+                Some(name) => element.set_attribute("aria-label", name)?,
+                None => element.remove_attribute("aria-label")?,
+            }
+            element.set_text_content(None);
+        }
+        None => {
+            element.remove_attribute("role")?;
+            element.remove_attribute("aria-label")?;
+            element.set_text_content(node.name.as_deref());
+        }
+    }
+
+    // Percentages keep elements aligned however the canvas is scaled by CSS.
+    let css = match node.bounds {
+        Some([x0, y0, x1, y1]) if tree.width > 0.0 && tree.height > 0.0 => format!(
+            "position: absolute; opacity: 0; left: {}%; top: {}%; width: {}%; height: {}%;",
+            x0 / tree.width * 100.0,
+            y0 / tree.height * 100.0,
+            (x1 - x0) / tree.width * 100.0,
+            (y1 - y0) / tree.height * 100.0,
+        ),
+        _ => "position: absolute; opacity: 0; left: 0; top: 0; width: 1px; height: 1px;".to_owned(),
+    };
+    element.style().set_css_text(&css);
+
+    Ok(())
+}
+
+fn element_id(id: u64) -> String {
+    format!("bevy-a11y-{id}")
+}
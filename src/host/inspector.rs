@@ -0,0 +1,191 @@
+//! Collapsible tree of worker's entities, see [`WorkerHandle::open_inspector`](super::WorkerHandle::open_inspector).
+//!
+//! Every component shows its RON value in an editable text field,
+//! applying it sends the edited value back to the worker.
+
+use std::collections::{BTreeMap, HashSet};
+
+use wasm_bindgen::prelude::{Closure, JsCast};
+use web_sys::{Document, Element, Event, HtmlElement, HtmlTextAreaElement};
+
+use super::SpawnError;
+use crate::protocol::InspectedEntity;
+
+/// Panel inside page's container, rebuilt on every snapshot.
+pub(super) struct InspectorPanel {
+    document: Document,
+    root: HtmlElement,
+    _onclick: Closure<dyn FnMut(Event)>,
+}
+
+impl InspectorPanel {
+    /// Add panel to container, `on_edit` gets entity, component type name and new value.
+    pub(super) fn new(
+        container: &Element,
+        on_edit: impl Fn(u64, String, String) + 'static,
+    ) -> Result<Self, SpawnError> {
+        let document = container.owner_document().ok_or(SpawnError::NoWindow)?;
+        let root: HtmlElement = document
+            .create_element("div")
+            .map_err(SpawnError::Dom)?
+            .unchecked_into();
+        root.style()
+            .set_css_text("font: 12px monospace; overflow: auto; max-height: 100%;");
+
+        // Single listener for all apply buttons, elements come and go with every snapshot.
+        let onclick = Closure::wrap(Box::new(move |event: Event| {
+            let Some(button) = event
+                .target()
+                .and_then(|target| target.dyn_into::<Element>().ok())
+            else {
+                return;
+            };
+            let (Some(entity), Some(component)) = (
+                button.get_attribute("data-entity"),
+                button.get_attribute("data-component"),
+            ) else {
+                return;
+            };
+            let Some(value) = button
+                .previous_element_sibling()
+                .and_then(|field| field.dyn_into::<HtmlTextAreaElement>().ok())
+                .map(|field| field.value())
+            else {
+                return;
+            };
+
+            if let Ok(entity) = entity.parse() {
+                on_edit(entity, component, value);
+            }
+        }) as Box<dyn FnMut(Event)>);
+        root.add_event_listener_with_callback("click", onclick.as_ref().unchecked_ref())
+            .map_err(SpawnError::Dom)?;
+
+        container.append_child(&root).map_err(SpawnError::Dom)?;
+
+        Ok(InspectorPanel {
+            document,
+            root,
+            _onclick: onclick,
+        })
+    }
+
+    /// Show snapshot, keeping expanded nodes expanded.
+    ///
+    /// Snapshot is skipped while one of the fields is focused, so edits in progress aren't lost.
+    pub(super) fn update(&self, entities: &[InspectedEntity]) -> Result<(), SpawnError> {
+        if let Some(active) = self.document.active_element() {
+            if self.root.contains(Some(&active)) && active.tag_name() == "TEXTAREA" {
+                return Ok(());
+            }
+        }
+
+        let mut open = HashSet::new();
+        let expanded = self
+            .root
+            .query_selector_all("details[open]")
+            .map_err(SpawnError::Dom)?;
+        for i in 0..expanded.length() {
+            let key = expanded
+                .item(i)
+                .and_then(|node| node.dyn_into::<Element>().ok())
+                .and_then(|element| element.get_attribute("data-key"));
+            open.extend(key);
+        }
+
+        let known: HashSet<u64> = entities.iter().map(|entity| entity.entity).collect();
+        let mut children: BTreeMap<Option<u64>, Vec<&InspectedEntity>> = BTreeMap::new();
+        for entity in entities {
+            let parent = entity.parent.filter(|parent| known.contains(parent));
+            children.entry(parent).or_default().push(entity);
+        }
+        for siblings in children.values_mut() {
+            siblings.sort_by_key(|entity| entity.entity);
+        }
+
+        self.root.set_inner_html("");
+        self.append_children(&self.root, None, &children, &open)
+    }
+
+    fn append_children(
+        &self,
+        parent: &Element,
+        of: Option<u64>,
+        children: &BTreeMap<Option<u64>, Vec<&InspectedEntity>>,
+        open: &HashSet<String>,
+    ) -> Result<(), SpawnError> {
+        for entity in children.get(&of).into_iter().flatten() {
+            let index = entity.entity as u32;
+            let generation = entity.entity >> 32;
+            let label = match &entity.name {
+                Some(name) => format!("{name} ({index}v{generation})"),
+                None => format!("Entity {index}v{generation}"),
+            };
+
+            let key = format!("{}", entity.entity);
+            let node = self.details(parent, &key, &label, open)?;
+
+            for (component, value) in &entity.components {
+                let short = component.rsplit("::").next().unwrap_or(component);
+                let key = format!("{}/{component}", entity.entity);
+                let details = self.details(&node, &key, short, open)?;
+
+                let field = self.element(&details, "textarea")?;
+                field.set_text_content(Some(value));
+                let rows = value.lines().count().clamp(1, 12);
+                field
+                    .set_attribute("rows", &rows.to_string())
+                    .map_err(SpawnError::Dom)?;
+
+                let button = self.element(&details, "button")?;
+                button.set_text_content(Some("Apply"));
+                button
+                    .set_attribute("data-entity", &entity.entity.to_string())
+                    .map_err(SpawnError::Dom)?;
+                button
+                    .set_attribute("data-component", component)
+                    .map_err(SpawnError::Dom)?;
+            }
+
+            self.append_children(&node, Some(entity.entity), children, open)?;
+        }
+
+        Ok(())
+    }
+
+    fn details(
+        &self,
+        parent: &Element,
+        key: &str,
+        label: &str,
+        open: &HashSet<String>,
+    ) -> Result<Element, SpawnError> {
+        let details = self.element(parent, "details")?;
+        details
+            .set_attribute("data-key", key)
+            .map_err(SpawnError::Dom)?;
+        details
+            .set_attribute("style", "margin-left: 1em;")
+            .map_err(SpawnError::Dom)?;
+        if open.contains(key) {
+            details.set_attribute("open", "").map_err(SpawnError::Dom)?;
+        }
+
+        let summary = self.element(&details, "summary")?;
+        summary.set_text_content(Some(label));
+
+        Ok(details)
+    }
+
+    fn element(&self, parent: &Element, tag: &str) -> Result<Element, SpawnError> {
+        let element = self.document.create_element(tag).map_err(SpawnError::Dom)?;
+        parent.append_child(&element).map_err(SpawnError::Dom)?;
+        Ok(element)
+    }
+}
+
+impl Drop for InspectorPanel {
+    fn drop(&mut self) {
+        self.root.remove();
+    }
+}
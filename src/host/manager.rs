@@ -0,0 +1,209 @@
+//! Several independent apps on one page, each in a worker of its own, e.g. product configurators in a grid.
+//!
+//! Unlike `worker::start_apps`, which packs small apps into a single worker,
+//! here every app gets a thread to itself, so a heavy one doesn't hold the others back.
+//! Workers still render canvases scrolled out of sight for nothing,
+//! so manager throttles them according to [`OffscreenPolicy`] until they are back.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+use web_sys::HtmlCanvasElement;
+
+use super::{SpawnError, WorkerBuilder, WorkerHandle};
+use crate::protocol::{HostMessage, ViewId};
+
+/// Reasons manager could not reach an app.
+#[derive(Debug, Clone)]
+pub enum ManagerError {
+    /// No app was spawned under this id.
+    UnknownApp(String),
+    /// Canvas could not be attached to the app.
+    Attach(SpawnError),
+}
+
+impl Display for ManagerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManagerError::UnknownApp(id) => write!(f, "no app with id `{id}`"),
+            ManagerError::Attach(err) => write!(f, "failed to attach canvas: {err}"),
+        }
+    }
+}
+
+impl Error for ManagerError {}
+
+/// What happens to apps whose canvas is out of sight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OffscreenPolicy {
+    /// Leave them running as usual.
+    Keep,
+    /// Cap them at `offscreen_fps`, and set `onscreen_fps` once they are back.
+    Throttle {
+        offscreen_fps: u32,
+        onscreen_fps: u32,
+    },
+    /// Freeze them until they are back.
+    #[default]
+    Pause,
+}
+
+struct Managed {
+    handle: WorkerHandle,
+    visible: bool,
+    // Whether manager paused the app for being out of sight, pauses of the page are left alone.
+    paused: bool,
+}
+
+impl Managed {
+    fn apply(&mut self, policy: OffscreenPolicy, visible: bool) {
+        self.visible = visible;
+
+        match (policy, visible) {
+            (OffscreenPolicy::Keep, _) => (),
+            (OffscreenPolicy::Throttle { offscreen_fps, .. }, false) => {
+                self.handle.set_target_fps(offscreen_fps)
+            }
+            (OffscreenPolicy::Throttle { onscreen_fps, .. }, true) => {
+                self.handle.set_target_fps(onscreen_fps)
+            }
+            (OffscreenPolicy::Pause, false) => {
+                if !self.paused && !self.handle.paused_by_page() {
+                    self.paused = true;
+                    self.handle.send(HostMessage::Pause);
+                }
+            }
+            (OffscreenPolicy::Pause, true) => {
+                if self.paused {
+                    self.paused = false;
+                    // Page may have paused it too in the meantime.
+                    if !self.handle.paused_by_page() {
+                        self.handle.send(HostMessage::Resume);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Apps running in their own workers, keyed by id.
+///
+/// Visibility of canvases comes from the same intersection reports their workers get.
+/// Dropping manager stops throttling, but leaves workers running; [`remove`](Self::remove) shuts them down.
+/// Canvas is gone once its worker restarts, pass a fresh one to [`attach`](Self::attach).
+pub struct WorkerManager {
+    apps: Rc<RefCell<BTreeMap<String, Managed>>>,
+    policy: OffscreenPolicy,
+}
+
+impl WorkerManager {
+    pub fn new(policy: OffscreenPolicy) -> Self {
+        WorkerManager {
+            apps: Rc::default(),
+            policy,
+        }
+    }
+
+    /// Spawn worker and attach canvas to it, replacing and shutting down app spawned under the same id.
+    pub fn spawn(
+        &mut self,
+        id: &str,
+        builder: WorkerBuilder,
+        canvas: &HtmlCanvasElement,
+    ) -> Result<WorkerHandle, SpawnError> {
+        let handle = builder.spawn()?;
+        if let Err(err) = handle.attach(canvas) {
+            // Worker never got the canvas, there is nothing to release.
+            handle.terminate();
+            return Err(err);
+        }
+
+        let apps = Rc::downgrade(&self.apps);
+        let key = id.to_owned();
+        let policy = self.policy;
+        handle.on_intersection(move |handle, view, visible| {
+            let Some(apps) = apps.upgrade().filter(|_| view == ViewId::PRIMARY) else {
+                return;
+            };
+            let mut apps = apps.borrow_mut();
+            // App spawned under the same id later has a worker of its own.
+            let Some(managed) = apps
+                .get_mut(&key)
+                .filter(|managed| Rc::ptr_eq(&managed.handle.inner, &handle.inner))
+            else {
+                return;
+            };
+
+            if managed.visible != visible {
+                managed.apply(policy, visible);
+            }
+        });
+
+        let previous = self.apps.borrow_mut().insert(
+            id.to_owned(),
+            Managed {
+                handle: handle.clone(),
+                visible: true,
+                paused: false,
+            },
+        );
+        if let Some(previous) = previous {
+            previous.handle.shutdown();
+        }
+
+        Ok(handle)
+    }
+
+    /// Attach fresh canvas to the app, e.g. after its worker restarted.
+    pub fn attach(&mut self, id: &str, canvas: &HtmlCanvasElement) -> Result<(), ManagerError> {
+        let handle = self
+            .get(id)
+            .ok_or_else(|| ManagerError::UnknownApp(id.to_owned()))?;
+        handle.attach(canvas).map_err(ManagerError::Attach)
+    }
+
+    /// Shut app's worker down and forget about it.
+    pub fn remove(&mut self, id: &str) -> Option<WorkerHandle> {
+        let managed = self.apps.borrow_mut().remove(id)?;
+        managed.handle.shutdown();
+
+        Some(managed.handle)
+    }
+
+    pub fn get(&self, id: &str) -> Option<WorkerHandle> {
+        self.apps
+            .borrow()
+            .get(id)
+            .map(|managed| managed.handle.clone())
+    }
+
+    /// Ids of managed apps, in alphabetical order.
+    pub fn ids(&self) -> Vec<String> {
+        self.apps.borrow().keys().cloned().collect()
+    }
+
+    /// Whether app's canvas is in sight, as of the latest intersection report.
+    pub fn is_visible(&self, id: &str) -> Option<bool> {
+        self.apps.borrow().get(id).map(|managed| managed.visible)
+    }
+
+    /// Send message to app with given id.
+    pub fn send(&self, id: &str, msg: HostMessage) -> Result<(), ManagerError> {
+        let handle = self
+            .get(id)
+            .ok_or_else(|| ManagerError::UnknownApp(id.to_owned()))?;
+        handle.send(msg);
+
+        Ok(())
+    }
+
+    /// Send message made by `f` to every app.
+    pub fn broadcast(&self, f: impl Fn() -> HostMessage) {
+        for managed in self.apps.borrow().values() {
+            managed.handle.send(f());
+        }
+    }
+}
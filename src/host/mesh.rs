@@ -0,0 +1,116 @@
+//! Several workers talking to each other directly, e.g. a render worker and a physics worker.
+//!
+//! Workers can't find each other on their own, so page creates a `MessageChannel` for every pair
+//! it connects and hands one end to each side.
+//! From then on messages skip the page, see [`worker::peers`](crate::worker::peers).
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use wasm_bindgen::JsValue;
+
+use super::{describe, WorkerHandle};
+use crate::protocol::{HostMessage, Transferable};
+
+/// Reasons workers of a mesh could not be connected.
+#[derive(Debug, Clone)]
+pub enum MeshError {
+    /// No worker was added under this name.
+    UnknownWorker(String),
+    /// Channel between workers could not be created.
+    Channel(JsValue),
+}
+
+impl Display for MeshError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MeshError::UnknownWorker(name) => write!(f, "no worker named `{name}` in the mesh"),
+            MeshError::Channel(err) => write!(f, "failed to create channel: {}", describe(err)),
+        }
+    }
+}
+
+impl Error for MeshError {}
+
+/// Named workers and direct connections between them.
+///
+/// Each worker knows the others it is connected to by their names in the mesh.
+/// Connections are gone when a worker restarts, call [`reconnect`](Self::reconnect) once it is ready again.
+#[derive(Default)]
+pub struct WorkerMesh {
+    workers: BTreeMap<String, WorkerHandle>,
+    // Pairs of names, ordered so each connection is stored once.
+    links: BTreeSet<(String, String)>,
+}
+
+impl WorkerMesh {
+    pub fn new() -> Self {
+        WorkerMesh::default()
+    }
+
+    /// Add worker under given name, replacing one added under the same name before.
+    pub fn add(&mut self, name: &str, worker: WorkerHandle) -> &mut Self {
+        self.workers.insert(name.to_owned(), worker);
+        self
+    }
+
+    pub fn worker(&self, name: &str) -> Option<&WorkerHandle> {
+        self.workers.get(name)
+    }
+
+    /// Connect two workers with a dedicated channel.
+    ///
+    /// Connecting the same pair again replaces the channel.
+    pub fn connect(&mut self, a: &str, b: &str) -> Result<(), MeshError> {
+        let link = if a <= b {
+            (a.to_owned(), b.to_owned())
+        } else {
+            (b.to_owned(), a.to_owned())
+        };
+
+        self.open(&link.0, &link.1)?;
+        self.links.insert(link);
+
+        Ok(())
+    }
+
+    /// Reopen every connection of the worker, after it was restarted.
+    pub fn reconnect(&self, name: &str) -> Result<(), MeshError> {
+        if !self.workers.contains_key(name) {
+            return Err(MeshError::UnknownWorker(name.to_owned()));
+        }
+
+        for (a, b) in &self.links {
+            if a == name || b == name {
+                self.open(a, b)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn open(&self, a: &str, b: &str) -> Result<(), MeshError> {
+        use web_sys::MessageChannel;
+
+        let worker = |name: &str| {
+            self.workers
+                .get(name)
+                .ok_or_else(|| MeshError::UnknownWorker(name.to_owned()))
+        };
+        let (worker_a, worker_b) = (worker(a)?, worker(b)?);
+
+        let channel = MessageChannel::new().map_err(MeshError::Channel)?;
+
+        worker_a.send(HostMessage::Peer {
+            peer: b.to_owned(),
+            port: Transferable::new(channel.port1()),
+        });
+        worker_b.send(HostMessage::Peer {
+            peer: a.to_owned(),
+            port: Transferable::new(channel.port2()),
+        });
+
+        Ok(())
+    }
+}
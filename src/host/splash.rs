@@ -0,0 +1,85 @@
+//! Splash element kept over the canvas until app presents its first frame,
+//! see [`WorkerBuilder::splash`](super::WorkerBuilder::splash).
+
+use wasm_bindgen::prelude::{Closure, JsCast};
+use web_sys::HtmlElement;
+
+/// How splash goes away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplashOptions {
+    /// Duration of fade out.
+    pub fade_ms: u32,
+    /// Report [`WorkerError::FirstFrameTimeout`](super::WorkerError::FirstFrameTimeout)
+    /// if there is no frame this long after a view is attached, splash stays up in this case.
+    pub timeout_ms: Option<u32>,
+}
+
+impl Default for SplashOptions {
+    fn default() -> Self {
+        SplashOptions {
+            fade_ms: 300,
+            timeout_ms: Some(30_000),
+        }
+    }
+}
+
+pub(super) struct Splash {
+    element: HtmlElement,
+    options: SplashOptions,
+    // Handle of timeout timer, `None` until a view is attached, once it fired or if there is none.
+    timer: Option<i32>,
+}
+
+impl Splash {
+    pub(super) fn new(element: HtmlElement, options: SplashOptions) -> Self {
+        Splash {
+            element,
+            options,
+            timer: None,
+        }
+    }
+
+    pub(super) fn timeout_ms(&self) -> Option<u32> {
+        self.options.timeout_ms
+    }
+
+    pub(super) fn is_armed(&self) -> bool {
+        self.timer.is_some()
+    }
+
+    pub(super) fn set_timer(&mut self, timer: i32) {
+        self.timer = Some(timer);
+    }
+
+    pub(super) fn clear_timer(&mut self) {
+        if let (Some(timer), Some(window)) = (self.timer.take(), web_sys::window()) {
+            window.clear_timeout_with_handle(timer);
+        }
+    }
+
+    /// Fade splash out and hide it once it's transparent.
+    pub(super) fn dismiss(mut self) {
+        self.clear_timer();
+
+        let style = self.element.style();
+        let _ = style.set_property(
+            "transition",
+            &format!("opacity {}ms ease-out", self.options.fade_ms),
+        );
+        let _ = style.set_property("opacity", "0");
+
+        let element = self.element.clone();
+        let hide = Closure::once_into_js(move || {
+            let _ = element.style().set_property("display", "none");
+        });
+        let scheduled = web_sys::window().map(|window| {
+            window.set_timeout_with_callback_and_timeout_and_arguments_0(
+                hide.unchecked_ref(),
+                self.options.fade_ms as i32,
+            )
+        });
+        if !matches!(scheduled, Some(Ok(_))) {
+            let _ = style.set_property("display", "none");
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Frame rate readout laid over the page, see [`WorkerHandle::show_frame_stats`](super::WorkerHandle::show_frame_stats).
+
+use wasm_bindgen::prelude::JsCast;
+use web_sys::{Element, HtmlElement};
+
+use super::SpawnError;
+use crate::protocol::FrameStats;
+
+const STYLE: &str = "position: absolute; top: 4px; right: 4px; padding: 2px 6px; \
+    background: rgba(0, 0, 32, 0.8); color: #0ff; font: 11px monospace; \
+    white-space: pre; pointer-events: none; z-index: 1;";
+
+/// Small box in the corner of page's container.
+pub(super) struct StatsOverlay {
+    root: HtmlElement,
+}
+
+impl StatsOverlay {
+    pub(super) fn new(container: &Element) -> Result<Self, SpawnError> {
+        let document = container.owner_document().ok_or(SpawnError::NoWindow)?;
+        let root: HtmlElement = document
+            .create_element("div")
+            .map_err(SpawnError::Dom)?
+            .unchecked_into();
+        root.style().set_css_text(STYLE);
+        root.set_text_content(Some("-- fps"));
+
+        container.append_child(&root).map_err(SpawnError::Dom)?;
+
+        Ok(StatsOverlay { root })
+    }
+
+    pub(super) fn update(&self, stats: &FrameStats) {
+        let text = format!(
+            "{:.0} fps  {:.1} ms  {} entities",
+            stats.fps, stats.frame_time_ms, stats.entities
+        );
+        self.root.set_text_content(Some(&text));
+    }
+}
+
+impl Drop for StatsOverlay {
+    fn drop(&mut self) {
+        self.root.remove();
+    }
+}
@@ -0,0 +1,13 @@
+//! Glue for running Bevy inside a dedicated web worker.
+//!
+//! The crate is split according to which side of the worker boundary the code runs on:
+//!
+//! * [`host`] lives on the page: it spawns the worker and hands it canvases.
+//! * [`worker`] lives inside the worker: plugins and app runner.
+//! * [`protocol`] describes messages exchanged between the two.
+//! * [`coords`] converts positions between pixel spaces on either side.
+
+pub mod coords;
+pub mod host;
+pub mod protocol;
+pub mod worker;
@@ -0,0 +1,3065 @@
+//! Messages exchanged between the page and the worker.
+//!
+//! Every message is a plain JS object tagged with a `kind` field.
+//! This lets transferable payloads (like `OffscreenCanvas`) ride along without extra wrapping.
+
+use std::cell::Cell;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::{Debug, Display, Formatter};
+use std::rc::Rc;
+
+use bevy::log::Level;
+use bevy::prelude::{Event, Resource};
+use js_sys::{Array, ArrayBuffer, Object, Reflect};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{ImageBitmap, MessagePort, OffscreenCanvas};
+
+/// Identifies a canvas slot on the page.
+///
+/// Each view is backed by its own `Window` entity inside the worker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ViewId(pub u32);
+
+impl ViewId {
+    /// View backing the primary window.
+    pub const PRIMARY: ViewId = ViewId(0);
+}
+
+/// When worker updates the app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UpdateMode {
+    /// Update every frame.
+    #[default]
+    Continuous,
+    /// Update only when something happens:
+    /// page sends a message, app requests redraw or `max_wait_ms` passes since last update.
+    Reactive { max_wait_ms: Option<u32> },
+    /// Update once per animation frame of the page, so frames line up with display refresh.
+    ///
+    /// Page drives it with ticks from `requestAnimationFrame` sent over a dedicated channel,
+    /// see [`HostMessage::FrameClock`]. Target frame rate is ignored,
+    /// and app stops updating while page doesn't get animation frames, e.g. in a background tab.
+    AnimationFrame,
+}
+
+/// Configuration page passes to the worker before any app is built, usually from URL query.
+///
+/// Flags the page didn't set are left for the app to decide.
+#[derive(Resource, Debug, Clone, PartialEq, Default)]
+pub struct BootFlags {
+    /// Most verbose level of log messages to output.
+    pub log_level: Option<Level>,
+    /// Scene to start in, meaning is up to the app.
+    pub scene: Option<String>,
+    pub quality: Option<QualityPreset>,
+    /// Seed for random number generators, so runs can be reproduced.
+    pub seed: Option<u64>,
+    /// Names of enabled debug toggles, meaning is up to the app.
+    pub debug: Vec<String>,
+    /// Frame rate app starts capped at.
+    pub fps: Option<u32>,
+    /// Graphics API renderer should use.
+    pub backend: Option<GraphicsBackend>,
+    /// MSAA, HDR and tonemapping app starts with.
+    pub graphics: GraphicsOptions,
+    /// Parameters flags don't know about, for app's own settings.
+    pub extra: BTreeMap<String, String>,
+}
+
+impl BootFlags {
+    /// Whether debug toggle with given name is enabled.
+    pub fn debug(&self, name: &str) -> bool {
+        self.debug.iter().any(|toggle| toggle == name)
+    }
+}
+
+/// Graphics API to render with.
+///
+/// Renderer only gets the ones the app was built with, picking another one leaves it without an adapter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsBackend {
+    WebGpu,
+    WebGl2,
+}
+
+impl GraphicsBackend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GraphicsBackend::WebGpu => "webgpu",
+            GraphicsBackend::WebGl2 => "webgl2",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let backend = match name {
+            "webgpu" => GraphicsBackend::WebGpu,
+            "webgl2" => GraphicsBackend::WebGl2,
+            _ => return None,
+        };
+
+        Some(backend)
+    }
+}
+
+/// Rendering quality app should aim for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityPreset {
+    Low,
+    Medium,
+    High,
+}
+
+impl QualityPreset {
+    pub fn name(&self) -> &'static str {
+        match self {
+            QualityPreset::Low => "low",
+            QualityPreset::Medium => "medium",
+            QualityPreset::High => "high",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let preset = match name {
+            "low" => QualityPreset::Low,
+            "medium" => QualityPreset::Medium,
+            "high" => QualityPreset::High,
+            _ => return None,
+        };
+
+        Some(preset)
+    }
+}
+
+/// Tonemapping method of cameras, mirrors Bevy's `Tonemapping`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TonemappingMethod {
+    None,
+    Reinhard,
+    ReinhardLuminance,
+    AcesFitted,
+    AgX,
+    SomewhatBoringDisplayTransform,
+    TonyMcMapface,
+    BlenderFilmic,
+}
+
+impl TonemappingMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TonemappingMethod::None => "none",
+            TonemappingMethod::Reinhard => "reinhard",
+            TonemappingMethod::ReinhardLuminance => "reinhard_luminance",
+            TonemappingMethod::AcesFitted => "aces_fitted",
+            TonemappingMethod::AgX => "agx",
+            TonemappingMethod::SomewhatBoringDisplayTransform => {
+                "somewhat_boring_display_transform"
+            }
+            TonemappingMethod::TonyMcMapface => "tony_mc_mapface",
+            TonemappingMethod::BlenderFilmic => "blender_filmic",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let method = match name {
+            "none" => TonemappingMethod::None,
+            "reinhard" => TonemappingMethod::Reinhard,
+            "reinhard_luminance" => TonemappingMethod::ReinhardLuminance,
+            "aces_fitted" => TonemappingMethod::AcesFitted,
+            "agx" => TonemappingMethod::AgX,
+            "somewhat_boring_display_transform" => {
+                TonemappingMethod::SomewhatBoringDisplayTransform
+            }
+            "tony_mc_mapface" => TonemappingMethod::TonyMcMapface,
+            "blender_filmic" => TonemappingMethod::BlenderFilmic,
+            _ => return None,
+        };
+
+        Some(method)
+    }
+}
+
+/// Present mode surface settled on, mirrors resolved modes of Bevy's `PresentMode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurfacePresentMode {
+    Fifo,
+    FifoRelaxed,
+    Immediate,
+    Mailbox,
+}
+
+impl SurfacePresentMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SurfacePresentMode::Fifo => "fifo",
+            SurfacePresentMode::FifoRelaxed => "fifo_relaxed",
+            SurfacePresentMode::Immediate => "immediate",
+            SurfacePresentMode::Mailbox => "mailbox",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let mode = match name {
+            "fifo" => SurfacePresentMode::Fifo,
+            "fifo_relaxed" => SurfacePresentMode::FifoRelaxed,
+            "immediate" => SurfacePresentMode::Immediate,
+            "mailbox" => SurfacePresentMode::Mailbox,
+            _ => return None,
+        };
+
+        Some(mode)
+    }
+
+    /// Whether frames wait for display refresh.
+    pub fn is_vsync(&self) -> bool {
+        matches!(
+            self,
+            SurfacePresentMode::Fifo | SurfacePresentMode::FifoRelaxed
+        )
+    }
+}
+
+/// Rendering settings tuned per device, see [`HostMessage::SetGraphics`].
+///
+/// Settings left `None` stay as app set them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GraphicsOptions {
+    /// MSAA sample count, 1 turns it off.
+    pub msaa: Option<u32>,
+    /// Render cameras to HDR textures.
+    pub hdr: Option<bool>,
+    pub tonemapping: Option<TonemappingMethod>,
+}
+
+impl GraphicsOptions {
+    fn encode(&self, msg: &Object) {
+        if let Some(msaa) = self.msaa {
+            set(msg, "msaa", &msaa.into());
+        }
+        if let Some(hdr) = self.hdr {
+            set(msg, "hdr", &hdr.into());
+        }
+        if let Some(tonemapping) = self.tonemapping {
+            set(msg, "tonemapping", &tonemapping.name().into());
+        }
+    }
+
+    fn decode(value: &JsValue) -> Self {
+        GraphicsOptions {
+            msaa: get(value, "msaa")
+                .and_then(|msaa| msaa.as_f64())
+                .map(|msaa| msaa as u32),
+            hdr: get(value, "hdr").and_then(|hdr| hdr.as_bool()),
+            tonemapping: get(value, "tonemapping")
+                .and_then(|method| TonemappingMethod::from_name(&method.as_string()?)),
+        }
+    }
+}
+
+/// What a browser context supports, probed separately on the page and in the worker.
+///
+/// The two can differ, e.g. some browsers expose WebGPU on the page but not in workers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Capabilities {
+    /// `OffscreenCanvas` exists and, on the page, canvas can transfer control to it.
+    pub offscreen_canvas: bool,
+    /// `navigator.gpu` exists.
+    pub webgpu: bool,
+    pub shared_array_buffer: bool,
+    /// Context is served with COOP/COEP headers, memory can't be shared between threads otherwise.
+    pub cross_origin_isolated: bool,
+    /// Workers can be spawned as ES modules.
+    pub module_workers: bool,
+    /// Origin Private File System is reachable through `navigator.storage`.
+    pub opfs: bool,
+}
+
+impl Capabilities {
+    /// Probe the current context, be it the page or a worker.
+    pub fn probe() -> Self {
+        let global = JsValue::from(js_sys::global());
+        let has = |target: &JsValue, name: &str| get(target, name).is_some();
+        let navigator = get(&global, "navigator").unwrap_or(JsValue::UNDEFINED);
+
+        // Workers have no canvas elements to transfer control of.
+        let transferable_canvas = match get(&global, "HTMLCanvasElement") {
+            Some(element) => get(&element, "prototype").map_or(false, |prototype| {
+                has(&prototype, "transferControlToOffscreen")
+            }),
+            None => true,
+        };
+
+        Capabilities {
+            offscreen_canvas: has(&global, "OffscreenCanvas") && transferable_canvas,
+            webgpu: has(&navigator, "gpu"),
+            shared_array_buffer: has(&global, "SharedArrayBuffer"),
+            cross_origin_isolated: get(&global, "crossOriginIsolated")
+                .and_then(|isolated| isolated.as_bool())
+                .unwrap_or(false),
+            module_workers: module_workers(&global),
+            opfs: get(&navigator, "storage").map_or(false, |storage| has(&storage, "getDirectory")),
+        }
+    }
+
+    /// Whether wasm memory can be shared with other threads.
+    pub fn shared_memory(&self) -> bool {
+        self.shared_array_buffer && self.cross_origin_isolated
+    }
+
+    fn encode(&self) -> Object {
+        let object = Object::new();
+        for (name, supported) in self.entries() {
+            set(&object, name, &supported.into());
+        }
+        object
+    }
+
+    fn decode(value: &JsValue) -> Option<Self> {
+        let flag = |name| get(value, name)?.as_bool();
+
+        Some(Capabilities {
+            offscreen_canvas: flag("offscreen_canvas")?,
+            webgpu: flag("webgpu")?,
+            shared_array_buffer: flag("shared_array_buffer")?,
+            cross_origin_isolated: flag("cross_origin_isolated")?,
+            module_workers: flag("module_workers")?,
+            opfs: flag("opfs")?,
+        })
+    }
+
+    /// Capabilities by name, in declaration order.
+    pub fn entries(&self) -> [(&'static str, bool); 6] {
+        [
+            ("offscreen_canvas", self.offscreen_canvas),
+            ("webgpu", self.webgpu),
+            ("shared_array_buffer", self.shared_array_buffer),
+            ("cross_origin_isolated", self.cross_origin_isolated),
+            ("module_workers", self.module_workers),
+            ("opfs", self.opfs),
+        ]
+    }
+}
+
+/// Module worker support can only be told by whether `Worker` constructor looks at `type` option.
+fn module_workers(global: &JsValue) -> bool {
+    use js_sys::Function;
+    use wasm_bindgen::closure::Closure;
+
+    let Some(constructor) =
+        get(global, "Worker").and_then(|worker| worker.dyn_into::<Function>().ok())
+    else {
+        return false;
+    };
+
+    let supported = Rc::new(Cell::new(false));
+    let getter = Closure::wrap(Box::new({
+        let supported = Rc::clone(&supported);
+        move || {
+            supported.set(true);
+            JsValue::from("module")
+        }
+    }) as Box<dyn FnMut() -> JsValue>);
+
+    let descriptor = Object::new();
+    set(&descriptor, "get", getter.as_ref());
+    let options = Object::new();
+    Object::define_property(&options, &"type".into(), &descriptor);
+
+    // URL doesn't point anywhere, so the worker never runs.
+    let args = Array::of2(&"blob://".into(), &options);
+    if let Ok(worker) = Reflect::construct(&constructor, &args) {
+        if let Ok(worker) = worker.dyn_into::<web_sys::Worker>() {
+            worker.terminate();
+        }
+    }
+
+    supported.get()
+}
+
+/// Capabilities of both sides of the bridge, exchanged during the handshake.
+///
+/// Features depending on both sides, like sharing memory, should check them here before enabling themselves.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CapabilityReport {
+    pub page: Capabilities,
+    pub worker: Capabilities,
+}
+
+impl CapabilityReport {
+    /// Whether memory can be shared between page and worker.
+    pub fn shared_memory(&self) -> bool {
+        self.page.shared_memory() && self.worker.shared_memory()
+    }
+}
+
+/// Identifies a single message as it travels across the bridge.
+///
+/// Each side numbers messages it sends on its own,
+/// so the id is only unique together with direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CorrelationId(pub u32);
+
+/// Delivery metadata riding along with a message.
+///
+/// It is stored in the message itself, so messages encoded without one still decode fine.
+#[derive(Debug, Clone, Copy)]
+pub struct Envelope {
+    pub id: CorrelationId,
+    /// Time of sending as reported by `Date.now()`.
+    ///
+    /// Unlike `performance.now()` it uses the same origin on both sides of the bridge.
+    /// Page stamps messages held back before sending, e.g. coalesced input, with when it came up with them.
+    pub sent_at: f64,
+}
+
+impl Envelope {
+    /// Envelope for message that is being sent right now.
+    pub fn new(id: CorrelationId) -> Self {
+        Envelope {
+            id,
+            sent_at: js_sys::Date::now(),
+        }
+    }
+
+    /// Attach envelope to encoded message.
+    pub fn stamp(&self, msg: &JsValue) {
+        if let Some(msg) = msg.dyn_ref() {
+            set(msg, "id", &self.id.0.into());
+            set(msg, "sent_at", &self.sent_at.into());
+        }
+    }
+
+    /// Read envelope from encoded message.
+    pub fn read(msg: &JsValue) -> Option<Self> {
+        Some(Envelope {
+            id: CorrelationId(get(msg, "id")?.as_f64()? as u32),
+            sent_at: get(msg, "sent_at")?.as_f64()?,
+        })
+    }
+}
+
+/// Identifies one of the apps running inside the same worker.
+///
+/// Every message belongs to a single app, so apps don't see each other's views or input.
+/// It is stored in the message itself, messages without one belong to [`AppId::DEFAULT`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AppId(pub u32);
+
+impl AppId {
+    /// App used by workers running a single app.
+    pub const DEFAULT: AppId = AppId(0);
+
+    /// Mark encoded message as belonging to this app.
+    pub fn stamp(&self, msg: &JsValue) {
+        // Keep messages of single-app workers as they were.
+        if *self == AppId::DEFAULT {
+            return;
+        }
+
+        if let Some(msg) = msg.dyn_ref() {
+            set(msg, "app", &self.0.into());
+        }
+    }
+
+    /// App encoded message belongs to.
+    pub fn read(msg: &JsValue) -> Self {
+        get(msg, "app")
+            .and_then(|app| app.as_f64())
+            .map_or(AppId::DEFAULT, |app| AppId(app as u32))
+    }
+}
+
+/// Which way message went through the bridge, from the point of view of the side logging it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrafficDirection {
+    Received,
+    Sent,
+}
+
+impl TrafficDirection {
+    pub fn name(self) -> &'static str {
+        match self {
+            TrafficDirection::Received => "received",
+            TrafficDirection::Sent => "sent",
+        }
+    }
+}
+
+/// Capped log of every message going through the bridge, kept for debugging.
+///
+/// Both sides keep one, comparing them tells whether a message got lost or just wasn't handled.
+#[derive(Debug, Clone)]
+pub struct TrafficLog {
+    capacity: usize,
+    next_seq: u64,
+    entries: VecDeque<TrafficLogEntry>,
+}
+
+/// Single logged message.
+#[derive(Debug, Clone)]
+pub struct TrafficLogEntry {
+    /// Position in the log, keeps counting when old entries are dropped.
+    pub seq: u64,
+    pub direction: TrafficDirection,
+    /// `None` for messages which could not be decoded.
+    pub kind: Option<&'static str>,
+    pub correlation_id: Option<CorrelationId>,
+    /// Rough size of the message in bytes, see [`message_size`].
+    pub size: usize,
+    /// When message was sent, by `Date.now()` on sending side.
+    pub sent_at: Option<f64>,
+    /// When message was logged, by `Date.now()`.
+    pub logged_at: f64,
+}
+
+impl TrafficLog {
+    /// Log keeping up to `capacity` latest messages.
+    pub fn new(capacity: usize) -> Self {
+        TrafficLog {
+            capacity,
+            next_seq: 0,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Log encoded message.
+    pub fn record(
+        &mut self,
+        direction: TrafficDirection,
+        kind: Option<&'static str>,
+        msg: &JsValue,
+    ) {
+        let envelope = Envelope::read(msg);
+
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+
+        self.entries.push_back(TrafficLogEntry {
+            seq: self.next_seq,
+            direction,
+            kind,
+            correlation_id: envelope.map(|envelope| envelope.id),
+            size: message_size(msg),
+            sent_at: envelope.map(|envelope| envelope.sent_at),
+            logged_at: js_sys::Date::now(),
+        });
+        self.next_seq += 1;
+    }
+
+    /// Logged messages, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &TrafficLogEntry> + '_ {
+        self.entries.iter()
+    }
+
+    /// Log as JSON array, oldest message first.
+    pub fn to_json(&self) -> String {
+        let entries: Vec<_> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                serde_json::json!({
+                    "seq": entry.seq,
+                    "direction": entry.direction.name(),
+                    "kind": entry.kind,
+                    "correlation_id": entry.correlation_id.map(|id| id.0),
+                    "size": entry.size,
+                    "sent_at": entry.sent_at,
+                    "logged_at": entry.logged_at,
+                })
+            })
+            .collect();
+
+        serde_json::Value::Array(entries).to_string()
+    }
+}
+
+/// Rough size of encoded message in bytes: its JSON plus buffers it carries.
+///
+/// Canvases and bitmaps don't count, they are moved rather than copied.
+pub fn message_size(msg: &JsValue) -> usize {
+    let json = js_sys::JSON::stringify(msg)
+        .ok()
+        .and_then(|json| json.as_string())
+        .map_or(0, |json| json.len());
+
+    let buffers = msg
+        .dyn_ref::<Object>()
+        .map(|msg| {
+            Object::values(msg)
+                .iter()
+                .filter_map(|value| value.dyn_into::<ArrayBuffer>().ok())
+                .map(|buffer| buffer.byte_length() as usize)
+                .sum()
+        })
+        .unwrap_or(0);
+
+    json + buffers
+}
+
+/// Failure to get a message across the bridge.
+///
+/// Inside the worker these are delivered as events.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub enum BridgeError {
+    /// Message could not be cloned into the other side.
+    ///
+    /// `kind` is `None` when the receiving side failed, by then there is nothing left to tell it from.
+    Serialization {
+        kind: Option<&'static str>,
+        reason: String,
+    },
+    /// Message carries a payload which was already sent, see [`Transferable`].
+    Transferred { kind: &'static str },
+}
+
+impl BridgeError {
+    pub fn serialization(kind: Option<&'static str>, err: &JsValue) -> Self {
+        let reason = match err.dyn_ref::<js_sys::Error>() {
+            Some(err) => err.message().into(),
+            None => err.as_string().unwrap_or_else(|| format!("{err:?}")),
+        };
+
+        BridgeError::Serialization { kind, reason }
+    }
+}
+
+impl Display for BridgeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BridgeError::Serialization {
+                kind: Some(kind),
+                reason,
+            } => write!(f, "failed to send `{kind}` message: {reason}"),
+            BridgeError::Serialization { kind: None, reason } => {
+                write!(f, "failed to receive message: {reason}")
+            }
+            BridgeError::Transferred { kind } => {
+                write!(
+                    f,
+                    "failed to send `{kind}` message: its payload was already transferred"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for BridgeError {}
+
+/// JS object which becomes unusable on this side once sent to the other.
+///
+/// Encoding message marks the object as transferred, clones share the mark.
+/// Object can't be sent twice this way, and taking it out after it's gone panics with a clear message
+/// instead of failing somewhere down the line with a detached buffer or a neutered canvas.
+pub struct Transferable<T> {
+    value: T,
+    transferred: Rc<Cell<bool>>,
+}
+
+impl<T: JsCast> Transferable<T> {
+    pub fn new(value: T) -> Self {
+        Transferable {
+            value,
+            transferred: Rc::new(Cell::new(false)),
+        }
+    }
+
+    /// Whether message carrying the object was sent.
+    pub fn is_transferred(&self) -> bool {
+        self.transferred.get()
+    }
+
+    /// Object, unless it was already sent.
+    pub fn get(&self) -> Option<&T> {
+        (!self.is_transferred()).then_some(&self.value)
+    }
+
+    /// Take the object, unless it was already sent.
+    pub fn try_into_inner(self) -> Result<T, Self> {
+        if self.is_transferred() {
+            Err(self)
+        } else {
+            Ok(self.value)
+        }
+    }
+
+    /// Take the object.
+    ///
+    /// # Panics
+    ///
+    /// When object was already sent to the other side.
+    pub fn into_inner(self) -> T {
+        match self.try_into_inner() {
+            Ok(value) => value,
+            Err(_) => panic!(
+                "{} was already transferred to the other side of the bridge",
+                std::any::type_name::<T>()
+            ),
+        }
+    }
+
+    /// Put object on transfer list and mark it as sent.
+    fn transfer(&self, transfer: &Array, kind: &'static str) -> Result<&T, BridgeError> {
+        if self.transferred.replace(true) {
+            return Err(BridgeError::Transferred { kind });
+        }
+
+        transfer.push(self.value.as_ref());
+        Ok(&self.value)
+    }
+}
+
+impl<T: Clone> Clone for Transferable<T> {
+    fn clone(&self) -> Self {
+        Transferable {
+            value: self.value.clone(),
+            transferred: Rc::clone(&self.transferred),
+        }
+    }
+}
+
+impl<T: Debug> Debug for Transferable<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Transferable")
+            .field("value", &self.value)
+            .field("transferred", &self.transferred.get())
+            .finish()
+    }
+}
+
+/// Catch transfer list entries `postMessage` is known to reject,
+/// so they can be reported with a reason instead of a bare `DataCloneError`.
+pub fn validate_transfer(transfer: &Array) -> Result<(), String> {
+    for (i, value) in transfer.iter().enumerate() {
+        if transfer.iter().take(i).any(|other| other == value) {
+            return Err("same object is transferred twice".to_owned());
+        }
+
+        if let Some(buffer) = value.dyn_ref::<ArrayBuffer>() {
+            // Older browsers don't have the flag, there is nothing else to check it by.
+            let detached = Reflect::get(buffer, &"detached".into())
+                .ok()
+                .and_then(|detached| detached.as_bool());
+
+            if detached == Some(true) {
+                return Err("array buffer was already transferred".to_owned());
+            }
+        } else if let Some(bitmap) = value.dyn_ref::<ImageBitmap>() {
+            if bitmap.width() == 0 && bitmap.height() == 0 {
+                return Err("image bitmap was already closed or transferred".to_owned());
+            }
+        } else if !value.is_instance_of::<OffscreenCanvas>()
+            && !value.is_instance_of::<MessagePort>()
+        {
+            return Err(format!("{value:?} is not transferable"));
+        }
+    }
+
+    Ok(())
+}
+
+/// What happened to the pointer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointerAction {
+    Move,
+    Down,
+    Up,
+    /// Pointer left the canvas.
+    Leave,
+}
+
+impl PointerAction {
+    fn name(&self) -> &'static str {
+        match self {
+            PointerAction::Move => "move",
+            PointerAction::Down => "down",
+            PointerAction::Up => "up",
+            PointerAction::Leave => "leave",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        let action = match name {
+            "move" => PointerAction::Move,
+            "down" => PointerAction::Down,
+            "up" => PointerAction::Up,
+            "leave" => PointerAction::Leave,
+            _ => return None,
+        };
+
+        Some(action)
+    }
+}
+
+/// What happened to a finger on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TouchAction {
+    Start,
+    Move,
+    End,
+    /// Browser took over the touch, e.g. to scroll the page.
+    Cancel,
+}
+
+impl TouchAction {
+    fn name(&self) -> &'static str {
+        match self {
+            TouchAction::Start => "start",
+            TouchAction::Move => "move",
+            TouchAction::End => "end",
+            TouchAction::Cancel => "cancel",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        let action = match name {
+            "start" => TouchAction::Start,
+            "move" => TouchAction::Move,
+            "end" => TouchAction::End,
+            "cancel" => TouchAction::Cancel,
+            _ => return None,
+        };
+
+        Some(action)
+    }
+}
+
+/// Unit of wheel deltas, as in `WheelEvent.deltaMode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WheelMode {
+    Pixel,
+    Line,
+    Page,
+}
+
+impl WheelMode {
+    /// Mode of given `deltaMode` value, unknown ones are taken for pixels.
+    pub fn from_dom(mode: u32) -> Self {
+        match mode {
+            1 => WheelMode::Line,
+            2 => WheelMode::Page,
+            _ => WheelMode::Pixel,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            WheelMode::Pixel => "pixel",
+            WheelMode::Line => "line",
+            WheelMode::Page => "page",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        let mode = match name {
+            "pixel" => WheelMode::Pixel,
+            "line" => WheelMode::Line,
+            "page" => WheelMode::Page,
+            _ => return None,
+        };
+
+        Some(mode)
+    }
+}
+
+/// Subsystem with a `MessageChannel` of its own.
+///
+/// Page creates the channels once worker is ready, before that everything goes over the worker itself.
+/// Messages of one subsystem keep their order, but nothing is promised across subsystems,
+/// so e.g. a large asset upload doesn't hold back input.
+/// Messages whose relative order matters therefore share a subsystem,
+/// e.g. views being attached go together with input meant for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Port {
+    /// Pointer, keyboard and text input, as well as requests page acts upon in response to it.
+    ///
+    /// Views are attached, detached and resized over it too, so input never overtakes its view.
+    Input,
+    /// Asset uploads, previews and dropped files.
+    Asset,
+    /// Diagnostics: traffic log, anomaly reports and debug drawing.
+    Log,
+    /// Everything else: view visibility, pacing, settings and lifecycle.
+    Control,
+}
+
+impl Port {
+    pub const ALL: [Port; 4] = [Port::Input, Port::Asset, Port::Log, Port::Control];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Port::Input => "input",
+            Port::Asset => "asset",
+            Port::Log => "log",
+            Port::Control => "control",
+        }
+    }
+}
+
+/// Stage of text input through view's hidden text element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImeAction {
+    /// Text being composed changed, it isn't final yet.
+    Preedit,
+    /// Text is final, either composition ended or it was typed directly.
+    Commit,
+}
+
+impl ImeAction {
+    fn name(&self) -> &'static str {
+        match self {
+            ImeAction::Preedit => "preedit",
+            ImeAction::Commit => "commit",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        let action = match name {
+            "preedit" => ImeAction::Preedit,
+            "commit" => ImeAction::Commit,
+            _ => return None,
+        };
+
+        Some(action)
+    }
+}
+
+/// Debug geometry in world space.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugShape {
+    Line {
+        start: [f32; 3],
+        end: [f32; 3],
+        color: [f32; 4],
+    },
+    /// Circle facing the camera of a 2D scene, i.e. lying in XY plane.
+    Circle {
+        center: [f32; 3],
+        radius: f32,
+        color: [f32; 4],
+    },
+    /// Axis-aligned rectangle lying in XY plane.
+    Rect {
+        center: [f32; 3],
+        size: [f32; 2],
+        color: [f32; 4],
+    },
+}
+
+impl DebugShape {
+    fn encode(&self) -> Object {
+        let shape = Object::new();
+
+        match self {
+            DebugShape::Line { start, end, color } => {
+                set(&shape, "shape", &"line".into());
+                set(&shape, "start", &floats(start));
+                set(&shape, "end", &floats(end));
+                set(&shape, "color", &floats(color));
+            }
+            DebugShape::Circle {
+                center,
+                radius,
+                color,
+            } => {
+                set(&shape, "shape", &"circle".into());
+                set(&shape, "center", &floats(center));
+                set(&shape, "radius", &(*radius).into());
+                set(&shape, "color", &floats(color));
+            }
+            DebugShape::Rect {
+                center,
+                size,
+                color,
+            } => {
+                set(&shape, "shape", &"rect".into());
+                set(&shape, "center", &floats(center));
+                set(&shape, "size", &floats(size));
+                set(&shape, "color", &floats(color));
+            }
+        }
+
+        shape
+    }
+
+    fn decode(value: &JsValue) -> Option<Self> {
+        let shape = match get(value, "shape")?.as_string()?.as_str() {
+            "line" => DebugShape::Line {
+                start: get_floats(value, "start")?,
+                end: get_floats(value, "end")?,
+                color: get_floats(value, "color")?,
+            },
+            "circle" => DebugShape::Circle {
+                center: get_floats(value, "center")?,
+                radius: get(value, "radius")?.as_f64()? as f32,
+                color: get_floats(value, "color")?,
+            },
+            "rect" => DebugShape::Rect {
+                center: get_floats(value, "center")?,
+                size: get_floats(value, "size")?,
+                color: get_floats(value, "color")?,
+            },
+            _ => return None,
+        };
+
+        Some(shape)
+    }
+}
+
+/// Messages sent from the page to the worker.
+pub enum HostMessage {
+    /// Render view into this canvas.
+    ///
+    /// If the view already has a canvas attached, the old one is detached first.
+    Attach {
+        view: ViewId,
+        canvas: Transferable<OffscreenCanvas>,
+    },
+    /// Drop rendering surface of the view, but keep the world running.
+    Detach { view: ViewId },
+    /// Pointer event on view's canvas.
+    Pointer {
+        view: ViewId,
+        action: PointerAction,
+        /// Position in pixels of canvas drawing buffer, see [`PhysicalPx`](crate::coords::PhysicalPx).
+        x: f32,
+        y: f32,
+        /// DOM button index, meaningful for `Down` and `Up`.
+        button: i16,
+    },
+    /// Finger touching view's canvas, every one of them is reported separately.
+    ///
+    /// First finger is reported as [`HostMessage::Pointer`] as well.
+    Touch {
+        view: ViewId,
+        /// `PointerEvent.pointerId`, same for the whole touch.
+        id: i32,
+        action: TouchAction,
+        /// Position in pixels of canvas drawing buffer.
+        x: f32,
+        y: f32,
+    },
+    /// Wheel or trackpad scrolled over view's canvas.
+    ///
+    /// Deltas are in DOM direction, positive `dy` scrolls down.
+    /// In [`WheelMode::Pixel`] they are pixels of canvas drawing buffer.
+    Wheel {
+        view: ViewId,
+        dx: f32,
+        dy: f32,
+        mode: WheelMode,
+        /// Trackpad pinch, which browsers report as wheel events with `ctrlKey` set.
+        pinch: bool,
+    },
+    /// Raw mouse movement over view's canvas while pointer is locked to it.
+    PointerMotion { view: ViewId, dx: f32, dy: f32 },
+    /// View's canvas gained or lost pointer lock.
+    PointerLockChanged { view: ViewId, locked: bool },
+    /// View's canvas scrolled into or out of sight, or more or less of it became visible.
+    ///
+    /// `ratio` is the part of canvas in sight, from 0 to 1.
+    ViewIntersection {
+        view: ViewId,
+        visible: bool,
+        ratio: f32,
+    },
+    /// Share of worker time app gets relative to other apps of the worker, 1 by default.
+    SetBudgetShare(f64),
+    /// Stop updating the app until [`HostMessage::Resume`], messages wait in the meantime.
+    Pause,
+    /// Continue updating paused app, time picks up where it stopped.
+    Resume,
+    /// Update paused app this many frames, each advancing time by one frame of its pacing.
+    StepFrames(u32),
+    /// Text input while view accepts it, see [`WorkerMessage::SetIme`].
+    Ime {
+        view: ViewId,
+        action: ImeAction,
+        text: String,
+    },
+    /// View's canvas changed size on the page, in physical pixels.
+    Resize {
+        view: ViewId,
+        width: u32,
+        height: u32,
+    },
+    /// View's canvas entered or left fullscreen.
+    ///
+    /// Preceded by [`HostMessage::Resize`] if canvas changed size.
+    FullscreenChanged { view: ViewId, fullscreen: bool },
+    /// Page became visible or hidden.
+    Visibility { visible: bool },
+    /// Page gained or lost keyboard focus, e.g. user switched to another window.
+    ///
+    /// Keys and buttons held at the moment never report being released, so worker releases them itself.
+    Focus { focused: bool },
+    /// Key pressed or released while page has focus.
+    Key {
+        /// Physical key as `KeyboardEvent.code`, e.g. `KeyW` wherever the key is in the layout.
+        code: String,
+        /// What the key produces as `KeyboardEvent.key`, e.g. `z` for `KeyW` on AZERTY.
+        key: String,
+        /// Legacy `KeyboardEvent.keyCode`, which Bevy takes for scan code on the web.
+        scan_code: u32,
+        pressed: bool,
+        /// No modifier keys were held, so `key` is what the key is labeled with.
+        plain: bool,
+    },
+    /// Latest `deviceorientation` reading, angles in degrees as the event has them.
+    DeviceOrientation { alpha: f32, beta: f32, gamma: f32 },
+    /// Latest `devicemotion` reading, in m/s² along device axes.
+    DeviceMotion {
+        /// Acceleration without gravity, `None` where device can't tell them apart.
+        acceleration: Option<[f32; 3]>,
+        with_gravity: [f32; 3],
+    },
+    /// Labels of keys in user's keyboard layout by `KeyboardEvent.code`,
+    /// from `navigator.keyboard.getLayoutMap()` where browser has it.
+    KeyboardLayout(Vec<(String, String)>),
+    /// User's color scheme and motion preferences, sent on spawn and whenever they change.
+    MediaPreferences { dark: bool, reduced_motion: bool },
+    /// Cap app at given frame rate.
+    SetTargetFps(u32),
+    /// Switch between continuous and reactive updates.
+    SetUpdateMode(UpdateMode),
+    /// Make app's time run slower or faster than real time, 1 is normal speed.
+    SetTimeScale(f32),
+    /// Run a command registered in app's [`ConsoleCommands`](crate::worker::console::ConsoleCommands),
+    /// answered with [`WorkerMessage::ConsoleOutput`].
+    ConsoleCommand(String),
+    /// Update the app at least once, even if in reactive mode.
+    RequestRedraw,
+    /// Multiply size of UI and text, on top of device pixel ratio.
+    SetUiScale(f64),
+    /// Change MSAA, HDR or tonemapping, answered with [`WorkerMessage::Graphics`].
+    SetGraphics(GraphicsOptions),
+    /// Present frames in sync with display refresh or as soon as they are ready,
+    /// answered with [`WorkerMessage::PresentMode`].
+    SetVsync(bool),
+    /// Flip named feature toggle.
+    SetFeature { name: String, enabled: bool },
+    /// Load config file again.
+    ReloadConfig,
+    /// Serve file contents under `inmem://{path}` asset path.
+    ///
+    /// Buffer is transferred, so it becomes unusable on page side.
+    AssetBytes {
+        path: String,
+        bytes: Transferable<ArrayBuffer>,
+    },
+    /// Drop every asset cached by worker.
+    ClearAssetCache,
+    /// Ask app for its entities as a `bevy_scene` file, answered with [`WorkerMessage::SceneExported`].
+    ///
+    /// With a list of component names, only those are kept, otherwise every reflected component is.
+    ExportScene { components: Option<Vec<String>> },
+    /// Spawn entities of a `bevy_scene` file in RON format into the app.
+    ///
+    /// Buffer is transferred, so it becomes unusable on page side.
+    ImportScene(Transferable<ArrayBuffer>),
+    /// Ask app for its persistent entities ahead of an upgrade, answered with [`WorkerMessage::Snapshot`].
+    TakeSnapshot,
+    /// Persistent entities of the app worker replaces, as a `bevy_scene` file in RON format.
+    ///
+    /// Sent to the new worker before it gets a canvas, so app restores them on its first frame.
+    RestoreSnapshot(String),
+    /// WebRTC data channel was established by the page, its messages are relayed through the port.
+    ///
+    /// Port carries channel's messages as they are, strings or array buffers.
+    DataChannel {
+        label: String,
+        port: Transferable<MessagePort>,
+    },
+    /// WebRTC data channel was closed.
+    DataChannelClosed { label: String },
+    /// Port connected directly to another worker, known to the app as `peer`.
+    ///
+    /// Created by the page for workers of a [`WorkerMesh`](crate::host::mesh::WorkerMesh),
+    /// see [`worker::peers`](crate::worker::peers) for what travels over it.
+    Peer {
+        peer: String,
+        port: Transferable<MessagePort>,
+    },
+    /// Start or stop logging bridge traffic on worker side.
+    SetTrafficLog(bool),
+    /// Start or stop reporting which input messages made it to the screen.
+    ///
+    /// While enabled, worker answers with [`WorkerMessage::InputPresented`] after every frame which handled input.
+    SetLatencyProbe(bool),
+    /// Ask worker for its traffic log, answered with [`WorkerMessage::TrafficLog`].
+    RequestTrafficLog,
+    /// Start or stop sending [`WorkerMessage::FrameStats`] a few times per second.
+    SetFrameStats(bool),
+    /// Start or stop recording replayable messages app handles, see [`HostMessage::is_replayable`].
+    ///
+    /// Stopping discards what was recorded so far.
+    SetRecording(bool),
+    /// Ask app for its recording, answered with [`WorkerMessage::Recording`].
+    RequestRecording,
+    /// Feed recorded messages back into the app at the frames they were handled, with a fixed timestep.
+    ///
+    /// Live replayable messages are dropped until replay ends.
+    /// Buffer is transferred, so it becomes unusable on page side.
+    Replay(Transferable<ArrayBuffer>),
+    /// Start streaming [`WorkerMessage::InspectorSnapshot`]s of app's entities, or stop with `None`.
+    SetInspector(Option<InspectQuery>),
+    /// Replace component of an entity, value is RON as found in inspector snapshots.
+    InspectorEdit {
+        entity: u64,
+        component: String,
+        value: String,
+    },
+    /// Answer to [`WorkerMessage::Ping`] with the same sequence number.
+    ///
+    /// `page_time` is page's clock at the moment of answering, `performance.timeOrigin + performance.now()`,
+    /// worker syncs its own clock to the page's with it.
+    Pong { seq: u32, page_time: f64 },
+    /// Click page played for [`WorkerMessage::LatencyClick`] reached audio output this many ms after the press.
+    ClickLatency(f64),
+    /// Answer to [`WorkerMessage::ClipboardPasteRequest`], text or reason it couldn't be read.
+    ClipboardPaste(Result<String, String>),
+    /// Settings page has stored, as key and JSON value pairs.
+    ///
+    /// Sent every time worker becomes ready.
+    StoredSettings(Vec<(String, String)>),
+    /// Configuration of the worker as a whole.
+    ///
+    /// Sent every time worker becomes ready, ahead of everything else, so it arrives before apps are built.
+    BootFlags(BootFlags),
+    /// Capabilities of both sides, sent every time worker becomes ready, right after boot flags.
+    Capabilities(CapabilityReport),
+    /// Ask worker for a bitmap of image asset at given path, loading it if needed.
+    ///
+    /// Answered with [`WorkerMessage::AssetPreview`] carrying the same `request`.
+    RequestAssetPreview { request: u32, path: String },
+    /// File was dropped onto view's canvas.
+    FileDropped {
+        view: ViewId,
+        name: String,
+        bytes: Transferable<ArrayBuffer>,
+    },
+    /// Draw debug geometry over the scene.
+    ///
+    /// Shapes are kept for `duration_ms`, zero means a single frame.
+    DebugDraw {
+        shapes: Vec<DebugShape>,
+        duration_ms: u32,
+    },
+    /// Channel page posts a tick over on every animation frame, while any app updates in
+    /// [`UpdateMode::AnimationFrame`].
+    ///
+    /// Ticks are bare timestamps from `performance.now()` rather than messages,
+    /// and don't queue up behind other traffic. Sent every time worker becomes ready.
+    FrameClock(Transferable<MessagePort>),
+    /// Channels to use from now on, worker's end for each subsystem.
+    ///
+    /// Sent over the worker itself as soon as it is ready, see [`Port`].
+    Ports(Vec<(Port, Transferable<MessagePort>)>),
+    /// Stop the app and release GPU resources.
+    ///
+    /// Worker replies with [`WorkerMessage::ShutdownComplete`], after which it is safe to terminate.
+    Shutdown,
+}
+
+impl HostMessage {
+    /// Short name of the message, as it appears in `kind` field.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            HostMessage::Attach { .. } => "attach",
+            HostMessage::Detach { .. } => "detach",
+            HostMessage::Pointer { .. } => "pointer",
+            HostMessage::Touch { .. } => "touch",
+            HostMessage::Wheel { .. } => "wheel",
+            HostMessage::PointerMotion { .. } => "pointer_motion",
+            HostMessage::PointerLockChanged { .. } => "pointer_lock_changed",
+            HostMessage::Ime { .. } => "ime",
+            HostMessage::ViewIntersection { .. } => "view_intersection",
+            HostMessage::SetBudgetShare(_) => "set_budget_share",
+            HostMessage::Pause => "pause",
+            HostMessage::Resume => "resume",
+            HostMessage::StepFrames(_) => "step_frames",
+            HostMessage::Resize { .. } => "resize",
+            HostMessage::FullscreenChanged { .. } => "fullscreen_changed",
+            HostMessage::Visibility { .. } => "visibility",
+            HostMessage::Focus { .. } => "focus",
+            HostMessage::Key { .. } => "key",
+            HostMessage::KeyboardLayout(_) => "keyboard_layout",
+            HostMessage::DeviceOrientation { .. } => "device_orientation",
+            HostMessage::DeviceMotion { .. } => "device_motion",
+            HostMessage::MediaPreferences { .. } => "media_preferences",
+            HostMessage::SetTargetFps(_) => "set_target_fps",
+            HostMessage::SetUpdateMode(_) => "set_update_mode",
+            HostMessage::SetTimeScale(_) => "set_time_scale",
+            HostMessage::ConsoleCommand(_) => "console_command",
+            HostMessage::RequestRedraw => "request_redraw",
+            HostMessage::SetUiScale(_) => "set_ui_scale",
+            HostMessage::SetGraphics(_) => "set_graphics",
+            HostMessage::SetVsync(_) => "set_vsync",
+            HostMessage::SetFeature { .. } => "set_feature",
+            HostMessage::ReloadConfig => "reload_config",
+            HostMessage::AssetBytes { .. } => "asset_bytes",
+            HostMessage::ExportScene { .. } => "export_scene",
+            HostMessage::ImportScene(_) => "import_scene",
+            HostMessage::TakeSnapshot => "take_snapshot",
+            HostMessage::RestoreSnapshot(_) => "restore_snapshot",
+            HostMessage::ClearAssetCache => "clear_asset_cache",
+            HostMessage::DataChannel { .. } => "data_channel",
+            HostMessage::DataChannelClosed { .. } => "data_channel_closed",
+            HostMessage::Peer { .. } => "peer",
+            HostMessage::ClipboardPaste(_) => "clipboard_paste",
+            HostMessage::SetTrafficLog(_) => "set_traffic_log",
+            HostMessage::SetLatencyProbe(_) => "set_latency_probe",
+            HostMessage::RequestTrafficLog => "request_traffic_log",
+            HostMessage::SetFrameStats(_) => "set_frame_stats",
+            HostMessage::SetRecording(_) => "set_recording",
+            HostMessage::RequestRecording => "request_recording",
+            HostMessage::Replay(_) => "replay",
+            HostMessage::SetInspector(_) => "set_inspector",
+            HostMessage::InspectorEdit { .. } => "inspector_edit",
+            HostMessage::Pong { .. } => "pong",
+            HostMessage::ClickLatency(_) => "click_latency",
+            HostMessage::StoredSettings(_) => "stored_settings",
+            HostMessage::BootFlags(_) => "boot_flags",
+            HostMessage::Capabilities(_) => "capabilities",
+            HostMessage::RequestAssetPreview { .. } => "request_asset_preview",
+            HostMessage::FileDropped { .. } => "file_dropped",
+            HostMessage::DebugDraw { .. } => "debug_draw",
+            HostMessage::FrameClock(_) => "frame_clock",
+            HostMessage::Ports(_) => "ports",
+            HostMessage::Shutdown => "shutdown",
+        }
+    }
+
+    /// Encode message into JS value together with its transfer list.
+    ///
+    /// Transferable payloads are marked as sent, so message can only be encoded once.
+    pub fn encode(&self) -> Result<(JsValue, Array), BridgeError> {
+        let kind = self.kind();
+        let transfer = Array::new();
+        let msg = tagged(kind);
+
+        match self {
+            HostMessage::Attach { view, canvas } => {
+                set(&msg, "view", &view.0.into());
+                set(&msg, "canvas", canvas.transfer(&transfer, kind)?);
+            }
+            HostMessage::Detach { view } => {
+                set(&msg, "view", &view.0.into());
+            }
+            HostMessage::Pointer {
+                view,
+                action,
+                x,
+                y,
+                button,
+            } => {
+                set(&msg, "view", &view.0.into());
+                set(&msg, "action", &action.name().into());
+                set(&msg, "x", &(*x).into());
+                set(&msg, "y", &(*y).into());
+                set(&msg, "button", &(*button).into());
+            }
+            HostMessage::Touch {
+                view,
+                id,
+                action,
+                x,
+                y,
+            } => {
+                set(&msg, "view", &view.0.into());
+                set(&msg, "id", &(*id).into());
+                set(&msg, "action", &action.name().into());
+                set(&msg, "x", &(*x).into());
+                set(&msg, "y", &(*y).into());
+            }
+            HostMessage::Wheel {
+                view,
+                dx,
+                dy,
+                mode,
+                pinch,
+            } => {
+                set(&msg, "view", &view.0.into());
+                set(&msg, "dx", &(*dx).into());
+                set(&msg, "dy", &(*dy).into());
+                set(&msg, "mode", &mode.name().into());
+                set(&msg, "pinch", &(*pinch).into());
+            }
+            HostMessage::ViewIntersection {
+                view,
+                visible,
+                ratio,
+            } => {
+                set(&msg, "view", &view.0.into());
+                set(&msg, "visible", &(*visible).into());
+                set(&msg, "ratio", &(*ratio).into());
+            }
+            HostMessage::SetBudgetShare(share) => {
+                set(&msg, "share", &(*share).into());
+            }
+            HostMessage::StepFrames(frames) => {
+                set(&msg, "frames", &(*frames).into());
+            }
+            HostMessage::SetTimeScale(scale) => {
+                set(&msg, "scale", &(*scale).into());
+            }
+            HostMessage::ConsoleCommand(line) => {
+                set(&msg, "line", &line.into());
+            }
+            HostMessage::Ime { view, action, text } => {
+                set(&msg, "view", &view.0.into());
+                set(&msg, "action", &action.name().into());
+                set(&msg, "text", &text.into());
+            }
+            HostMessage::PointerMotion { view, dx, dy } => {
+                set(&msg, "view", &view.0.into());
+                set(&msg, "dx", &(*dx).into());
+                set(&msg, "dy", &(*dy).into());
+            }
+            HostMessage::PointerLockChanged { view, locked } => {
+                set(&msg, "view", &view.0.into());
+                set(&msg, "locked", &(*locked).into());
+            }
+            HostMessage::Resize {
+                view,
+                width,
+                height,
+            } => {
+                set(&msg, "view", &view.0.into());
+                set(&msg, "width", &(*width).into());
+                set(&msg, "height", &(*height).into());
+            }
+            HostMessage::FullscreenChanged { view, fullscreen } => {
+                set(&msg, "view", &view.0.into());
+                set(&msg, "fullscreen", &(*fullscreen).into());
+            }
+            HostMessage::Visibility { visible } => {
+                set(&msg, "visible", &(*visible).into());
+            }
+            HostMessage::Focus { focused } => {
+                set(&msg, "focused", &(*focused).into());
+            }
+            HostMessage::Key {
+                code,
+                key,
+                scan_code,
+                pressed,
+                plain,
+            } => {
+                set(&msg, "code", &code.into());
+                set(&msg, "key", &key.into());
+                set(&msg, "scan_code", &(*scan_code).into());
+                set(&msg, "pressed", &(*pressed).into());
+                set(&msg, "plain", &(*plain).into());
+            }
+            HostMessage::DeviceOrientation { alpha, beta, gamma } => {
+                set(&msg, "alpha", &(*alpha).into());
+                set(&msg, "beta", &(*beta).into());
+                set(&msg, "gamma", &(*gamma).into());
+            }
+            HostMessage::DeviceMotion {
+                acceleration,
+                with_gravity,
+            } => {
+                if let Some(acceleration) = acceleration {
+                    set(&msg, "acceleration", &floats(acceleration));
+                }
+                set(&msg, "with_gravity", &floats(with_gravity));
+            }
+            HostMessage::KeyboardLayout(layout) => {
+                let map = Object::new();
+                for (code, label) in layout {
+                    set(&map, code, &label.into());
+                }
+
+                set(&msg, "layout", &map);
+            }
+            HostMessage::MediaPreferences {
+                dark,
+                reduced_motion,
+            } => {
+                set(&msg, "dark", &(*dark).into());
+                set(&msg, "reduced_motion", &(*reduced_motion).into());
+            }
+            HostMessage::SetTargetFps(fps) => {
+                set(&msg, "fps", &(*fps).into());
+            }
+            HostMessage::SetUpdateMode(mode) => match mode {
+                UpdateMode::Continuous => (),
+                UpdateMode::Reactive { max_wait_ms } => {
+                    set(&msg, "reactive", &true.into());
+                    if let Some(max_wait_ms) = max_wait_ms {
+                        set(&msg, "max_wait_ms", &(*max_wait_ms).into());
+                    }
+                }
+                UpdateMode::AnimationFrame => set(&msg, "animation_frame", &true.into()),
+            },
+            HostMessage::SetUiScale(scale) => {
+                set(&msg, "scale", &(*scale).into());
+            }
+            HostMessage::SetGraphics(options) => options.encode(&msg),
+            HostMessage::SetVsync(vsync) => {
+                set(&msg, "vsync", &(*vsync).into());
+            }
+            HostMessage::SetFeature { name, enabled } => {
+                set(&msg, "name", &name.into());
+                set(&msg, "enabled", &(*enabled).into());
+            }
+            HostMessage::AssetBytes { path, bytes } => {
+                set(&msg, "path", &path.into());
+                set(&msg, "bytes", bytes.transfer(&transfer, kind)?);
+            }
+            HostMessage::ExportScene { components } => {
+                if let Some(components) = components {
+                    let components: Array = components.iter().map(JsValue::from).collect();
+                    set(&msg, "components", &components);
+                }
+            }
+            HostMessage::ImportScene(scene) => {
+                set(&msg, "scene", scene.transfer(&transfer, kind)?);
+            }
+            HostMessage::RestoreSnapshot(snapshot) => {
+                set(&msg, "snapshot", &snapshot.into());
+            }
+            HostMessage::SetTrafficLog(enabled)
+            | HostMessage::SetLatencyProbe(enabled)
+            | HostMessage::SetRecording(enabled)
+            | HostMessage::SetFrameStats(enabled) => {
+                set(&msg, "enabled", &(*enabled).into());
+            }
+            HostMessage::DataChannel { label, port } => {
+                set(&msg, "label", &label.into());
+                set(&msg, "port", port.transfer(&transfer, kind)?);
+            }
+            HostMessage::DataChannelClosed { label } => {
+                set(&msg, "label", &label.into());
+            }
+            HostMessage::Peer { peer, port } => {
+                set(&msg, "peer", &peer.into());
+                set(&msg, "port", port.transfer(&transfer, kind)?);
+            }
+            HostMessage::FrameClock(port) => {
+                set(&msg, "port", port.transfer(&transfer, kind)?);
+            }
+            HostMessage::Ports(ports) => {
+                let object = Object::new();
+                for (port, channel) in ports {
+                    set(&object, port.name(), channel.transfer(&transfer, kind)?);
+                }
+                set(&msg, "ports", &object);
+            }
+            HostMessage::ClipboardPaste(result) => match result {
+                Ok(text) => set(&msg, "text", &text.into()),
+                Err(error) => set(&msg, "error", &error.into()),
+            },
+            HostMessage::StoredSettings(entries) => {
+                let map = Object::new();
+                for (key, value) in entries {
+                    set(&map, key, &value.into());
+                }
+
+                set(&msg, "entries", &map);
+            }
+            HostMessage::BootFlags(flags) => {
+                if let Some(level) = flags.log_level {
+                    set(&msg, "log_level", &level.as_str().into());
+                }
+                if let Some(scene) = &flags.scene {
+                    set(&msg, "scene", &scene.into());
+                }
+                if let Some(quality) = flags.quality {
+                    set(&msg, "quality", &quality.name().into());
+                }
+                // Doesn't fit into a JS number.
+                if let Some(seed) = flags.seed {
+                    set(&msg, "seed", &seed.to_string().into());
+                }
+                let debug: Array = flags.debug.iter().map(JsValue::from).collect();
+                set(&msg, "debug", &debug);
+                if let Some(fps) = flags.fps {
+                    set(&msg, "fps", &fps.into());
+                }
+                if let Some(backend) = flags.backend {
+                    set(&msg, "backend", &backend.name().into());
+                }
+                flags.graphics.encode(&msg);
+                let extra = Object::new();
+                for (key, value) in &flags.extra {
+                    set(&extra, key, &value.into());
+                }
+                set(&msg, "extra", &extra);
+            }
+            HostMessage::Capabilities(report) => {
+                set(&msg, "page", &report.page.encode());
+                set(&msg, "worker", &report.worker.encode());
+            }
+            HostMessage::RequestAssetPreview { request, path } => {
+                set(&msg, "request", &(*request).into());
+                set(&msg, "path", &path.into());
+            }
+            HostMessage::FileDropped { view, name, bytes } => {
+                set(&msg, "view", &view.0.into());
+                set(&msg, "name", &name.into());
+                set(&msg, "bytes", bytes.transfer(&transfer, kind)?);
+            }
+            HostMessage::DebugDraw {
+                shapes,
+                duration_ms,
+            } => {
+                let shapes: Array = shapes.iter().map(DebugShape::encode).collect();
+                set(&msg, "shapes", &shapes);
+                set(&msg, "duration_ms", &(*duration_ms).into());
+            }
+            HostMessage::Pong { seq, page_time } => {
+                set(&msg, "seq", &(*seq).into());
+                set(&msg, "page_time", &(*page_time).into());
+            }
+            HostMessage::ClickLatency(latency_ms) => {
+                set(&msg, "latency_ms", &(*latency_ms).into());
+            }
+            HostMessage::SetInspector(query) => {
+                if let Some(query) = query {
+                    let components: Array = query.components.iter().map(JsValue::from).collect();
+                    set(&msg, "components", &components);
+                    if let Some(name_filter) = &query.name_filter {
+                        set(&msg, "name_filter", &name_filter.into());
+                    }
+                }
+            }
+            HostMessage::InspectorEdit {
+                entity,
+                component,
+                value,
+            } => {
+                // Doesn't fit into a JS number.
+                set(&msg, "entity", &entity.to_string().into());
+                set(&msg, "component", &component.into());
+                set(&msg, "value", &value.into());
+            }
+            HostMessage::Replay(recording) => {
+                set(&msg, "recording", recording.transfer(&transfer, kind)?);
+            }
+            HostMessage::RequestRedraw
+            | HostMessage::ReloadConfig
+            | HostMessage::ClearAssetCache
+            | HostMessage::RequestTrafficLog
+            | HostMessage::RequestRecording
+            | HostMessage::TakeSnapshot
+            | HostMessage::Pause
+            | HostMessage::Resume
+            | HostMessage::Shutdown => (),
+        }
+
+        Ok((msg.into(), transfer))
+    }
+
+    /// Decode message, returns `None` if value doesn't look like one.
+    pub fn decode(value: &JsValue) -> Option<Self> {
+        let msg = match kind(value)?.as_str() {
+            "attach" => HostMessage::Attach {
+                view: view(value)?,
+                canvas: Transferable::new(get(value, "canvas")?.dyn_into().ok()?),
+            },
+            "detach" => HostMessage::Detach { view: view(value)? },
+            "pointer" => HostMessage::Pointer {
+                view: view(value)?,
+                action: PointerAction::from_name(&get(value, "action")?.as_string()?)?,
+                x: get(value, "x")?.as_f64()? as f32,
+                y: get(value, "y")?.as_f64()? as f32,
+                button: get(value, "button")?.as_f64()? as i16,
+            },
+            "touch" => HostMessage::Touch {
+                view: view(value)?,
+                id: get(value, "id")?.as_f64()? as i32,
+                action: TouchAction::from_name(&get(value, "action")?.as_string()?)?,
+                x: get(value, "x")?.as_f64()? as f32,
+                y: get(value, "y")?.as_f64()? as f32,
+            },
+            "wheel" => HostMessage::Wheel {
+                view: view(value)?,
+                dx: get(value, "dx")?.as_f64()? as f32,
+                dy: get(value, "dy")?.as_f64()? as f32,
+                mode: WheelMode::from_name(&get(value, "mode")?.as_string()?)?,
+                pinch: get(value, "pinch")?.as_bool()?,
+            },
+            "view_intersection" => HostMessage::ViewIntersection {
+                view: view(value)?,
+                visible: get(value, "visible")?.as_bool()?,
+                ratio: get(value, "ratio")?.as_f64()? as f32,
+            },
+            "set_budget_share" => HostMessage::SetBudgetShare(get(value, "share")?.as_f64()?),
+            "pause" => HostMessage::Pause,
+            "resume" => HostMessage::Resume,
+            "step_frames" => HostMessage::StepFrames(get(value, "frames")?.as_f64()? as u32),
+            "set_time_scale" => HostMessage::SetTimeScale(get(value, "scale")?.as_f64()? as f32),
+            "console_command" => HostMessage::ConsoleCommand(get(value, "line")?.as_string()?),
+            "ime" => HostMessage::Ime {
+                view: view(value)?,
+                action: ImeAction::from_name(&get(value, "action")?.as_string()?)?,
+                text: get(value, "text")?.as_string()?,
+            },
+            "pointer_motion" => HostMessage::PointerMotion {
+                view: view(value)?,
+                dx: get(value, "dx")?.as_f64()? as f32,
+                dy: get(value, "dy")?.as_f64()? as f32,
+            },
+            "pointer_lock_changed" => HostMessage::PointerLockChanged {
+                view: view(value)?,
+                locked: get(value, "locked")?.as_bool()?,
+            },
+            "resize" => HostMessage::Resize {
+                view: view(value)?,
+                width: get(value, "width")?.as_f64()? as u32,
+                height: get(value, "height")?.as_f64()? as u32,
+            },
+            "fullscreen_changed" => HostMessage::FullscreenChanged {
+                view: view(value)?,
+                fullscreen: get(value, "fullscreen")?.as_bool()?,
+            },
+            "visibility" => HostMessage::Visibility {
+                visible: get(value, "visible")?.as_bool()?,
+            },
+            "focus" => HostMessage::Focus {
+                focused: get(value, "focused")?.as_bool()?,
+            },
+            "key" => HostMessage::Key {
+                code: get(value, "code")?.as_string()?,
+                key: get(value, "key")?.as_string()?,
+                scan_code: get(value, "scan_code")?.as_f64()? as u32,
+                pressed: get(value, "pressed")?.as_bool()?,
+                plain: get(value, "plain")?.as_bool()?,
+            },
+            "device_orientation" => HostMessage::DeviceOrientation {
+                alpha: get(value, "alpha")?.as_f64()? as f32,
+                beta: get(value, "beta")?.as_f64()? as f32,
+                gamma: get(value, "gamma")?.as_f64()? as f32,
+            },
+            "device_motion" => HostMessage::DeviceMotion {
+                acceleration: get_floats(value, "acceleration"),
+                with_gravity: get_floats(value, "with_gravity")?,
+            },
+            "keyboard_layout" => {
+                let map = get(value, "layout")?;
+                let layout = Object::entries(map.dyn_ref()?)
+                    .iter()
+                    .filter_map(|entry| {
+                        let entry: Array = entry.dyn_into().ok()?;
+                        Some((entry.get(0).as_string()?, entry.get(1).as_string()?))
+                    })
+                    .collect();
+
+                HostMessage::KeyboardLayout(layout)
+            }
+            "media_preferences" => HostMessage::MediaPreferences {
+                dark: get(value, "dark")?.as_bool()?,
+                reduced_motion: get(value, "reduced_motion")?.as_bool()?,
+            },
+            "set_target_fps" => HostMessage::SetTargetFps(get(value, "fps")?.as_f64()? as u32),
+            "set_update_mode" => {
+                let flag =
+                    |key: &str| get(value, key).and_then(|value| value.as_bool()) == Some(true);
+                let mode = if flag("reactive") {
+                    UpdateMode::Reactive {
+                        max_wait_ms: get(value, "max_wait_ms")
+                            .and_then(|value| value.as_f64())
+                            .map(|ms| ms as u32),
+                    }
+                } else if flag("animation_frame") {
+                    UpdateMode::AnimationFrame
+                } else {
+                    UpdateMode::Continuous
+                };
+
+                HostMessage::SetUpdateMode(mode)
+            }
+            "request_redraw" => HostMessage::RequestRedraw,
+            "set_ui_scale" => HostMessage::SetUiScale(get(value, "scale")?.as_f64()?),
+            "set_graphics" => HostMessage::SetGraphics(GraphicsOptions::decode(value)),
+            "set_vsync" => HostMessage::SetVsync(get(value, "vsync")?.as_bool()?),
+            "set_feature" => HostMessage::SetFeature {
+                name: get(value, "name")?.as_string()?,
+                enabled: get(value, "enabled")?.as_bool()?,
+            },
+            "reload_config" => HostMessage::ReloadConfig,
+            "asset_bytes" => HostMessage::AssetBytes {
+                path: get(value, "path")?.as_string()?,
+                bytes: Transferable::new(get(value, "bytes")?.dyn_into().ok()?),
+            },
+            "clear_asset_cache" => HostMessage::ClearAssetCache,
+            "export_scene" => HostMessage::ExportScene {
+                components: match get(value, "components") {
+                    Some(components) => {
+                        let components: Array = components.dyn_into().ok()?;
+                        Some(
+                            components
+                                .iter()
+                                .filter_map(|name| name.as_string())
+                                .collect(),
+                        )
+                    }
+                    None => None,
+                },
+            },
+            "import_scene" => {
+                HostMessage::ImportScene(Transferable::new(get(value, "scene")?.dyn_into().ok()?))
+            }
+            "take_snapshot" => HostMessage::TakeSnapshot,
+            "restore_snapshot" => {
+                HostMessage::RestoreSnapshot(get(value, "snapshot")?.as_string()?)
+            }
+            "data_channel" => HostMessage::DataChannel {
+                label: get(value, "label")?.as_string()?,
+                port: Transferable::new(get(value, "port")?.dyn_into().ok()?),
+            },
+            "frame_clock" => {
+                HostMessage::FrameClock(Transferable::new(get(value, "port")?.dyn_into().ok()?))
+            }
+            "peer" => HostMessage::Peer {
+                peer: get(value, "peer")?.as_string()?,
+                port: Transferable::new(get(value, "port")?.dyn_into().ok()?),
+            },
+            "data_channel_closed" => HostMessage::DataChannelClosed {
+                label: get(value, "label")?.as_string()?,
+            },
+            "set_traffic_log" => HostMessage::SetTrafficLog(get(value, "enabled")?.as_bool()?),
+            "set_latency_probe" => HostMessage::SetLatencyProbe(get(value, "enabled")?.as_bool()?),
+            "request_traffic_log" => HostMessage::RequestTrafficLog,
+            "set_frame_stats" => HostMessage::SetFrameStats(get(value, "enabled")?.as_bool()?),
+            "set_recording" => HostMessage::SetRecording(get(value, "enabled")?.as_bool()?),
+            "request_recording" => HostMessage::RequestRecording,
+            "replay" => {
+                HostMessage::Replay(Transferable::new(get(value, "recording")?.dyn_into().ok()?))
+            }
+            "set_inspector" => HostMessage::SetInspector(match get(value, "components") {
+                Some(components) => {
+                    let components: Array = components.dyn_into().ok()?;
+
+                    Some(InspectQuery {
+                        components: components
+                            .iter()
+                            .filter_map(|name| name.as_string())
+                            .collect(),
+                        name_filter: get(value, "name_filter")
+                            .and_then(|filter| filter.as_string()),
+                    })
+                }
+                None => None,
+            }),
+            "inspector_edit" => HostMessage::InspectorEdit {
+                entity: get(value, "entity")?.as_string()?.parse().ok()?,
+                component: get(value, "component")?.as_string()?,
+                value: get(value, "value")?.as_string()?,
+            },
+            "pong" => HostMessage::Pong {
+                seq: get(value, "seq")?.as_f64()? as u32,
+                page_time: get(value, "page_time")?.as_f64()?,
+            },
+            "click_latency" => HostMessage::ClickLatency(get(value, "latency_ms")?.as_f64()?),
+            "clipboard_paste" => {
+                let result = match get(value, "text") {
+                    Some(text) => Ok(text.as_string()?),
+                    None => Err(get(value, "error")?.as_string()?),
+                };
+
+                HostMessage::ClipboardPaste(result)
+            }
+            "stored_settings" => {
+                let map = get(value, "entries")?;
+                let entries = Object::entries(map.dyn_ref()?)
+                    .iter()
+                    .filter_map(|entry| {
+                        let entry: Array = entry.dyn_into().ok()?;
+                        Some((entry.get(0).as_string()?, entry.get(1).as_string()?))
+                    })
+                    .collect();
+
+                HostMessage::StoredSettings(entries)
+            }
+            "boot_flags" => {
+                let debug: Array = get(value, "debug")?.dyn_into().ok()?;
+
+                HostMessage::BootFlags(BootFlags {
+                    log_level: get(value, "log_level")
+                        .and_then(|level| level.as_string()?.parse().ok()),
+                    scene: get(value, "scene").and_then(|scene| scene.as_string()),
+                    quality: get(value, "quality")
+                        .and_then(|quality| QualityPreset::from_name(&quality.as_string()?)),
+                    seed: get(value, "seed").and_then(|seed| seed.as_string()?.parse().ok()),
+                    debug: debug.iter().filter_map(|name| name.as_string()).collect(),
+                    fps: get(value, "fps")
+                        .and_then(|fps| fps.as_f64())
+                        .map(|fps| fps as u32),
+                    backend: get(value, "backend")
+                        .and_then(|backend| GraphicsBackend::from_name(&backend.as_string()?)),
+                    graphics: GraphicsOptions::decode(value),
+                    extra: get(value, "extra")
+                        .and_then(|extra| Some(Object::entries(extra.dyn_ref()?)))
+                        .map(|entries| {
+                            entries
+                                .iter()
+                                .filter_map(|entry| {
+                                    let entry: Array = entry.dyn_into().ok()?;
+                                    Some((entry.get(0).as_string()?, entry.get(1).as_string()?))
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                })
+            }
+            "capabilities" => HostMessage::Capabilities(CapabilityReport {
+                page: Capabilities::decode(&get(value, "page")?)?,
+                worker: Capabilities::decode(&get(value, "worker")?)?,
+            }),
+            "request_asset_preview" => HostMessage::RequestAssetPreview {
+                request: get(value, "request")?.as_f64()? as u32,
+                path: get(value, "path")?.as_string()?,
+            },
+            "file_dropped" => HostMessage::FileDropped {
+                view: view(value)?,
+                name: get(value, "name")?.as_string()?,
+                bytes: Transferable::new(get(value, "bytes")?.dyn_into().ok()?),
+            },
+            "debug_draw" => {
+                let shapes: Array = get(value, "shapes")?.dyn_into().ok()?;
+
+                HostMessage::DebugDraw {
+                    shapes: shapes
+                        .iter()
+                        .filter_map(|shape| DebugShape::decode(&shape))
+                        .collect(),
+                    duration_ms: get(value, "duration_ms")?.as_f64()? as u32,
+                }
+            }
+            "ports" => {
+                let ports = get(value, "ports")?;
+
+                HostMessage::Ports(
+                    Port::ALL
+                        .into_iter()
+                        .filter_map(|port| {
+                            let channel = get(&ports, port.name())?.dyn_into().ok()?;
+                            Some((port, Transferable::new(channel)))
+                        })
+                        .collect(),
+                )
+            }
+            "shutdown" => HostMessage::Shutdown,
+            _ => return None,
+        };
+
+        Some(msg)
+    }
+
+    /// View this message is addressed to, if any.
+    pub fn view(&self) -> Option<ViewId> {
+        match self {
+            HostMessage::Attach { view, .. }
+            | HostMessage::Detach { view }
+            | HostMessage::Pointer { view, .. }
+            | HostMessage::PointerMotion { view, .. }
+            | HostMessage::Touch { view, .. }
+            | HostMessage::Wheel { view, .. }
+            | HostMessage::PointerLockChanged { view, .. }
+            | HostMessage::Ime { view, .. }
+            | HostMessage::ViewIntersection { view, .. }
+            | HostMessage::Resize { view, .. }
+            | HostMessage::FullscreenChanged { view, .. }
+            | HostMessage::FileDropped { view, .. } => Some(*view),
+            HostMessage::Visibility { .. }
+            | HostMessage::Focus { .. }
+            | HostMessage::Key { .. }
+            | HostMessage::KeyboardLayout(_)
+            | HostMessage::DeviceOrientation { .. }
+            | HostMessage::DeviceMotion { .. }
+            | HostMessage::MediaPreferences { .. }
+            | HostMessage::SetTargetFps(_)
+            | HostMessage::SetUpdateMode(_)
+            | HostMessage::RequestRedraw
+            | HostMessage::SetUiScale(_)
+            | HostMessage::SetGraphics(_)
+            | HostMessage::SetVsync(_)
+            | HostMessage::SetFeature { .. }
+            | HostMessage::ReloadConfig
+            | HostMessage::AssetBytes { .. }
+            | HostMessage::ClearAssetCache
+            | HostMessage::ExportScene { .. }
+            | HostMessage::ImportScene(_)
+            | HostMessage::TakeSnapshot
+            | HostMessage::RestoreSnapshot(_)
+            | HostMessage::DataChannel { .. }
+            | HostMessage::DataChannelClosed { .. }
+            | HostMessage::Peer { .. }
+            | HostMessage::ClipboardPaste(_)
+            | HostMessage::SetTrafficLog(_)
+            | HostMessage::SetLatencyProbe(_)
+            | HostMessage::RequestTrafficLog
+            | HostMessage::SetFrameStats(_)
+            | HostMessage::SetRecording(_)
+            | HostMessage::RequestRecording
+            | HostMessage::Replay(_)
+            | HostMessage::SetInspector(_)
+            | HostMessage::InspectorEdit { .. }
+            | HostMessage::Pong { .. }
+            | HostMessage::ClickLatency(_)
+            | HostMessage::SetBudgetShare(_)
+            | HostMessage::Pause
+            | HostMessage::Resume
+            | HostMessage::StepFrames(_)
+            | HostMessage::SetTimeScale(_)
+            | HostMessage::ConsoleCommand(_)
+            | HostMessage::StoredSettings(_)
+            | HostMessage::BootFlags(_)
+            | HostMessage::Capabilities(_)
+            | HostMessage::RequestAssetPreview { .. }
+            | HostMessage::DebugDraw { .. }
+            | HostMessage::FrameClock(_)
+            | HostMessage::Ports(_)
+            | HostMessage::Shutdown => None,
+        }
+    }
+
+    /// Merge message sent right after this one into it, when the pair means the same as the latter alone.
+    ///
+    /// Pointer and touch moves keep the latest position, locked pointer motion and wheel deltas of the same kind add up
+    /// and resizes keep the latest size, as long as both messages concern the same view.
+    /// Sensor readings keep the latest one.
+    /// Returns `next` back if messages can't be merged.
+    pub fn coalesce(&mut self, next: HostMessage) -> Result<(), HostMessage> {
+        match (self, next) {
+            (
+                HostMessage::Pointer {
+                    view,
+                    action: PointerAction::Move,
+                    x,
+                    y,
+                    ..
+                },
+                HostMessage::Pointer {
+                    view: next_view,
+                    action: PointerAction::Move,
+                    x: next_x,
+                    y: next_y,
+                    ..
+                },
+            ) if *view == next_view => {
+                *x = next_x;
+                *y = next_y;
+                Ok(())
+            }
+            (
+                HostMessage::Touch {
+                    view,
+                    id,
+                    action: TouchAction::Move,
+                    x,
+                    y,
+                },
+                HostMessage::Touch {
+                    view: next_view,
+                    id: next_id,
+                    action: TouchAction::Move,
+                    x: next_x,
+                    y: next_y,
+                },
+            ) if *view == next_view && *id == next_id => {
+                *x = next_x;
+                *y = next_y;
+                Ok(())
+            }
+            (
+                reading @ HostMessage::DeviceOrientation { .. },
+                next @ HostMessage::DeviceOrientation { .. },
+            )
+            | (
+                reading @ HostMessage::DeviceMotion { .. },
+                next @ HostMessage::DeviceMotion { .. },
+            ) => {
+                *reading = next;
+                Ok(())
+            }
+            (
+                HostMessage::Wheel {
+                    view,
+                    dx,
+                    dy,
+                    mode,
+                    pinch,
+                },
+                HostMessage::Wheel {
+                    view: next_view,
+                    dx: next_dx,
+                    dy: next_dy,
+                    mode: next_mode,
+                    pinch: next_pinch,
+                },
+            ) if *view == next_view && *mode == next_mode && *pinch == next_pinch => {
+                *dx += next_dx;
+                *dy += next_dy;
+                Ok(())
+            }
+            (
+                HostMessage::PointerMotion { view, dx, dy },
+                HostMessage::PointerMotion {
+                    view: next_view,
+                    dx: next_dx,
+                    dy: next_dy,
+                },
+            ) if *view == next_view => {
+                *dx += next_dx;
+                *dy += next_dy;
+                Ok(())
+            }
+            (
+                HostMessage::Resize {
+                    view,
+                    width,
+                    height,
+                },
+                HostMessage::Resize {
+                    view: next_view,
+                    width: next_width,
+                    height: next_height,
+                },
+            ) if *view == next_view => {
+                *width = next_width;
+                *height = next_height;
+                Ok(())
+            }
+            (_, next) => Err(next),
+        }
+    }
+
+    /// Channel message travels over once page and worker have them.
+    pub fn port(&self) -> Port {
+        match self {
+            HostMessage::Pointer { .. }
+            | HostMessage::PointerMotion { .. }
+            | HostMessage::Touch { .. }
+            | HostMessage::Wheel { .. }
+            | HostMessage::PointerLockChanged { .. }
+            | HostMessage::Focus { .. }
+            | HostMessage::Key { .. }
+            | HostMessage::KeyboardLayout(_)
+            | HostMessage::DeviceOrientation { .. }
+            | HostMessage::DeviceMotion { .. }
+            | HostMessage::Ime { .. }
+            | HostMessage::Resize { .. }
+            | HostMessage::FullscreenChanged { .. }
+            | HostMessage::ClipboardPaste(_)
+            | HostMessage::Attach { .. }
+            | HostMessage::Detach { .. } => Port::Input,
+            HostMessage::AssetBytes { .. }
+            | HostMessage::ClearAssetCache
+            | HostMessage::ExportScene { .. }
+            | HostMessage::ImportScene(_)
+            | HostMessage::RequestAssetPreview { .. }
+            | HostMessage::FileDropped { .. } => Port::Asset,
+            HostMessage::SetTrafficLog(_)
+            | HostMessage::SetLatencyProbe(_)
+            | HostMessage::RequestTrafficLog
+            | HostMessage::SetFrameStats(_)
+            | HostMessage::SetRecording(_)
+            | HostMessage::RequestRecording
+            | HostMessage::Replay(_)
+            | HostMessage::SetInspector(_)
+            | HostMessage::InspectorEdit { .. }
+            | HostMessage::DebugDraw { .. } => Port::Log,
+            HostMessage::ViewIntersection { .. }
+            | HostMessage::SetBudgetShare(_)
+            | HostMessage::Pause
+            | HostMessage::Resume
+            | HostMessage::StepFrames(_)
+            | HostMessage::SetTimeScale(_)
+            | HostMessage::ConsoleCommand(_)
+            | HostMessage::TakeSnapshot
+            | HostMessage::RestoreSnapshot(_)
+            | HostMessage::Visibility { .. }
+            | HostMessage::MediaPreferences { .. }
+            | HostMessage::SetTargetFps(_)
+            | HostMessage::SetUpdateMode(_)
+            | HostMessage::RequestRedraw
+            | HostMessage::SetUiScale(_)
+            | HostMessage::SetGraphics(_)
+            | HostMessage::SetVsync(_)
+            | HostMessage::SetFeature { .. }
+            | HostMessage::ReloadConfig
+            | HostMessage::DataChannel { .. }
+            | HostMessage::DataChannelClosed { .. }
+            | HostMessage::Peer { .. }
+            | HostMessage::StoredSettings(_)
+            | HostMessage::BootFlags(_)
+            | HostMessage::Capabilities(_)
+            | HostMessage::Pong { .. }
+            | HostMessage::ClickLatency(_)
+            | HostMessage::FrameClock(_)
+            | HostMessage::Ports(_)
+            | HostMessage::Shutdown => Port::Control,
+        }
+    }
+
+    /// Whether message drives app's simulation and carries nothing but data,
+    /// so it can be recorded and fed back into the app later.
+    pub fn is_replayable(&self) -> bool {
+        match self {
+            HostMessage::Pointer { .. }
+            | HostMessage::PointerMotion { .. }
+            | HostMessage::Touch { .. }
+            | HostMessage::Wheel { .. }
+            | HostMessage::PointerLockChanged { .. }
+            | HostMessage::Focus { .. }
+            | HostMessage::Key { .. }
+            | HostMessage::KeyboardLayout(_)
+            | HostMessage::DeviceOrientation { .. }
+            | HostMessage::DeviceMotion { .. }
+            | HostMessage::Ime { .. }
+            | HostMessage::Resize { .. }
+            | HostMessage::FullscreenChanged { .. }
+            | HostMessage::ClipboardPaste(_)
+            | HostMessage::ViewIntersection { .. }
+            | HostMessage::Visibility { .. }
+            | HostMessage::MediaPreferences { .. }
+            | HostMessage::SetUiScale(_)
+            | HostMessage::SetFeature { .. }
+            | HostMessage::SetTimeScale(_)
+            | HostMessage::ConsoleCommand(_)
+            | HostMessage::InspectorEdit { .. } => true,
+            // Carry transferable objects.
+            HostMessage::Attach { .. }
+            | HostMessage::AssetBytes { .. }
+            | HostMessage::ImportScene(_)
+            | HostMessage::DataChannel { .. }
+            | HostMessage::Peer { .. }
+            | HostMessage::FileDropped { .. }
+            | HostMessage::FrameClock(_)
+            | HostMessage::Ports(_)
+            | HostMessage::Replay(_) => false,
+            // Pacing, diagnostics and lifecycle, which don't change what app computes.
+            HostMessage::Detach { .. }
+            | HostMessage::SetBudgetShare(_)
+            | HostMessage::Pause
+            | HostMessage::Resume
+            | HostMessage::StepFrames(_)
+            | HostMessage::SetTargetFps(_)
+            | HostMessage::SetUpdateMode(_)
+            | HostMessage::RequestRedraw
+            | HostMessage::SetGraphics(_)
+            | HostMessage::SetVsync(_)
+            | HostMessage::ReloadConfig
+            | HostMessage::ExportScene { .. }
+            | HostMessage::ClearAssetCache
+            | HostMessage::DataChannelClosed { .. }
+            | HostMessage::SetTrafficLog(_)
+            | HostMessage::SetLatencyProbe(_)
+            | HostMessage::RequestTrafficLog
+            | HostMessage::SetFrameStats(_)
+            | HostMessage::SetRecording(_)
+            | HostMessage::RequestRecording
+            | HostMessage::SetInspector(_)
+            | HostMessage::Pong { .. }
+            | HostMessage::ClickLatency(_)
+            | HostMessage::StoredSettings(_)
+            | HostMessage::BootFlags(_)
+            | HostMessage::Capabilities(_)
+            | HostMessage::RequestAssetPreview { .. }
+            | HostMessage::DebugDraw { .. }
+            | HostMessage::TakeSnapshot
+            | HostMessage::RestoreSnapshot(_)
+            | HostMessage::Shutdown => false,
+        }
+    }
+}
+
+/// Messages sent from the worker to the page.
+pub enum WorkerMessage {
+    /// Worker finished loading and is ready to receive messages.
+    Ready {
+        /// Threads worker runs on, its own included, see [`worker::threads`](crate::worker::threads).
+        threads: u32,
+        /// What worker context supports.
+        capabilities: Capabilities,
+    },
+    /// Worker failed to initialize.
+    ///
+    /// Posted by bootstrap script, so it never originates from Rust code.
+    Error(String),
+    /// Progress of wasm module download, `total` is `None` when size isn't known up front.
+    ///
+    /// Posted by bootstrap script while wasm is fetched, so it never originates from Rust code either.
+    WasmLoading { received: u64, total: Option<u64> },
+    /// Worker released its resources and can be terminated.
+    ShutdownComplete,
+    /// Current state of feature toggles.
+    Features(Vec<(String, bool)>),
+    /// Graphics context or WebGPU device was lost and app was torn down.
+    ///
+    /// Worker rebuilds the app once page attaches a fresh canvas to primary view,
+    /// carrying over only its persistent entities.
+    DeviceLost,
+    /// App presented its first frame, canvas shows something meaningful from now on.
+    ///
+    /// Sent again after app is rebuilt, e.g. once graphics context is restored.
+    FirstFrameRendered,
+    /// Accessibility tree of primary window changed.
+    Accessibility(AccessTree),
+    /// Lock pointer to view's canvas.
+    ///
+    /// Browsers only grant the lock shortly after user input, e.g. a click on the canvas.
+    RequestPointerLock { view: ViewId },
+    /// Release pointer lock, whichever canvas holds it.
+    ExitPointerLock,
+    /// Show given cursor over view's canvas.
+    ///
+    /// Cursor is a value of CSS `cursor` property, `none` hides it.
+    SetCursor { view: ViewId, cursor: String },
+    /// Start or stop accepting text input for the view.
+    ///
+    /// Page focuses a hidden text element while enabled, so IME candidate window
+    /// shows up at given position, in pixels of canvas drawing buffer.
+    SetIme {
+        view: ViewId,
+        enabled: bool,
+        x: f32,
+        y: f32,
+    },
+    /// Anchors of the view which moved since last message, by id.
+    ///
+    /// Positions are in pixels of canvas drawing buffer, `None` hides the element:
+    /// anchor is behind the camera, outside of viewport or gone.
+    /// See [`worker::dom_anchor`](crate::worker::dom_anchor).
+    DomAnchors {
+        view: ViewId,
+        anchors: Vec<(String, Option<(f32, f32)>)>,
+    },
+    /// Put text into system clipboard.
+    ClipboardCopy(String),
+    /// Read text from system clipboard, answered with [`HostMessage::ClipboardPaste`].
+    ///
+    /// Browsers may ask user for permission or refuse outright without recent user input.
+    ClipboardPasteRequest,
+    /// Make view's canvas enter or leave fullscreen.
+    ///
+    /// Same as pointer lock, browsers only allow entering fullscreen shortly after user input.
+    SetFullscreen { view: ViewId, fullscreen: bool },
+    /// Frame took unusually long, report is a JSON document describing it.
+    AnomalyReport(String),
+    /// Worker's traffic log as JSON, see [`TrafficLog::to_json`].
+    TrafficLog(String),
+    /// Entities matching [`HostMessage::SetInspector`] query, sent periodically while it is set.
+    InspectorSnapshot(Vec<InspectedEntity>),
+    /// Answer to [`HostMessage::ExportScene`], scene in RON format or reason it couldn't be made.
+    SceneExported(Result<String, String>),
+    /// Answer to [`HostMessage::TakeSnapshot`], persistent entities in RON format
+    /// or reason they couldn't be written out.
+    Snapshot(Result<String, String>),
+    /// Answer to [`HostMessage::RequestRecording`], recording as JSON document.
+    ///
+    /// Buffer is transferred to the page.
+    Recording(Transferable<ArrayBuffer>),
+    /// Measure round trip through the bridge, page answers with [`HostMessage::Pong`].
+    Ping(u32),
+    /// Latest bridge diagnostics of the app.
+    BridgeStats(BridgeStats),
+    /// Latest frame rate and world size, sent while [`HostMessage::SetFrameStats`] is on.
+    FrameStats(FrameStats),
+    /// Worker's wasm memory grew past the threshold.
+    MemoryWarning(MemoryWarning),
+    /// Latest render statistics of the app.
+    RenderStats(RenderStats),
+    /// Graphics settings in effect after boot flags or [`HostMessage::SetGraphics`] were applied.
+    ///
+    /// MSAA is always set, HDR and tonemapping only once page forced them.
+    Graphics(GraphicsOptions),
+    /// Present mode surface settled on after [`HostMessage::SetVsync`].
+    ///
+    /// Surface may not support turning vsync off, in which case it stays on.
+    PresentMode(SurfacePresentMode),
+    /// Answer to [`HostMessage::ConsoleCommand`], command's output or reason it failed.
+    ConsoleOutput {
+        command: String,
+        result: Result<String, String>,
+    },
+    /// Frame handling input messages with these ids was submitted, see [`HostMessage::SetLatencyProbe`].
+    InputPresented(Vec<CorrelationId>),
+    /// Latency test scene flashed for a press page captured at `pressed_at`, by `Date.now()`.
+    ///
+    /// Worker has no audio output, so page plays the click and answers with [`HostMessage::ClickLatency`].
+    LatencyClick { pressed_at: f64 },
+    /// Store settings under given key, value is JSON.
+    SaveSettings { key: String, value: String },
+    /// Answer to [`HostMessage::RequestAssetPreview`].
+    ///
+    /// Bitmap is transferred to the page.
+    AssetPreview {
+        request: u32,
+        result: Result<Transferable<ImageBitmap>, String>,
+    },
+}
+
+/// Frame rate and world size of the app, averaged over the last few frames.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FrameStats {
+    pub fps: f64,
+    pub frame_time_ms: f64,
+    pub entities: u32,
+}
+
+/// Worker's linear memory crossed a threshold, see [`MemoryDiagnosticsPlugin`](crate::worker::memory::MemoryDiagnosticsPlugin).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryWarning {
+    /// Size of wasm memory.
+    pub bytes: u64,
+    /// Threshold it went past.
+    pub threshold: u64,
+    /// Loaded images and meshes, whichever app has.
+    pub assets: u32,
+}
+
+/// What the render world drew in the last frame, see [`RenderStatsPlugin`](crate::worker::render_stats::RenderStatsPlugin).
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RenderStats {
+    /// Phase items across all views.
+    ///
+    /// Close to the number of draw calls, but not exact: draw functions may issue none or several.
+    pub phase_items: u32,
+    /// Pipelines compiled so far.
+    pub pipelines: u32,
+    /// Images uploaded to the GPU.
+    pub textures: u32,
+    /// Memory taken by uploaded images, estimated from their size and format.
+    ///
+    /// Render targets and buffers aren't counted.
+    pub texture_bytes: u64,
+}
+
+/// Throughput and latency of the bridge as seen from the worker, averaged over the last second or so.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BridgeStats {
+    pub received_per_sec: f64,
+    pub sent_per_sec: f64,
+    /// Mean time to decode a message from the page.
+    pub decode_ms: f64,
+    /// Mean time to encode and post a message to the page.
+    pub encode_ms: f64,
+    /// Latest round trip from worker to page and back, not counting frame time.
+    pub round_trip_ms: Option<f64>,
+}
+
+/// What inspector snapshots include, see [`HostMessage::SetInspector`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct InspectQuery {
+    /// Reflected components to include besides transforms, by short or full type name.
+    pub components: Vec<String>,
+    /// Only include entities whose name contains this, along with their ancestors.
+    pub name_filter: Option<String>,
+}
+
+/// Entity as seen by the inspector.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InspectedEntity {
+    /// Bits of `Entity`.
+    pub entity: u64,
+    pub name: Option<String>,
+    pub parent: Option<u64>,
+    /// Full type name and RON value of included components.
+    pub components: Vec<(String, String)>,
+}
+
+/// Snapshot of accessibility tree, flattened in reading order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessTree {
+    /// Logical size of the window node bounds are relative to.
+    pub width: f64,
+    pub height: f64,
+    pub nodes: Vec<AccessNode>,
+    /// Node holding keyboard focus.
+    pub focus: Option<u64>,
+}
+
+/// Single accessible element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessNode {
+    pub id: u64,
+    /// ARIA role, `None` for plain text.
+    pub role: Option<String>,
+    pub name: Option<String>,
+    /// Rectangle in logical window pixels: `[x0, y0, x1, y1]`.
+    pub bounds: Option<[f64; 4]>,
+}
+
+impl WorkerMessage {
+    /// Short name of the message, as it appears in `kind` field.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WorkerMessage::Ready { .. } => "ready",
+            WorkerMessage::Error(_) => "error",
+            WorkerMessage::WasmLoading { .. } => "wasm_loading",
+            WorkerMessage::ShutdownComplete => "shutdown_complete",
+            WorkerMessage::Features(_) => "features",
+            WorkerMessage::DeviceLost => "device_lost",
+            WorkerMessage::FirstFrameRendered => "first_frame_rendered",
+            WorkerMessage::Accessibility(_) => "accessibility",
+            WorkerMessage::RequestPointerLock { .. } => "request_pointer_lock",
+            WorkerMessage::ExitPointerLock => "exit_pointer_lock",
+            WorkerMessage::SetFullscreen { .. } => "set_fullscreen",
+            WorkerMessage::SetCursor { .. } => "set_cursor",
+            WorkerMessage::SetIme { .. } => "set_ime",
+            WorkerMessage::DomAnchors { .. } => "dom_anchors",
+            WorkerMessage::ClipboardCopy(_) => "clipboard_copy",
+            WorkerMessage::ClipboardPasteRequest => "clipboard_paste_request",
+            WorkerMessage::AnomalyReport(_) => "anomaly_report",
+            WorkerMessage::TrafficLog(_) => "traffic_log",
+            WorkerMessage::InspectorSnapshot(_) => "inspector_snapshot",
+            WorkerMessage::SceneExported(_) => "scene_exported",
+            WorkerMessage::Snapshot(_) => "snapshot",
+            WorkerMessage::Recording(_) => "recording",
+            WorkerMessage::Ping(_) => "ping",
+            WorkerMessage::InputPresented(_) => "input_presented",
+            WorkerMessage::LatencyClick { .. } => "latency_click",
+            WorkerMessage::BridgeStats(_) => "bridge_stats",
+            WorkerMessage::FrameStats(_) => "frame_stats",
+            WorkerMessage::MemoryWarning(_) => "memory_warning",
+            WorkerMessage::RenderStats(_) => "render_stats",
+            WorkerMessage::Graphics(_) => "graphics",
+            WorkerMessage::PresentMode(_) => "present_mode",
+            WorkerMessage::ConsoleOutput { .. } => "console_output",
+            WorkerMessage::SaveSettings { .. } => "save_settings",
+            WorkerMessage::AssetPreview { .. } => "asset_preview",
+        }
+    }
+
+    /// Encode message into JS value, together with objects which need to be transferred.
+    ///
+    /// Transferable payloads are marked as sent, so message can only be encoded once.
+    pub fn encode(&self) -> Result<(JsValue, Array), BridgeError> {
+        let kind = self.kind();
+        let transfer = Array::new();
+        let msg = tagged(kind);
+
+        match self {
+            WorkerMessage::Error(message) => {
+                set(&msg, "message", &message.into());
+            }
+            WorkerMessage::WasmLoading { received, total } => {
+                set(&msg, "received", &(*received as f64).into());
+                if let Some(total) = total {
+                    set(&msg, "total", &(*total as f64).into());
+                }
+            }
+            WorkerMessage::Features(features) => {
+                let map = Object::new();
+                for (name, enabled) in features {
+                    set(&map, name, &(*enabled).into());
+                }
+
+                set(&msg, "features", &map);
+            }
+            WorkerMessage::Accessibility(tree) => {
+                let nodes: Array = tree
+                    .nodes
+                    .iter()
+                    .map(|node| {
+                        let object = Object::new();
+                        // Entity bits don't fit into JS numbers.
+                        set(&object, "id", &node.id.to_string().into());
+                        if let Some(role) = &node.role {
+                            set(&object, "role", &role.into());
+                        }
+                        if let Some(name) = &node.name {
+                            set(&object, "name", &name.into());
+                        }
+                        if let Some(bounds) = node.bounds {
+                            let bounds: Array = bounds.iter().map(|&x| JsValue::from(x)).collect();
+                            set(&object, "bounds", &bounds);
+                        }
+                        JsValue::from(object)
+                    })
+                    .collect();
+
+                set(&msg, "width", &tree.width.into());
+                set(&msg, "height", &tree.height.into());
+                set(&msg, "nodes", &nodes);
+                if let Some(focus) = tree.focus {
+                    set(&msg, "focus", &focus.to_string().into());
+                }
+            }
+            WorkerMessage::RequestPointerLock { view } => {
+                set(&msg, "view", &view.0.into());
+            }
+            WorkerMessage::SetFullscreen { view, fullscreen } => {
+                set(&msg, "view", &view.0.into());
+                set(&msg, "fullscreen", &(*fullscreen).into());
+            }
+            WorkerMessage::SetCursor { view, cursor } => {
+                set(&msg, "view", &view.0.into());
+                set(&msg, "cursor", &cursor.into());
+            }
+            WorkerMessage::SetIme {
+                view,
+                enabled,
+                x,
+                y,
+            } => {
+                set(&msg, "view", &view.0.into());
+                set(&msg, "enabled", &(*enabled).into());
+                set(&msg, "x", &(*x).into());
+                set(&msg, "y", &(*y).into());
+            }
+            WorkerMessage::DomAnchors { view, anchors } => {
+                let anchors: Array = anchors
+                    .iter()
+                    .map(|(id, position)| {
+                        let object = Object::new();
+                        set(&object, "id", &id.into());
+                        if let Some((x, y)) = position {
+                            set(&object, "x", &(*x).into());
+                            set(&object, "y", &(*y).into());
+                        }
+                        JsValue::from(object)
+                    })
+                    .collect();
+
+                set(&msg, "view", &view.0.into());
+                set(&msg, "anchors", &anchors);
+            }
+            WorkerMessage::ClipboardCopy(text) => {
+                set(&msg, "text", &text.into());
+            }
+            WorkerMessage::AnomalyReport(report) => {
+                set(&msg, "report", &report.into());
+            }
+            WorkerMessage::TrafficLog(log) => {
+                set(&msg, "log", &log.into());
+            }
+            WorkerMessage::Recording(recording) => {
+                set(&msg, "recording", recording.transfer(&transfer, kind)?);
+            }
+            WorkerMessage::SceneExported(result) => match result {
+                Ok(scene) => set(&msg, "scene", &scene.into()),
+                Err(error) => set(&msg, "error", &error.into()),
+            },
+            WorkerMessage::Snapshot(result) => match result {
+                Ok(snapshot) => set(&msg, "snapshot", &snapshot.into()),
+                Err(error) => set(&msg, "error", &error.into()),
+            },
+            WorkerMessage::ConsoleOutput { command, result } => {
+                set(&msg, "command", &command.into());
+                match result {
+                    Ok(output) => set(&msg, "output", &output.into()),
+                    Err(error) => set(&msg, "error", &error.into()),
+                }
+            }
+            WorkerMessage::InspectorSnapshot(entities) => {
+                // Entity bits don't fit into JS numbers, so the snapshot travels as JSON.
+                let snapshot =
+                    serde_json::to_string(entities).map_err(|err| BridgeError::Serialization {
+                        kind: Some(kind),
+                        reason: err.to_string(),
+                    })?;
+                set(&msg, "snapshot", &snapshot.into());
+            }
+            WorkerMessage::Ping(seq) => {
+                set(&msg, "seq", &(*seq).into());
+            }
+            WorkerMessage::InputPresented(ids) => {
+                let ids: Array = ids.iter().map(|id| JsValue::from(id.0)).collect();
+                set(&msg, "ids", &ids);
+            }
+            WorkerMessage::LatencyClick { pressed_at } => {
+                set(&msg, "pressed_at", &(*pressed_at).into());
+            }
+            WorkerMessage::BridgeStats(stats) => {
+                set(&msg, "received_per_sec", &stats.received_per_sec.into());
+                set(&msg, "sent_per_sec", &stats.sent_per_sec.into());
+                set(&msg, "decode_ms", &stats.decode_ms.into());
+                set(&msg, "encode_ms", &stats.encode_ms.into());
+                if let Some(round_trip_ms) = stats.round_trip_ms {
+                    set(&msg, "round_trip_ms", &round_trip_ms.into());
+                }
+            }
+            WorkerMessage::FrameStats(stats) => {
+                set(&msg, "fps", &stats.fps.into());
+                set(&msg, "frame_time_ms", &stats.frame_time_ms.into());
+                set(&msg, "entities", &stats.entities.into());
+            }
+            WorkerMessage::MemoryWarning(warning) => {
+                set(&msg, "bytes", &(warning.bytes as f64).into());
+                set(&msg, "threshold", &(warning.threshold as f64).into());
+                set(&msg, "assets", &warning.assets.into());
+            }
+            WorkerMessage::RenderStats(stats) => {
+                set(&msg, "phase_items", &stats.phase_items.into());
+                set(&msg, "pipelines", &stats.pipelines.into());
+                set(&msg, "textures", &stats.textures.into());
+                set(&msg, "texture_bytes", &(stats.texture_bytes as f64).into());
+            }
+            WorkerMessage::Graphics(options) => options.encode(&msg),
+            WorkerMessage::PresentMode(mode) => {
+                set(&msg, "mode", &mode.name().into());
+            }
+            WorkerMessage::SaveSettings { key, value } => {
+                set(&msg, "key", &key.into());
+                set(&msg, "value", &value.into());
+            }
+            WorkerMessage::AssetPreview { request, result } => {
+                set(&msg, "request", &(*request).into());
+                match result {
+                    Ok(bitmap) => set(&msg, "bitmap", bitmap.transfer(&transfer, kind)?),
+                    Err(error) => set(&msg, "error", &error.into()),
+                }
+            }
+            WorkerMessage::Ready {
+                threads,
+                capabilities,
+            } => {
+                set(&msg, "threads", &(*threads).into());
+                set(&msg, "capabilities", &capabilities.encode());
+            }
+            WorkerMessage::ShutdownComplete
+            | WorkerMessage::DeviceLost
+            | WorkerMessage::FirstFrameRendered
+            | WorkerMessage::ExitPointerLock
+            | WorkerMessage::ClipboardPasteRequest => {}
+        }
+
+        Ok((msg.into(), transfer))
+    }
+
+    /// Decode message, returns `None` if value doesn't look like one.
+    pub fn decode(value: &JsValue) -> Option<Self> {
+        let msg = match kind(value)?.as_str() {
+            "ready" => WorkerMessage::Ready {
+                threads: get(value, "threads")?.as_f64()? as u32,
+                capabilities: Capabilities::decode(&get(value, "capabilities")?)?,
+            },
+            "error" => WorkerMessage::Error(get(value, "message")?.as_string()?),
+            "wasm_loading" => WorkerMessage::WasmLoading {
+                received: get(value, "received")?.as_f64()? as u64,
+                total: get(value, "total")
+                    .and_then(|total| total.as_f64())
+                    .map(|total| total as u64),
+            },
+            "shutdown_complete" => WorkerMessage::ShutdownComplete,
+            "features" => {
+                let map = get(value, "features")?;
+                let features = Object::entries(map.dyn_ref()?)
+                    .iter()
+                    .filter_map(|entry| {
+                        let entry: Array = entry.dyn_into().ok()?;
+                        Some((entry.get(0).as_string()?, entry.get(1).as_bool()?))
+                    })
+                    .collect();
+
+                WorkerMessage::Features(features)
+            }
+            "device_lost" => WorkerMessage::DeviceLost,
+            "first_frame_rendered" => WorkerMessage::FirstFrameRendered,
+            "accessibility" => {
+                let nodes: Array = get(value, "nodes")?.dyn_into().ok()?;
+                let nodes = nodes
+                    .iter()
+                    .filter_map(|node| {
+                        let bounds = get(&node, "bounds")
+                            .and_then(|bounds| bounds.dyn_into::<Array>().ok())
+                            .and_then(|bounds| {
+                                let mut rect = [0.0; 4];
+                                for (i, x) in rect.iter_mut().enumerate() {
+                                    *x = bounds.get(i as u32).as_f64()?;
+                                }
+                                Some(rect)
+                            });
+
+                        Some(AccessNode {
+                            id: node_id(&node, "id")?,
+                            role: get(&node, "role").and_then(|role| role.as_string()),
+                            name: get(&node, "name").and_then(|name| name.as_string()),
+                            bounds,
+                        })
+                    })
+                    .collect();
+
+                WorkerMessage::Accessibility(AccessTree {
+                    width: get(value, "width")?.as_f64()?,
+                    height: get(value, "height")?.as_f64()?,
+                    nodes,
+                    focus: node_id(value, "focus"),
+                })
+            }
+            "request_pointer_lock" => WorkerMessage::RequestPointerLock { view: view(value)? },
+            "exit_pointer_lock" => WorkerMessage::ExitPointerLock,
+            "set_fullscreen" => WorkerMessage::SetFullscreen {
+                view: view(value)?,
+                fullscreen: get(value, "fullscreen")?.as_bool()?,
+            },
+            "clipboard_copy" => WorkerMessage::ClipboardCopy(get(value, "text")?.as_string()?),
+            "clipboard_paste_request" => WorkerMessage::ClipboardPasteRequest,
+            "set_cursor" => WorkerMessage::SetCursor {
+                view: view(value)?,
+                cursor: get(value, "cursor")?.as_string()?,
+            },
+            "set_ime" => WorkerMessage::SetIme {
+                view: view(value)?,
+                enabled: get(value, "enabled")?.as_bool()?,
+                x: get(value, "x")?.as_f64()? as f32,
+                y: get(value, "y")?.as_f64()? as f32,
+            },
+            "dom_anchors" => {
+                let anchors: Array = get(value, "anchors")?.dyn_into().ok()?;
+                let anchors = anchors
+                    .iter()
+                    .filter_map(|anchor| {
+                        let position = get(&anchor, "x")
+                            .zip(get(&anchor, "y"))
+                            .and_then(|(x, y)| Some((x.as_f64()? as f32, y.as_f64()? as f32)));
+                        Some((get(&anchor, "id")?.as_string()?, position))
+                    })
+                    .collect();
+
+                WorkerMessage::DomAnchors {
+                    view: view(value)?,
+                    anchors,
+                }
+            }
+            "anomaly_report" => WorkerMessage::AnomalyReport(get(value, "report")?.as_string()?),
+            "traffic_log" => WorkerMessage::TrafficLog(get(value, "log")?.as_string()?),
+            "recording" => WorkerMessage::Recording(Transferable::new(
+                get(value, "recording")?.dyn_into().ok()?,
+            )),
+            "scene_exported" => WorkerMessage::SceneExported(match get(value, "scene") {
+                Some(scene) => Ok(scene.as_string()?),
+                None => Err(get(value, "error")?.as_string()?),
+            }),
+            "snapshot" => WorkerMessage::Snapshot(match get(value, "snapshot") {
+                Some(snapshot) => Ok(snapshot.as_string()?),
+                None => Err(get(value, "error")?.as_string()?),
+            }),
+            "console_output" => WorkerMessage::ConsoleOutput {
+                command: get(value, "command")?.as_string()?,
+                result: match get(value, "output") {
+                    Some(output) => Ok(output.as_string()?),
+                    None => Err(get(value, "error")?.as_string()?),
+                },
+            },
+            "inspector_snapshot" => WorkerMessage::InspectorSnapshot(
+                serde_json::from_str(&get(value, "snapshot")?.as_string()?).ok()?,
+            ),
+            "ping" => WorkerMessage::Ping(get(value, "seq")?.as_f64()? as u32),
+            "latency_click" => WorkerMessage::LatencyClick {
+                pressed_at: get(value, "pressed_at")?.as_f64()?,
+            },
+            "input_presented" => {
+                let ids: Array = get(value, "ids")?.dyn_into().ok()?;
+
+                WorkerMessage::InputPresented(
+                    ids.iter()
+                        .filter_map(|id| Some(CorrelationId(id.as_f64()? as u32)))
+                        .collect(),
+                )
+            }
+            "bridge_stats" => WorkerMessage::BridgeStats(BridgeStats {
+                received_per_sec: get(value, "received_per_sec")?.as_f64()?,
+                sent_per_sec: get(value, "sent_per_sec")?.as_f64()?,
+                decode_ms: get(value, "decode_ms")?.as_f64()?,
+                encode_ms: get(value, "encode_ms")?.as_f64()?,
+                round_trip_ms: get(value, "round_trip_ms").and_then(|value| value.as_f64()),
+            }),
+            "frame_stats" => WorkerMessage::FrameStats(FrameStats {
+                fps: get(value, "fps")?.as_f64()?,
+                frame_time_ms: get(value, "frame_time_ms")?.as_f64()?,
+                entities: get(value, "entities")?.as_f64()? as u32,
+            }),
+            "memory_warning" => WorkerMessage::MemoryWarning(MemoryWarning {
+                bytes: get(value, "bytes")?.as_f64()? as u64,
+                threshold: get(value, "threshold")?.as_f64()? as u64,
+                assets: get(value, "assets")?.as_f64()? as u32,
+            }),
+            "render_stats" => WorkerMessage::RenderStats(RenderStats {
+                phase_items: get(value, "phase_items")?.as_f64()? as u32,
+                pipelines: get(value, "pipelines")?.as_f64()? as u32,
+                textures: get(value, "textures")?.as_f64()? as u32,
+                texture_bytes: get(value, "texture_bytes")?.as_f64()? as u64,
+            }),
+            "graphics" => WorkerMessage::Graphics(GraphicsOptions::decode(value)),
+            "present_mode" => WorkerMessage::PresentMode(SurfacePresentMode::from_name(
+                &get(value, "mode")?.as_string()?,
+            )?),
+            "save_settings" => WorkerMessage::SaveSettings {
+                key: get(value, "key")?.as_string()?,
+                value: get(value, "value")?.as_string()?,
+            },
+            "asset_preview" => {
+                let result = match get(value, "bitmap") {
+                    Some(bitmap) => Ok(Transferable::new(bitmap.dyn_into().ok()?)),
+                    None => Err(get(value, "error")?.as_string()?),
+                };
+
+                WorkerMessage::AssetPreview {
+                    request: get(value, "request")?.as_f64()? as u32,
+                    result,
+                }
+            }
+            _ => return None,
+        };
+
+        Some(msg)
+    }
+
+    /// Channel message travels over once page and worker have them.
+    pub fn port(&self) -> Port {
+        match self {
+            WorkerMessage::RequestPointerLock { .. }
+            | WorkerMessage::ExitPointerLock
+            | WorkerMessage::SetCursor { .. }
+            | WorkerMessage::SetIme { .. }
+            | WorkerMessage::ClipboardCopy(_)
+            | WorkerMessage::ClipboardPasteRequest
+            | WorkerMessage::SetFullscreen { .. }
+            | WorkerMessage::InputPresented(_)
+            | WorkerMessage::LatencyClick { .. } => Port::Input,
+            WorkerMessage::AssetPreview { .. } | WorkerMessage::SceneExported(_) => Port::Asset,
+            WorkerMessage::AnomalyReport(_)
+            | WorkerMessage::TrafficLog(_)
+            | WorkerMessage::InspectorSnapshot(_)
+            | WorkerMessage::Recording(_)
+            | WorkerMessage::BridgeStats(_)
+            | WorkerMessage::FrameStats(_)
+            | WorkerMessage::MemoryWarning(_)
+            | WorkerMessage::RenderStats(_)
+            | WorkerMessage::ConsoleOutput { .. } => Port::Log,
+            WorkerMessage::Ready { .. }
+            | WorkerMessage::Ping(_)
+            | WorkerMessage::Error(_)
+            | WorkerMessage::WasmLoading { .. }
+            | WorkerMessage::ShutdownComplete
+            | WorkerMessage::Features(_)
+            | WorkerMessage::DeviceLost
+            | WorkerMessage::FirstFrameRendered
+            | WorkerMessage::Snapshot(_)
+            | WorkerMessage::Accessibility(_)
+            | WorkerMessage::DomAnchors { .. }
+            | WorkerMessage::Graphics(_)
+            | WorkerMessage::PresentMode(_)
+            | WorkerMessage::SaveSettings { .. } => Port::Control,
+        }
+    }
+}
+
+fn tagged(kind: &str) -> Object {
+    let msg = Object::new();
+    set(&msg, "kind", &kind.into());
+    msg
+}
+
+fn kind(value: &JsValue) -> Option<String> {
+    get(value, "kind")?.as_string()
+}
+
+fn view(value: &JsValue) -> Option<ViewId> {
+    let id = get(value, "view")?.as_f64()?;
+    Some(ViewId(id as u32))
+}
+
+fn floats(values: &[f32]) -> Array {
+    values.iter().map(|&x| JsValue::from(x)).collect()
+}
+
+fn get_floats<const N: usize>(value: &JsValue, key: &str) -> Option<[f32; N]> {
+    let array: Array = get(value, key)?.dyn_into().ok()?;
+    let mut values = [0.0; N];
+    for (i, x) in values.iter_mut().enumerate() {
+        *x = array.get(i as u32).as_f64()? as f32;
+    }
+
+    Some(values)
+}
+
+fn node_id(value: &JsValue, key: &str) -> Option<u64> {
+    get(value, key)?.as_string()?.parse().ok()
+}
+
+fn set(object: &Object, key: &str, value: &JsValue) {
+    // Setting a property on a fresh plain object cannot fail.
+    let _ = Reflect::set(object, &key.into(), value);
+}
+
+fn get(value: &JsValue, key: &str) -> Option<JsValue> {
+    if !value.is_object() {
+        return None;
+    }
+
+    Reflect::get(value, &key.into())
+        .ok()
+        .filter(|value| !value.is_undefined())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pointer(view: u32, action: PointerAction, x: f32) -> HostMessage {
+        HostMessage::Pointer {
+            view: ViewId(view),
+            action,
+            x,
+            y: x,
+            button: 0,
+        }
+    }
+
+    fn wheel(dy: f32, mode: WheelMode) -> HostMessage {
+        HostMessage::Wheel {
+            view: ViewId::PRIMARY,
+            dx: 0.0,
+            dy,
+            mode,
+            pinch: false,
+        }
+    }
+
+    #[test]
+    fn pointer_moves_keep_latest_position() {
+        let mut msg = pointer(0, PointerAction::Move, 1.0);
+        assert!(msg.coalesce(pointer(0, PointerAction::Move, 2.0)).is_ok());
+
+        let HostMessage::Pointer { x, y, .. } = msg else {
+            panic!("message changed its kind");
+        };
+        assert_eq!((x, y), (2.0, 2.0));
+    }
+
+    #[test]
+    fn clicks_and_other_views_are_not_merged() {
+        let mut msg = pointer(0, PointerAction::Move, 1.0);
+
+        assert!(msg.coalesce(pointer(0, PointerAction::Down, 2.0)).is_err());
+        assert!(msg.coalesce(pointer(1, PointerAction::Move, 2.0)).is_err());
+        assert!(msg.coalesce(HostMessage::Pause).is_err());
+    }
+
+    #[test]
+    fn wheel_deltas_add_up() {
+        let mut msg = wheel(1.0, WheelMode::Line);
+        assert!(msg.coalesce(wheel(2.0, WheelMode::Line)).is_ok());
+        assert!(msg.coalesce(wheel(3.0, WheelMode::Pixel)).is_err());
+
+        let HostMessage::Wheel { dy, .. } = msg else {
+            panic!("message changed its kind");
+        };
+        assert_eq!(dy, 3.0);
+    }
+
+    #[test]
+    fn resize_keeps_latest_size() {
+        let resize = |width, height| HostMessage::Resize {
+            view: ViewId::PRIMARY,
+            width,
+            height,
+        };
+        let mut msg = resize(100, 100);
+        assert!(msg.coalesce(resize(200, 50)).is_ok());
+
+        let HostMessage::Resize { width, height, .. } = msg else {
+            panic!("message changed its kind");
+        };
+        assert_eq!((width, height), (200, 50));
+    }
+}
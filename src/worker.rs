@@ -0,0 +1,2250 @@
+//! Worker side of the bridge.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+use bevy::app::PluginGroupBuilder;
+use bevy::ecs::event::ManualEventReader;
+use bevy::ecs::schedule::{BoxedScheduleLabel, ScheduleLabel};
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy::window::{
+    AbstractHandleWrapper, PrimaryWindow, RequestRedraw, WebElement, WebHandle, WindowClosed,
+    WindowResolution,
+};
+use wasm_bindgen::prelude::{Closure, JsValue};
+use web_sys::{DedicatedWorkerGlobalScope, MessagePort, OffscreenCanvas};
+
+pub use crate::protocol::AppId;
+pub use crate::protocol::TrafficDirection;
+use crate::protocol::{
+    validate_transfer, BridgeError, CorrelationId, Envelope, GraphicsBackend, HostMessage, Port,
+    Transferable, UpdateMode, ViewId, WorkerMessage,
+};
+
+pub mod accessibility;
+pub mod anomaly;
+pub mod antialiasing;
+pub mod asset_cache;
+pub mod boot;
+pub mod bridge_diagnostics;
+pub mod broadcast;
+pub mod cache_storage;
+pub mod capabilities;
+pub mod clipboard;
+pub mod config;
+pub mod console;
+pub mod cursor;
+pub mod dashboard;
+pub mod debug_draw;
+pub mod depth;
+pub mod dom_anchor;
+pub mod features;
+pub mod file_drop;
+pub mod filters;
+pub mod first_frame;
+pub mod focus;
+pub mod frame_stats;
+pub mod fullscreen;
+pub mod gestures;
+pub mod graphics;
+pub mod image_bitmap;
+pub mod ime;
+pub mod inmem;
+pub mod input;
+pub mod inspector;
+pub mod keyboard;
+pub mod latency;
+pub mod media;
+pub mod memory;
+#[cfg(feature = "mock-page")]
+pub mod mock;
+pub mod offscreen;
+pub mod peers;
+pub mod pointer_lock;
+pub mod present_mode;
+pub mod preview;
+pub mod profiling;
+pub mod render_stats;
+pub mod replay;
+pub mod save_data;
+pub mod scene;
+mod scheduler;
+pub mod sensors;
+pub mod settings;
+pub mod shared;
+pub mod simulation;
+pub mod software_cursor;
+pub mod threads;
+pub mod traffic_log;
+pub mod ui_scale;
+pub mod upgrade;
+pub mod viewport;
+pub mod virtual_resolution;
+pub mod webrtc;
+pub mod websocket;
+pub mod webtransport;
+
+thread_local! {
+    static INBOX: RefCell<VecDeque<Inbound>> = RefCell::new(VecDeque::new());
+    static METRICS: RefCell<BridgeMetrics> = RefCell::new(BridgeMetrics::default());
+    static BRIDGE_ERRORS: RefCell<Vec<(AppId, BridgeError)>> = RefCell::new(Vec::new());
+    static NEXT_ID: Cell<u32> = Cell::new(0);
+    static HANDLED_SENT_AT: Cell<Option<f64>> = Cell::new(None);
+    static LAST_UPDATE_MS: Cell<Option<f64>> = Cell::new(None);
+    static CURRENT_APP: Cell<AppId> = Cell::new(AppId::DEFAULT);
+    static APPS: RefCell<BTreeMap<AppId, Driver>> = RefCell::new(BTreeMap::new());
+    // Apps built ahead of their canvas by `start_warm`, not running yet.
+    static WARM_APPS: RefCell<BTreeMap<AppId, App>> = RefCell::new(BTreeMap::new());
+    static TIMER: Cell<Option<i32>> = Cell::new(None);
+    static TICK: RefCell<Option<Closure<dyn FnMut()>>> = RefCell::new(None);
+    // Worker's ends of subsystem channels, empty until page sends them.
+    static PORTS: RefCell<HashMap<Port, MessagePort>> = RefCell::new(HashMap::new());
+    static INPUT_DRAIN: RefCell<HashMap<AppId, InputDrain>> = RefCell::new(HashMap::new());
+    static FRAME_CLOCK: RefCell<Option<FrameClock>> = RefCell::new(None);
+    // Paused apps with frames they are yet to step.
+    static PAUSED: RefCell<HashMap<AppId, u32>> = RefCell::new(HashMap::new());
+    // Apps resumed since their last update.
+    static RESUMED: RefCell<HashSet<AppId>> = RefCell::new(HashSet::new());
+    // Whether any page is on the other side, false until the first message arrives
+    // and again once every tab of a shared worker is gone.
+    static PAGE_CONNECTED: Cell<bool> = Cell::new(false);
+}
+
+fn scope() -> DedicatedWorkerGlobalScope {
+    use wasm_bindgen::prelude::JsCast;
+
+    JsValue::from(js_sys::global()).unchecked_into()
+}
+
+/// Call `target[method](...args)` on an object `web-sys` has no bindings for.
+fn call(target: &JsValue, method: &str, args: &[JsValue]) -> Result<JsValue, JsValue> {
+    use js_sys::{Array, Function, Reflect};
+    use wasm_bindgen::prelude::JsCast;
+
+    let function: Function = Reflect::get(target, &method.into())?.dyn_into()?;
+    function.apply(target, &args.iter().collect::<Array>())
+}
+
+/// Same as [`call`], for methods returning a promise.
+async fn call_async(target: &JsValue, method: &str, args: &[JsValue]) -> Result<JsValue, JsValue> {
+    use wasm_bindgen::prelude::JsCast;
+
+    let promise: js_sys::Promise = call(target, method, args)?.dyn_into()?;
+    wasm_bindgen_futures::JsFuture::from(promise).await
+}
+
+/// Current time in ms by `performance.now()`, for measuring short intervals.
+///
+/// Unlike `Date.now()` it has sub-millisecond precision, but its origin differs from page's.
+fn precise_now() -> f64 {
+    scope()
+        .performance()
+        .map_or_else(js_sys::Date::now, |performance| performance.now())
+}
+
+/// Start listening to the page.
+///
+/// `build` is invoked with the first canvas page sends for primary view,
+/// all following messages are handled by [`HostBridgePlugin`].
+/// If graphics context is lost, app is torn down and `build` is invoked again
+/// once page sends a fresh canvas for primary view.
+/// Entities marked [`Persistent`](upgrade::Persistent) are carried over into the rebuilt app,
+/// the rest of its state is lost with it.
+///
+/// With `mock-page` feature worker doesn't wait for the page,
+/// instead `build` is invoked right away with a canvas worker created by itself.
+///
+/// Every app page attaches a canvas for is built by the same `build`,
+/// use [`start_apps`] if they need to differ.
+pub fn start(build: impl Fn(OffscreenCanvas) + 'static) {
+    start_apps(move |_, canvas| build(canvas));
+}
+
+/// Start listening to the page, running separate app for every [`AppId`] page attaches canvas for.
+///
+/// Apps are isolated from each other: each has its own world, views and messages,
+/// only the wasm module and the worker are shared.
+/// This makes many small views, e.g. chart widgets, cheaper than a worker per view.
+///
+/// Feature modules keeping state outside of the world, like save data or traffic log,
+/// are shared between apps, and so is [`BridgeMetrics`].
+///
+/// With `mock-page` feature only [`AppId::DEFAULT`] is built.
+pub fn start_apps(build: impl Fn(AppId, OffscreenCanvas) + 'static) {
+    if threads::is_thread() {
+        return;
+    }
+
+    #[cfg(feature = "mock-page")]
+    mock::start(Build::Canvas(Box::new(build)));
+
+    #[cfg(not(feature = "mock-page"))]
+    listen(Build::Canvas(Box::new(build)));
+}
+
+/// Start listening to the page, running an app without any canvas.
+///
+/// Meant for workers doing background simulation, pathfinding or procedural generation
+/// and talking to the page or to other workers only through messages.
+/// App should be built with [`DefaultPlugins::headless`].
+///
+/// `build` is invoked for [`AppId::DEFAULT`] once handshake with the page is complete,
+/// so [`boot::boot_flags`] and capabilities are known by then.
+/// With `mock-page` feature it is invoked right away.
+pub fn start_headless(build: impl Fn(AppId) + 'static) {
+    if threads::is_thread() {
+        return;
+    }
+
+    #[cfg(feature = "mock-page")]
+    mock::start(Build::Headless(Box::new(build)));
+
+    #[cfg(not(feature = "mock-page"))]
+    listen(Build::Headless(Box::new(build)));
+}
+
+/// Start listening to the page, building app before page attaches its canvas.
+///
+/// Building plugins takes a good part of startup, but normally it can't begin until canvas arrives.
+/// Here app is built in two steps instead:
+/// `prepare` is invoked for [`AppId::DEFAULT`] once handshake with the page is complete
+/// and should build everything that doesn't need a window,
+/// typically [`DefaultPlugins::headless`] together with app's own plugins and systems.
+/// `attach` is invoked with the prepared app once page attaches canvas for primary view,
+/// and should add [`CanvasPlugins`] along with whatever else needs rendering, then run the app.
+/// Page which spawns the worker early and attaches canvas later
+/// gets wasm compilation and most of the build done in the meantime.
+///
+/// Other apps, as well as app rebuilt after graphics context is lost,
+/// are prepared right before `attach`.
+/// With `mock-page` feature both are invoked right away.
+pub fn start_warm(
+    prepare: impl Fn(AppId) -> App + 'static,
+    attach: impl Fn(AppId, App, OffscreenCanvas) + 'static,
+) {
+    if threads::is_thread() {
+        return;
+    }
+
+    let build = Build::Warm {
+        prepare: Box::new(prepare),
+        attach: Box::new(attach),
+    };
+
+    #[cfg(feature = "mock-page")]
+    mock::start(build);
+
+    #[cfg(not(feature = "mock-page"))]
+    listen(build);
+}
+
+/// Start listening to tabs of the same origin connecting to a `SharedWorker`, running a single app for all of them.
+///
+/// `build` is invoked with the first canvas any tab attaches.
+/// Canvases of the other tabs become additional views of the same app,
+/// add [`shared::SharedTabsPlugin`] to learn about them and point a camera at each.
+/// Input is routed to the views of the tab it came from.
+/// App keeps running until the last tab shuts it down.
+///
+/// Worker has to be loaded by the static loader script, see [`WorkerBuilder::shared`](crate::host::WorkerBuilder::shared).
+/// With `mock-page` feature behaves like [`start`].
+pub fn start_shared(build: impl Fn(OffscreenCanvas) + 'static) {
+    if threads::is_thread() {
+        return;
+    }
+
+    let build = Build::Canvas(Box::new(move |_, canvas| build(canvas)));
+
+    #[cfg(feature = "mock-page")]
+    mock::start(build);
+
+    #[cfg(not(feature = "mock-page"))]
+    shared::listen(build);
+}
+
+/// How apps get built, see [`start_apps`], [`start_headless`], [`start_warm`] and [`start_shared`].
+enum Build {
+    /// Once page attaches primary view.
+    Canvas(Box<dyn Fn(AppId, OffscreenCanvas)>),
+    /// Once handshake is complete.
+    Headless(Box<dyn Fn(AppId)>),
+    /// Prepared once handshake is complete and finished once page attaches primary view.
+    Warm {
+        prepare: Box<dyn Fn(AppId) -> App>,
+        attach: Box<dyn Fn(AppId, App, OffscreenCanvas)>,
+    },
+}
+
+/// App which is being updated right now, or which handles the current page message.
+pub fn current_app() -> AppId {
+    CURRENT_APP.with(Cell::get)
+}
+
+// Adapted from https://github.com/thedodd/trunk/blob/master/examples/webworker/src/bin/worker.rs
+#[cfg(not(feature = "mock-page"))]
+fn listen(build: Build) {
+    use wasm_bindgen::prelude::JsCast;
+    use web_sys::MessageEvent;
+
+    let onmessage = Closure::wrap(Box::new(move |event: MessageEvent| {
+        PAGE_CONNECTED.with(|connected| connected.set(true));
+        receive(&build, event.data());
+    }) as Box<dyn FnMut(MessageEvent)>);
+
+    let onmessageerror = Closure::wrap(Box::new(|_: MessageEvent| {
+        bridge_error(BridgeError::Serialization {
+            kind: None,
+            reason: "message from page could not be deserialized".to_owned(),
+        });
+        wake();
+    }) as Box<dyn FnMut(MessageEvent)>);
+
+    let scope = scope();
+    scope.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+    scope.set_onmessageerror(Some(onmessageerror.as_ref().unchecked_ref()));
+    onmessage.forget();
+    onmessageerror.forget();
+    watch_gpu_devices();
+
+    // The worker must send a message to indicate that it's ready to receive messages.
+    scope
+        .post_message(&ready_message())
+        .expect("posting ready message succeeds");
+}
+
+/// Threads are spawned before worker reports readiness, so page learns how many there are.
+#[cfg(not(feature = "mock-page"))]
+fn ready_message() -> JsValue {
+    let (ready, _) = WorkerMessage::Ready {
+        threads: threads::thread_count(),
+        capabilities: capabilities::worker_capabilities(),
+    }
+    .encode()
+    .expect("ready message has no payload");
+
+    ready
+}
+
+/// Handle message from the page, one of the tabs for shared workers, or the mock page.
+fn receive(build: &Build, data: JsValue) {
+    let decode_start = precise_now();
+    let msg = HostMessage::decode(&data);
+    let decode_ms = precise_now() - decode_start;
+    METRICS.with(|metrics| metrics.borrow_mut().decoding.record(decode_ms));
+    traffic_log::record(
+        TrafficDirection::Received,
+        msg.as_ref().map(HostMessage::kind),
+        &data,
+    );
+
+    let Some(msg) = msg else {
+        warn!("received malformed message from host");
+        return;
+    };
+    let envelope = Envelope::read(&data);
+    let app = AppId::read(&data);
+
+    let running = APPS.with(|apps| apps.borrow().contains_key(&app));
+
+    // Scheduling concerns the worker rather than the app, so it can't wait for app's next frame.
+    // App still gets the message to stop rendering the view.
+    if let HostMessage::ViewIntersection { view, visible, .. } = &msg {
+        if scheduler::set_view_visible(app, *view, *visible) {
+            hurry(app);
+        }
+    }
+
+    // App waiting for animation frame would only see the new mode on the next one.
+    if let HostMessage::SetUpdateMode(_) = &msg {
+        hurry(app);
+    }
+
+    match msg {
+        HostMessage::Shutdown => shutdown(),
+        HostMessage::Ports(ports) => open_ports(ports),
+        HostMessage::FrameClock(port) => open_frame_clock(port.into_inner()),
+        HostMessage::BootFlags(flags) => boot::set_boot_flags(flags),
+        HostMessage::Capabilities(report) => {
+            capabilities::set_report(report);
+
+            // Capabilities are the last part of the handshake.
+            match build {
+                Build::Headless(build) if !running => {
+                    CURRENT_APP.with(|cell| cell.set(app));
+                    build(app);
+                }
+                Build::Warm { prepare, .. }
+                    if !running && WARM_APPS.with(|warm| !warm.borrow().contains_key(&app)) =>
+                {
+                    CURRENT_APP.with(|cell| cell.set(app));
+                    let prepared = prepare(app);
+                    WARM_APPS.with(|warm| warm.borrow_mut().insert(app, prepared));
+                }
+                _ => (),
+            }
+        }
+        HostMessage::SetBudgetShare(share) => scheduler::set_share(app, share),
+        HostMessage::Pause => pause(app),
+        HostMessage::Resume => resume(app),
+        HostMessage::StepFrames(frames) => step_frames(app, frames),
+        HostMessage::Pong { seq, page_time } => bridge_diagnostics::pong(app, seq, page_time),
+        HostMessage::SetLatencyProbe(enabled) => latency::set_probe(app, enabled),
+        HostMessage::Attach {
+            view: ViewId::PRIMARY,
+            canvas,
+        } if !running && !matches!(build, Build::Headless(_)) => {
+            let canvas = canvas.into_inner();
+            watch_context(app, &canvas);
+            // App rebuilt after losing its device gets persistent entities back on its first frame.
+            if let Some(msg) = upgrade::kept(app) {
+                INBOX.with(|inbox| {
+                    inbox.borrow_mut().push_back(Inbound {
+                        app,
+                        msg,
+                        envelope: None,
+                        received_at: js_sys::Date::now(),
+                    })
+                });
+            }
+            CURRENT_APP.with(|cell| cell.set(app));
+            match build {
+                Build::Canvas(build) => build(app, canvas),
+                Build::Warm { prepare, attach } => {
+                    let prepared = WARM_APPS.with(|warm| warm.borrow_mut().remove(&app));
+                    let prepared = prepared.unwrap_or_else(|| prepare(app));
+                    attach(app, prepared, canvas);
+                }
+                Build::Headless(_) => unreachable!(),
+            }
+        }
+        msg => {
+            if let HostMessage::Attach { canvas, .. } = &msg {
+                if let Some(canvas) = canvas.get() {
+                    watch_context(app, canvas);
+                }
+            }
+
+            INBOX.with(|inbox| {
+                let mut inbox = inbox.borrow_mut();
+
+                // Input piling up while app is busy is merged, so it catches up sooner.
+                // Merged message is as new as the latest one, so it takes over its envelope too.
+                let msg = match inbox.back_mut() {
+                    Some(last) if last.app == app => match last.msg.coalesce(msg) {
+                        Ok(()) => {
+                            last.envelope = envelope;
+                            last.received_at = js_sys::Date::now();
+                            METRICS.with(|metrics| metrics.borrow_mut().coalesced += 1);
+                            return;
+                        }
+                        Err(msg) => msg,
+                    },
+                    _ => msg,
+                };
+
+                inbox.push_back(Inbound {
+                    app,
+                    msg,
+                    envelope,
+                    received_at: js_sys::Date::now(),
+                });
+            });
+            wake_app(app);
+        }
+    }
+}
+
+/// Listen to subsystem channels page created and post over them from now on.
+fn open_ports(ports: Vec<(Port, Transferable<MessagePort>)>) {
+    // Ports share handlers with the worker itself, messages carry everything needed to route them.
+    let scope = scope();
+    let onmessage = scope.onmessage();
+    let onmessageerror = scope.onmessageerror();
+
+    PORTS.with(|open| {
+        let mut open = open.borrow_mut();
+
+        for (port, channel) in ports {
+            let channel = channel.into_inner();
+            channel.set_onmessage(onmessage.as_ref());
+            channel.set_onmessageerror(onmessageerror.as_ref());
+
+            if let Some(previous) = open.insert(port, channel) {
+                previous.close();
+            }
+        }
+    });
+}
+
+/// Page's end of frame clock channel, see [`HostMessage::FrameClock`].
+struct FrameClock {
+    port: MessagePort,
+    _onmessage: Closure<dyn FnMut(web_sys::MessageEvent)>,
+}
+
+impl Drop for FrameClock {
+    fn drop(&mut self) {
+        self.port.set_onmessage(None);
+        self.port.close();
+    }
+}
+
+fn open_frame_clock(port: MessagePort) {
+    use wasm_bindgen::prelude::JsCast;
+
+    let onmessage =
+        Closure::wrap(Box::new(|_| animation_frame()) as Box<dyn FnMut(web_sys::MessageEvent)>);
+    port.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+
+    let previous = FRAME_CLOCK.with(|clock| {
+        clock.borrow_mut().replace(FrameClock {
+            port,
+            _onmessage: onmessage,
+        })
+    });
+    drop(previous);
+}
+
+/// Page got an animation frame, update apps waiting for it.
+fn animation_frame() {
+    let now = js_sys::Date::now();
+
+    let woken = APPS.with(|apps| {
+        let mut woken = false;
+
+        for driver in apps.borrow_mut().values_mut() {
+            // Throttled app skips frames until it is allowed to update again.
+            if driver
+                .awaiting_frame
+                .map_or(false, |not_before| not_before <= now)
+            {
+                driver.awaiting_frame = None;
+                driver.due_at = Some(now);
+                woken = true;
+            }
+        }
+
+        woken
+    });
+
+    if woken {
+        reschedule();
+    }
+}
+
+/// Tear app down when graphics context of the canvas is lost.
+///
+/// Bevy cannot rebuild renderer on the fly, so the whole app goes, other apps of the worker stay.
+/// Only its persistent entities are kept for the app rebuilt on a fresh canvas.
+/// Page is notified with [`WorkerMessage::DeviceLost`] and is expected to attach a fresh canvas.
+fn watch_context(app: AppId, canvas: &OffscreenCanvas) {
+    use wasm_bindgen::prelude::JsCast;
+    use web_sys::Event;
+
+    let oncontextlost = Closure::wrap(Box::new(move |_: Event| {
+        device_lost(app);
+    }) as Box<dyn Fn(Event)>);
+
+    if let Err(err) = canvas.add_event_listener_with_callback(
+        "webglcontextlost",
+        oncontextlost.as_ref().unchecked_ref(),
+    ) {
+        warn!("failed to watch for lost graphics context: {err:?}");
+    }
+    oncontextlost.forget();
+}
+
+/// Watch WebGPU devices for loss, the way [`watch_context`] does for WebGL2.
+///
+/// wgpu doesn't expose `GPUDevice.lost`, so `navigator.gpu` is patched to catch devices as apps request them.
+/// Adapter is requested while app is being built, which tells the app device belongs to.
+#[cfg(not(feature = "mock-page"))]
+fn watch_gpu_devices() {
+    use js_sys::Reflect;
+
+    let navigator = Reflect::get(&js_sys::global(), &"navigator".into());
+    let gpu = match navigator.and_then(|navigator| Reflect::get(&navigator, &"gpu".into())) {
+        Ok(gpu) if !gpu.is_undefined() => gpu,
+        _ => return,
+    };
+
+    let patched = patch_method(&gpu, "requestAdapter", |request_adapter, options| {
+        let app = current_app();
+        let adapter = call_promise(request_adapter, &options);
+
+        on_resolve(&adapter, move |adapter| {
+            if adapter.is_null() {
+                return;
+            }
+
+            let patched = patch_method(&adapter, "requestDevice", move |request_device, desc| {
+                let device = call_promise(request_device, &desc);
+                on_resolve(&device, move |device| watch_device(app, &device));
+                device.into()
+            });
+            if let Err(err) = patched {
+                warn!("failed to watch for lost GPU device: {err:?}");
+            }
+        });
+
+        adapter.into()
+    });
+    if let Err(err) = patched {
+        warn!("failed to watch for lost GPU device: {err:?}");
+    }
+}
+
+#[cfg(not(feature = "mock-page"))]
+fn watch_device(app: AppId, device: &JsValue) {
+    use js_sys::{Promise, Reflect};
+    use wasm_bindgen::prelude::JsCast;
+
+    let Ok(lost) = Reflect::get(device, &"lost".into()).and_then(JsCast::dyn_into::<Promise>)
+    else {
+        return;
+    };
+
+    on_resolve(&lost, move |info| {
+        // App being torn down destroys its device on purpose.
+        let reason = Reflect::get(&info, &"reason".into()).ok();
+        if reason.and_then(|reason| reason.as_string()).as_deref() == Some("destroyed") {
+            return;
+        }
+
+        device_lost(app);
+    });
+}
+
+/// Replace `target[name]` with `wrap`, which gets the original method bound to `target`.
+#[cfg(not(feature = "mock-page"))]
+fn patch_method(
+    target: &JsValue,
+    name: &str,
+    mut wrap: impl FnMut(&js_sys::Function, JsValue) -> JsValue + 'static,
+) -> Result<(), JsValue> {
+    use js_sys::{Function, Reflect};
+    use wasm_bindgen::prelude::JsCast;
+
+    let original: Function = Reflect::get(target, &name.into())?.dyn_into()?;
+    let original = original.bind(target);
+
+    let patched =
+        Closure::wrap(Box::new(move |arg: JsValue| wrap(&original, arg))
+            as Box<dyn FnMut(JsValue) -> JsValue>);
+    Reflect::set(target, &name.into(), patched.as_ref())?;
+    patched.forget();
+
+    Ok(())
+}
+
+#[cfg(not(feature = "mock-page"))]
+fn call_promise(f: &js_sys::Function, arg: &JsValue) -> js_sys::Promise {
+    use js_sys::Promise;
+
+    match f.call1(&JsValue::UNDEFINED, arg) {
+        Ok(value) => Promise::resolve(&value),
+        Err(err) => Promise::reject(&err),
+    }
+}
+
+/// Run `f` once promise resolves, leaving promise itself to whoever else awaits it.
+#[cfg(not(feature = "mock-page"))]
+fn on_resolve(promise: &js_sys::Promise, f: impl FnOnce(JsValue) + 'static) {
+    let resolved = Closure::once(f);
+    // Rejection is handled by the other side, this only keeps it from being reported twice.
+    let rejected = Closure::once(|_: JsValue| ());
+    let _ = promise.then2(&resolved, &rejected);
+    resolved.forget();
+    rejected.forget();
+}
+
+/// Tear down app which lost its graphics device and let page know.
+///
+/// App is rebuilt once page attaches a fresh canvas,
+/// its [`Persistent`](upgrade::Persistent) entities are carried over, see [`upgrade::keep`].
+fn device_lost(app: AppId) {
+    // Canvas may have been detached from the app already, we only care while it is running.
+    let running = APPS.with(|apps| match apps.borrow_mut().get_mut(&app) {
+        Some(driver) => {
+            upgrade::keep(app, &mut driver.app.world);
+            true
+        }
+        None => false,
+    });
+    if !running {
+        return;
+    }
+
+    teardown(app);
+    CURRENT_APP.with(|cell| cell.set(app));
+    post(&WorkerMessage::DeviceLost);
+}
+
+/// How often app gets updated.
+///
+/// Page can change it at runtime with [`HostMessage::SetTargetFps`]
+/// and [`HostMessage::SetUpdateMode`].
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FramePacing {
+    pub target_fps: u32,
+    /// In reactive mode systems can send [`RequestRedraw`]
+    /// to keep the app updating, e.g. while assets are loading or animations play.
+    pub mode: UpdateMode,
+}
+
+impl FramePacing {
+    fn frame_time_ms(&self) -> f64 {
+        1000.0 / self.target_fps.max(1) as f64
+    }
+}
+
+impl Default for FramePacing {
+    fn default() -> Self {
+        FramePacing {
+            target_fps: 60,
+            mode: UpdateMode::default(),
+        }
+    }
+}
+
+enum Tick {
+    /// Schedule next frame after this delay.
+    Continue { delay_ms: i32 },
+    /// Wait until something happens, but no longer than timeout.
+    Idle { timeout_ms: Option<i32> },
+    /// Wait for the next animation frame of the page.
+    AwaitFrame,
+    /// App wants to exit.
+    Exit,
+}
+
+struct Driver {
+    app: App,
+    initialized: bool,
+    frame: u64,
+    redraw_requests: ManualEventReader<RequestRedraw>,
+    /// When app should be updated next, `None` while idling without timeout.
+    due_at: Option<f64>,
+    /// Whether app waits for something to happen, see [`Tick::Idle`].
+    idle: bool,
+    /// Earliest time of the next update while app waits for animation frame, see [`Tick::AwaitFrame`].
+    awaiting_frame: Option<f64>,
+    /// Time update strategy app had before it was stepped, e.g. one of a replay, restored on resume.
+    stepped_from: Option<TimeUpdateStrategy>,
+}
+
+impl Driver {
+    fn new(app: App) -> Self {
+        Driver {
+            app,
+            initialized: false,
+            frame: 0,
+            redraw_requests: Default::default(),
+            due_at: Some(js_sys::Date::now()),
+            idle: false,
+            awaiting_frame: None,
+            stepped_from: None,
+        }
+    }
+
+    /// Advance app by one frame.
+    fn tick(&mut self) -> Tick {
+        use bevy::app::AppExit;
+
+        let start = js_sys::Date::now();
+
+        if !self.initialized {
+            // Renderer initializes asynchronously, we cannot block on it here.
+            if !self.app.ready() {
+                return Tick::Continue {
+                    delay_ms: self.delay_ms(start),
+                };
+            }
+
+            self.app.finish();
+            self.app.cleanup();
+            self.initialized = true;
+        }
+
+        let app = current_app();
+        let step = PAUSED.with(|paused| match paused.borrow_mut().get_mut(&app) {
+            None => Some(false),
+            Some(0) => None,
+            Some(steps) => {
+                *steps -= 1;
+                Some(true)
+            }
+        });
+        let Some(step) = step else {
+            // Resuming or stepping wakes it up.
+            return Tick::Idle { timeout_ms: None };
+        };
+
+        // Paused app missed the time that passed, it shouldn't show up as one long frame.
+        let resumed = RESUMED.with(|resumed| resumed.borrow_mut().remove(&app));
+        let step_time = if step {
+            if self.stepped_from.is_none() {
+                self.stepped_from = Some(self.app.world.remove_resource().unwrap_or_default());
+            }
+            // Steps of a replay keep its timestep, so it stays deterministic.
+            let step_time = match self.stepped_from {
+                Some(TimeUpdateStrategy::ManualDuration(duration)) => duration,
+                _ => Duration::from_secs_f64(self.pacing().frame_time_ms() / 1000.0),
+            };
+            self.app
+                .world
+                .insert_resource(TimeUpdateStrategy::ManualDuration(step_time));
+            Some(step_time)
+        } else {
+            if resumed {
+                if let Some(stepped_from) = self.stepped_from.take() {
+                    self.app.world.insert_resource(stepped_from);
+                }
+            }
+            None
+        };
+        let hold_time = resumed
+            && self
+                .app
+                .world
+                .get_resource::<Time>()
+                .map_or(false, |time| !time.is_paused());
+        if hold_time {
+            self.app.world.resource_mut::<Time>().pause();
+        }
+
+        // Messages handled during update nest under this span,
+        // which ties them to the frame that consumed them.
+        let span = info_span!("frame", frame = self.frame).entered();
+        let update_start = js_sys::Date::now();
+        INPUT_DRAIN.with(|drain| {
+            if let Some(drain) = drain.borrow_mut().get_mut(&current_app()) {
+                drain.taken = 0;
+            }
+        });
+        self.app.update();
+        LAST_UPDATE_MS.with(|cell| cell.set(Some(js_sys::Date::now() - update_start)));
+        // App switched strategies during the step, e.g. replay finished, so resuming should go with the new one.
+        if let Some(step_time) = step_time {
+            let switched = !matches!(
+                self.app.world.get_resource::<TimeUpdateStrategy>(),
+                Some(TimeUpdateStrategy::ManualDuration(duration)) if *duration == step_time
+            );
+            if switched {
+                self.stepped_from = self.app.world.remove_resource();
+            }
+        }
+        if hold_time {
+            self.app.world.resource_mut::<Time>().unpause();
+        }
+        latency::frame_submitted(current_app());
+
+        if !drain_input(current_app()) {
+            let deferred = INBOX.with(|inbox| {
+                inbox
+                    .borrow()
+                    .iter()
+                    .filter(|inbound| {
+                        inbound.app == current_app() && inbound.msg.port() == Port::Input
+                    })
+                    .count()
+            });
+            METRICS.with(|metrics| metrics.borrow_mut().deferred += deferred as u64);
+        }
+        drop_unclaimed(current_app(), self.unclaimed_timeout());
+        self.frame += 1;
+        drop(span);
+
+        let exit = self
+            .app
+            .world
+            .get_resource::<Events<AppExit>>()
+            .map_or(false, |events| !events.is_empty());
+
+        if exit {
+            return Tick::Exit;
+        }
+
+        let redraw_requested = match self.app.world.get_resource::<Events<RequestRedraw>>() {
+            Some(events) => self.redraw_requests.iter(events).count() > 0,
+            None => false,
+        };
+        // Some messages could be deferred until next frame.
+        let redraw_requested = redraw_requested || {
+            let app = current_app();
+            INBOX.with(|inbox| inbox.borrow().iter().any(|inbound| inbound.app == app))
+        };
+
+        match self.pacing().mode {
+            UpdateMode::Reactive { max_wait_ms } if !redraw_requested => Tick::Idle {
+                timeout_ms: max_wait_ms.map(|ms| ms as i32),
+            },
+            // Page turning vsync off wants frames as fast as target frame rate allows.
+            UpdateMode::AnimationFrame if self.vsync() => Tick::AwaitFrame,
+            _ => Tick::Continue {
+                delay_ms: self.delay_ms(start),
+            },
+        }
+    }
+
+    fn pacing(&self) -> FramePacing {
+        self.app
+            .world
+            .get_resource::<FramePacing>()
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn unclaimed_timeout(&self) -> Duration {
+        self.app
+            .world
+            .get_resource::<UnclaimedTimeout>()
+            .map_or(UnclaimedTimeout::default().0, |timeout| timeout.0)
+    }
+
+    fn vsync(&self) -> bool {
+        self.app
+            .world
+            .get_resource::<present_mode::RequestedPresentMode>()
+            .map_or(true, present_mode::RequestedPresentMode::vsync)
+    }
+
+    /// Time left until next frame is due.
+    fn delay_ms(&self, frame_start: f64) -> i32 {
+        let frame_time = self.pacing().frame_time_ms();
+        let elapsed = js_sys::Date::now() - frame_start;
+
+        (frame_time - elapsed).max(0.0) as i32
+    }
+}
+
+/// Stop updating the app until it is resumed.
+fn pause(app: AppId) {
+    PAUSED.with(|paused| paused.borrow_mut().entry(app).or_insert(0));
+}
+
+/// Continue updating paused app.
+fn resume(app: AppId) {
+    if PAUSED
+        .with(|paused| paused.borrow_mut().remove(&app))
+        .is_some()
+    {
+        RESUMED.with(|resumed| resumed.borrow_mut().insert(app));
+        hurry(app);
+    }
+}
+
+/// Update paused app given number of frames.
+fn step_frames(app: AppId, frames: u32) {
+    let stepping = PAUSED.with(|paused| match paused.borrow_mut().get_mut(&app) {
+        Some(steps) => {
+            *steps += frames;
+            true
+        }
+        None => false,
+    });
+
+    if stepping {
+        hurry(app);
+    } else {
+        warn!("app {app:?} is not paused, so it can't be stepped");
+    }
+}
+
+/// Update idling apps as soon as possible.
+fn wake() {
+    wake_where(|_| true);
+}
+
+/// Update given app as soon as possible if it is idling.
+fn wake_app(app: AppId) {
+    wake_where(|id| id == app);
+}
+
+/// Update given app as soon as possible, even if it is throttled.
+fn hurry(app: AppId) {
+    let now = js_sys::Date::now();
+
+    let hurried = APPS.with(|apps| {
+        let mut apps = apps.borrow_mut();
+        let Some(driver) = apps.get_mut(&app) else {
+            return false;
+        };
+
+        driver.idle = false;
+        driver.awaiting_frame = None;
+        driver.due_at = Some(now);
+        true
+    });
+
+    if hurried {
+        reschedule();
+    }
+}
+
+fn wake_where(f: impl Fn(AppId) -> bool) {
+    let now = js_sys::Date::now();
+
+    let woken = APPS.with(|apps| {
+        let mut woken = false;
+
+        for (_, driver) in apps.borrow_mut().iter_mut().filter(|(id, _)| f(**id)) {
+            if std::mem::replace(&mut driver.idle, false) {
+                driver.due_at = Some(now);
+                woken = true;
+            }
+        }
+
+        woken
+    });
+
+    if woken {
+        reschedule();
+    }
+}
+
+/// Stop updating apps and release everything they hold.
+///
+/// Dropping an app takes render device and window surfaces with it,
+/// so page can safely terminate worker afterwards.
+/// Page is notified with [`WorkerMessage::ShutdownComplete`].
+pub fn shutdown() {
+    if let Some(timer) = TIMER.with(Cell::take) {
+        scope().clear_timeout_with_handle(timer);
+    }
+
+    let apps = APPS.with(|apps| std::mem::take(&mut *apps.borrow_mut()));
+    drop(apps);
+    let warm = WARM_APPS.with(|warm| std::mem::take(&mut *warm.borrow_mut()));
+    drop(warm);
+
+    let clock = FRAME_CLOCK.with(|clock| clock.borrow_mut().take());
+    drop(clock);
+
+    INBOX.with(|inbox| inbox.borrow_mut().clear());
+    post(&WorkerMessage::ShutdownComplete);
+}
+
+/// Drop the app together with messages waiting for it.
+fn teardown(app: AppId) {
+    // Dropping app runs arbitrary code, it must not find `APPS` borrowed.
+    let driver = APPS.with(|apps| apps.borrow_mut().remove(&app));
+    drop(driver);
+
+    INBOX.with(|inbox| inbox.borrow_mut().retain(|inbound| inbound.app != app));
+    PAUSED.with(|paused| paused.borrow_mut().remove(&app));
+    RESUMED.with(|resumed| resumed.borrow_mut().remove(&app));
+    reschedule();
+}
+
+/// Swap app's replayable messages waiting in inbox for given ones, see [`replay`].
+fn replace_replayable(app: AppId, messages: impl IntoIterator<Item = HostMessage>) {
+    INBOX.with(|inbox| {
+        let mut inbox = inbox.borrow_mut();
+
+        inbox.retain(|inbound| inbound.app != app || !inbound.msg.is_replayable());
+        inbox.extend(messages.into_iter().map(|msg| Inbound {
+            app,
+            msg,
+            envelope: None,
+            received_at: js_sys::Date::now(),
+        }));
+    });
+}
+
+/// Keep app updating on a timer.
+///
+/// Default runners either block the thread or expect `window` to be around,
+/// neither of which works inside a worker.
+/// Frames are paced according to [`FramePacing`],
+/// or follow page's animation frames in [`UpdateMode::AnimationFrame`].
+///
+/// All apps of the worker share a single timer, which updates whichever apps are due.
+#[derive(Default)]
+pub struct WorkerRunnerPlugin;
+
+impl Plugin for WorkerRunnerPlugin {
+    fn build(&self, app: &mut App) {
+        let mut pacing = FramePacing::default();
+        if let Some(fps) = boot::boot_flags().fps {
+            pacing.target_fps = fps;
+        }
+
+        app.insert_resource(pacing).set_runner(worker_runner);
+    }
+}
+
+fn worker_runner(app: App) {
+    let id = current_app();
+    let previous = APPS.with(|apps| apps.borrow_mut().insert(id, Driver::new(app)));
+    drop(previous);
+
+    // Closure is reused for every frame and across app restarts,
+    // it must not be dropped as it may be the one calling us.
+    TICK.with(|cell| {
+        cell.borrow_mut().get_or_insert_with(|| {
+            Closure::wrap(Box::new(|| {
+                TIMER.with(|cell| cell.set(None));
+                update_due();
+                reschedule();
+            }) as Box<dyn FnMut()>)
+        });
+    });
+
+    reschedule();
+}
+
+/// Update every app whose next frame is due, as far as the round budget goes.
+///
+/// Apps left over are updated next round, after the event loop had a chance to deliver messages.
+fn update_due() {
+    let now = js_sys::Date::now();
+    let (due, running): (Vec<_>, Vec<_>) = APPS.with(|apps| {
+        let apps = apps.borrow();
+        let due = apps
+            .iter()
+            .filter(|(_, driver)| driver.due_at.map_or(false, |at| at <= now))
+            .map(|(id, _)| *id)
+            .collect();
+
+        (due, apps.keys().copied().collect())
+    });
+
+    for (i, id) in scheduler::order(due).into_iter().enumerate() {
+        // At least one app makes progress every round, however slow it is.
+        if i > 0 && !scheduler::round_has_time(now) {
+            break;
+        }
+
+        // App is taken out while it updates, so systems waking other apps don't find `APPS` borrowed.
+        let Some(mut driver) = APPS.with(|apps| apps.borrow_mut().remove(&id)) else {
+            continue;
+        };
+
+        CURRENT_APP.with(|cell| cell.set(id));
+
+        let tick = driver.tick();
+        let update_ms = last_update_ms().unwrap_or(0.0);
+        let next_due = |due_at| scheduler::next_due(id, update_ms, due_at, &running);
+
+        driver.awaiting_frame = None;
+
+        match tick {
+            Tick::Continue { delay_ms } => {
+                driver.idle = false;
+                driver.due_at = Some(next_due(js_sys::Date::now() + delay_ms as f64));
+            }
+            // Page messages will wake it up earlier.
+            Tick::Idle { timeout_ms } => {
+                driver.idle = true;
+                driver.due_at = timeout_ms.map(|ms| next_due(js_sys::Date::now() + ms as f64));
+            }
+            // Messages wait for the frame like everything else.
+            Tick::AwaitFrame => {
+                driver.idle = false;
+                driver.due_at = None;
+                driver.awaiting_frame = Some(next_due(js_sys::Date::now()));
+            }
+            Tick::Exit => {
+                drop(driver);
+                teardown(id);
+
+                if APPS.with(|apps| apps.borrow().is_empty()) {
+                    shutdown();
+                }
+
+                continue;
+            }
+        }
+
+        APPS.with(|apps| apps.borrow_mut().insert(id, driver));
+    }
+}
+
+/// Set the timer for the earliest app due.
+fn reschedule() {
+    use wasm_bindgen::prelude::JsCast;
+
+    if let Some(timer) = TIMER.with(Cell::take) {
+        scope().clear_timeout_with_handle(timer);
+    }
+
+    let next = APPS.with(|apps| {
+        apps.borrow()
+            .values()
+            .filter_map(|driver| driver.due_at)
+            .reduce(f64::min)
+    });
+    let Some(next) = next else {
+        return;
+    };
+    let delay_ms = (next - js_sys::Date::now()).max(0.0) as i32;
+
+    TICK.with(|cell| {
+        let Some(tick) = cell.borrow().as_ref() else {
+            return;
+        };
+
+        let timer = scope()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(
+                tick.as_ref().unchecked_ref(),
+                delay_ms,
+            )
+            .expect("setting timeout succeeds");
+        TIMER.with(|cell| cell.set(Some(timer)));
+    });
+}
+
+/// Query primary window and set up the handle to it so rendering can pick it up.
+///
+/// Normally this job is done by WinitPlugin, however it is hopelessly broken for web workers.
+/// We definitely don't do everything that we need to, but this is enough to get us rendering.
+///
+/// Window starts at the size of canvas drawing buffer, page reports changes to it afterwards.
+///
+/// Secondary views are registered by [`HostBridgePlugin`] as page attaches them.
+#[derive(Default)]
+pub struct RegisterPrimaryWindow;
+
+impl Plugin for RegisterPrimaryWindow {
+    fn build(&self, app: &mut App) {
+        use bevy::ecs::system::SystemState;
+
+        #[allow(clippy::type_complexity)]
+        let mut system_state: SystemState<(
+            Commands,
+            Query<(Entity, &mut Window, With<PrimaryWindow>)>,
+        )> = SystemState::from_world(&mut app.world);
+        let (mut commands, mut query) = system_state.get_mut(&mut app.world);
+
+        let (entity, mut window, _) = query.get_single_mut().unwrap();
+
+        let handle: AbstractHandleWrapper = {
+            let canvas = match &window.web_element {
+                WebElement::OffscreenCanvas(canvas) => canvas.clone(),
+                // Ignore other options.
+                _ => unreachable!(),
+            };
+            window
+                .resolution
+                .set_physical_resolution(canvas.width().max(1), canvas.height().max(1));
+
+            AbstractHandleWrapper::WebHandle(WebHandle::OffscreenCanvas(canvas))
+        };
+
+        commands.entity(entity).insert(handle);
+        system_state.apply(&mut app.world);
+
+        let mut views = Views::default();
+        views.windows.insert(ViewId::PRIMARY, entity);
+        app.insert_resource(views);
+    }
+}
+
+/// Window entities backing views page attached so far.
+///
+/// Use it to point cameras at secondary canvases.
+#[derive(Resource, Default)]
+pub struct Views {
+    windows: HashMap<ViewId, Entity>,
+}
+
+impl Views {
+    /// Window entity backing the view, if page ever attached it.
+    pub fn window(&self, view: ViewId) -> Option<Entity> {
+        self.windows.get(&view).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ViewId, Entity)> + '_ {
+        self.windows.iter().map(|(view, entity)| (*view, *entity))
+    }
+}
+
+/// What worker knows about the page.
+#[derive(Resource, Debug, Clone)]
+pub struct PageState {
+    /// Whether there is a page on the other side of the bridge.
+    ///
+    /// Starts out `false` and turns on once page says anything.
+    pub connected: bool,
+    /// Whether page is currently visible to the user.
+    pub visible: bool,
+}
+
+impl Default for PageState {
+    fn default() -> Self {
+        PageState {
+            connected: false,
+            visible: true,
+        }
+    }
+}
+
+/// Run condition: page is on the other side of the bridge.
+pub fn bridge_connected() -> impl FnMut(Res<PageState>) -> bool + Clone {
+    |page: Res<PageState>| page.connected
+}
+
+/// Run condition: page is visible to the user.
+pub fn page_visible() -> impl FnMut(Res<PageState>) -> bool + Clone {
+    |page: Res<PageState>| page.visible
+}
+
+/// Run condition: primary view has a canvas to render to.
+#[allow(clippy::type_complexity)]
+pub fn canvas_attached(
+) -> impl FnMut(Query<(), (With<PrimaryWindow>, With<AbstractHandleWrapper>)>) -> bool + Clone {
+    |window: Query<(), (With<PrimaryWindow>, With<AbstractHandleWrapper>)>| !window.is_empty()
+}
+
+/// Systems applying messages received from the page.
+///
+/// Runs in [`First`] by default.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BridgeReceive;
+
+/// Systems feeding page input into Bevy's input resources.
+///
+/// Runs in [`PreUpdate`] before [`InputSystem`](bevy::input::InputSystem) by default.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InputInject;
+
+/// Systems posting messages back to the page.
+///
+/// Runs in [`Last`] by default.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BridgeSend;
+
+/// Schedules hosting bridge system sets.
+///
+/// Inserted by [`HostBridgePlugin`], so plugins adding their own bridge systems can follow its placement.
+#[derive(Resource, Debug, Clone)]
+pub struct BridgeSchedules {
+    /// Schedule running [`BridgeReceive`].
+    pub receive: BoxedScheduleLabel,
+    /// Schedule running [`InputInject`].
+    pub input: BoxedScheduleLabel,
+    /// Schedule running [`BridgeSend`].
+    pub send: BoxedScheduleLabel,
+}
+
+impl BridgeSchedules {
+    /// Schedules configured for the app, or defaults if bridge isn't there.
+    pub fn of(app: &App) -> Self {
+        app.world
+            .get_resource::<BridgeSchedules>()
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl Default for BridgeSchedules {
+    fn default() -> Self {
+        BridgeSchedules {
+            receive: Box::new(First),
+            input: Box::new(PreUpdate),
+            send: Box::new(Last),
+        }
+    }
+}
+
+/// Apply messages page sent after the app has started.
+///
+/// Bridge systems are grouped into [`BridgeReceive`], [`InputInject`] and [`BridgeSend`] sets,
+/// order your own systems relative to those.
+/// Schedules hosting each set can be changed through corresponding methods.
+///
+/// Messages which fail to cross the bridge in either direction are reported as [`BridgeError`] events.
+pub struct HostBridgePlugin {
+    schedules: BridgeSchedules,
+    input_budget: usize,
+    unclaimed_timeout: Duration,
+}
+
+impl Default for HostBridgePlugin {
+    fn default() -> Self {
+        HostBridgePlugin {
+            schedules: BridgeSchedules::default(),
+            input_budget: 256,
+            unclaimed_timeout: UnclaimedTimeout::default().0,
+        }
+    }
+}
+
+impl HostBridgePlugin {
+    /// Handle at most this many input messages per frame, the rest waits for the next one.
+    ///
+    /// Keeps a burst of input from stalling a frame, other messages are not limited.
+    /// Consecutive pointer moves and resizes are merged while they wait,
+    /// see [`BridgeMetrics::coalesced`] and [`BridgeMetrics::deferred`].
+    pub fn input_budget(mut self, messages: usize) -> Self {
+        self.input_budget = messages;
+        self
+    }
+
+    /// Drop messages no system claimed for this long, see [`BridgeMetrics::unclaimed`].
+    ///
+    /// Messages for systems which run rarely, e.g. in [`receive_in`](Self::receive_in) schedule
+    /// or behind run conditions, need it longer than that.
+    pub fn unclaimed_timeout(mut self, timeout: Duration) -> Self {
+        self.unclaimed_timeout = timeout;
+        self
+    }
+
+    /// Schedule to run [`BridgeReceive`] in.
+    pub fn receive_in(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedules.receive = Box::new(schedule);
+        self
+    }
+
+    /// Schedule to run [`InputInject`] in.
+    ///
+    /// The set is always ordered before [`InputSystem`](bevy::input::InputSystem),
+    /// so make sure the schedule runs it.
+    pub fn input_in(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedules.input = Box::new(schedule);
+        self
+    }
+
+    /// Schedule to run [`BridgeSend`] in.
+    pub fn send_in(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedules.send = Box::new(schedule);
+        self
+    }
+}
+
+impl Plugin for HostBridgePlugin {
+    fn build(&self, app: &mut App) {
+        use bevy::input::InputSystem;
+
+        let schedules = self.schedules.clone();
+
+        INPUT_DRAIN.with(|drain| {
+            drain.borrow_mut().insert(
+                current_app(),
+                InputDrain {
+                    budget: self.input_budget,
+                    taken: 0,
+                },
+            )
+        });
+
+        // Registered by `WindowPlugin` as well, which headless apps don't have.
+        app.add_event::<WindowClosed>()
+            .init_resource::<Views>()
+            .init_resource::<PageState>()
+            .init_resource::<FramePacing>()
+            .init_resource::<BridgeMetrics>()
+            .insert_resource(UnclaimedTimeout(self.unclaimed_timeout))
+            .add_event::<BridgeError>()
+            .configure_set(schedules.receive.clone(), BridgeReceive)
+            .configure_set(
+                schedules.input.clone(),
+                InputInject.after(BridgeReceive).before(InputSystem),
+            )
+            .configure_set(schedules.send.clone(), BridgeSend.after(BridgeReceive))
+            .add_systems(
+                schedules.receive.clone(),
+                (
+                    receive_view_messages,
+                    receive_page_messages,
+                    deliver_bridge_errors,
+                )
+                    .in_set(BridgeReceive),
+            )
+            .add_systems(schedules.send.clone(), publish_metrics.in_set(BridgeSend))
+            .insert_resource(schedules);
+    }
+}
+
+/// How long messages wait for a system to claim them, see [`HostBridgePlugin::unclaimed_timeout`].
+#[derive(Resource)]
+struct UnclaimedTimeout(Duration);
+
+impl Default for UnclaimedTimeout {
+    fn default() -> Self {
+        UnclaimedTimeout(Duration::from_secs(5))
+    }
+}
+
+/// Input messages app handled during current frame.
+struct InputDrain {
+    budget: usize,
+    taken: usize,
+}
+
+/// Message waiting in the inbox.
+struct Inbound {
+    app: AppId,
+    msg: HostMessage,
+    envelope: Option<Envelope>,
+    received_at: f64,
+}
+
+/// Take messages of the current app `f` accepts out of inbox, leaving the rest in place.
+///
+/// Each message is handled inside a span carrying its correlation id.
+pub(crate) fn take_messages<T>(mut f: impl FnMut(HostMessage) -> Result<T, HostMessage>) -> Vec<T> {
+    INBOX.with(|inbox| {
+        let mut inbox = inbox.borrow_mut();
+        let mut taken = Vec::new();
+        let mut rest = VecDeque::with_capacity(inbox.len());
+        let app = current_app();
+
+        for inbound in inbox.drain(..) {
+            if inbound.app != app {
+                rest.push_back(inbound);
+                continue;
+            }
+
+            let input = inbound.msg.port() == Port::Input;
+            if input && !drain_input(app) {
+                rest.push_back(inbound);
+                continue;
+            }
+
+            let Inbound {
+                app,
+                msg,
+                envelope,
+                received_at,
+            } = inbound;
+            let kind = msg.kind();
+            let _span = info_span!(
+                "bridge_receive",
+                kind,
+                correlation_id = envelope.map(|envelope| envelope.id.0)
+            )
+            .entered();
+
+            let sent_at = envelope.map_or(received_at, |envelope| envelope.sent_at);
+            let recorded = replay::encode(app, &msg);
+            HANDLED_SENT_AT.with(|cell| cell.set(Some(sent_at)));
+            let result = f(msg);
+            HANDLED_SENT_AT.with(|cell| cell.set(None));
+
+            match result {
+                Ok(t) => {
+                    let latency_ms = js_sys::Date::now() - sent_at;
+                    METRICS.with(|metrics| {
+                        metrics.borrow_mut().record_received(
+                            kind,
+                            envelope.map(|envelope| envelope.id),
+                            latency_ms,
+                        )
+                    });
+
+                    if input {
+                        INPUT_DRAIN.with(|drain| {
+                            if let Some(drain) = drain.borrow_mut().get_mut(&app) {
+                                drain.taken += 1;
+                            }
+                        });
+
+                        if let Some(envelope) = envelope {
+                            latency::handled_input(app, envelope.id);
+                        }
+                    }
+
+                    if let Some(recorded) = recorded {
+                        replay::record(app, recorded);
+                    }
+
+                    taken.push(t);
+                }
+                Err(msg) => rest.push_back(Inbound {
+                    app,
+                    msg,
+                    envelope,
+                    received_at,
+                }),
+            }
+        }
+
+        *inbox = rest;
+        taken
+    })
+}
+
+/// Whether app has input budget left this frame.
+fn drain_input(app: AppId) -> bool {
+    INPUT_DRAIN.with(|drain| {
+        drain
+            .borrow()
+            .get(&app)
+            .map_or(true, |drain| drain.taken < drain.budget)
+    })
+}
+
+/// Drop messages nobody claimed in time, e.g. ones meant for plugins app doesn't have.
+///
+/// Left in the inbox they would pile up and keep reactive apps updating for nothing.
+/// Systems claiming messages may skip frames, e.g. behind run conditions or in a schedule
+/// which doesn't run every frame, so messages get time rather than a number of frames.
+fn drop_unclaimed(app: AppId, timeout: Duration) {
+    let input_budget_left = drain_input(app);
+    let received_before = js_sys::Date::now() - timeout.as_secs_f64() * 1000.0;
+
+    let dropped = INBOX.with(|inbox| {
+        expire(
+            &mut inbox.borrow_mut(),
+            app,
+            input_budget_left,
+            received_before,
+        )
+    });
+    for kind in &dropped {
+        debug!("no system handled `{kind}` message in time, dropping it");
+    }
+    METRICS.with(|metrics| metrics.borrow_mut().unclaimed += dropped.len() as u64);
+}
+
+/// Remove messages of the app received before given time, returns their kinds.
+///
+/// Input held back by the input budget stays until budget lets it through.
+fn expire(
+    inbox: &mut VecDeque<Inbound>,
+    app: AppId,
+    input_budget_left: bool,
+    received_before: f64,
+) -> Vec<&'static str> {
+    let mut expired = Vec::new();
+
+    inbox.retain(|inbound| {
+        let deferred = inbound.msg.port() == Port::Input && !input_budget_left;
+        if inbound.app != app || deferred || inbound.received_at >= received_before {
+            return true;
+        }
+
+        expired.push(inbound.msg.kind());
+        false
+    });
+
+    expired
+}
+
+/// Time at which page sent the message currently handled by [`take_messages`].
+///
+/// Reported by `Date.now()` on page side, compare it against the same clock.
+pub(crate) fn handled_sent_at() -> Option<f64> {
+    HANDLED_SENT_AT.with(Cell::get)
+}
+
+/// How long the latest [`App::update`] took, in ms, including rendering.
+pub(crate) fn last_update_ms() -> Option<f64> {
+    LAST_UPDATE_MS.with(Cell::get)
+}
+
+fn receive_view_messages(
+    mut commands: Commands,
+    mut views: ResMut<Views>,
+    mut windows: Query<(&mut Window, Option<&AbstractHandleWrapper>)>,
+    mut closed: EventWriter<WindowClosed>,
+) {
+    // Renderer processes window closures after picking up new windows,
+    // so old surface has to be gone for a frame before new canvas can take its place.
+    // To keep things simple every view is allowed to change at most once per frame,
+    // the rest is left for later.
+    let mut touched = HashSet::new();
+
+    take_messages(|msg| {
+        let view = match &msg {
+            HostMessage::Attach { view, .. } | HostMessage::Detach { view } => *view,
+            _ => return Err(msg),
+        };
+
+        if !touched.insert(view) {
+            return Err(msg);
+        }
+
+        let window = views
+            .window(view)
+            .and_then(|entity| Some((entity, windows.get_mut(entity).ok()?)));
+
+        match (msg, window) {
+            (HostMessage::Attach { canvas, .. }, None) => {
+                let canvas = canvas.into_inner();
+                let entity = commands
+                    .spawn((
+                        Window {
+                            resolution: WindowResolution::new(
+                                canvas.width() as f32,
+                                canvas.height() as f32,
+                            ),
+                            web_element: WebElement::OffscreenCanvas(canvas.clone()),
+                            ..Window::default()
+                        },
+                        AbstractHandleWrapper::WebHandle(WebHandle::OffscreenCanvas(canvas)),
+                    ))
+                    .id();
+
+                views.windows.insert(view, entity);
+            }
+            (msg @ HostMessage::Attach { .. }, Some((entity, (_, Some(_))))) => {
+                closed.send(WindowClosed { window: entity });
+                commands.entity(entity).remove::<AbstractHandleWrapper>();
+
+                // New canvas goes in next frame.
+                return Err(msg);
+            }
+            (HostMessage::Attach { canvas, .. }, Some((entity, (mut window, None)))) => {
+                let canvas = canvas.into_inner();
+                window
+                    .resolution
+                    .set_physical_resolution(canvas.width().max(1), canvas.height().max(1));
+                window.web_element = WebElement::OffscreenCanvas(canvas.clone());
+                commands
+                    .entity(entity)
+                    .insert(AbstractHandleWrapper::WebHandle(
+                        WebHandle::OffscreenCanvas(canvas),
+                    ));
+            }
+            (HostMessage::Detach { .. }, Some((entity, (_, Some(_))))) => {
+                closed.send(WindowClosed { window: entity });
+                commands.entity(entity).remove::<AbstractHandleWrapper>();
+            }
+            _ => (),
+        }
+
+        Ok(())
+    });
+}
+
+fn receive_page_messages(
+    mut page: ResMut<PageState>,
+    mut pacing: ResMut<FramePacing>,
+    mut time: Option<ResMut<Time>>,
+) {
+    let connected = PAGE_CONNECTED.with(Cell::get);
+    if page.connected != connected {
+        page.connected = connected;
+    }
+
+    take_messages(|msg| match msg {
+        HostMessage::Visibility { visible } => {
+            page.visible = visible;
+            Ok(())
+        }
+        HostMessage::SetTargetFps(fps) => {
+            pacing.target_fps = fps;
+            Ok(())
+        }
+        HostMessage::SetUpdateMode(mode) => {
+            pacing.mode = mode;
+            Ok(())
+        }
+        HostMessage::SetTimeScale(scale) => {
+            match &mut time {
+                // Time refuses to run backwards.
+                Some(time) if scale.is_finite() && scale >= 0.0 => time.set_relative_speed(scale),
+                Some(_) => warn!("time scale {scale} is not supported"),
+                None => (),
+            }
+            Ok(())
+        }
+        // Its only purpose is to wake the app up, which already happened.
+        HostMessage::RequestRedraw => Ok(()),
+        msg => Err(msg),
+    });
+}
+
+/// Post message to the page.
+pub(crate) fn post(msg: &WorkerMessage) {
+    let encode_start = precise_now();
+    let id = CorrelationId(NEXT_ID.with(|cell| cell.replace(cell.get().wrapping_add(1))));
+    let kind = msg.kind();
+    let _span = info_span!("bridge_send", kind, correlation_id = id.0).entered();
+
+    let (value, transfer) = match msg.encode() {
+        Ok(encoded) => encoded,
+        Err(err) => return bridge_error(err),
+    };
+    Envelope::new(id).stamp(&value);
+    current_app().stamp(&value);
+
+    if let Err(reason) = validate_transfer(&transfer) {
+        bridge_error(BridgeError::Serialization {
+            kind: Some(kind),
+            reason,
+        });
+        return;
+    }
+
+    // Transferred buffers are detached after posting, so their size has to be taken now.
+    traffic_log::record(TrafficDirection::Sent, Some(kind), &value);
+
+    let port = PORTS.with(|ports| ports.borrow().get(&msg.port()).cloned());
+    let result = match port {
+        Some(port) => port.post_message_with_transferable(&value, &transfer),
+        #[cfg(not(feature = "mock-page"))]
+        None if shared::is_shared() => shared::post(&value, &transfer),
+        None => scope().post_message_with_transfer(&value, &transfer),
+    };
+
+    match result {
+        Ok(()) => METRICS.with(|metrics| {
+            let mut metrics = metrics.borrow_mut();
+            metrics.record_sent(kind, id);
+            metrics.encoding.record(precise_now() - encode_start);
+        }),
+        Err(err) => bridge_error(BridgeError::serialization(Some(kind), &err)),
+    }
+}
+
+/// Report failure to the app, it is picked up as event next frame.
+fn bridge_error(err: BridgeError) {
+    warn!("{err}");
+    BRIDGE_ERRORS.with(|errors| errors.borrow_mut().push((current_app(), err)));
+}
+
+fn deliver_bridge_errors(mut events: EventWriter<BridgeError>) {
+    let app = current_app();
+    let errors: Vec<_> = BRIDGE_ERRORS.with(|errors| {
+        let mut errors = errors.borrow_mut();
+        let (ours, rest) = errors.drain(..).partition(|(id, _)| *id == app);
+        *errors = rest;
+        ours
+    });
+
+    events.send_batch(errors.into_iter().map(|(_, err)| err));
+}
+
+/// Per-message-type counters of bridge traffic.
+///
+/// Updated once per frame by [`HostBridgePlugin`].
+#[derive(Resource, Debug, Clone, Default)]
+pub struct BridgeMetrics {
+    received: HashMap<&'static str, MessageStats>,
+    sent: HashMap<&'static str, u64>,
+    recent: VecDeque<TrafficRecord>,
+    coalesced: u64,
+    deferred: u64,
+    unclaimed: u64,
+    decoding: CodecStats,
+    encoding: CodecStats,
+}
+
+/// Time spent turning messages into JS values or back.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CodecStats {
+    pub count: u64,
+    pub total_ms: f64,
+}
+
+impl CodecStats {
+    pub fn mean_ms(&self) -> f64 {
+        self.mean_ms_since(&CodecStats::default())
+    }
+
+    /// Mean time of messages counted after `earlier` snapshot was taken.
+    pub fn mean_ms_since(&self, earlier: &CodecStats) -> f64 {
+        let count = self.count - earlier.count;
+        if count == 0 {
+            0.0
+        } else {
+            (self.total_ms - earlier.total_ms) / count as f64
+        }
+    }
+
+    fn record(&mut self, ms: f64) {
+        self.count += 1;
+        self.total_ms += ms;
+    }
+}
+
+/// One message which went through the bridge.
+#[derive(Debug, Clone, Copy)]
+pub struct TrafficRecord {
+    pub direction: TrafficDirection,
+    pub kind: &'static str,
+    pub correlation_id: Option<CorrelationId>,
+    /// When message was handled or posted, by `Date.now()`.
+    pub at: f64,
+}
+
+/// Statistics of handled messages of one type.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessageStats {
+    pub count: u64,
+    /// Time from the page sending message to the app handling it, summed over all messages.
+    pub total_latency_ms: f64,
+    pub max_latency_ms: f64,
+}
+
+impl MessageStats {
+    pub fn mean_latency_ms(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.total_latency_ms / self.count as f64
+        }
+    }
+}
+
+impl BridgeMetrics {
+    /// Stats of handled messages of given kind.
+    pub fn received(&self, kind: &str) -> Option<&MessageStats> {
+        self.received.get(kind)
+    }
+
+    /// Number of messages of given kind posted to the page.
+    pub fn sent(&self, kind: &str) -> u64 {
+        self.sent.get(kind).copied().unwrap_or_default()
+    }
+
+    pub fn iter_received(&self) -> impl Iterator<Item = (&'static str, &MessageStats)> + '_ {
+        self.received.iter().map(|(kind, stats)| (*kind, stats))
+    }
+
+    pub fn iter_sent(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        self.sent.iter().map(|(kind, count)| (*kind, *count))
+    }
+
+    /// Number of messages merged into the one before them while waiting in the inbox.
+    ///
+    /// Counts merges inside the worker only, page coalesces on its side as well.
+    pub fn coalesced(&self) -> u64 {
+        self.coalesced
+    }
+
+    /// Number of times input message was left for the next frame because input budget ran out.
+    ///
+    /// Message waiting several frames is counted every time.
+    pub fn deferred(&self) -> u64 {
+        self.deferred
+    }
+
+    /// Number of messages dropped because no system handled them in time,
+    /// see [`HostBridgePlugin::unclaimed_timeout`].
+    pub fn unclaimed(&self) -> u64 {
+        self.unclaimed
+    }
+
+    /// Decoding of every message received from the page, malformed ones included.
+    pub fn decoding(&self) -> CodecStats {
+        self.decoding
+    }
+
+    /// Encoding and posting of every message successfully sent to the page.
+    pub fn encoding(&self) -> CodecStats {
+        self.encoding
+    }
+
+    /// Latest messages in both directions, oldest first.
+    pub fn recent(&self) -> impl Iterator<Item = &TrafficRecord> + '_ {
+        self.recent.iter()
+    }
+
+    fn record_received(
+        &mut self,
+        kind: &'static str,
+        correlation_id: Option<CorrelationId>,
+        latency_ms: f64,
+    ) {
+        let stats = self.received.entry(kind).or_default();
+        stats.count += 1;
+        stats.total_latency_ms += latency_ms;
+        stats.max_latency_ms = stats.max_latency_ms.max(latency_ms);
+
+        self.push_recent(TrafficDirection::Received, kind, correlation_id);
+    }
+
+    fn record_sent(&mut self, kind: &'static str, correlation_id: CorrelationId) {
+        *self.sent.entry(kind).or_default() += 1;
+
+        self.push_recent(TrafficDirection::Sent, kind, Some(correlation_id));
+    }
+
+    fn push_recent(
+        &mut self,
+        direction: TrafficDirection,
+        kind: &'static str,
+        correlation_id: Option<CorrelationId>,
+    ) {
+        const RECENT_LEN: usize = 64;
+
+        if self.recent.len() == RECENT_LEN {
+            self.recent.pop_front();
+        }
+
+        self.recent.push_back(TrafficRecord {
+            direction,
+            kind,
+            correlation_id,
+            at: js_sys::Date::now(),
+        });
+    }
+}
+
+fn publish_metrics(mut metrics: ResMut<BridgeMetrics>) {
+    METRICS.with(|cell| metrics.clone_from(&cell.borrow()));
+}
+
+/// Refreshed version of Bevy's default plugins, now with web-worker flavor.
+///
+/// Note: it isn't a faithful recreation of `DefaultPlugins` with all configs, it just works here.
+pub struct DefaultPlugins {
+    // `None` for headless apps.
+    primary_window: Option<WebElement>,
+}
+
+impl DefaultPlugins {
+    pub fn new(canvas: OffscreenCanvas) -> Self {
+        DefaultPlugins {
+            primary_window: Some(WebElement::OffscreenCanvas(canvas)),
+        }
+    }
+
+    /// Variant without windows, input and rendering, for apps started with [`start_headless`].
+    ///
+    /// Bridge, settings, save data, assets and peers are still there.
+    /// Apps started with [`start_warm`] get the rest from [`CanvasPlugins`] later.
+    pub fn headless() -> Self {
+        DefaultPlugins {
+            primary_window: None,
+        }
+    }
+}
+
+impl PluginGroup for DefaultPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        use bevy::diagnostic::{DiagnosticsPlugin, FrameTimeDiagnosticsPlugin};
+        use bevy::time::TimePlugin;
+
+        let windowed = self.primary_window.is_some();
+
+        let log_plugin = match boot::boot_flags().log_level {
+            Some(level) => profiling::ProfilingLogPlugin {
+                level,
+                ..profiling::ProfilingLogPlugin::default()
+            },
+            None => profiling::ProfilingLogPlugin::default(),
+        };
+
+        let mut group = PluginGroupBuilder::start::<Self>()
+            .add(log_plugin)
+            .add(TaskPoolPlugin::default())
+            .add(TypeRegistrationPlugin::default())
+            .add(TimePlugin::default())
+            .add(FrameCountPlugin::default())
+            .add(TransformPlugin::default())
+            .add(HierarchyPlugin::default())
+            .add(DiagnosticsPlugin::default())
+            .add(FrameTimeDiagnosticsPlugin)
+            .add(anomaly::AnomalyCapturePlugin::default());
+
+        if let Some(web_element) = self.primary_window {
+            group = add_window_plugins(group, web_element);
+        }
+
+        group = group
+            .add(HostBridgePlugin::default())
+            .add(boot::BootFlagsPlugin)
+            .add(capabilities::CapabilitiesPlugin)
+            .add(media::MediaPreferencesPlugin)
+            .add(sensors::MotionSensorsPlugin)
+            .add(threads::ThreadsPlugin)
+            .add(features::FeatureTogglesPlugin);
+
+        if windowed {
+            group = add_page_input_plugins(group);
+        }
+
+        group = group
+            .add(webrtc::WebRtcPlugin)
+            .add(peers::PeersPlugin)
+            .add(inmem::InMemoryAssetPlugin::default())
+            .add(save_data::SaveDataPlugin::default())
+            .add(settings::StoredSettingsPlugin)
+            .add(AssetPlugin::default());
+
+        if windowed {
+            group = add_render_plugins(group);
+        }
+
+        group = group
+            .add(dashboard::DashboardPlugin::default())
+            .add(frame_stats::FrameStatsPlugin::default())
+            .add(console::ConsolePlugin)
+            .add(memory::MemoryDiagnosticsPlugin::default())
+            .add(inspector::RemoteInspectorPlugin::default())
+            .add(scene::SceneBridgePlugin)
+            .add(upgrade::UpgradePlugin)
+            .add(replay::ReplayPlugin::default())
+            .add(traffic_log::TrafficLogPlugin::default())
+            .add(WorkerRunnerPlugin::default());
+
+        // Mock page makes up input for the canvas, headless app has neither.
+        #[cfg(feature = "mock-page")]
+        if windowed {
+            group = group.add(mock::MockPagePlugin);
+        }
+
+        group
+    }
+}
+
+/// Part of [`DefaultPlugins`] which needs a canvas: windows, input and rendering.
+///
+/// Meant for apps started with [`start_warm`], which are built from [`DefaultPlugins::headless`]
+/// before canvas arrives and get these once it does.
+pub struct CanvasPlugins {
+    primary_window: WebElement,
+}
+
+impl CanvasPlugins {
+    pub fn new(canvas: OffscreenCanvas) -> Self {
+        CanvasPlugins {
+            primary_window: WebElement::OffscreenCanvas(canvas),
+        }
+    }
+}
+
+impl PluginGroup for CanvasPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        let group = PluginGroupBuilder::start::<Self>();
+        let group = add_window_plugins(group, self.primary_window);
+        let group = add_page_input_plugins(group);
+        let group = add_render_plugins(group);
+
+        #[cfg(feature = "mock-page")]
+        let group = group.add(mock::MockPagePlugin);
+
+        group
+    }
+}
+
+fn add_window_plugins(group: PluginGroupBuilder, web_element: WebElement) -> PluginGroupBuilder {
+    use bevy::a11y::AccessibilityPlugin;
+    use bevy::input::InputPlugin;
+
+    let primary_window = Window {
+        web_element,
+        ..Window::default()
+    };
+
+    let window_plugin = WindowPlugin {
+        primary_window: Some(primary_window),
+        ..WindowPlugin::default()
+    };
+
+    group
+        .add(InputPlugin::default())
+        .add(window_plugin)
+        .add(AccessibilityPlugin)
+        .add(RegisterPrimaryWindow::default())
+}
+
+fn add_page_input_plugins(group: PluginGroupBuilder) -> PluginGroupBuilder {
+    group
+        .add(accessibility::AccessibilityBridgePlugin)
+        .add(input::PointerInputPlugin)
+        .add(keyboard::KeyboardPlugin)
+        .add(focus::FocusPlugin)
+        .add(pointer_lock::PointerLockPlugin)
+        .add(fullscreen::FullscreenPlugin)
+        .add(cursor::CursorPlugin)
+        .add(clipboard::ClipboardPlugin)
+        .add(ime::ImePlugin)
+        .add(file_drop::FileDropPlugin)
+}
+
+fn add_render_plugins(group: PluginGroupBuilder) -> PluginGroupBuilder {
+    use bevy::core_pipeline::CorePipelinePlugin;
+    use bevy::gizmos::GizmoPlugin;
+    use bevy::render::settings::{Backends, WgpuSettings};
+    use bevy::render::RenderPlugin;
+    use bevy::sprite::SpritePlugin;
+    use bevy::text::TextPlugin;
+    use bevy::ui::UiPlugin;
+
+    let backends = boot::boot_flags().backend.map(|backend| match backend {
+        GraphicsBackend::WebGpu => Backends::BROWSER_WEBGPU,
+        GraphicsBackend::WebGl2 => Backends::GL,
+    });
+
+    group
+        .add(RenderPlugin {
+            wgpu_settings: WgpuSettings {
+                backends: backends.or(WgpuSettings::default().backends),
+                ..default()
+            },
+        })
+        .add(first_frame::FirstFramePlugin)
+        .add(ImagePlugin::default())
+        .add(image_bitmap::ImageBitmapPlugin)
+        .add(CorePipelinePlugin)
+        .add(antialiasing::AntialiasingPlugin::default())
+        .add(graphics::GraphicsPlugin)
+        .add(present_mode::PresentModePlugin)
+        .add(depth::YSortPlugin)
+        .add(SpritePlugin::default())
+        .add(TextPlugin)
+        .add(UiPlugin)
+        .add(ui_scale::UiScalePlugin)
+        .add(offscreen::OffscreenPlugin::default())
+        .add(dom_anchor::DomAnchorPlugin)
+        .add(viewport::ViewportPlugin::default())
+        .add(GizmoPlugin)
+        .add(render_stats::RenderStatsPlugin::default())
+        .add(debug_draw::DebugDrawPlugin)
+        .add(preview::AssetPreviewPlugin)
+        .add(filters::FiltersPlugin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inbound(app: AppId, msg: HostMessage, received_at: f64) -> Inbound {
+        Inbound {
+            app,
+            msg,
+            envelope: None,
+            received_at,
+        }
+    }
+
+    #[test]
+    fn gated_consumer_gets_its_message() {
+        let timeout_ms = UnclaimedTimeout::default().0.as_secs_f64() * 1000.0;
+        let mut inbox =
+            VecDeque::from([inbound(AppId::DEFAULT, HostMessage::SetTargetFps(30), 0.0)]);
+
+        // Consumer behind a run condition sits out a second worth of frames.
+        for frame in 1..=60 {
+            let now = frame as f64 * 16.0;
+            assert!(expire(&mut inbox, AppId::DEFAULT, true, now - timeout_ms).is_empty());
+        }
+
+        // Once it runs, the message is still there.
+        let claimed: Vec<_> = inbox
+            .drain(..)
+            .filter_map(|inbound| match inbound.msg {
+                HostMessage::SetTargetFps(fps) => Some(fps),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(claimed, [30]);
+    }
+
+    #[test]
+    fn unclaimed_messages_expire() {
+        let mut inbox = VecDeque::from([
+            inbound(AppId::DEFAULT, HostMessage::SetTargetFps(30), 0.0),
+            inbound(AppId::DEFAULT, HostMessage::Detach { view: ViewId(1) }, 0.0),
+            inbound(AppId(1), HostMessage::SetTargetFps(30), 0.0),
+            inbound(AppId::DEFAULT, HostMessage::SetTargetFps(60), 2.0),
+        ]);
+
+        // Input waiting for the budget stays, and so do messages of other apps.
+        assert_eq!(
+            expire(&mut inbox, AppId::DEFAULT, false, 1.0),
+            ["set_target_fps"]
+        );
+        assert_eq!(expire(&mut inbox, AppId::DEFAULT, true, 1.0), ["detach"]);
+        assert_eq!(inbox.len(), 2);
+    }
+}
@@ -0,0 +1,119 @@
+//! Mirror AccessKit tree to the page.
+//!
+//! There is no platform adapter inside a worker, so the tree is flattened
+//! and posted to the page, which maintains hidden DOM elements for screen readers to find.
+
+use bevy::a11y::accesskit::{NodeClassSet, Role};
+use bevy::a11y::{AccessibilityNode, AccessibilityRequested, Focus};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use super::{bridge_connected, post, BridgeSchedules, BridgeSend};
+use crate::protocol::{AccessNode, AccessTree, WorkerMessage};
+
+/// Post accessibility tree of primary window to the page whenever it changes.
+#[derive(Default)]
+pub struct AccessibilityBridgePlugin;
+
+impl Plugin for AccessibilityBridgePlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.add_systems(
+            schedules.send,
+            send_tree.run_if(bridge_connected()).in_set(BridgeSend),
+        );
+    }
+
+    fn finish(&self, app: &mut App) {
+        // Page always keeps the mirror, so there is somebody listening from the start.
+        if let Some(requested) = app.world.get_resource::<AccessibilityRequested>() {
+            requested.set(true);
+        }
+    }
+}
+
+fn send_tree(
+    nodes: Query<(Entity, &AccessibilityNode)>,
+    changed: Query<(), Changed<AccessibilityNode>>,
+    mut removed: RemovedComponents<AccessibilityNode>,
+    focus: Res<Focus>,
+    children: Query<&Children>,
+    parents: Query<&Parent>,
+    window: Query<&Window, With<PrimaryWindow>>,
+) {
+    let removed = removed.iter().count() > 0;
+    if changed.is_empty() && !removed && !focus.is_changed() {
+        return;
+    }
+
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+
+    // Roots are nodes without accessible ancestors.
+    let mut roots: Vec<_> = nodes
+        .iter()
+        .map(|(entity, _)| entity)
+        .filter(|&entity| {
+            !parents
+                .iter_ancestors(entity)
+                .any(|ancestor| nodes.contains(ancestor))
+        })
+        .collect();
+    roots.sort();
+
+    let mut classes = NodeClassSet::lock_global();
+    let mut flattened = Vec::new();
+    let mut stack: Vec<_> = roots.into_iter().rev().collect();
+
+    while let Some(entity) = stack.pop() {
+        if let Ok((entity, node)) = nodes.get(entity) {
+            let node = node.0.clone().build(&mut classes);
+
+            flattened.push(AccessNode {
+                id: entity.to_bits(),
+                role: aria_role(node.role()).map(str::to_owned),
+                name: node.name().map(str::to_owned),
+                bounds: node
+                    .bounds()
+                    .map(|rect| [rect.x0, rect.y0, rect.x1, rect.y1]),
+            });
+        }
+
+        if let Ok(children) = children.get(entity) {
+            stack.extend(children.iter().rev());
+        }
+    }
+
+    post(&WorkerMessage::Accessibility(AccessTree {
+        width: window.width() as f64,
+        height: window.height() as f64,
+        nodes: flattened,
+        focus: focus.0.map(Entity::to_bits),
+    }));
+}
+
+/// Closest ARIA role, `None` for nodes that are just text.
+fn aria_role(role: Role) -> Option<&'static str> {
+    let role = match role {
+        Role::Button => "button",
+        Role::CheckBox => "checkbox",
+        Role::RadioButton => "radio",
+        Role::Slider => "slider",
+        Role::TextField => "textbox",
+        Role::Link => "link",
+        Role::Heading => "heading",
+        Role::Image => "img",
+        Role::List => "list",
+        Role::ListItem => "listitem",
+        Role::Menu => "menu",
+        Role::MenuItem => "menuitem",
+        Role::ProgressIndicator => "progressbar",
+        Role::Dialog => "dialog",
+        Role::StaticText | Role::Label => return None,
+        _ => "group",
+    };
+
+    Some(role)
+}
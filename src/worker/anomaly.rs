@@ -0,0 +1,197 @@
+//! Automatic reports on slow frames.
+//!
+//! While [`ANOMALY_CAPTURE`] toggle is enabled, every frame taking longer than the threshold
+//! is described in a JSON report posted to the page with [`WorkerMessage::AnomalyReport`].
+//! Report contains time spent in each main schedule and in rendering, current diagnostics
+//! and the latest bridge traffic, which is usually enough to tell a hitch from a stall.
+//!
+//! Timings are per schedule rather than per system, the latter needs Bevy's `trace` feature
+//! and a profiler attached to the page.
+
+use bevy::app::MainScheduleOrder;
+use bevy::core::FrameCount;
+use bevy::diagnostic::DiagnosticsStore;
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::prelude::*;
+use serde_json::{json, Value};
+
+use super::features::{FeatureToggles, ANOMALY_CAPTURE};
+use super::{last_update_ms, post, BridgeMetrics};
+use crate::protocol::WorkerMessage;
+
+/// Report frames exceeding the threshold to the page.
+pub struct AnomalyCapturePlugin {
+    /// Frames taking longer than this are reported.
+    pub threshold_ms: f64,
+    /// Minimal time between reports, so a long stall doesn't flood the page.
+    pub cooldown_ms: f64,
+}
+
+impl Default for AnomalyCapturePlugin {
+    fn default() -> Self {
+        AnomalyCapturePlugin {
+            threshold_ms: 50.0,
+            cooldown_ms: 1000.0,
+        }
+    }
+}
+
+impl Plugin for AnomalyCapturePlugin {
+    fn build(&self, app: &mut App) {
+        // Checkpoint schedules are interleaved with the main ones, which gives exact time spent in each.
+        let mut order = app.world.resource_mut::<MainScheduleOrder>();
+        let labels = std::mem::take(&mut order.labels);
+        let last = labels.len();
+        let names = labels.iter().map(|label| format!("{label:?}")).collect();
+
+        for (i, label) in labels.into_iter().enumerate() {
+            order.labels.push(Box::new(Checkpoint(i)));
+            order.labels.push(label);
+        }
+        order.labels.push(Box::new(Checkpoint(last)));
+
+        app.insert_resource(ScheduleTimings {
+            names,
+            marks: Vec::new(),
+        })
+        .insert_resource(Capture {
+            threshold_ms: self.threshold_ms,
+            cooldown_ms: self.cooldown_ms,
+            last_report_at: None,
+        })
+        .add_systems(Checkpoint(0), start_frame);
+
+        for i in 1..=last {
+            app.add_systems(Checkpoint(i), |mut timings: ResMut<ScheduleTimings>| {
+                timings.marks.push(js_sys::Date::now());
+            });
+        }
+    }
+}
+
+/// Runs right before main schedule with the same index, the last one runs after all of them.
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct Checkpoint(usize);
+
+#[derive(Resource)]
+struct ScheduleTimings {
+    names: Vec<String>,
+    /// Times checkpoints of current frame were reached.
+    marks: Vec<f64>,
+}
+
+impl ScheduleTimings {
+    /// Time spent in each schedule during the frame, slowest first.
+    ///
+    /// Rendering is everything [`App::update`] did after the last main schedule.
+    fn durations(&self, update_ms: f64) -> Vec<(&str, f64)> {
+        let mut durations: Vec<_> = self
+            .names
+            .iter()
+            .map(String::as_str)
+            .zip(self.marks.windows(2).map(|pair| pair[1] - pair[0]))
+            .collect();
+
+        if let (Some(first), Some(last)) = (self.marks.first(), self.marks.last()) {
+            durations.push(("render", (update_ms - (last - first)).max(0.0)));
+        }
+
+        durations.sort_by(|a, b| b.1.total_cmp(&a.1));
+        durations
+    }
+}
+
+#[derive(Resource)]
+struct Capture {
+    threshold_ms: f64,
+    cooldown_ms: f64,
+    last_report_at: Option<f64>,
+}
+
+/// Inspect the frame which just finished, then start timing a new one.
+fn start_frame(
+    toggles: Res<FeatureToggles>,
+    frame: Res<FrameCount>,
+    diagnostics: Res<DiagnosticsStore>,
+    metrics: Res<BridgeMetrics>,
+    mut capture: ResMut<Capture>,
+    mut timings: ResMut<ScheduleTimings>,
+) {
+    let now = js_sys::Date::now();
+    let update_ms = last_update_ms().unwrap_or_default();
+
+    let cooled_down = capture
+        .last_report_at
+        .map_or(true, |at| now - at >= capture.cooldown_ms);
+
+    if toggles.is_enabled(ANOMALY_CAPTURE) && update_ms > capture.threshold_ms && cooled_down {
+        capture.last_report_at = Some(now);
+
+        let report = report(
+            // Counter was already advanced past the frame in question.
+            frame.0.wrapping_sub(1),
+            update_ms,
+            capture.threshold_ms,
+            &timings,
+            &diagnostics,
+            &metrics,
+        );
+        post(&WorkerMessage::AnomalyReport(report.to_string()));
+    }
+
+    timings.marks.clear();
+    timings.marks.push(now);
+}
+
+fn report(
+    frame: u32,
+    update_ms: f64,
+    threshold_ms: f64,
+    timings: &ScheduleTimings,
+    diagnostics: &DiagnosticsStore,
+    metrics: &BridgeMetrics,
+) -> Value {
+    let schedules: Vec<_> = timings
+        .durations(update_ms)
+        .into_iter()
+        .map(|(name, ms)| json!({ "name": name, "ms": ms }))
+        .collect();
+
+    json!({
+        "frame": frame,
+        "frame_ms": update_ms,
+        "threshold_ms": threshold_ms,
+        "captured_at": js_sys::Date::now(),
+        "schedules": schedules,
+        "diagnostics": diagnostics_json(diagnostics),
+        "traffic": traffic_json(metrics),
+    })
+}
+
+pub(super) fn diagnostics_json(diagnostics: &DiagnosticsStore) -> Value {
+    diagnostics
+        .iter()
+        .map(|diagnostic| {
+            json!({
+                "name": diagnostic.name,
+                "value": diagnostic.value(),
+                "average": diagnostic.average(),
+                "suffix": diagnostic.suffix,
+            })
+        })
+        .collect()
+}
+
+pub(super) fn traffic_json(metrics: &BridgeMetrics) -> Value {
+    metrics
+        .recent()
+        .map(|record| {
+            json!({
+                "direction": record.direction.name(),
+                "kind": record.kind,
+                "correlation_id": record.correlation_id.map(|id| id.0),
+                "at": record.at,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,147 @@
+//! Antialiasing which degrades predictably across GPUs.
+//!
+//! WebGL implementations differ wildly in which sample counts they support for multisampling,
+//! and asking for unsupported one is a validation error rather than a graceful fallback.
+//! Instead app states what it would like in order of preference,
+//! and the first method adapter can actually do is used.
+
+use bevy::core_pipeline::fxaa::Fxaa;
+use bevy::prelude::*;
+use bevy::render::render_resource::TextureFormat;
+use bevy::render::renderer::RenderAdapter;
+use bevy::render::texture::BevyDefault;
+
+/// Antialiasing method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Antialiasing {
+    /// Multisampling with given sample count: 2, 4 or 8.
+    ///
+    /// Counts GPU can't do are lowered to the highest one it can, with a warning.
+    Msaa(u32),
+    /// FXAA post-process, works everywhere but blurs the image somewhat.
+    Fxaa,
+    None,
+}
+
+/// Pick the best antialiasing method GPU supports.
+pub struct AntialiasingPlugin {
+    /// Methods to try, most preferred first.
+    ///
+    /// [`Antialiasing::None`] is used if none of them work.
+    pub preference: Vec<Antialiasing>,
+}
+
+impl Default for AntialiasingPlugin {
+    fn default() -> Self {
+        AntialiasingPlugin {
+            preference: vec![Antialiasing::Msaa(4), Antialiasing::Fxaa],
+        }
+    }
+}
+
+impl Plugin for AntialiasingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AntialiasingPreference(self.preference.clone()))
+            .insert_resource(ActiveAntialiasing(Antialiasing::None))
+            .add_systems(Startup, choose_antialiasing)
+            .add_systems(Update, apply_fxaa);
+    }
+}
+
+#[derive(Resource)]
+struct AntialiasingPreference(Vec<Antialiasing>);
+
+/// Antialiasing method which ended up being used.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveAntialiasing(pub Antialiasing);
+
+fn choose_antialiasing(
+    preference: Res<AntialiasingPreference>,
+    adapter: Option<Res<RenderAdapter>>,
+    mut active: ResMut<ActiveAntialiasing>,
+    mut msaa: ResMut<Msaa>,
+) {
+    // Sample counts adapter can't do fall back to the highest lower one it can.
+    let resolve = |method: Antialiasing| match method {
+        Antialiasing::Msaa(samples) => {
+            let chosen = adapter.as_ref().map_or(1, |adapter| {
+                msaa_samples(adapter, samples, TextureFormat::bevy_default())
+            });
+            (chosen > 1).then_some(Antialiasing::Msaa(chosen))
+        }
+        Antialiasing::Fxaa | Antialiasing::None => Some(method),
+    };
+
+    let mut rejected = Vec::new();
+    let mut chosen = Antialiasing::None;
+    for &method in &preference.0 {
+        let Some(resolved) = resolve(method) else {
+            rejected.push(method);
+            continue;
+        };
+        if let (Antialiasing::Msaa(requested), Antialiasing::Msaa(samples)) = (method, resolved) {
+            if requested != samples {
+                warn!("MSAA with {requested} samples requested, GPU only does {samples}");
+            }
+        }
+        chosen = resolved;
+        break;
+    }
+
+    *msaa = match chosen {
+        Antialiasing::Msaa(samples) => msaa_from_samples(samples),
+        Antialiasing::Fxaa | Antialiasing::None => Msaa::Off,
+    };
+    active.0 = chosen;
+
+    if rejected.is_empty() {
+        info!("using {chosen:?} antialiasing");
+    } else {
+        info!("using {chosen:?} antialiasing, GPU doesn't support {rejected:?}");
+    }
+}
+
+/// Highest sample count up to `requested` adapter can multisample given format with, 1 if none.
+pub(super) fn msaa_samples(adapter: &RenderAdapter, requested: u32, format: TextureFormat) -> u32 {
+    [8, 4, 2]
+        .into_iter()
+        .filter(|&count| count <= requested)
+        .find(|&count| msaa_supported(adapter, count, format))
+        .unwrap_or(1)
+}
+
+pub(super) fn msaa_from_samples(samples: u32) -> Msaa {
+    match samples {
+        2 => Msaa::Sample2,
+        4 => Msaa::Sample4,
+        8 => Msaa::Sample8,
+        _ => Msaa::Off,
+    }
+}
+
+/// Whether adapter can multisample cameras rendering to given format with given sample count.
+fn msaa_supported(adapter: &RenderAdapter, samples: u32, format: TextureFormat) -> bool {
+    // Both color and depth attachments are multisampled.
+    [format, TextureFormat::Depth32Float]
+        .into_iter()
+        .all(|format| {
+            adapter
+                .get_texture_format_features(format)
+                .flags
+                .sample_count_supported(samples)
+        })
+}
+
+fn apply_fxaa(
+    mut commands: Commands,
+    active: Res<ActiveAntialiasing>,
+    cameras: Query<Entity, (Added<Camera>, Without<Fxaa>)>,
+) {
+    if active.0 != Antialiasing::Fxaa {
+        return;
+    }
+
+    for entity in &cameras {
+        commands.entity(entity).insert(Fxaa::default());
+    }
+}
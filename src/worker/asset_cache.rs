@@ -0,0 +1,380 @@
+//! Persistent cache of fetched assets.
+//!
+//! Assets are kept in IndexedDB keyed by URL, so repeat visits don't download them again.
+//! Every load still asks the server whether the asset changed using its ETag,
+//! which is cheap compared to the asset itself; responses without ETag are not cached.
+//! Least recently used assets are evicted once cache grows past its size limit.
+//!
+//! Page can drop the whole cache with [`HostMessage::ClearAssetCache`].
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bevy::asset::{AssetIo, AssetIoError, ChangeWatcher, Metadata};
+use bevy::prelude::*;
+use bevy::utils::BoxedFuture;
+use js_sys::{Array, ArrayBuffer, Object, Promise, Reflect, Uint8Array};
+use wasm_bindgen::prelude::{Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{IdbDatabase, IdbObjectStore, IdbRequest, IdbTransactionMode, RequestInit, Response};
+
+use super::cache_storage::OfflineAssetCache;
+use super::{scope, take_messages};
+use crate::protocol::HostMessage;
+
+// Small records describing cached assets, so eviction doesn't have to touch the bytes.
+const ENTRIES: &str = "entries";
+const BLOBS: &str = "blobs";
+
+/// How assets are cached.
+#[derive(Debug, Clone)]
+pub struct AssetCacheSettings {
+    /// Name of IndexedDB database holding the cache.
+    pub database: String,
+    /// Limit on total size of cached assets, in bytes.
+    pub max_bytes: u64,
+    /// URL asset paths are resolved against.
+    ///
+    /// `None` picks asset folder at page origin.
+    pub base_url: Option<String>,
+}
+
+impl Default for AssetCacheSettings {
+    fn default() -> Self {
+        AssetCacheSettings {
+            database: "bevy-asset-cache".to_owned(),
+            max_bytes: 256 * 1024 * 1024,
+            base_url: None,
+        }
+    }
+}
+
+/// Handle to the asset cache.
+///
+/// Inserted by [`InMemoryAssetPlugin`](super::inmem::InMemoryAssetPlugin) when caching is enabled.
+#[derive(Resource, Debug, Clone)]
+pub struct AssetCache {
+    settings: Arc<AssetCacheSettings>,
+}
+
+impl AssetCache {
+    pub fn new(settings: AssetCacheSettings) -> Self {
+        AssetCache {
+            settings: Arc::new(settings),
+        }
+    }
+
+    /// Drop every cached asset.
+    ///
+    /// Happens in background, assets being loaded at the moment may still end up cached.
+    pub fn clear(&self) {
+        let cache = self.clone();
+
+        wasm_bindgen_futures::spawn_local(async move {
+            let result = async {
+                let db = cache.open().await?;
+                let (entries, blobs) = stores(&db, IdbTransactionMode::Readwrite)?;
+                done(&entries.clear()?).await?;
+                done(&blobs.clear()?).await?;
+                Ok::<_, JsValue>(())
+            };
+
+            if let Err(err) = result.await {
+                warn!("failed to clear asset cache: {err:?}");
+            }
+        });
+    }
+
+    /// Get asset from the network, or from cache if it didn't change.
+    ///
+    /// Returns `None` if server doesn't know about the asset.
+    async fn fetch(&self, url: &str) -> Result<Option<Vec<u8>>, JsValue> {
+        let db = self.open().await?;
+
+        let cached = {
+            let (entries, _) = stores(&db, IdbTransactionMode::Readonly)?;
+            let entry = done(&entries.get(&url.into())?).await?;
+            get(&entry, "etag").and_then(|etag| etag.as_string())
+        };
+
+        let mut init = RequestInit::new();
+        if let Some(etag) = &cached {
+            let headers = Object::new();
+            Reflect::set(&headers, &"If-None-Match".into(), &etag.into())?;
+            init.headers(&headers);
+        }
+
+        let response = JsFuture::from(scope().fetch_with_str_and_init(url, &init)).await;
+
+        let response: Response = match response {
+            Ok(response) => response.dyn_into()?,
+            // Offline, but we may have a copy.
+            Err(err) => {
+                return match cached {
+                    Some(_) => self.hit(&db, url).await?.map(Some).ok_or(err),
+                    None => Err(err),
+                }
+            }
+        };
+
+        if response.status() == 304 {
+            return match self.hit(&db, url).await? {
+                Some(bytes) => Ok(Some(bytes)),
+                None => Err(JsValue::from_str("cached asset disappeared")),
+            };
+        }
+
+        if response.status() == 404 {
+            return Ok(None);
+        }
+
+        if !response.ok() {
+            return Err(JsValue::from_str(&format!(
+                "server responded with {}",
+                response.status()
+            )));
+        }
+
+        let etag = response.headers().get("ETag")?;
+        let buffer: ArrayBuffer = JsFuture::from(response.array_buffer()?).await?.dyn_into()?;
+
+        if let Some(etag) = etag {
+            if let Err(err) = self.store(&db, url, &etag, &buffer).await {
+                warn!("failed to cache asset {url}: {err:?}");
+            }
+        }
+
+        Ok(Some(Uint8Array::new(&buffer).to_vec()))
+    }
+
+    async fn open(&self) -> Result<IdbDatabase, JsValue> {
+        let factory = scope()
+            .indexed_db()?
+            .ok_or_else(|| JsValue::from_str("IndexedDB is not available"))?;
+        let request = factory.open_with_u32(&self.settings.database, 1)?;
+
+        let onupgradeneeded = Closure::once({
+            let request = request.clone();
+
+            move || {
+                let Some(db) = request
+                    .result()
+                    .ok()
+                    .and_then(|db| db.dyn_into::<IdbDatabase>().ok())
+                else {
+                    return;
+                };
+
+                for name in [ENTRIES, BLOBS] {
+                    if let Err(err) = db.create_object_store(name) {
+                        warn!("failed to create asset cache store: {err:?}");
+                    }
+                }
+            }
+        });
+        request.set_onupgradeneeded(Some(onupgradeneeded.as_ref().unchecked_ref()));
+
+        let db = done(&request).await?.dyn_into();
+        request.set_onupgradeneeded(None);
+
+        db
+    }
+
+    /// Read cached asset and mark it as recently used.
+    async fn hit(&self, db: &IdbDatabase, url: &str) -> Result<Option<Vec<u8>>, JsValue> {
+        let (entries, blobs) = stores(db, IdbTransactionMode::Readwrite)?;
+        let key = JsValue::from_str(url);
+
+        let entry = done(&entries.get(&key)?).await?;
+        let blob = done(&blobs.get(&key)?).await?;
+        if entry.is_undefined() || blob.is_undefined() {
+            return Ok(None);
+        }
+
+        Reflect::set(&entry, &"used_at".into(), &js_sys::Date::now().into())?;
+        done(&entries.put_with_key(&entry, &key)?).await?;
+
+        Ok(Some(Uint8Array::new(&blob).to_vec()))
+    }
+
+    async fn store(
+        &self,
+        db: &IdbDatabase,
+        url: &str,
+        etag: &str,
+        bytes: &ArrayBuffer,
+    ) -> Result<(), JsValue> {
+        let size = bytes.byte_length() as u64;
+        if size > self.settings.max_bytes {
+            return Ok(());
+        }
+
+        let (entries, blobs) = stores(db, IdbTransactionMode::Readwrite)?;
+        let key = JsValue::from_str(url);
+
+        // Make room, least recently used assets go first.
+        let keys: Array = done(&entries.get_all_keys()?).await?.dyn_into()?;
+        let records: Array = done(&entries.get_all()?).await?.dyn_into()?;
+
+        let mut cached: Vec<_> = keys
+            .iter()
+            .zip(records.iter())
+            .filter(|(other, _)| *other != key)
+            .map(|(other, record)| {
+                let size = get(&record, "size").and_then(|size| size.as_f64());
+                let used_at = get(&record, "used_at").and_then(|used_at| used_at.as_f64());
+                (
+                    other,
+                    size.unwrap_or_default() as u64,
+                    used_at.unwrap_or_default(),
+                )
+            })
+            .collect();
+        cached.sort_by(|a, b| a.2.total_cmp(&b.2));
+
+        let mut total: u64 = cached.iter().map(|(_, size, _)| size).sum();
+        for (other, other_size, _) in cached {
+            if total + size <= self.settings.max_bytes {
+                break;
+            }
+
+            done(&entries.delete(&other)?).await?;
+            done(&blobs.delete(&other)?).await?;
+            total -= other_size;
+        }
+
+        let entry = Object::new();
+        Reflect::set(&entry, &"etag".into(), &etag.into())?;
+        Reflect::set(&entry, &"size".into(), &(size as f64).into())?;
+        Reflect::set(&entry, &"used_at".into(), &js_sys::Date::now().into())?;
+
+        done(&blobs.put_with_key(bytes, &key)?).await?;
+        done(&entries.put_with_key(&entry, &key)?).await?;
+
+        Ok(())
+    }
+}
+
+/// Asset source fetching assets through [`AssetCache`].
+///
+/// Falls back to another source if cache is unusable, e.g. in private browsing.
+pub(super) struct CachedAssetIo {
+    cache: AssetCache,
+    base_url: String,
+    fallback: Box<dyn AssetIo>,
+}
+
+impl CachedAssetIo {
+    pub(super) fn new(cache: AssetCache, asset_folder: &str, fallback: Box<dyn AssetIo>) -> Self {
+        let base_url = match &cache.settings.base_url {
+            Some(url) => url.clone(),
+            None => {
+                let origin = scope().location().origin();
+                format!("{origin}/{asset_folder}")
+            }
+        };
+
+        CachedAssetIo {
+            cache,
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            fallback,
+        }
+    }
+}
+
+impl AssetIo for CachedAssetIo {
+    fn load_path<'a>(&'a self, path: &'a Path) -> BoxedFuture<'a, Result<Vec<u8>, AssetIoError>> {
+        Box::pin(async move {
+            let Some(relative) = path.to_str() else {
+                return self.fallback.load_path(path).await;
+            };
+            let url = format!("{}/{}", self.base_url, relative.trim_start_matches('/'));
+
+            match self.cache.fetch(&url).await {
+                Ok(Some(bytes)) => Ok(bytes),
+                Ok(None) => Err(AssetIoError::NotFound(path.to_owned())),
+                Err(err) => {
+                    warn!("asset cache failed for {url}: {err:?}");
+                    self.fallback.load_path(path).await
+                }
+            }
+        })
+    }
+
+    fn read_directory(
+        &self,
+        path: &Path,
+    ) -> Result<Box<dyn Iterator<Item = PathBuf>>, AssetIoError> {
+        self.fallback.read_directory(path)
+    }
+
+    fn get_metadata(&self, path: &Path) -> Result<Metadata, AssetIoError> {
+        self.fallback.get_metadata(path)
+    }
+
+    fn watch_path_for_changes(
+        &self,
+        to_watch: &Path,
+        to_reload: Option<PathBuf>,
+    ) -> Result<(), AssetIoError> {
+        self.fallback.watch_path_for_changes(to_watch, to_reload)
+    }
+
+    fn watch_for_changes(&self, configuration: &ChangeWatcher) -> Result<(), AssetIoError> {
+        self.fallback.watch_for_changes(configuration)
+    }
+}
+
+/// Handle [`HostMessage::ClearAssetCache`], offline cache included.
+///
+/// Message is accepted even when caching is disabled, there is simply nothing to clear.
+pub(super) fn receive_clear(
+    cache: Option<Res<AssetCache>>,
+    offline: Option<Res<OfflineAssetCache>>,
+) {
+    take_messages(|msg| match msg {
+        HostMessage::ClearAssetCache => {
+            if let Some(cache) = &cache {
+                cache.clear();
+            }
+            if let Some(offline) = &offline {
+                offline.clear();
+            }
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
+
+fn stores(
+    db: &IdbDatabase,
+    mode: IdbTransactionMode,
+) -> Result<(IdbObjectStore, IdbObjectStore), JsValue> {
+    let names: Array = [ENTRIES, BLOBS]
+        .iter()
+        .map(|&name| JsValue::from(name))
+        .collect();
+    let transaction = db.transaction_with_str_sequence_and_mode(&names, mode)?;
+
+    Ok((
+        transaction.object_store(ENTRIES)?,
+        transaction.object_store(BLOBS)?,
+    ))
+}
+
+/// Wait for IndexedDB request to complete.
+async fn done(request: &IdbRequest) -> Result<JsValue, JsValue> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+    });
+
+    JsFuture::from(promise).await?;
+    request.result()
+}
+
+fn get(value: &JsValue, field: &str) -> Option<JsValue> {
+    Reflect::get(value, &field.into())
+        .ok()
+        .filter(|value| !value.is_undefined())
+}
@@ -0,0 +1,37 @@
+//! Boot flags page passed to the worker, see [`BootFlags`].
+//!
+//! Page sends them ahead of everything else, so they are known by the time apps are built
+//! and can decide which plugins to add, see [`boot_flags`].
+
+use std::cell::RefCell;
+
+use bevy::prelude::*;
+
+use crate::protocol::BootFlags;
+
+thread_local! {
+    static FLAGS: RefCell<BootFlags> = RefCell::new(BootFlags::default());
+}
+
+/// Boot flags of the worker, flags page didn't set are `None`.
+///
+/// Apps in the same worker share them.
+pub fn boot_flags() -> BootFlags {
+    FLAGS.with(|flags| flags.borrow().clone())
+}
+
+pub(super) fn set_boot_flags(flags: BootFlags) {
+    FLAGS.with(|cell| *cell.borrow_mut() = flags);
+}
+
+/// Expose [`BootFlags`] as a resource.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins), which also applies log level.
+#[derive(Default)]
+pub struct BootFlagsPlugin;
+
+impl Plugin for BootFlagsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(boot_flags());
+    }
+}
@@ -0,0 +1,244 @@
+//! Bridge throughput and latency as Bevy diagnostics.
+//!
+//! Message rates and codec times come from [`BridgeMetrics`], which counts traffic of the whole worker.
+//! Round trip is measured by pinging the page, pong is picked up as soon as it arrives
+//! rather than on the next frame, so frame time doesn't count towards it.
+//! Pongs also carry page's clock, which syncs worker's clock to the page's, see [`page_now`].
+//!
+//! Diagnostics show up in `LogDiagnosticsPlugin` output like any other,
+//! and can be forwarded to the page, see [`WorkerHandle::bridge_stats`](crate::host::WorkerHandle::bridge_stats).
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+
+use bevy::diagnostic::{Diagnostic, DiagnosticId, Diagnostics, RegisterDiagnostic};
+use bevy::prelude::*;
+
+use super::{current_app, post, precise_now, scope, AppId, BridgeMetrics, CodecStats};
+use crate::protocol::{BridgeStats, WorkerMessage};
+
+/// Messages received from the page per second.
+pub const BRIDGE_RECEIVED: DiagnosticId =
+    DiagnosticId::from_u128(0x2b6e_91d4_57a3_4f0c_8e21_c9d0_3f74_a615);
+/// Messages posted to the page per second.
+pub const BRIDGE_SENT: DiagnosticId =
+    DiagnosticId::from_u128(0x9c14_0e7f_b2d8_46a1_a53b_7e6f_21c8_d90e);
+/// Mean time to decode a message from the page, in ms.
+pub const BRIDGE_DECODE_TIME: DiagnosticId =
+    DiagnosticId::from_u128(0x47d2_c8a0_1e9b_4b35_b6f4_0a1d_92e7_5c38);
+/// Mean time to encode and post a message to the page, in ms.
+pub const BRIDGE_ENCODE_TIME: DiagnosticId =
+    DiagnosticId::from_u128(0xe083_5f1b_6c47_4d92_9a0e_b3c5_784f_1d26);
+/// Time from posting ping to the page to receiving its pong, in ms.
+pub const BRIDGE_ROUND_TRIP: DiagnosticId =
+    DiagnosticId::from_u128(0x15a9_d36e_f802_4c7b_8d4e_6b2f_a0c1_93e7);
+
+thread_local! {
+    static PINGS: RefCell<HashMap<AppId, Pings>> = RefCell::new(HashMap::new());
+}
+
+/// Register bridge diagnostics and ping the page periodically.
+pub struct BridgeDiagnosticsPlugin {
+    /// How often rates are computed and page is pinged.
+    pub interval_secs: f64,
+    /// Send [`BridgeStats`] to the page every interval.
+    pub forward_to_page: bool,
+}
+
+impl Default for BridgeDiagnosticsPlugin {
+    fn default() -> Self {
+        BridgeDiagnosticsPlugin {
+            interval_secs: 1.0,
+            forward_to_page: false,
+        }
+    }
+}
+
+impl Plugin for BridgeDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        PINGS.with(|pings| pings.borrow_mut().insert(current_app(), Pings::default()));
+
+        app.register_diagnostic(
+            Diagnostic::new(BRIDGE_RECEIVED, "bridge_received", 20).with_suffix("/s"),
+        )
+        .register_diagnostic(Diagnostic::new(BRIDGE_SENT, "bridge_sent", 20).with_suffix("/s"))
+        .register_diagnostic(
+            Diagnostic::new(BRIDGE_DECODE_TIME, "bridge_decode_time", 20).with_suffix("ms"),
+        )
+        .register_diagnostic(
+            Diagnostic::new(BRIDGE_ENCODE_TIME, "bridge_encode_time", 20).with_suffix("ms"),
+        )
+        .register_diagnostic(
+            Diagnostic::new(BRIDGE_ROUND_TRIP, "bridge_round_trip", 20).with_suffix("ms"),
+        )
+        .insert_resource(DiagnosticsSettings {
+            interval_secs: self.interval_secs,
+            forward_to_page: self.forward_to_page,
+        })
+        .add_systems(Last, measure);
+    }
+}
+
+#[derive(Resource)]
+struct DiagnosticsSettings {
+    interval_secs: f64,
+    forward_to_page: bool,
+}
+
+/// Clock samples clock offset is picked from.
+const CLOCK_SAMPLES: usize = 8;
+
+#[derive(Default)]
+struct Pings {
+    next_seq: u32,
+    // Only the latest ping is waiting, pong to an older one arrived too late to matter.
+    waiting: Option<(u32, f64)>,
+    round_trip_ms: Option<f64>,
+    // Round trip and page clock minus worker clock measured with it, latest last.
+    clock_samples: VecDeque<(f64, f64)>,
+}
+
+impl Pings {
+    /// Offset measured over the quickest recent round trip, which leaves the least room for error.
+    fn clock_offset_ms(&self) -> Option<f64> {
+        self.clock_samples
+            .iter()
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, offset)| *offset)
+    }
+}
+
+/// Page answered the ping at `page_time` by its own clock.
+pub(super) fn pong(app: AppId, seq: u32, page_time: f64) {
+    PINGS.with(|pings| {
+        let mut pings = pings.borrow_mut();
+        let Some(pings) = pings.get_mut(&app) else {
+            return;
+        };
+
+        let Some((_, sent_at)) = pings.waiting.filter(|(waiting, _)| *waiting == seq) else {
+            return;
+        };
+        let round_trip_ms = precise_now() - sent_at;
+        pings.round_trip_ms = Some(round_trip_ms);
+        pings.waiting = None;
+
+        // Page is assumed to answer halfway through the round trip.
+        let offset = page_time - (time_origin() + sent_at + round_trip_ms / 2.0);
+        if pings.clock_samples.len() == CLOCK_SAMPLES {
+            pings.clock_samples.pop_front();
+        }
+        pings.clock_samples.push_back((round_trip_ms, offset));
+    });
+}
+
+/// Ping the page on behalf of the current app.
+pub(super) fn ping() {
+    let seq = PINGS.with(|pings| {
+        let mut pings = pings.borrow_mut();
+        let pings = pings.entry(current_app()).or_default();
+        let seq = pings.next_seq;
+        pings.next_seq = seq.wrapping_add(1);
+        pings.waiting = Some((seq, precise_now()));
+        seq
+    });
+    post(&WorkerMessage::Ping(seq));
+}
+
+/// Current time by page's clock, comparable with `Date.now()` on page side, e.g. with input timestamps.
+///
+/// Worker's clock is synced to the page's with pongs, current app has to ping the page for that,
+/// either through [`BridgeDiagnosticsPlugin`] or on its own.
+/// Until the first pong arrives worker's own `Date.now()` is used.
+pub fn page_now() -> f64 {
+    let offset = PINGS.with(|pings| {
+        pings
+            .borrow()
+            .get(&current_app())
+            .and_then(Pings::clock_offset_ms)
+    });
+
+    match offset {
+        Some(offset) => time_origin() + precise_now() + offset,
+        None => js_sys::Date::now(),
+    }
+}
+
+/// Worker's `performance.timeOrigin`, by which [`precise_now`] is offset from `Date.now()`.
+fn time_origin() -> f64 {
+    scope()
+        .performance()
+        .map_or(0.0, |performance| performance.time_origin())
+}
+
+/// Traffic totals at the start of the interval.
+struct Snapshot {
+    at: f64,
+    received: u64,
+    sent: u64,
+    decoding: CodecStats,
+    encoding: CodecStats,
+}
+
+impl Snapshot {
+    fn take(at: f64, metrics: &BridgeMetrics) -> Self {
+        Snapshot {
+            at,
+            received: metrics.iter_received().map(|(_, stats)| stats.count).sum(),
+            sent: metrics.iter_sent().map(|(_, count)| count).sum(),
+            decoding: metrics.decoding(),
+            encoding: metrics.encoding(),
+        }
+    }
+}
+
+fn measure(
+    time: Res<Time>,
+    settings: Res<DiagnosticsSettings>,
+    metrics: Res<BridgeMetrics>,
+    mut diagnostics: Diagnostics,
+    mut previous: Local<Option<Snapshot>>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let Some(start) = &*previous else {
+        *previous = Some(Snapshot::take(now, &metrics));
+        return;
+    };
+
+    let elapsed = now - start.at;
+    if elapsed < settings.interval_secs {
+        return;
+    }
+
+    let current = Snapshot::take(now, &metrics);
+    let round_trip_ms = PINGS.with(|pings| {
+        pings
+            .borrow()
+            .get(&current_app())
+            .and_then(|pings| pings.round_trip_ms)
+    });
+
+    let stats = BridgeStats {
+        received_per_sec: (current.received - start.received) as f64 / elapsed,
+        sent_per_sec: (current.sent - start.sent) as f64 / elapsed,
+        decode_ms: current.decoding.mean_ms_since(&start.decoding),
+        encode_ms: current.encoding.mean_ms_since(&start.encoding),
+        round_trip_ms,
+    };
+
+    diagnostics.add_measurement(BRIDGE_RECEIVED, || stats.received_per_sec);
+    diagnostics.add_measurement(BRIDGE_SENT, || stats.sent_per_sec);
+    diagnostics.add_measurement(BRIDGE_DECODE_TIME, || stats.decode_ms);
+    diagnostics.add_measurement(BRIDGE_ENCODE_TIME, || stats.encode_ms);
+    if let Some(round_trip_ms) = round_trip_ms {
+        diagnostics.add_measurement(BRIDGE_ROUND_TRIP, || round_trip_ms);
+    }
+
+    if settings.forward_to_page {
+        post(&WorkerMessage::BridgeStats(stats));
+    }
+
+    ping();
+
+    *previous = Some(current);
+}
@@ -0,0 +1,340 @@
+//! State shared with other tabs of the site over `BroadcastChannel`.
+//!
+//! Meant for lightweight updates, e.g. leaderboard changes or settings, not for per-frame data.
+//! Messages carry a topic and a string payload (JSON or whatever app likes),
+//! app receives only topics it subscribed to, as events next frame.
+//! Messages app publishes are queued in [`Broadcast`] resource and go out at the end of the frame.
+//!
+//! Plugin can also guard against the app running in several tabs at once:
+//! tab which got hold of a Web Lock is [`InstanceState::Primary`], the rest are [`InstanceState::Secondary`]
+//! and wait for the lock, so one of them takes over once the primary tab is gone.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use bevy::prelude::*;
+use js_sys::{Function, Object, Promise, Reflect};
+use wasm_bindgen::prelude::{Closure, JsCast, JsValue};
+use web_sys::{BroadcastChannel, MessageEvent};
+
+use super::{current_app, wake_app, AppId, BridgeReceive, BridgeSchedules, BridgeSend};
+
+thread_local! {
+    static CHANNELS: RefCell<HashMap<AppId, Channel>> = RefCell::new(HashMap::new());
+    // Locks are held by the worker for as long as it lives, apps rebuilt later pick their state up from here.
+    static LOCKS: RefCell<HashMap<String, InstanceState>> = RefCell::new(HashMap::new());
+}
+
+/// Publish and receive messages on a `BroadcastChannel`, optionally guarding against several running instances.
+pub struct BroadcastChannelPlugin {
+    /// Name of the channel, tabs talk to each other only on the same one.
+    pub channel: String,
+    /// Name of Web Lock held by the primary instance, `None` disables the guard.
+    pub single_instance: Option<String>,
+}
+
+impl BroadcastChannelPlugin {
+    pub fn new(channel: &str) -> Self {
+        BroadcastChannelPlugin {
+            channel: channel.to_owned(),
+            single_instance: None,
+        }
+    }
+
+    /// Let only one tab be the primary instance, see [`InstanceState`].
+    pub fn single_instance(mut self, lock: &str) -> Self {
+        self.single_instance = Some(lock.to_owned());
+        self
+    }
+}
+
+impl Plugin for BroadcastChannelPlugin {
+    fn build(&self, app: &mut App) {
+        let id = current_app();
+
+        let instance = match &self.single_instance {
+            Some(lock) => acquire(lock),
+            None => InstanceState::Primary,
+        };
+
+        let channel = match BroadcastChannel::new(&self.channel) {
+            Ok(channel) => Some(channel),
+            Err(err) => {
+                warn!(
+                    "broadcast channel `{}` is unavailable: {err:?}",
+                    self.channel
+                );
+                None
+            }
+        };
+
+        let onmessage = Closure::wrap(Box::new(move |event: MessageEvent| {
+            let data = event.data();
+            let field = |name: &str| {
+                Reflect::get(&data, &name.into())
+                    .ok()
+                    .and_then(|value| value.as_string())
+            };
+            let (Some(topic), Some(payload)) = (field("topic"), field("payload")) else {
+                return;
+            };
+
+            with_channel(id, |channel| {
+                channel
+                    .incoming
+                    .push(Incoming::Message(BroadcastReceived { topic, payload }));
+            });
+            wake_app(id);
+        }) as Box<dyn FnMut(MessageEvent)>);
+        if let Some(channel) = &channel {
+            channel.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        }
+
+        // Channel of the app built before, e.g. one which lost its graphics context, goes away.
+        let previous = CHANNELS.with(|channels| {
+            channels.borrow_mut().insert(
+                id,
+                Channel {
+                    channel,
+                    lock: self.single_instance.clone(),
+                    incoming: Vec::new(),
+                    _onmessage: onmessage,
+                },
+            )
+        });
+        if let Some(channel) = previous.and_then(|previous| previous.channel) {
+            // Callback is dropped with the channel, it must not be invoked afterwards.
+            channel.set_onmessage(None);
+            channel.close();
+        }
+
+        let schedules = BridgeSchedules::of(app);
+
+        app.insert_resource(Broadcast {
+            subscriptions: HashSet::new(),
+            outgoing: VecDeque::new(),
+            instance,
+        })
+        .add_event::<BroadcastReceived>()
+        .add_event::<InstanceChanged>()
+        .add_systems(schedules.receive, receive.in_set(BridgeReceive))
+        .add_systems(schedules.send, flush.in_set(BridgeSend));
+    }
+}
+
+/// Whether this tab is the one instance allowed to run, see [`BroadcastChannelPlugin::single_instance`].
+///
+/// Without the guard, or if browser doesn't support Web Locks, every tab is primary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstanceState {
+    /// Lock is being requested.
+    Checking,
+    /// This tab holds the lock.
+    Primary,
+    /// Another tab holds the lock, this one takes over once it is gone.
+    Secondary,
+}
+
+/// Message published by another tab on a subscribed topic.
+#[derive(Event, Debug, Clone)]
+pub struct BroadcastReceived {
+    pub topic: String,
+    pub payload: String,
+}
+
+/// This tab became primary or secondary instance.
+#[derive(Event, Debug, Clone)]
+pub struct InstanceChanged {
+    pub state: InstanceState,
+}
+
+/// Access to the channel.
+#[derive(Resource, Debug)]
+pub struct Broadcast {
+    subscriptions: HashSet<String>,
+    outgoing: VecDeque<(String, String)>,
+    instance: InstanceState,
+}
+
+impl Broadcast {
+    /// Receive messages published on the topic from now on.
+    pub fn subscribe(&mut self, topic: &str) {
+        self.subscriptions.insert(topic.to_owned());
+    }
+
+    pub fn unsubscribe(&mut self, topic: &str) {
+        self.subscriptions.remove(topic);
+    }
+
+    /// Queue message to be published to other tabs at the end of the frame.
+    ///
+    /// Tab publishing the message doesn't receive it.
+    pub fn publish(&mut self, topic: &str, payload: &str) {
+        self.outgoing
+            .push_back((topic.to_owned(), payload.to_owned()));
+    }
+
+    pub fn instance(&self) -> InstanceState {
+        self.instance
+    }
+
+    /// Whether this tab holds the instance lock, always `true` without the guard.
+    pub fn is_primary(&self) -> bool {
+        self.instance == InstanceState::Primary
+    }
+}
+
+struct Channel {
+    // `None` if channel couldn't be opened, instance guard still works then.
+    channel: Option<BroadcastChannel>,
+    lock: Option<String>,
+    incoming: Vec<Incoming>,
+    _onmessage: Closure<dyn FnMut(MessageEvent)>,
+}
+
+enum Incoming {
+    Instance(InstanceState),
+    Message(BroadcastReceived),
+}
+
+fn with_channel<T>(app: AppId, f: impl FnOnce(&mut Channel) -> T) -> Option<T> {
+    CHANNELS.with(|channels| channels.borrow_mut().get_mut(&app).map(f))
+}
+
+/// Start requesting the lock unless worker does already, returns its current state.
+fn acquire(lock: &str) -> InstanceState {
+    let known = LOCKS.with(|locks| locks.borrow().get(lock).copied());
+    if let Some(state) = known {
+        return state;
+    }
+    LOCKS.with(|locks| {
+        locks
+            .borrow_mut()
+            .insert(lock.to_owned(), InstanceState::Checking)
+    });
+
+    let name = lock.to_owned();
+    let onlock = Closure::once_into_js(move |held: JsValue| -> JsValue {
+        if held.is_null() {
+            set_instance(&name, InstanceState::Secondary);
+            // Wait for the lock in line with other secondary tabs.
+            if let Err(err) = request_lock(&name, false, hold(name.clone())) {
+                warn!("failed to wait for instance lock `{name}`: {err:?}");
+            }
+            return JsValue::UNDEFINED;
+        }
+
+        set_instance(&name, InstanceState::Primary);
+        keep_forever()
+    });
+
+    match request_lock(lock, true, onlock) {
+        Ok(()) => InstanceState::Checking,
+        Err(err) => {
+            warn!("Web Locks are unavailable, assuming single instance: {err:?}");
+            LOCKS.with(|locks| {
+                locks
+                    .borrow_mut()
+                    .insert(lock.to_owned(), InstanceState::Primary)
+            });
+            InstanceState::Primary
+        }
+    }
+}
+
+/// Callback becoming primary once lock is granted.
+fn hold(name: String) -> JsValue {
+    Closure::once_into_js(move |_: JsValue| -> JsValue {
+        set_instance(&name, InstanceState::Primary);
+        keep_forever()
+    })
+}
+
+/// Lock is released once promise returned from its callback settles, this one never does.
+fn keep_forever() -> JsValue {
+    Promise::new(&mut |_, _| ()).into()
+}
+
+/// `navigator.locks.request`, web-sys doesn't expose Web Locks without unstable APIs.
+fn request_lock(name: &str, if_available: bool, callback: JsValue) -> Result<(), JsValue> {
+    let navigator = Reflect::get(&js_sys::global(), &"navigator".into())?;
+    let locks = Reflect::get(&navigator, &"locks".into())?;
+    let request: Function = Reflect::get(&locks, &"request".into())?.dyn_into()?;
+
+    let options = Object::new();
+    Reflect::set(&options, &"ifAvailable".into(), &if_available.into())?;
+    request.call3(&locks, &name.into(), &options, &callback)?;
+
+    Ok(())
+}
+
+/// Record new state of the lock and tell every app guarded by it.
+fn set_instance(lock: &str, state: InstanceState) {
+    LOCKS.with(|locks| locks.borrow_mut().insert(lock.to_owned(), state));
+
+    let apps: Vec<_> = CHANNELS.with(|channels| {
+        let mut channels = channels.borrow_mut();
+        channels
+            .iter_mut()
+            .filter(|(_, channel)| channel.lock.as_deref() == Some(lock))
+            .map(|(app, channel)| {
+                channel.incoming.push(Incoming::Instance(state));
+                *app
+            })
+            .collect()
+    });
+
+    for app in apps {
+        wake_app(app);
+    }
+}
+
+fn receive(
+    mut broadcast: ResMut<Broadcast>,
+    mut received: EventWriter<BroadcastReceived>,
+    mut changed: EventWriter<InstanceChanged>,
+) {
+    let incoming = with_channel(current_app(), |channel| {
+        std::mem::take(&mut channel.incoming)
+    });
+
+    for incoming in incoming.into_iter().flatten() {
+        match incoming {
+            Incoming::Instance(state) => {
+                if broadcast.instance != state {
+                    broadcast.instance = state;
+                    changed.send(InstanceChanged { state });
+                }
+            }
+            Incoming::Message(message) => {
+                if broadcast.subscriptions.contains(&message.topic) {
+                    received.send(message);
+                }
+            }
+        }
+    }
+}
+
+fn flush(mut broadcast: ResMut<Broadcast>) {
+    if broadcast.outgoing.is_empty() {
+        return;
+    }
+
+    let outgoing = std::mem::take(&mut broadcast.outgoing);
+    with_channel(current_app(), |channel| {
+        let Some(channel) = &channel.channel else {
+            return;
+        };
+
+        for (topic, payload) in outgoing {
+            let message = Object::new();
+            // Setting a field on a fresh object cannot fail.
+            let _ = Reflect::set(&message, &"topic".into(), &topic.into());
+            let _ = Reflect::set(&message, &"payload".into(), &payload.into());
+
+            if let Err(err) = channel.post_message(&message) {
+                warn!("failed to publish broadcast message: {err:?}");
+            }
+        }
+    });
+}
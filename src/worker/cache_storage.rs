@@ -0,0 +1,188 @@
+//! Assets kept in Cache Storage, so the game keeps working offline after the first load.
+//!
+//! Unlike [`AssetCache`](super::asset_cache::AssetCache), assets are served from cache without asking the server,
+//! so deployments have to bump [`CacheStorageSettings::cache_name`] for players to pick up changed assets.
+//! Cache is the same one service worker sees: it can precache assets under the same name,
+//! or clean up caches of old deployments on activation.
+//! Worker's own fetches go through the page's service worker as well, if there is one.
+//!
+//! Page can drop the cache with [`HostMessage::ClearAssetCache`](crate::protocol::HostMessage::ClearAssetCache).
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bevy::asset::{AssetIo, AssetIoError, ChangeWatcher, Metadata};
+use bevy::prelude::*;
+use bevy::utils::BoxedFuture;
+use js_sys::{ArrayBuffer, Uint8Array};
+use wasm_bindgen::prelude::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Cache, Response};
+
+use super::scope;
+
+/// Where assets are cached.
+#[derive(Debug, Clone)]
+pub struct CacheStorageSettings {
+    /// Name of the cache, include build version to start afresh with every deployment.
+    pub cache_name: String,
+    /// URL asset paths are resolved against.
+    ///
+    /// `None` picks asset folder at page origin.
+    pub base_url: Option<String>,
+}
+
+impl Default for CacheStorageSettings {
+    fn default() -> Self {
+        CacheStorageSettings {
+            cache_name: "bevy-assets-v1".to_owned(),
+            base_url: None,
+        }
+    }
+}
+
+/// Handle to the cache.
+///
+/// Inserted by [`InMemoryAssetPlugin`](super::inmem::InMemoryAssetPlugin) when offline caching is enabled.
+#[derive(Resource, Debug, Clone)]
+pub struct OfflineAssetCache {
+    settings: Arc<CacheStorageSettings>,
+}
+
+impl OfflineAssetCache {
+    pub fn new(settings: CacheStorageSettings) -> Self {
+        OfflineAssetCache {
+            settings: Arc::new(settings),
+        }
+    }
+
+    /// Delete the cache.
+    ///
+    /// Happens in background, assets being loaded at the moment may still end up cached.
+    pub fn clear(&self) {
+        let name = self.settings.cache_name.clone();
+
+        wasm_bindgen_futures::spawn_local(async move {
+            let result = async {
+                JsFuture::from(scope().caches()?.delete(&name)).await?;
+                Ok::<_, JsValue>(())
+            };
+
+            if let Err(err) = result.await {
+                warn!("failed to clear offline asset cache: {err:?}");
+            }
+        });
+    }
+
+    /// Get asset from cache, or from the network putting it into cache.
+    ///
+    /// Returns `None` if server doesn't know about the asset.
+    async fn fetch(&self, url: &str) -> Result<Option<Vec<u8>>, JsValue> {
+        let cache: Cache = JsFuture::from(scope().caches()?.open(&self.settings.cache_name))
+            .await?
+            .dyn_into()?;
+
+        let cached = JsFuture::from(cache.match_with_str(url)).await?;
+        if !cached.is_undefined() {
+            return bytes(cached.dyn_into()?).await.map(Some);
+        }
+
+        let response: Response = JsFuture::from(scope().fetch_with_str(url))
+            .await?
+            .dyn_into()?;
+
+        if response.status() == 404 {
+            return Ok(None);
+        }
+
+        if !response.ok() {
+            return Err(JsValue::from_str(&format!(
+                "server responded with {}",
+                response.status()
+            )));
+        }
+
+        // Body can only be read once, cache gets a copy.
+        if let Err(err) = JsFuture::from(cache.put_with_str(url, &response.clone()?)).await {
+            warn!("failed to cache asset {url}: {err:?}");
+        }
+
+        bytes(response).await.map(Some)
+    }
+}
+
+async fn bytes(response: Response) -> Result<Vec<u8>, JsValue> {
+    let buffer: ArrayBuffer = JsFuture::from(response.array_buffer()?).await?.dyn_into()?;
+
+    Ok(Uint8Array::new(&buffer).to_vec())
+}
+
+/// Asset source checking [`OfflineAssetCache`] before hitting the network.
+///
+/// Falls back to another source if Cache Storage is unusable, e.g. on insecure origins.
+pub struct CachedAssetReader {
+    cache: OfflineAssetCache,
+    base_url: String,
+    fallback: Box<dyn AssetIo>,
+}
+
+impl CachedAssetReader {
+    pub fn new(cache: OfflineAssetCache, asset_folder: &str, fallback: Box<dyn AssetIo>) -> Self {
+        let base_url = match &cache.settings.base_url {
+            Some(url) => url.clone(),
+            None => {
+                let origin = scope().location().origin();
+                format!("{origin}/{asset_folder}")
+            }
+        };
+
+        CachedAssetReader {
+            cache,
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            fallback,
+        }
+    }
+}
+
+impl AssetIo for CachedAssetReader {
+    fn load_path<'a>(&'a self, path: &'a Path) -> BoxedFuture<'a, Result<Vec<u8>, AssetIoError>> {
+        Box::pin(async move {
+            let Some(relative) = path.to_str() else {
+                return self.fallback.load_path(path).await;
+            };
+            let url = format!("{}/{}", self.base_url, relative.trim_start_matches('/'));
+
+            match self.cache.fetch(&url).await {
+                Ok(Some(bytes)) => Ok(bytes),
+                Ok(None) => Err(AssetIoError::NotFound(path.to_owned())),
+                Err(err) => {
+                    warn!("offline asset cache failed for {url}: {err:?}");
+                    self.fallback.load_path(path).await
+                }
+            }
+        })
+    }
+
+    fn read_directory(
+        &self,
+        path: &Path,
+    ) -> Result<Box<dyn Iterator<Item = PathBuf>>, AssetIoError> {
+        self.fallback.read_directory(path)
+    }
+
+    fn get_metadata(&self, path: &Path) -> Result<Metadata, AssetIoError> {
+        self.fallback.get_metadata(path)
+    }
+
+    fn watch_path_for_changes(
+        &self,
+        to_watch: &Path,
+        to_reload: Option<PathBuf>,
+    ) -> Result<(), AssetIoError> {
+        self.fallback.watch_path_for_changes(to_watch, to_reload)
+    }
+
+    fn watch_for_changes(&self, configuration: &ChangeWatcher) -> Result<(), AssetIoError> {
+        self.fallback.watch_for_changes(configuration)
+    }
+}
@@ -0,0 +1,53 @@
+//! What page and worker support, see [`CapabilityReport`].
+//!
+//! Worker probes itself and reports the result in its ready message,
+//! page answers with the full report, which arrives together with boot flags ahead of any app.
+
+use std::cell::Cell;
+
+use bevy::prelude::*;
+
+pub use crate::protocol::{Capabilities, CapabilityReport};
+
+thread_local! {
+    static WORKER: Cell<Option<Capabilities>> = Cell::new(None);
+    static PAGE: Cell<Option<Capabilities>> = Cell::new(None);
+}
+
+/// Capabilities of the worker context, probed on the first call.
+pub fn worker_capabilities() -> Capabilities {
+    WORKER.with(|cell| match cell.get() {
+        Some(capabilities) => capabilities,
+        None => {
+            let capabilities = Capabilities::probe();
+            cell.set(Some(capabilities));
+            capabilities
+        }
+    })
+}
+
+/// Capabilities of both sides.
+///
+/// Page side is all `false` until page reports it, which is always the case with `mock-page` feature.
+pub fn capabilities() -> CapabilityReport {
+    CapabilityReport {
+        page: PAGE.with(Cell::get).unwrap_or_default(),
+        worker: worker_capabilities(),
+    }
+}
+
+pub(super) fn set_report(report: CapabilityReport) {
+    PAGE.with(|cell| cell.set(Some(report.page)));
+}
+
+/// Expose [`CapabilityReport`] as a resource.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins).
+#[derive(Default)]
+pub struct CapabilitiesPlugin;
+
+impl Plugin for CapabilitiesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(capabilities());
+    }
+}
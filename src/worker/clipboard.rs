@@ -0,0 +1,60 @@
+//! System clipboard access.
+//!
+//! Clipboard API is only available to the page, so worker asks page to copy text
+//! and to read it back, the latter arriving as [`ClipboardPasted`] event some frames later.
+
+use bevy::prelude::*;
+
+use super::{post, take_messages, BridgeReceive, BridgeSchedules};
+use crate::protocol::{HostMessage, WorkerMessage};
+
+/// Copy and paste text through the page.
+#[derive(Default)]
+pub struct ClipboardPlugin;
+
+impl Plugin for ClipboardPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.init_resource::<Clipboard>()
+            .add_event::<ClipboardPasted>()
+            .add_systems(schedules.receive, receive_paste.in_set(BridgeReceive));
+    }
+}
+
+/// Access to system clipboard.
+///
+/// Browsers may refuse either operation without recent user input,
+/// so call these in response to a key press or a click.
+#[derive(Resource, Debug, Default)]
+pub struct Clipboard;
+
+impl Clipboard {
+    /// Put text into clipboard.
+    ///
+    /// Failures are only logged on the page, there is nothing app can do about them.
+    pub fn copy(&self, text: &str) {
+        post(&WorkerMessage::ClipboardCopy(text.to_owned()));
+    }
+
+    /// Read text from clipboard, it arrives with [`ClipboardPasted`].
+    pub fn request_paste(&self) {
+        post(&WorkerMessage::ClipboardPasteRequest);
+    }
+}
+
+/// Clipboard contents, or reason they could not be read.
+#[derive(Event, Debug, Clone)]
+pub struct ClipboardPasted {
+    pub result: Result<String, String>,
+}
+
+fn receive_paste(mut pasted: EventWriter<ClipboardPasted>) {
+    take_messages(|msg| match msg {
+        HostMessage::ClipboardPaste(result) => {
+            pasted.send(ClipboardPasted { result });
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
@@ -0,0 +1,237 @@
+//! Tuning values loaded from a config asset.
+//!
+//! Config file is split into named sections, each deserialized into its own resource.
+//! File is reloaded whenever asset server notices it changed
+//! or page asks for it with [`HostMessage::ReloadConfig`].
+
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::{TypePath, TypeUuid};
+use bevy::utils::BoxedFuture;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use super::{take_messages, BridgeReceive, BridgeSchedules};
+use crate::protocol::HostMessage;
+
+/// Parsed config file.
+///
+/// Both RON (`*.config.ron`) and JSON (`*.config.json`) files are understood,
+/// top level must be a map (or in RON a struct) of sections.
+#[derive(Debug, TypeUuid, TypePath)]
+#[uuid = "4f6c2b0e-8d3a-4c57-9a1e-2f61b7c0d953"]
+pub struct ConfigFile {
+    sections: Map<String, Value>,
+}
+
+impl ConfigFile {
+    /// Deserialize named section, `None` if there is no such section.
+    pub fn section<T: DeserializeOwned>(&self, name: &str) -> Option<Result<T, serde_json::Error>> {
+        let section = self.sections.get(name)?;
+        Some(serde_json::from_value(section.clone()))
+    }
+}
+
+#[derive(Default)]
+struct ConfigLoader;
+
+impl AssetLoader for ConfigLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let is_json = load_context
+                .path()
+                .extension()
+                .map_or(false, |extension| extension == "json");
+
+            let sections = parse(bytes, is_json)?;
+
+            load_context.set_default_asset(LoadedAsset::new(ConfigFile { sections }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["config.ron", "config.json"]
+    }
+}
+
+/// Sections of config file.
+///
+/// RON is read into its own value first, since top level there is normally an unnamed struct
+/// rather than a map, then turned into JSON so sections of both formats deserialize the same way.
+fn parse(bytes: &[u8], is_json: bool) -> Result<Map<String, Value>, bevy::asset::Error> {
+    let value = if is_json {
+        serde_json::from_slice(bytes)?
+    } else {
+        let value: ron::Value = ron::de::from_bytes(bytes)?;
+        serde_json::to_value(value)?
+    };
+
+    match value {
+        Value::Object(sections) => Ok(sections),
+        _ => Err(bevy::asset::Error::msg(
+            "top level of config file must be a map of sections",
+        )),
+    }
+}
+
+/// Handle to config file in use.
+#[derive(Resource, Debug, Clone)]
+pub struct ConfigHandle(pub Handle<ConfigFile>);
+
+#[derive(Resource)]
+struct ConfigPath(String);
+
+/// Load config file and keep section resources in sync with it.
+///
+/// Section resources start out with default values and get overwritten once the file is loaded.
+/// Invalid sections are reported and leave resource as is.
+pub struct ConfigPlugin {
+    path: String,
+    sections: Vec<Box<dyn Fn(&mut App) + Send + Sync>>,
+}
+
+impl ConfigPlugin {
+    /// Config at given asset path.
+    pub fn new(path: &str) -> Self {
+        ConfigPlugin {
+            path: path.to_owned(),
+            sections: Vec::new(),
+        }
+    }
+
+    /// Deserialize named section into resource `T`.
+    pub fn section<T>(mut self, name: &'static str) -> Self
+    where
+        T: Resource + DeserializeOwned + Default,
+    {
+        self.sections.push(Box::new(move |app| {
+            app.init_resource::<T>()
+                .add_systems(Update, apply_section::<T>(name));
+        }));
+        self
+    }
+}
+
+impl Default for ConfigPlugin {
+    fn default() -> Self {
+        ConfigPlugin::new("app.config.ron")
+    }
+}
+
+impl Plugin for ConfigPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.add_asset::<ConfigFile>()
+            .init_asset_loader::<ConfigLoader>()
+            .insert_resource(ConfigPath(self.path.clone()))
+            .add_systems(Startup, load_config)
+            .add_systems(schedules.receive, receive_reload.in_set(BridgeReceive));
+
+        for section in &self.sections {
+            section(app);
+        }
+    }
+}
+
+fn load_config(mut commands: Commands, path: Res<ConfigPath>, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ConfigHandle(asset_server.load(path.0.as_str())));
+}
+
+fn receive_reload(path: Res<ConfigPath>, asset_server: Res<AssetServer>) {
+    let requests = take_messages(|msg| match msg {
+        HostMessage::ReloadConfig => Ok(()),
+        msg => Err(msg),
+    });
+
+    if !requests.is_empty() {
+        asset_server.reload_asset(path.0.as_str());
+    }
+}
+
+fn apply_section<T>(
+    name: &'static str,
+) -> impl FnMut(
+    EventReader<AssetEvent<ConfigFile>>,
+    Res<Assets<ConfigFile>>,
+    Option<Res<ConfigHandle>>,
+    Commands,
+)
+where
+    T: Resource + DeserializeOwned,
+{
+    move |mut events, configs, handle, mut commands| {
+        let Some(handle) = handle else {
+            return;
+        };
+
+        let changed = events.iter().any(|event| match event {
+            AssetEvent::Created { handle: changed } | AssetEvent::Modified { handle: changed } => {
+                *changed == handle.0
+            }
+            AssetEvent::Removed { .. } => false,
+        });
+
+        if !changed {
+            return;
+        }
+
+        match configs
+            .get(&handle.0)
+            .and_then(|config| config.section::<T>(name))
+        {
+            Some(Ok(section)) => commands.insert_resource(section),
+            Some(Err(err)) => warn!("invalid config section `{name}`: {err}"),
+            None => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Shapes {
+        spin_speed: f32,
+    }
+
+    #[test]
+    fn shipped_config() {
+        let sections = parse(include_bytes!("../../assets/app.config.ron"), false).unwrap();
+        let config = ConfigFile { sections };
+
+        let shapes = config.section::<Shapes>("shapes").unwrap().unwrap();
+        assert_eq!(shapes, Shapes { spin_speed: 0.5 });
+        assert!(config.section::<Shapes>("missing").is_none());
+    }
+
+    #[test]
+    fn ron_map_and_json() {
+        let ron = parse(br#"{ "shapes": (spin_speed: 2.0) }"#, false).unwrap();
+        let json = parse(br#"{ "shapes": { "spin_speed": 2.0 } }"#, true).unwrap();
+
+        assert_eq!(ron, json);
+    }
+
+    #[test]
+    fn invalid_section() {
+        let sections = parse(b"(shapes: (spin_speed: \"fast\"))", false).unwrap();
+        let config = ConfigFile { sections };
+
+        assert!(config.section::<Shapes>("shapes").unwrap().is_err());
+    }
+
+    #[test]
+    fn top_level_must_be_map() {
+        assert!(parse(b"[1, 2]", true).is_err());
+        assert!(parse(b"42", false).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! Commands typed into browser console and run inside the app.
+//!
+//! Page sends a line with [`HostMessage::ConsoleCommand`], e.g. `bevy.command("spin 2.5")`.
+//! First word picks the command from [`ConsoleCommands`], the rest are its arguments,
+//! split on whitespace with double quotes keeping words together.
+//! Whatever command returns goes back as [`WorkerMessage::ConsoleOutput`] and is printed by the page.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use bevy::prelude::*;
+
+use super::{post, take_messages, BridgeReceive, BridgeSchedules};
+use crate::protocol::{HostMessage, WorkerMessage};
+
+/// Run commands page sends.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins), comes with `help` command.
+#[derive(Default)]
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.init_resource::<ConsoleCommands>()
+            .add_systems(schedules.receive, receive.in_set(BridgeReceive));
+    }
+}
+
+type Handler = Box<dyn Fn(&mut World, &ConsoleArgs) -> Result<String, String> + Send + Sync>;
+
+/// Registry of console commands.
+///
+/// Register commands from plugins or startup systems:
+///
+/// ```ignore
+/// fn register_commands(mut console: ResMut<ConsoleCommands>) {
+///     console.register("spin", "spin <speed>: set spin speed", |world, args| {
+///         world.resource_mut::<SpinSpeed>().0 = args.get(0, "speed")?;
+///         Ok(String::new())
+///     });
+/// }
+/// ```
+#[derive(Resource, Default)]
+pub struct ConsoleCommands {
+    commands: BTreeMap<String, (String, Handler)>,
+}
+
+impl ConsoleCommands {
+    /// Add command, replacing one registered under the same name.
+    ///
+    /// `help` is shown by the `help` command, conventionally starting with usage.
+    pub fn register(
+        &mut self,
+        name: &str,
+        help: &str,
+        handler: impl Fn(&mut World, &ConsoleArgs) -> Result<String, String> + Send + Sync + 'static,
+    ) {
+        self.commands
+            .insert(name.to_owned(), (help.to_owned(), Box::new(handler)));
+    }
+
+    /// Remove command, if it was registered.
+    pub fn unregister(&mut self, name: &str) {
+        self.commands.remove(name);
+    }
+
+    /// Names of registered commands, in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.commands.keys().map(String::as_str)
+    }
+
+    fn help(&self) -> String {
+        let lines: Vec<_> = self
+            .commands
+            .iter()
+            .map(|(name, (help, _))| {
+                if help.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{name}: {help}")
+                }
+            })
+            .collect();
+
+        format!("help: list commands\n{}", lines.join("\n"))
+    }
+}
+
+/// Arguments of a console command, without the command name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsoleArgs {
+    args: Vec<String>,
+}
+
+impl ConsoleArgs {
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+
+    /// Raw argument at index.
+    pub fn raw(&self, index: usize) -> Option<&str> {
+        self.args.get(index).map(String::as_str)
+    }
+
+    /// Argument at index parsed as `T`, `name` is used in error messages.
+    pub fn get<T>(&self, index: usize, name: &str) -> Result<T, String>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.optional(index, name)?
+            .ok_or_else(|| format!("missing argument `{name}`"))
+    }
+
+    /// Same as [`get`](Self::get), but it's fine for argument to be missing.
+    pub fn optional<T>(&self, index: usize, name: &str) -> Result<Option<T>, String>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.raw(index)
+            .map(|arg| {
+                arg.parse()
+                    .map_err(|err| format!("invalid argument `{name}`: {err}"))
+            })
+            .transpose()
+    }
+
+    /// Arguments from index on, joined back with spaces.
+    pub fn rest(&self, from: usize) -> String {
+        self.args.get(from..).unwrap_or_default().join(" ")
+    }
+}
+
+/// Split line into words, double quotes keep spaces inside a word.
+fn split(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quoted = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if quoted {
+        return Err("unterminated quote".to_owned());
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+fn run(world: &mut World, line: &str) -> Result<String, String> {
+    let mut words = split(line)?.into_iter();
+    let Some(name) = words.next() else {
+        return Err("empty command".to_owned());
+    };
+    let args = ConsoleArgs {
+        args: words.collect(),
+    };
+
+    world.resource_scope(|world, commands: Mut<ConsoleCommands>| {
+        if name == "help" && !commands.commands.contains_key("help") {
+            return Ok(commands.help());
+        }
+
+        match commands.commands.get(&name) {
+            Some((_, handler)) => handler(world, &args),
+            None => Err(format!("unknown command `{name}`, try `help`")),
+        }
+    })
+}
+
+fn receive(world: &mut World) {
+    let lines = take_messages(|msg| match msg {
+        HostMessage::ConsoleCommand(line) => Ok(line),
+        msg => Err(msg),
+    });
+
+    for line in lines {
+        let result = run(world, &line);
+
+        post(&WorkerMessage::ConsoleOutput {
+            command: line,
+            result,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        split(line).unwrap()
+    }
+
+    #[test]
+    fn split_on_whitespace() {
+        assert_eq!(words("spawn  cube\t3 "), ["spawn", "cube", "3"]);
+        assert!(words("   ").is_empty());
+    }
+
+    #[test]
+    fn quotes_keep_spaces() {
+        assert_eq!(
+            words(r#"say "hello world" now"#),
+            ["say", "hello world", "now"]
+        );
+        assert_eq!(words(r#"name"d ab"c"#), ["named abc"]);
+    }
+
+    #[test]
+    fn empty_quotes_are_a_word() {
+        assert_eq!(words(r#"set title """#), ["set", "title", ""]);
+    }
+
+    #[test]
+    fn unterminated_quote() {
+        assert!(split(r#"say "hello"#).is_err());
+    }
+}
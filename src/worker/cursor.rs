@@ -0,0 +1,88 @@
+//! Cursor appearance over worker windows.
+//!
+//! Cursor belongs to the canvas element on the page, so changes to `Window::cursor`
+//! are turned into CSS `cursor` values and sent there.
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy::window::CursorIcon;
+
+use super::{post, BridgeSchedules, BridgeSend, Views};
+use crate::protocol::WorkerMessage;
+
+/// Apply cursor icon and visibility of windows to their canvases.
+#[derive(Default)]
+pub struct CursorPlugin;
+
+impl Plugin for CursorPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.add_systems(schedules.send, send_cursor.in_set(BridgeSend));
+    }
+}
+
+fn send_cursor(
+    views: Res<Views>,
+    windows: Query<&Window>,
+    mut sent: Local<HashMap<Entity, &'static str>>,
+) {
+    for (view, entity) in views.iter() {
+        let Ok(window) = windows.get(entity) else {
+            continue;
+        };
+
+        let cursor = if window.cursor.visible {
+            css_cursor(window.cursor.icon)
+        } else {
+            "none"
+        };
+
+        if sent.insert(entity, cursor) != Some(cursor) {
+            post(&WorkerMessage::SetCursor {
+                view,
+                cursor: cursor.to_owned(),
+            });
+        }
+    }
+}
+
+fn css_cursor(icon: CursorIcon) -> &'static str {
+    match icon {
+        CursorIcon::Default => "default",
+        CursorIcon::Crosshair => "crosshair",
+        CursorIcon::Hand => "pointer",
+        CursorIcon::Arrow => "default",
+        CursorIcon::Move => "move",
+        CursorIcon::Text => "text",
+        CursorIcon::Wait => "wait",
+        CursorIcon::Help => "help",
+        CursorIcon::Progress => "progress",
+        CursorIcon::NotAllowed => "not-allowed",
+        CursorIcon::ContextMenu => "context-menu",
+        CursorIcon::Cell => "cell",
+        CursorIcon::VerticalText => "vertical-text",
+        CursorIcon::Alias => "alias",
+        CursorIcon::Copy => "copy",
+        CursorIcon::NoDrop => "no-drop",
+        CursorIcon::Grab => "grab",
+        CursorIcon::Grabbing => "grabbing",
+        CursorIcon::AllScroll => "all-scroll",
+        CursorIcon::ZoomIn => "zoom-in",
+        CursorIcon::ZoomOut => "zoom-out",
+        CursorIcon::EResize => "e-resize",
+        CursorIcon::NResize => "n-resize",
+        CursorIcon::NeResize => "ne-resize",
+        CursorIcon::NwResize => "nw-resize",
+        CursorIcon::SResize => "s-resize",
+        CursorIcon::SeResize => "se-resize",
+        CursorIcon::SwResize => "sw-resize",
+        CursorIcon::WResize => "w-resize",
+        CursorIcon::EwResize => "ew-resize",
+        CursorIcon::NsResize => "ns-resize",
+        CursorIcon::NeswResize => "nesw-resize",
+        CursorIcon::NwseResize => "nwse-resize",
+        CursorIcon::ColResize => "col-resize",
+        CursorIcon::RowResize => "row-resize",
+    }
+}
@@ -0,0 +1,180 @@
+//! Live world statistics for the debug dashboard.
+//!
+//! Dashboard is a separate page (`/debug`), which cannot talk to a worker it didn't spawn,
+//! so both sides meet on a `BroadcastChannel` instead.
+//! Dashboard keeps pinging the channel while it is open and worker publishes snapshots
+//! of its world for as long as it does, nothing is sent when nobody is watching.
+//!
+//! Logs are not part of snapshots: Bevy's log plugin has no way to tap into them,
+//! they stay in worker's console.
+
+use std::cell::{Cell, RefCell};
+
+use bevy::core::FrameCount;
+use bevy::diagnostic::DiagnosticsStore;
+use bevy::prelude::*;
+use js_sys::Reflect;
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::{Closure, JsCast, JsValue};
+use web_sys::{BroadcastChannel, MessageEvent};
+
+use super::anomaly::{diagnostics_json, traffic_json};
+use super::{wake, BridgeMetrics, BridgeSchedules, BridgeSend};
+
+/// Dashboard stops receiving snapshots if it didn't ping for this long.
+const SUBSCRIPTION_MS: f64 = 3000.0;
+
+thread_local! {
+    static CHANNEL: RefCell<Option<BroadcastChannel>> = RefCell::new(None);
+    static SUBSCRIBED_AT: Cell<Option<f64>> = Cell::new(None);
+    static SCHEDULES_REQUESTED: Cell<bool> = Cell::new(false);
+}
+
+/// Publish world statistics to dashboard pages.
+pub struct DashboardPlugin {
+    /// Name of `BroadcastChannel` dashboard listens on.
+    pub channel: String,
+    /// Time between snapshots.
+    pub interval_ms: f64,
+}
+
+impl Default for DashboardPlugin {
+    fn default() -> Self {
+        DashboardPlugin {
+            channel: "bevy-worker-dashboard".to_owned(),
+            interval_ms: 500.0,
+        }
+    }
+}
+
+impl Plugin for DashboardPlugin {
+    fn build(&self, app: &mut App) {
+        let channel = match BroadcastChannel::new(&self.channel) {
+            Ok(channel) => channel,
+            Err(err) => {
+                warn!("debug dashboard is unavailable: {err:?}");
+                return;
+            }
+        };
+
+        let onmessage = Closure::wrap(Box::new(|event: MessageEvent| {
+            let kind = Reflect::get(&event.data(), &"kind".into())
+                .ok()
+                .and_then(|kind| kind.as_string());
+
+            match kind.as_deref() {
+                Some("subscribe") => {
+                    SUBSCRIBED_AT.with(|at| at.set(Some(js_sys::Date::now())));
+                    // Idle reactive app would otherwise have nothing to show.
+                    wake();
+                }
+                Some("request_schedules") => {
+                    SCHEDULES_REQUESTED.with(|requested| requested.set(true));
+                    wake();
+                }
+                _ => (),
+            }
+        }) as Box<dyn Fn(MessageEvent)>);
+
+        channel.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        onmessage.forget();
+        CHANNEL.with(|cell| *cell.borrow_mut() = Some(channel));
+
+        let schedules = BridgeSchedules::of(app);
+
+        app.insert_resource(Dashboard {
+            // Several workers may be publishing on the same channel, e.g. from different tabs.
+            id: format!("{:08x}", (js_sys::Math::random() * u32::MAX as f64) as u32),
+            interval_ms: self.interval_ms,
+            published_at: None,
+        })
+        .add_systems(schedules.send, publish.in_set(BridgeSend));
+    }
+}
+
+#[derive(Resource)]
+struct Dashboard {
+    id: String,
+    interval_ms: f64,
+    published_at: Option<f64>,
+}
+
+fn publish(world: &mut World) {
+    let now = js_sys::Date::now();
+
+    let subscribed = SUBSCRIBED_AT
+        .with(Cell::get)
+        .map_or(false, |at| now - at < SUBSCRIPTION_MS);
+    if !subscribed {
+        return;
+    }
+
+    let dashboard = world.resource::<Dashboard>();
+    let id = dashboard.id.clone();
+
+    if SCHEDULES_REQUESTED.with(|requested| requested.replace(false)) {
+        send(json!({
+            "kind": "schedules",
+            "worker": id,
+            "schedules": schedules_json(world),
+        }));
+    }
+
+    let due = dashboard
+        .published_at
+        .map_or(true, |at| now - at >= dashboard.interval_ms);
+    if !due {
+        return;
+    }
+
+    world.resource_mut::<Dashboard>().published_at = Some(now);
+
+    send(json!({
+        "kind": "snapshot",
+        "worker": id,
+        "at": now,
+        "frame": world.resource::<FrameCount>().0,
+        "entities": world.entities().len(),
+        "archetypes": world.archetypes().len(),
+        "components": world.components().len(),
+        "diagnostics": diagnostics_json(world.resource::<DiagnosticsStore>()),
+        "traffic": traffic_json(world.resource::<BridgeMetrics>()),
+    }));
+}
+
+/// Systems of every schedule, in no particular order.
+///
+/// Schedules which are running at the moment, e.g. `Main`, are taken out of the world and don't show up.
+fn schedules_json(world: &World) -> Value {
+    let Some(schedules) = world.get_resource::<Schedules>() else {
+        return Value::Array(Vec::new());
+    };
+
+    schedules
+        .iter()
+        .map(|(label, schedule)| {
+            let systems: Vec<_> = schedule
+                .graph()
+                .systems()
+                .map(|(_, system, _)| system.name().to_string())
+                .collect();
+
+            json!({
+                "name": format!("{label:?}"),
+                "systems": systems,
+            })
+        })
+        .collect()
+}
+
+fn send(message: Value) {
+    CHANNEL.with(|channel| {
+        let Some(channel) = &*channel.borrow() else {
+            return;
+        };
+
+        if let Err(err) = channel.post_message(&JsValue::from_str(&message.to_string())) {
+            warn!("failed to publish dashboard snapshot: {err:?}");
+        }
+    });
+}
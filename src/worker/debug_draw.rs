@@ -0,0 +1,82 @@
+//! Debug geometry drawn on behalf of the page.
+//!
+//! Shapes arrive with [`HostMessage::DebugDraw`] and are drawn with gizmos,
+//! so page devtools can mark things up in the worker-rendered scene.
+
+use bevy::prelude::*;
+
+use super::{take_messages, BridgeReceive, BridgeSchedules};
+use crate::protocol::{DebugShape, HostMessage};
+
+/// Draw shapes sent by the page.
+#[derive(Default)]
+pub struct DebugDrawPlugin;
+
+impl Plugin for DebugDrawPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.init_resource::<DebugDrawQueue>()
+            .add_systems(schedules.receive, receive_shapes.in_set(BridgeReceive))
+            .add_systems(Update, draw_shapes);
+    }
+}
+
+/// Shapes currently on screen.
+#[derive(Resource, Default)]
+pub struct DebugDrawQueue {
+    // Shapes with time they expire at, in seconds of app time.
+    shapes: Vec<(DebugShape, f32)>,
+}
+
+impl DebugDrawQueue {
+    pub fn clear(&mut self) {
+        self.shapes.clear();
+    }
+}
+
+fn receive_shapes(time: Res<Time>, mut queue: ResMut<DebugDrawQueue>) {
+    let now = time.elapsed_seconds();
+
+    take_messages(|msg| match msg {
+        HostMessage::DebugDraw {
+            shapes,
+            duration_ms,
+        } => {
+            let expires_at = now + duration_ms as f32 / 1000.0;
+            queue
+                .shapes
+                .extend(shapes.into_iter().map(|shape| (shape, expires_at)));
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
+
+fn draw_shapes(time: Res<Time>, mut queue: ResMut<DebugDrawQueue>, mut gizmos: Gizmos) {
+    for (shape, _) in &queue.shapes {
+        match *shape {
+            DebugShape::Line { start, end, color } => {
+                gizmos.line(start.into(), end.into(), color.into());
+            }
+            DebugShape::Circle {
+                center,
+                radius,
+                color,
+            } => {
+                gizmos.circle(center.into(), Vec3::Z, radius, color.into());
+            }
+            DebugShape::Rect {
+                center,
+                size,
+                color,
+            } => {
+                gizmos.rect(center.into(), Quat::IDENTITY, size.into(), color.into());
+            }
+        }
+    }
+
+    // Shapes are drawn at least once, even if they expired before reaching the app.
+    let now = time.elapsed_seconds();
+    queue.shapes.retain(|(_, expires_at)| *expires_at > now);
+}
@@ -0,0 +1,123 @@
+//! Depth buffer configuration and 2.5D layering of sprites.
+//!
+//! Bevy's pipelines fix the depth format to `Depth32Float` without a stencil aspect,
+//! so what can be configured is how the buffer is cleared and whether shaders may sample it.
+//!
+//! 2D cameras have no depth buffer at all and draw sprites in order of their Z coordinate.
+//! [`YSort`] derives it from Y, so things lower on the screen are drawn over things above them,
+//! which is what top-down games usually want.
+
+use bevy::core_pipeline::core_3d::{Camera3dDepthLoadOp, Camera3dDepthTextureUsage};
+use bevy::prelude::*;
+use bevy::render::render_resource::TextureUsages;
+use bevy::transform::TransformSystem;
+
+/// Apply depth buffer settings to every 3D camera.
+///
+/// Settings are applied as cameras are spawned and again every time [`DepthSettings`] change,
+/// overwriting whatever camera had before.
+#[derive(Default)]
+pub struct DepthPlugin {
+    pub settings: DepthSettings,
+}
+
+impl Plugin for DepthPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone())
+            .add_systems(PostUpdate, apply_depth_settings);
+    }
+}
+
+/// How depth buffer of 3D cameras is set up.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct DepthSettings {
+    /// Value depth buffer is cleared to before camera renders.
+    ///
+    /// `None` keeps depth left by the camera rendered before, so e.g. an overlay camera
+    /// can be occluded by the scene. Bevy uses reversed Z, `0.0` is infinitely far away.
+    pub clear: Option<f32>,
+    /// Allow shaders to sample depth texture, e.g. for soft particles or outlines.
+    ///
+    /// Some WebGL implementations can't do it together with multisampling.
+    pub readable: bool,
+}
+
+impl Default for DepthSettings {
+    fn default() -> Self {
+        DepthSettings {
+            clear: Some(0.0),
+            readable: false,
+        }
+    }
+}
+
+fn apply_depth_settings(settings: Res<DepthSettings>, mut cameras: Query<&mut Camera3d>) {
+    let usage = if settings.readable {
+        TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING
+    } else {
+        TextureUsages::RENDER_ATTACHMENT
+    };
+
+    for mut camera in &mut cameras {
+        if !settings.is_changed() && !camera.is_added() {
+            continue;
+        }
+
+        camera.depth_load_op = match settings.clear {
+            Some(depth) => Camera3dDepthLoadOp::Clear(depth),
+            None => Camera3dDepthLoadOp::Load,
+        };
+        camera.depth_texture_usages = Camera3dDepthTextureUsage::from(usage);
+    }
+}
+
+/// Keep Z coordinate of sprites derived from their Y coordinate.
+pub struct YSortPlugin;
+
+impl Plugin for YSortPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<YSortScale>().add_systems(
+            PostUpdate,
+            y_sort.before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Derive Z coordinate of the entity from its Y coordinate within given layer.
+///
+/// Resulting Z is `layer - y * scale`, where scale is [`YSortScale`].
+/// Layers draw over each other as usual, entities within a layer are sorted by Y,
+/// as long as layers are spaced further apart than the range of Y times scale.
+///
+/// Only local transform is looked at, so it is meant for entities without a parent.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct YSort {
+    pub layer: f32,
+}
+
+/// How much Z changes per unit of Y for [`YSort`] entities.
+///
+/// Default keeps a layer within `[-0.5, 0.5]` for Y within `±5000`.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct YSortScale(pub f32);
+
+impl Default for YSortScale {
+    fn default() -> Self {
+        YSortScale(1e-4)
+    }
+}
+
+fn y_sort(scale: Res<YSortScale>, mut query: Query<(Ref<YSort>, &mut Transform)>) {
+    for (sort, mut transform) in &mut query {
+        if !sort.is_changed() && !transform.is_changed() && !scale.is_changed() {
+            continue;
+        }
+
+        let z = sort.layer - transform.translation.y * scale.0;
+
+        // Writing the same value would mark transform changed and have it sorted again next frame.
+        if transform.translation.z != z {
+            transform.translation.z = z;
+        }
+    }
+}
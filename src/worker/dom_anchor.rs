@@ -0,0 +1,131 @@
+//! Screen positions of entities for HTML elements the page lays over the canvas,
+//! e.g. labels or health bars which look crisper as text than as rendered sprites.
+//!
+//! Positions are taken from the camera with the highest order rendering to view's window,
+//! in pixels of canvas drawing buffer, same as IME position.
+//! Cameras rendering at [`VirtualResolution`] count as rendering to the window they are upscaled to.
+//! Anchors outside of camera's viewport are out of sight.
+//! Only anchors which moved, appeared or went out of sight are sent, at most once per frame and view,
+//! see [`WorkerMessage::DomAnchors`].
+//! Page side is [`WorkerHandle::anchor`](crate::host::WorkerHandle::anchor).
+
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::utils::HashMap;
+use bevy::window::{PrimaryWindow, WindowRef};
+
+use super::virtual_resolution::{Redirected, Upscale, VirtualResolution};
+use super::{post, BridgeSchedules, BridgeSend, Views};
+use crate::coords::LogicalPx;
+use crate::protocol::{ViewId, WorkerMessage};
+
+/// Stream positions of [`DomAnchor`] entities to the page.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins) for apps with a window.
+#[derive(Default)]
+pub struct DomAnchorPlugin;
+
+impl Plugin for DomAnchorPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.add_systems(schedules.send, send_anchors.in_set(BridgeSend));
+    }
+}
+
+/// Entity page places an HTML element over, element is registered on the page under the same id.
+#[derive(Component, Debug, Clone)]
+pub struct DomAnchor {
+    pub id: String,
+    /// Point anchored to, relative to the entity, e.g. above character's head.
+    pub offset: Vec3,
+}
+
+impl DomAnchor {
+    pub fn new(id: &str) -> Self {
+        DomAnchor {
+            id: id.to_owned(),
+            offset: Vec3::ZERO,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Vec3) -> Self {
+        self.offset = offset;
+        self
+    }
+}
+
+fn send_anchors(
+    views: Res<Views>,
+    resolution: Option<Res<VirtualResolution>>,
+    primary: Query<Entity, With<PrimaryWindow>>,
+    windows: Query<&Window>,
+    cameras: Query<(&Camera, &GlobalTransform, Option<&Redirected>), Without<Upscale>>,
+    anchors: Query<(&DomAnchor, &GlobalTransform)>,
+    mut sent: Local<HashMap<ViewId, HashMap<String, Option<Vec2>>>>,
+) {
+    for (view, window_entity) in views.iter() {
+        let Ok(window) = windows.get(window_entity) else {
+            continue;
+        };
+        let camera = cameras
+            .iter()
+            .filter(|(camera, _, redirected)| {
+                let target = match (&camera.target, redirected) {
+                    (_, Some(Redirected(window))) => Some(*window),
+                    (RenderTarget::Window(WindowRef::Primary), _) => primary.get_single().ok(),
+                    (RenderTarget::Window(WindowRef::Entity(window)), _) => Some(*window),
+                    _ => None,
+                };
+                target == Some(window_entity)
+            })
+            .max_by_key(|(camera, _, _)| camera.order);
+
+        let current: HashMap<String, Option<Vec2>> = anchors
+            .iter()
+            .map(|(anchor, transform)| {
+                let position = camera.and_then(|(camera, camera_transform, redirected)| {
+                    let world = transform.transform_point(anchor.offset);
+                    let viewport = camera.logical_viewport_rect()?;
+                    let position = camera.world_to_viewport(camera_transform, world)?;
+                    // Points in front of the camera, but off to the side are out of sight just as well.
+                    if !Rect::from_corners(Vec2::ZERO, viewport.size()).contains(position) {
+                        return None;
+                    }
+                    let mut position = position + viewport.min;
+                    // Redirected camera renders in virtual pixels, which are then upscaled to the window.
+                    if redirected.is_some() {
+                        position = resolution.as_ref()?.to_window(window, position);
+                    }
+                    Some(LogicalPx(position).to_physical(window.scale_factor()).0)
+                });
+                (anchor.id.clone(), position)
+            })
+            .collect();
+
+        let previous = sent.entry(view).or_default();
+        let mut changed: Vec<(String, Option<(f32, f32)>)> = current
+            .iter()
+            .filter(|(id, position)| previous.get(*id) != Some(*position))
+            .map(|(id, position)| (id.clone(), position.map(|position| position.into())))
+            .collect();
+        // Anchors which are gone hide their elements.
+        changed.extend(
+            previous
+                .iter()
+                .filter(|(id, position)| position.is_some() && !current.contains_key(*id))
+                .map(|(id, _)| (id.clone(), None)),
+        );
+
+        *previous = current;
+
+        if !changed.is_empty() {
+            post(&WorkerMessage::DomAnchors {
+                view,
+                anchors: changed,
+            });
+        }
+    }
+
+    sent.retain(|view, _| views.window(*view).is_some());
+}
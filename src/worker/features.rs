@@ -0,0 +1,95 @@
+//! Named switches page can flip at runtime.
+//!
+//! Toggles are mirrored back to the page whenever they change,
+//! so it always knows what is currently enabled.
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use super::{post, take_messages, BridgeReceive, BridgeSchedules, BridgeSend};
+use crate::protocol::{HostMessage, WorkerMessage};
+
+/// Toggle enabling input latency test scene.
+pub const LATENCY_TEST: &str = "latency_test";
+
+/// Toggle enabling deuteranopia simulation filter.
+pub const DEUTERANOPIA: &str = "deuteranopia";
+
+/// Toggle enabling protanopia simulation filter.
+pub const PROTANOPIA: &str = "protanopia";
+
+/// Toggle enabling high-contrast filter.
+pub const HIGH_CONTRAST: &str = "high_contrast";
+
+/// Toggle enabling automatic reports on slow frames.
+pub const ANOMALY_CAPTURE: &str = "anomaly_capture";
+
+/// Named on/off switches.
+///
+/// Toggles which were never set are considered disabled.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct FeatureToggles {
+    toggles: BTreeMap<String, bool>,
+}
+
+impl FeatureToggles {
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.toggles.get(name).copied().unwrap_or(false)
+    }
+
+    pub fn set(&mut self, name: &str, enabled: bool) {
+        self.toggles.insert(name.to_owned(), enabled);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, bool)> + '_ {
+        self.toggles
+            .iter()
+            .map(|(name, enabled)| (name.as_str(), *enabled))
+    }
+}
+
+/// Run condition: feature toggle is enabled.
+pub fn feature_enabled(name: &'static str) -> impl FnMut(Res<FeatureToggles>) -> bool + Clone {
+    move |toggles: Res<FeatureToggles>| toggles.is_enabled(name)
+}
+
+/// Keep [`FeatureToggles`] in sync with the page.
+#[derive(Default)]
+pub struct FeatureTogglesPlugin;
+
+impl Plugin for FeatureTogglesPlugin {
+    fn build(&self, app: &mut App) {
+        use bevy::ecs::schedule::common_conditions::resource_changed;
+
+        let schedules = BridgeSchedules::of(app);
+
+        app.init_resource::<FeatureToggles>()
+            .add_systems(schedules.receive, receive_toggles.in_set(BridgeReceive))
+            .add_systems(
+                schedules.send,
+                send_toggles
+                    .run_if(resource_changed::<FeatureToggles>())
+                    .in_set(BridgeSend),
+            );
+    }
+}
+
+fn receive_toggles(mut toggles: ResMut<FeatureToggles>) {
+    take_messages(|msg| match msg {
+        HostMessage::SetFeature { name, enabled } => {
+            toggles.set(&name, enabled);
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
+
+fn send_toggles(toggles: Res<FeatureToggles>) {
+    let features = toggles
+        .iter()
+        .map(|(name, enabled)| (name.to_owned(), enabled))
+        .collect();
+
+    post(&WorkerMessage::Features(features));
+}
@@ -0,0 +1,73 @@
+//! Files dropped onto page canvases.
+//!
+//! Page reads every dropped file and posts its contents with [`HostMessage::FileDropped`].
+//! Besides [`FileDropped`] carrying the bytes, app receives the usual [`FileDragAndDrop::DroppedFile`]
+//! pointing at `inmem://<name>`, so dropped images or scenes can be loaded with asset server right away.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use bevy::window::FileDragAndDrop;
+
+use super::inmem::{InMemoryAssets, SCHEME};
+use super::{take_messages, BridgeSchedules, InputInject, Views};
+use crate::protocol::HostMessage;
+
+/// Forward files dropped on page canvases into the app.
+///
+/// Works best together with [`InMemoryAssetPlugin`](super::inmem::InMemoryAssetPlugin),
+/// without it dropped files are only available through [`FileDropped`] events.
+#[derive(Default)]
+pub struct FileDropPlugin;
+
+impl Plugin for FileDropPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.add_event::<FileDropped>()
+            .add_systems(schedules.input, receive_drops.in_set(InputInject));
+    }
+}
+
+/// File was dropped onto a window.
+#[derive(Event, Debug, Clone)]
+pub struct FileDropped {
+    pub window: Entity,
+    /// File name, without any directories.
+    pub name: String,
+    pub bytes: Arc<[u8]>,
+}
+
+fn receive_drops(
+    views: Res<Views>,
+    files: Option<Res<InMemoryAssets>>,
+    mut dropped: EventWriter<FileDropped>,
+    mut drag_and_drop: EventWriter<FileDragAndDrop>,
+) {
+    take_messages(|msg| match msg {
+        HostMessage::FileDropped { view, name, bytes } => {
+            let Some(window) = views.window(view) else {
+                return Ok(());
+            };
+
+            let bytes: Arc<[u8]> = js_sys::Uint8Array::new(&bytes.into_inner()).to_vec().into();
+
+            if let Some(files) = &files {
+                files.insert(&name, bytes.clone());
+                drag_and_drop.send(FileDragAndDrop::DroppedFile {
+                    window,
+                    path_buf: PathBuf::from(format!("{SCHEME}{name}")),
+                });
+            }
+
+            dropped.send(FileDropped {
+                window,
+                name,
+                bytes,
+            });
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
@@ -0,0 +1,340 @@
+//! Full-screen accessibility filters.
+//!
+//! Color blindness simulation and high-contrast mode, applied as a post-process pass
+//! after tonemapping. Page switches them with feature toggles, at most one filter is active,
+//! with [`DEUTERANOPIA`] taking precedence over [`PROTANOPIA`] over [`HIGH_CONTRAST`].
+
+use bevy::asset::load_internal_asset;
+use bevy::core_pipeline::fullscreen_vertex_shader::fullscreen_shader_vertex_state;
+use bevy::core_pipeline::{core_2d, core_3d};
+use bevy::ecs::query::QueryItem;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::extract_component::{
+    ComponentUniforms, DynamicUniformIndex, ExtractComponent, ExtractComponentPlugin,
+    UniformComponentPlugin,
+};
+use bevy::render::render_graph::{
+    NodeRunError, RenderGraphApp, RenderGraphContext, ViewNode, ViewNodeRunner,
+};
+use bevy::render::render_resource::{
+    BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BufferBindingType, CachedRenderPipelineId,
+    ColorTargetState, ColorWrites, FragmentState, MultisampleState, Operations, PipelineCache,
+    PrimitiveState, RenderPassColorAttachment, RenderPassDescriptor, RenderPipelineDescriptor,
+    Sampler, SamplerBindingType, SamplerDescriptor, ShaderStages, ShaderType,
+    SpecializedRenderPipeline, SpecializedRenderPipelines, TextureFormat, TextureSampleType,
+    TextureViewDimension,
+};
+use bevy::render::renderer::{RenderContext, RenderDevice};
+use bevy::render::view::ViewTarget;
+use bevy::render::{Render, RenderApp, RenderSet};
+
+use super::features::{FeatureToggles, DEUTERANOPIA, HIGH_CONTRAST, PROTANOPIA};
+
+const FILTERS_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x3c1f_9b27_e8d4_5a60);
+
+/// Filter applied to everything camera renders.
+///
+/// Inserted onto cameras according to feature toggles, but can be managed by hand as well.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorFilter {
+    Deuteranopia,
+    Protanopia,
+    HighContrast,
+}
+
+impl ColorFilter {
+    fn from_toggles(toggles: &FeatureToggles) -> Option<Self> {
+        if toggles.is_enabled(DEUTERANOPIA) {
+            Some(ColorFilter::Deuteranopia)
+        } else if toggles.is_enabled(PROTANOPIA) {
+            Some(ColorFilter::Protanopia)
+        } else if toggles.is_enabled(HIGH_CONTRAST) {
+            Some(ColorFilter::HighContrast)
+        } else {
+            None
+        }
+    }
+}
+
+impl ExtractComponent for ColorFilter {
+    type Query = &'static Self;
+    type Filter = ();
+    type Out = FilterUniform;
+
+    fn extract_component(filter: QueryItem<'_, Self::Query>) -> Option<Self::Out> {
+        // Must agree with `filters.wgsl`.
+        let mode = match filter {
+            ColorFilter::Deuteranopia => 1,
+            ColorFilter::Protanopia => 2,
+            ColorFilter::HighContrast => 3,
+        };
+
+        Some(FilterUniform {
+            mode,
+            _padding: Vec3::ZERO,
+        })
+    }
+}
+
+#[doc(hidden)]
+#[derive(Component, Clone, Copy, ShaderType)]
+pub struct FilterUniform {
+    mode: u32,
+    // WebGL2 requires uniforms to be 16 byte aligned.
+    _padding: Vec3,
+}
+
+/// Apply [`ColorFilter`] to cameras.
+#[derive(Default)]
+pub struct FiltersPlugin;
+
+impl Plugin for FiltersPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            FILTERS_SHADER_HANDLE,
+            "filters.wgsl",
+            Shader::from_wgsl
+        );
+
+        app.add_plugins((
+            ExtractComponentPlugin::<ColorFilter>::default(),
+            UniformComponentPlugin::<FilterUniform>::default(),
+        ))
+        .add_systems(PostUpdate, apply_toggles);
+
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app
+            .init_resource::<SpecializedRenderPipelines<FilterPipeline>>()
+            .add_systems(Render, prepare_pipelines.in_set(RenderSet::Prepare))
+            .add_render_graph_node::<ViewNodeRunner<FilterNode>>(
+                core_2d::graph::NAME,
+                FilterNode::NAME,
+            )
+            .add_render_graph_edges(
+                core_2d::graph::NAME,
+                &[
+                    core_2d::graph::node::TONEMAPPING,
+                    FilterNode::NAME,
+                    core_2d::graph::node::END_MAIN_PASS_POST_PROCESSING,
+                ],
+            )
+            .add_render_graph_node::<ViewNodeRunner<FilterNode>>(
+                core_3d::graph::NAME,
+                FilterNode::NAME,
+            )
+            .add_render_graph_edges(
+                core_3d::graph::NAME,
+                &[
+                    core_3d::graph::node::TONEMAPPING,
+                    FilterNode::NAME,
+                    core_3d::graph::node::END_MAIN_PASS_POST_PROCESSING,
+                ],
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.init_resource::<FilterPipeline>();
+    }
+}
+
+fn apply_toggles(
+    mut commands: Commands,
+    toggles: Res<FeatureToggles>,
+    cameras: Query<(Entity, Option<&ColorFilter>), With<Camera>>,
+) {
+    let filter = ColorFilter::from_toggles(&toggles);
+
+    for (entity, current) in &cameras {
+        if current == filter.as_ref() {
+            continue;
+        }
+
+        match filter {
+            Some(filter) => commands.entity(entity).insert(filter),
+            None => commands.entity(entity).remove::<ColorFilter>(),
+        };
+    }
+}
+
+#[derive(Default)]
+struct FilterNode;
+
+impl FilterNode {
+    const NAME: &'static str = "accessibility_filter";
+}
+
+impl ViewNode for FilterNode {
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static DynamicUniformIndex<FilterUniform>,
+        &'static ViewFilterPipeline,
+    );
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        (view_target, uniform_index, view_pipeline): QueryItem<Self::ViewQuery>,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let filter_pipeline = world.resource::<FilterPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+
+        // Shader may still be compiling.
+        let Some(pipeline) = pipeline_cache.get_render_pipeline(view_pipeline.0) else {
+            return Ok(());
+        };
+
+        let uniforms = world.resource::<ComponentUniforms<FilterUniform>>();
+        let Some(uniforms) = uniforms.uniforms().binding() else {
+            return Ok(());
+        };
+
+        let post_process = view_target.post_process_write();
+
+        let bind_group = render_context
+            .render_device()
+            .create_bind_group(&BindGroupDescriptor {
+                label: Some("accessibility_filter_bind_group"),
+                layout: &filter_pipeline.layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(post_process.source),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(&filter_pipeline.sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: uniforms,
+                    },
+                ],
+            });
+
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("accessibility_filter_pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: post_process.destination,
+                resolve_target: None,
+                ops: Operations::default(),
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_render_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[uniform_index.index()]);
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+}
+
+/// Pipeline for the format camera renders to, which differs once HDR is on.
+#[derive(Component)]
+struct ViewFilterPipeline(CachedRenderPipelineId);
+
+fn prepare_pipelines(
+    mut commands: Commands,
+    pipeline_cache: Res<PipelineCache>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<FilterPipeline>>,
+    filter_pipeline: Res<FilterPipeline>,
+    views: Query<(Entity, &ViewTarget), With<FilterUniform>>,
+) {
+    for (entity, view_target) in &views {
+        let pipeline_id = pipelines.specialize(
+            &pipeline_cache,
+            &filter_pipeline,
+            view_target.main_texture_format(),
+        );
+        commands
+            .entity(entity)
+            .insert(ViewFilterPipeline(pipeline_id));
+    }
+}
+
+#[derive(Resource)]
+struct FilterPipeline {
+    layout: BindGroupLayout,
+    sampler: Sampler,
+}
+
+impl FromWorld for FilterPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("accessibility_filter_bind_group_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: Some(FilterUniform::min_size()),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let sampler = render_device.create_sampler(&SamplerDescriptor::default());
+
+        FilterPipeline { layout, sampler }
+    }
+}
+
+impl SpecializedRenderPipeline for FilterPipeline {
+    type Key = TextureFormat;
+
+    fn specialize(&self, format: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("accessibility_filter_pipeline".into()),
+            layout: vec![self.layout.clone()],
+            vertex: fullscreen_shader_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: FILTERS_SHADER_HANDLE.typed(),
+                shader_defs: vec![],
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            push_constant_ranges: vec![],
+        }
+    }
+}
@@ -0,0 +1,50 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader FullscreenVertexOutput
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var texture_sampler: sampler;
+
+struct FilterSettings {
+    mode: u32,
+    // WebGL2 requires uniforms to be 16 byte aligned.
+    _padding: vec3<f32>,
+}
+@group(0) @binding(2) var<uniform> settings: FilterSettings;
+
+// Simulation matrices from Machado et al. 2009, full severity, applied to linear RGB.
+fn simulate(rgb: vec3<f32>, r: vec3<f32>, g: vec3<f32>, b: vec3<f32>) -> vec3<f32> {
+    return clamp(vec3(dot(r, rgb), dot(g, rgb), dot(b, rgb)), vec3(0.0), vec3(1.0));
+}
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(screen_texture, texture_sampler, in.uv);
+    var rgb = color.rgb;
+
+    switch settings.mode {
+        // Deuteranopia
+        case 1u: {
+            rgb = simulate(
+                rgb,
+                vec3(0.367322, 0.860646, -0.227968),
+                vec3(0.280085, 0.672501, 0.047413),
+                vec3(-0.011820, 0.042940, 0.968881),
+            );
+        }
+        // Protanopia
+        case 2u: {
+            rgb = simulate(
+                rgb,
+                vec3(0.152286, 1.052583, -0.204868),
+                vec3(0.114503, 0.786281, 0.099216),
+                vec3(-0.003882, -0.048116, 1.051998),
+            );
+        }
+        // High contrast
+        case 3u: {
+            rgb = clamp((rgb - vec3(0.5)) * 1.8 + vec3(0.5), vec3(0.0), vec3(1.0));
+        }
+        default: {}
+    }
+
+    return vec4(rgb, color.a);
+}
@@ -0,0 +1,61 @@
+//! Tell the page when app presents its first frame.
+//!
+//! Until then canvas is blank or shows garbage, so page keeps its splash screen up,
+//! see [`WorkerBuilder::splash`](crate::host::WorkerBuilder::splash).
+
+use bevy::prelude::*;
+use bevy::render::renderer::render_system;
+use bevy::render::view::ExtractedWindows;
+use bevy::render::{Render, RenderApp, RenderSet};
+
+use super::post;
+use crate::protocol::WorkerMessage;
+
+/// Post [`WorkerMessage::FirstFrameRendered`] once app presents a frame.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins) for apps with a window, needs `RenderPlugin`.
+#[derive(Default)]
+pub struct FirstFramePlugin;
+
+impl Plugin for FirstFramePlugin {
+    fn build(&self, app: &mut App) {
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.init_resource::<FirstFrame>().add_systems(
+            Render,
+            (
+                check_presenting.before(render_system),
+                announce.after(render_system),
+            )
+                .in_set(RenderSet::Render),
+        );
+    }
+}
+
+#[derive(Resource, Default)]
+struct FirstFrame {
+    // Window got a surface texture this frame, so render system presents it.
+    presenting: bool,
+    announced: bool,
+}
+
+fn check_presenting(windows: Res<ExtractedWindows>, mut first_frame: ResMut<FirstFrame>) {
+    if first_frame.announced {
+        return;
+    }
+
+    first_frame.presenting = windows
+        .values()
+        .any(|window| window.swap_chain_texture.is_some());
+}
+
+fn announce(mut first_frame: ResMut<FirstFrame>) {
+    if first_frame.announced || !first_frame.presenting {
+        return;
+    }
+
+    first_frame.announced = true;
+    post(&WorkerMessage::FirstFrameRendered);
+}
@@ -0,0 +1,88 @@
+//! Page focus, forwarded from `focus` and `blur` events of the page, see [`HostMessage::Focus`].
+//!
+//! Keys and mouse buttons held while page loses focus never get released events,
+//! so they are released here instead of staying stuck until pressed again.
+//! Focus is reported on every view window as [`WindowFocused`] events,
+//! which game code can listen to for pausing itself.
+
+use bevy::input::keyboard::{KeyboardInput, ScanCode};
+use bevy::input::mouse::MouseButtonInput;
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy::window::WindowFocused;
+
+use super::{take_messages, BridgeSchedules, InputInject, Views};
+use crate::protocol::HostMessage;
+
+/// Track page focus on view windows and release held input once it is gone.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins) for apps with a window.
+#[derive(Default)]
+pub struct FocusPlugin;
+
+impl Plugin for FocusPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.add_systems(schedules.input, receive_focus.in_set(InputInject));
+    }
+}
+
+fn receive_focus(
+    views: Res<Views>,
+    keys: Res<Input<KeyCode>>,
+    scan_codes: Res<Input<ScanCode>>,
+    buttons: Res<Input<MouseButton>>,
+    mut windows: Query<&mut Window>,
+    mut focused_events: EventWriter<WindowFocused>,
+    mut keyboard: EventWriter<KeyboardInput>,
+    mut mouse: EventWriter<MouseButtonInput>,
+) {
+    take_messages(|msg| match msg {
+        HostMessage::Focus { focused } => {
+            for (_, entity) in views.iter() {
+                let Ok(mut window) = windows.get_mut(entity) else {
+                    continue;
+                };
+
+                if window.focused != focused {
+                    window.focused = focused;
+                    focused_events.send(WindowFocused {
+                        window: entity,
+                        focused,
+                    });
+                }
+            }
+
+            if focused {
+                return Ok(());
+            }
+
+            // Release through events rather than on resources directly,
+            // so systems reading input events see it too and `just_released` survives input systems.
+            let Some((_, window)) = views.iter().next() else {
+                return Ok(());
+            };
+            keyboard.send_batch(keys.get_pressed().map(|&key_code| KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key_code),
+                state: ButtonState::Released,
+                window,
+            }));
+            keyboard.send_batch(scan_codes.get_pressed().map(|scan_code| KeyboardInput {
+                scan_code: scan_code.0,
+                key_code: None,
+                state: ButtonState::Released,
+                window,
+            }));
+            mouse.send_batch(buttons.get_pressed().map(|&button| MouseButtonInput {
+                button,
+                state: ButtonState::Released,
+                window,
+            }));
+
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
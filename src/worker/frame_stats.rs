@@ -0,0 +1,97 @@
+//! Frame rate of the app, forwarded to the page for a stats overlay.
+//!
+//! Numbers come from `FrameTimeDiagnosticsPlugin`, smoothed the same way `LogDiagnosticsPlugin` shows them.
+//! Nothing is sent until page turns stats on with [`HostMessage::SetFrameStats`].
+
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::ecs::entity::Entities;
+use bevy::prelude::*;
+
+use super::{post, take_messages, BridgeReceive, BridgeSchedules, BridgeSend};
+use crate::protocol::{FrameStats, HostMessage, WorkerMessage};
+
+/// Send [`FrameStats`] to the page while it asks for them.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins), requires `FrameTimeDiagnosticsPlugin`.
+pub struct FrameStatsPlugin {
+    /// How often stats are sent.
+    pub interval_secs: f64,
+}
+
+impl Default for FrameStatsPlugin {
+    fn default() -> Self {
+        FrameStatsPlugin {
+            interval_secs: 0.25,
+        }
+    }
+}
+
+impl Plugin for FrameStatsPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.insert_resource(FrameStatsSettings {
+            interval_secs: self.interval_secs,
+            enabled: false,
+            last_sent: None,
+        })
+        .add_systems(schedules.receive, receive_commands.in_set(BridgeReceive))
+        .add_systems(schedules.send, send.in_set(BridgeSend));
+    }
+}
+
+#[derive(Resource)]
+struct FrameStatsSettings {
+    interval_secs: f64,
+    enabled: bool,
+    last_sent: Option<f64>,
+}
+
+fn receive_commands(mut settings: ResMut<FrameStatsSettings>) {
+    take_messages(|msg| match msg {
+        HostMessage::SetFrameStats(enabled) => {
+            settings.enabled = enabled;
+            settings.last_sent = None;
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
+
+fn send(
+    time: Res<Time>,
+    diagnostics: Res<DiagnosticsStore>,
+    entities: &Entities,
+    mut settings: ResMut<FrameStatsSettings>,
+) {
+    if !settings.enabled {
+        return;
+    }
+
+    // Intervals are in real time, paused or slowed down app still reports.
+    let now = time.raw_elapsed_seconds_f64();
+    if let Some(last_sent) = settings.last_sent {
+        if now - last_sent < settings.interval_secs {
+            return;
+        }
+    }
+
+    let smoothed = |id| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.smoothed())
+    };
+    let (Some(fps), Some(frame_time_ms)) = (
+        smoothed(FrameTimeDiagnosticsPlugin::FPS),
+        smoothed(FrameTimeDiagnosticsPlugin::FRAME_TIME),
+    ) else {
+        return;
+    };
+
+    settings.last_sent = Some(now);
+    post(&WorkerMessage::FrameStats(FrameStats {
+        fps,
+        frame_time_ms,
+        entities: entities.len(),
+    }));
+}
@@ -0,0 +1,120 @@
+//! Fullscreen for worker windows.
+//!
+//! Worker has no say over page layout, so it follows `WindowMode` of its windows
+//! and asks page to put their canvas in fullscreen or take it out.
+//! Page reports what actually happened, including canvas changing size,
+//! which keeps `Window::mode` and resolution accurate even when user leaves fullscreen by themselves.
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy::window::WindowMode;
+
+use super::{post, take_messages, BridgeReceive, BridgeSchedules, BridgeSend, Views};
+use crate::protocol::{HostMessage, WorkerMessage};
+
+/// Put views in fullscreen when their window mode asks for it.
+///
+/// Every mode other than `Windowed` means fullscreen, browsers don't distinguish between them.
+#[derive(Default)]
+pub struct FullscreenPlugin;
+
+impl Plugin for FullscreenPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.init_resource::<Fullscreen>()
+            .add_systems(schedules.receive, receive_fullscreen.in_set(BridgeReceive))
+            .add_systems(schedules.send, request_fullscreen.in_set(BridgeSend));
+    }
+}
+
+/// Window which is in fullscreen, as reported by the page.
+#[derive(Resource, Default)]
+struct Fullscreen {
+    window: Option<Entity>,
+}
+
+fn receive_fullscreen(
+    views: Res<Views>,
+    mut fullscreen: ResMut<Fullscreen>,
+    mut windows: Query<&mut Window>,
+) {
+    take_messages(|msg| match msg {
+        HostMessage::Resize {
+            view,
+            width,
+            height,
+        } => {
+            let window = views
+                .window(view)
+                .and_then(|entity| windows.get_mut(entity).ok());
+
+            if let Some(mut window) = window {
+                window
+                    .resolution
+                    .set_physical_resolution(width.max(1), height.max(1));
+            }
+
+            Ok(())
+        }
+        HostMessage::FullscreenChanged {
+            view,
+            fullscreen: now,
+        } => {
+            let Some(entity) = views.window(view) else {
+                return Ok(());
+            };
+
+            if now {
+                fullscreen.window = Some(entity);
+            } else if fullscreen.window == Some(entity) {
+                fullscreen.window = None;
+            }
+
+            // Keep mode in line with reality, so fullscreen left by user is not requested again.
+            if let Ok(mut window) = windows.get_mut(entity) {
+                match (now, window.mode) {
+                    (true, WindowMode::Windowed) => window.mode = WindowMode::BorderlessFullscreen,
+                    (false, WindowMode::Windowed) | (true, _) => (),
+                    (false, _) => window.mode = WindowMode::Windowed,
+                }
+            }
+
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
+
+fn request_fullscreen(
+    views: Res<Views>,
+    fullscreen: Res<Fullscreen>,
+    windows: Query<(Entity, &Window)>,
+    mut requested: Local<HashMap<Entity, WindowMode>>,
+) {
+    for (entity, window) in &windows {
+        let mode = window.mode;
+        let previous = requested
+            .insert(entity, mode)
+            .unwrap_or(WindowMode::Windowed);
+        if previous == mode {
+            continue;
+        }
+
+        let wanted = mode != WindowMode::Windowed;
+        if wanted == (fullscreen.window == Some(entity)) {
+            continue;
+        }
+
+        let view = views
+            .iter()
+            .find_map(|(view, window)| (window == entity).then_some(view));
+
+        if let Some(view) = view {
+            post(&WorkerMessage::SetFullscreen {
+                view,
+                fullscreen: wanted,
+            });
+        }
+    }
+}
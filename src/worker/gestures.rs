@@ -0,0 +1,453 @@
+//! Double clicks, long presses and drags recognized from pointer input forwarded by the page,
+//! as well as two finger pinches and rotations.
+//!
+//! Gestures are built from the same [`MouseButtonInput`], [`CursorMoved`] and touch events app gets,
+//! so they come out of recorded input on replay as well.
+//! Positions are in logical pixels of the window, as cursor position is.
+
+use std::time::Duration;
+
+use bevy::input::mouse::MouseButtonInput;
+use bevy::input::{ButtonState, InputSystem};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy::window::CursorLeft;
+
+/// Emit gesture events from pointer input.
+///
+/// Not part of [`DefaultPlugins`](super::DefaultPlugins), add it when app needs gestures.
+#[derive(Default)]
+pub struct GesturePlugin {
+    pub settings: GestureSettings,
+}
+
+impl Plugin for GesturePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone())
+            .init_resource::<GestureState>()
+            .add_event::<DoubleClick>()
+            .add_event::<LongPress>()
+            .add_event::<DragStart>()
+            .add_event::<Drag>()
+            .add_event::<DragEnd>()
+            .add_event::<Pinch>()
+            .add_event::<Rotate>()
+            .add_systems(
+                PreUpdate,
+                (recognize_gestures, recognize_two_finger_gestures).after(InputSystem),
+            );
+    }
+}
+
+/// Thresholds gestures are told apart by, can be changed at runtime.
+#[derive(Resource, Debug, Clone)]
+pub struct GestureSettings {
+    /// Longest time between two clicks of a double click.
+    pub double_click_time: Duration,
+    /// Farthest second click of a double click can land from the first one, in logical pixels.
+    pub double_click_distance: f32,
+    /// How long button has to be held in place to make a long press.
+    pub long_press_time: Duration,
+    /// Distance pointer has to move with button held to start a drag, in logical pixels.
+    ///
+    /// Pointer moving less than that is taken for a shaky click.
+    pub drag_distance: f32,
+}
+
+impl Default for GestureSettings {
+    fn default() -> Self {
+        GestureSettings {
+            double_click_time: Duration::from_millis(500),
+            double_click_distance: 4.0,
+            long_press_time: Duration::from_millis(500),
+            drag_distance: 6.0,
+        }
+    }
+}
+
+/// Button was clicked twice in quick succession at about the same place.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DoubleClick {
+    pub window: Entity,
+    pub button: MouseButton,
+    pub position: Vec2,
+}
+
+/// Button was held in place for [`GestureSettings::long_press_time`].
+///
+/// Fires while button is still held, releasing it afterwards doesn't count as a click.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct LongPress {
+    pub window: Entity,
+    pub button: MouseButton,
+    pub position: Vec2,
+}
+
+/// Pointer moved past [`GestureSettings::drag_distance`] with button held.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DragStart {
+    pub window: Entity,
+    pub button: MouseButton,
+    /// Where button was pressed.
+    pub position: Vec2,
+}
+
+/// Pointer moved during a drag.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Drag {
+    pub window: Entity,
+    pub button: MouseButton,
+    pub position: Vec2,
+    /// Movement since previous [`Drag`] or [`DragStart`].
+    pub delta: Vec2,
+}
+
+/// Button was released or pointer left the window during a drag.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DragEnd {
+    pub window: Entity,
+    pub button: MouseButton,
+    pub position: Vec2,
+}
+
+/// Two fingers moved closer together or apart.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Pinch {
+    /// Distance between fingers relative to the previous frame, above 1 when they spread.
+    pub scale: f32,
+    /// Point between fingers.
+    pub center: Vec2,
+}
+
+/// Two fingers turned around each other.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Rotate {
+    /// Radians turned since the previous frame, counterclockwise on screen.
+    pub angle: f32,
+    /// Point between fingers.
+    pub center: Vec2,
+}
+
+#[derive(Resource, Default)]
+struct GestureState {
+    presses: HashMap<(Entity, MouseButton), Press>,
+    // Latest click of each window, with when it happened.
+    clicks: HashMap<Entity, (MouseButton, Vec2, Duration)>,
+    // Ids and positions of two fingers as of the previous frame.
+    fingers: Option<[(u64, Vec2); 2]>,
+}
+
+struct Press {
+    origin: Vec2,
+    last: Vec2,
+    at: Duration,
+    dragging: bool,
+    // Long press fired, so release isn't a click.
+    held: bool,
+}
+
+/// What released button turned out to be, plain clicks and long presses end with nothing to report.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Released {
+    Drag(Vec2),
+    DoubleClick(Vec2),
+}
+
+impl GestureState {
+    fn press(&mut self, key: (Entity, MouseButton), position: Vec2, now: Duration) {
+        self.presses.insert(
+            key,
+            Press {
+                origin: position,
+                last: position,
+                at: now,
+                dragging: false,
+                held: false,
+            },
+        );
+    }
+
+    fn release(
+        &mut self,
+        settings: &GestureSettings,
+        (window, button): (Entity, MouseButton),
+        cursor: Option<Vec2>,
+        now: Duration,
+    ) -> Option<Released> {
+        let press = self.presses.remove(&(window, button))?;
+        // Buttons are released without cursor when page loses focus.
+        let position = cursor.unwrap_or(press.last);
+
+        if press.dragging {
+            return Some(Released::Drag(position));
+        }
+        if press.held {
+            return None;
+        }
+
+        let double = match self.clicks.get(&window) {
+            Some(&(previous_button, previous, at)) => {
+                previous_button == button
+                    && now - at <= settings.double_click_time
+                    && previous.distance(position) <= settings.double_click_distance
+            }
+            None => false,
+        };
+        if double {
+            self.clicks.remove(&window);
+            Some(Released::DoubleClick(position))
+        } else {
+            self.clicks.insert(window, (button, position, now));
+            None
+        }
+    }
+
+    /// Presses held in place long enough, with where pointer is.
+    fn long_presses(
+        &mut self,
+        settings: &GestureSettings,
+        now: Duration,
+    ) -> Vec<((Entity, MouseButton), Vec2)> {
+        let mut held = Vec::new();
+        for (&key, press) in &mut self.presses {
+            if !press.dragging && !press.held && now - press.at >= settings.long_press_time {
+                press.held = true;
+                held.push((key, press.last));
+            }
+        }
+        held
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn recognize_gestures(
+    time: Res<Time>,
+    settings: Res<GestureSettings>,
+    mut state: ResMut<GestureState>,
+    windows: Query<&Window>,
+    mut buttons: EventReader<MouseButtonInput>,
+    mut moved: EventReader<CursorMoved>,
+    mut left: EventReader<CursorLeft>,
+    mut double_clicks: EventWriter<DoubleClick>,
+    mut long_presses: EventWriter<LongPress>,
+    mut drag_starts: EventWriter<DragStart>,
+    mut drags: EventWriter<Drag>,
+    mut drag_ends: EventWriter<DragEnd>,
+) {
+    let now = time.elapsed();
+    let state = &mut *state;
+
+    for event in buttons.iter() {
+        let cursor = windows
+            .get(event.window)
+            .ok()
+            .and_then(Window::cursor_position);
+        let key = (event.window, event.button);
+
+        match event.state {
+            ButtonState::Pressed => {
+                if let Some(position) = cursor {
+                    state.press(key, position, now);
+                }
+            }
+            ButtonState::Released => match state.release(&settings, key, cursor, now) {
+                Some(Released::Drag(position)) => drag_ends.send(DragEnd {
+                    window: event.window,
+                    button: event.button,
+                    position,
+                }),
+                Some(Released::DoubleClick(position)) => double_clicks.send(DoubleClick {
+                    window: event.window,
+                    button: event.button,
+                    position,
+                }),
+                None => (),
+            },
+        }
+    }
+
+    for event in moved.iter() {
+        for (&(window, button), press) in &mut state.presses {
+            if window != event.window {
+                continue;
+            }
+
+            if !press.dragging && press.origin.distance(event.position) >= settings.drag_distance {
+                press.dragging = true;
+                drag_starts.send(DragStart {
+                    window,
+                    button,
+                    position: press.origin,
+                });
+            }
+            if press.dragging {
+                drags.send(Drag {
+                    window,
+                    button,
+                    position: event.position,
+                    delta: event.position - press.last,
+                });
+            }
+            press.last = event.position;
+        }
+    }
+
+    // Releases outside of the window never arrive, so drags end when pointer leaves.
+    for event in left.iter() {
+        state.presses.retain(|&(window, button), press| {
+            if window != event.window {
+                return true;
+            }
+
+            if press.dragging {
+                drag_ends.send(DragEnd {
+                    window,
+                    button,
+                    position: press.last,
+                });
+            }
+            false
+        });
+    }
+
+    for ((window, button), position) in state.long_presses(&settings, now) {
+        long_presses.send(LongPress {
+            window,
+            button,
+            position,
+        });
+    }
+}
+
+fn recognize_two_finger_gestures(
+    touches: Res<Touches>,
+    mut state: ResMut<GestureState>,
+    mut pinches: EventWriter<Pinch>,
+    mut rotations: EventWriter<Rotate>,
+) {
+    let mut pressed = touches.iter();
+    let (Some(first), Some(second), None) = (pressed.next(), pressed.next(), pressed.next()) else {
+        state.fingers = None;
+        return;
+    };
+
+    // Order of touches isn't stable, keep the one fingers were seen in.
+    let mut fingers = [
+        (first.id(), first.position()),
+        (second.id(), second.position()),
+    ];
+    let previous = state.fingers;
+    if let Some([(id, _), _]) = previous {
+        if id == fingers[1].0 {
+            fingers.swap(0, 1);
+        }
+    }
+    state.fingers = Some(fingers);
+
+    let Some([(first_id, first_previous), (second_id, second_previous)]) = previous else {
+        return;
+    };
+    if (first_id, second_id) != (fingers[0].0, fingers[1].0) {
+        return;
+    }
+
+    let before = second_previous - first_previous;
+    let after = fingers[1].1 - fingers[0].1;
+    if before == after || before.length() < f32::EPSILON {
+        return;
+    }
+
+    let center = (fingers[0].1 + fingers[1].1) / 2.0;
+    let scale = after.length() / before.length();
+    if scale != 1.0 {
+        pinches.send(Pinch { scale, center });
+    }
+    // Window coordinates have Y pointing down, which flips the direction.
+    let angle = -before.angle_between(after);
+    if angle != 0.0 && angle.is_finite() {
+        rotations.send(Rotate { angle, center });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Entity = Entity::PLACEHOLDER;
+    const LEFT: (Entity, MouseButton) = (WINDOW, MouseButton::Left);
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn click(state: &mut GestureState, position: Vec2, at: Duration) -> Option<Released> {
+        let settings = GestureSettings::default();
+        state.press(LEFT, position, at);
+        state.release(&settings, LEFT, Some(position), at + ms(50))
+    }
+
+    #[test]
+    fn double_click() {
+        let mut state = GestureState::default();
+
+        assert_eq!(click(&mut state, Vec2::new(10.0, 10.0), ms(0)), None);
+        assert_eq!(
+            click(&mut state, Vec2::new(12.0, 10.0), ms(200)),
+            Some(Released::DoubleClick(Vec2::new(12.0, 10.0)))
+        );
+        // Third click starts over.
+        assert_eq!(click(&mut state, Vec2::new(12.0, 10.0), ms(400)), None);
+    }
+
+    #[test]
+    fn slow_or_distant_clicks_are_not_double() {
+        let mut state = GestureState::default();
+
+        assert_eq!(click(&mut state, Vec2::ZERO, ms(0)), None);
+        assert_eq!(click(&mut state, Vec2::ZERO, ms(1000)), None);
+        assert_eq!(click(&mut state, Vec2::new(20.0, 0.0), ms(1200)), None);
+    }
+
+    #[test]
+    fn different_buttons_are_not_double() {
+        let settings = GestureSettings::default();
+        let mut state = GestureState::default();
+        let right = (WINDOW, MouseButton::Right);
+
+        assert_eq!(click(&mut state, Vec2::ZERO, ms(0)), None);
+        state.press(right, Vec2::ZERO, ms(100));
+        assert_eq!(state.release(&settings, right, None, ms(150)), None);
+    }
+
+    #[test]
+    fn long_press_fires_once_and_is_not_a_click() {
+        let settings = GestureSettings::default();
+        let mut state = GestureState::default();
+
+        state.press(LEFT, Vec2::ONE, ms(0));
+        assert!(state.long_presses(&settings, ms(499)).is_empty());
+        assert_eq!(state.long_presses(&settings, ms(500)), [(LEFT, Vec2::ONE)]);
+        assert!(state.long_presses(&settings, ms(600)).is_empty());
+        assert_eq!(
+            state.release(&settings, LEFT, Some(Vec2::ONE), ms(700)),
+            None
+        );
+
+        // Long press doesn't count as the first click of a double click either.
+        assert_eq!(click(&mut state, Vec2::ONE, ms(800)), None);
+    }
+
+    #[test]
+    fn drags_are_not_long_presses() {
+        let settings = GestureSettings::default();
+        let mut state = GestureState::default();
+
+        state.press(LEFT, Vec2::ZERO, ms(0));
+        state.presses.get_mut(&LEFT).unwrap().dragging = true;
+        assert!(state.long_presses(&settings, ms(1000)).is_empty());
+        assert_eq!(
+            state.release(&settings, LEFT, None, ms(1100)),
+            Some(Released::Drag(Vec2::ZERO))
+        );
+    }
+}
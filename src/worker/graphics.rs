@@ -0,0 +1,220 @@
+//! MSAA, HDR and tonemapping tuned per device class by the page.
+//!
+//! Page picks them with boot flags or changes them later with [`HostMessage::SetGraphics`].
+//! Requests are checked against the adapter, since WebGL2 and WebGPU differ in what they can do:
+//! sample counts adapter can't multisample fall back to lower ones,
+//! and HDR is turned down where float textures can't be rendered to.
+//! Settings in effect are reported back with [`WorkerMessage::Graphics`].
+
+use bevy::core_pipeline::fxaa::Fxaa;
+use bevy::core_pipeline::tonemapping::Tonemapping;
+use bevy::prelude::*;
+use bevy::render::render_resource::{TextureFormat, TextureUsages};
+use bevy::render::renderer::RenderAdapter;
+use bevy::render::texture::BevyDefault;
+use bevy::render::view::ViewTarget;
+
+use super::antialiasing::{msaa_from_samples, msaa_samples, ActiveAntialiasing, Antialiasing};
+use super::boot::boot_flags;
+use super::{post, take_messages, BridgeReceive, BridgeSchedules};
+use crate::protocol::{GraphicsOptions, HostMessage, TonemappingMethod, WorkerMessage};
+
+/// Apply graphics settings from boot flags and the page to the app.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins) for apps with a window,
+/// must be added after [`AntialiasingPlugin`](super::antialiasing::AntialiasingPlugin).
+#[derive(Default)]
+pub struct GraphicsPlugin;
+
+impl Plugin for GraphicsPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.init_resource::<GraphicsOverrides>()
+            .add_systems(PostStartup, apply_boot_flags)
+            .add_systems(schedules.receive, receive_graphics.in_set(BridgeReceive))
+            .add_systems(PostUpdate, apply_to_cameras);
+    }
+}
+
+/// HDR and tonemapping page asked for, after checking them against the adapter.
+///
+/// Settings left `None` are up to the app, otherwise they are forced onto every camera.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GraphicsOverrides {
+    pub hdr: Option<bool>,
+    pub tonemapping: Option<Tonemapping>,
+}
+
+fn apply_boot_flags(
+    mut commands: Commands,
+    adapter: Option<Res<RenderAdapter>>,
+    mut overrides: ResMut<GraphicsOverrides>,
+    mut msaa: ResMut<Msaa>,
+    mut active: ResMut<ActiveAntialiasing>,
+    fxaa_cameras: Query<Entity, With<Fxaa>>,
+) {
+    apply(
+        boot_flags().graphics,
+        adapter.as_deref(),
+        &mut overrides,
+        &mut msaa,
+        &mut active,
+    );
+    remove_fxaa(&mut commands, &active, &fxaa_cameras);
+    report(&overrides, &msaa);
+}
+
+fn receive_graphics(
+    mut commands: Commands,
+    adapter: Option<Res<RenderAdapter>>,
+    mut overrides: ResMut<GraphicsOverrides>,
+    mut msaa: ResMut<Msaa>,
+    mut active: ResMut<ActiveAntialiasing>,
+    fxaa_cameras: Query<Entity, With<Fxaa>>,
+) {
+    let requests = take_messages(|msg| match msg {
+        HostMessage::SetGraphics(options) => Ok(options),
+        msg => Err(msg),
+    });
+    if requests.is_empty() {
+        return;
+    }
+
+    for options in requests {
+        apply(
+            options,
+            adapter.as_deref(),
+            &mut overrides,
+            &mut msaa,
+            &mut active,
+        );
+    }
+    remove_fxaa(&mut commands, &active, &fxaa_cameras);
+    report(&overrides, &msaa);
+}
+
+fn apply(
+    options: GraphicsOptions,
+    adapter: Option<&RenderAdapter>,
+    overrides: &mut GraphicsOverrides,
+    msaa: &mut Msaa,
+    active: &mut ActiveAntialiasing,
+) {
+    if let Some(hdr) = options.hdr {
+        let supported = adapter.map_or(false, hdr_supported);
+        if hdr && !supported {
+            warn!("HDR requested, but GPU can't render to float textures");
+        }
+        overrides.hdr = Some(hdr && supported);
+    }
+    if let Some(method) = options.tonemapping {
+        overrides.tonemapping = Some(tonemapping(method));
+    }
+
+    // Turning HDR on changes the format cameras render to, which current sample count may not support.
+    if options.msaa.is_none() && options.hdr.is_none() {
+        return;
+    }
+    let samples = options.msaa.unwrap_or(msaa.samples());
+
+    let format = if overrides.hdr == Some(true) {
+        ViewTarget::TEXTURE_FORMAT_HDR
+    } else {
+        TextureFormat::bevy_default()
+    };
+    let chosen = adapter.map_or(1, |adapter| msaa_samples(adapter, samples, format));
+    if chosen != samples {
+        warn!("MSAA with {samples} samples requested, GPU only does {chosen}");
+    }
+
+    *msaa = msaa_from_samples(chosen);
+    // FXAA only stays as a fallback when page turns MSAA off.
+    if chosen > 1 {
+        active.0 = Antialiasing::Msaa(chosen);
+    } else if matches!(active.0, Antialiasing::Msaa(_)) {
+        active.0 = Antialiasing::None;
+    }
+}
+
+fn hdr_supported(adapter: &RenderAdapter) -> bool {
+    adapter
+        .get_texture_format_features(ViewTarget::TEXTURE_FORMAT_HDR)
+        .allowed_usages
+        .contains(TextureUsages::RENDER_ATTACHMENT)
+}
+
+fn tonemapping(method: TonemappingMethod) -> Tonemapping {
+    match method {
+        TonemappingMethod::None => Tonemapping::None,
+        TonemappingMethod::Reinhard => Tonemapping::Reinhard,
+        TonemappingMethod::ReinhardLuminance => Tonemapping::ReinhardLuminance,
+        TonemappingMethod::AcesFitted => Tonemapping::AcesFitted,
+        TonemappingMethod::AgX => Tonemapping::AgX,
+        TonemappingMethod::SomewhatBoringDisplayTransform => {
+            Tonemapping::SomewhatBoringDisplayTransform
+        }
+        TonemappingMethod::TonyMcMapface => Tonemapping::TonyMcMapface,
+        TonemappingMethod::BlenderFilmic => Tonemapping::BlenderFilmic,
+    }
+}
+
+fn tonemapping_method(tonemapping: Tonemapping) -> TonemappingMethod {
+    match tonemapping {
+        Tonemapping::None => TonemappingMethod::None,
+        Tonemapping::Reinhard => TonemappingMethod::Reinhard,
+        Tonemapping::ReinhardLuminance => TonemappingMethod::ReinhardLuminance,
+        Tonemapping::AcesFitted => TonemappingMethod::AcesFitted,
+        Tonemapping::AgX => TonemappingMethod::AgX,
+        Tonemapping::SomewhatBoringDisplayTransform => {
+            TonemappingMethod::SomewhatBoringDisplayTransform
+        }
+        Tonemapping::TonyMcMapface => TonemappingMethod::TonyMcMapface,
+        Tonemapping::BlenderFilmic => TonemappingMethod::BlenderFilmic,
+    }
+}
+
+fn remove_fxaa(
+    commands: &mut Commands,
+    active: &ActiveAntialiasing,
+    fxaa_cameras: &Query<Entity, With<Fxaa>>,
+) {
+    if active.0 == Antialiasing::Fxaa {
+        return;
+    }
+
+    for entity in fxaa_cameras {
+        commands.entity(entity).remove::<Fxaa>();
+    }
+}
+
+fn report(overrides: &GraphicsOverrides, msaa: &Msaa) {
+    post(&WorkerMessage::Graphics(GraphicsOptions {
+        msaa: Some(msaa.samples()),
+        hdr: overrides.hdr,
+        tonemapping: overrides.tonemapping.map(tonemapping_method),
+    }));
+}
+
+fn apply_to_cameras(
+    mut commands: Commands,
+    overrides: Res<GraphicsOverrides>,
+    mut cameras: Query<(Entity, &mut Camera, Option<&Tonemapping>)>,
+) {
+    for (entity, mut camera, current) in &mut cameras {
+        if !overrides.is_changed() && !camera.is_added() {
+            continue;
+        }
+
+        if let Some(hdr) = overrides.hdr {
+            if camera.hdr != hdr {
+                camera.hdr = hdr;
+            }
+        }
+        if let Some(tonemapping) = overrides.tonemapping {
+            if current != Some(&tonemapping) {
+                commands.entity(entity).insert(tonemapping);
+            }
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Images decoded by the page.
+//!
+//! Browser decodes images with `createImageBitmap` faster than image crates compiled to wasm,
+//! and does so before the worker ever sees them.
+//! Page reads their pixels back and sends them as `.bitmap` files to [`InMemoryAssets`](super::inmem::InMemoryAssets),
+//! which [`ImageBitmapLoader`] turns into [`Image`] assets.
+//! Page side is `WorkerHandle::send_image`.
+
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::utils::BoxedFuture;
+
+/// Load images page decoded.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins) for apps with a window,
+/// needs [`InMemoryAssetPlugin`](super::inmem::InMemoryAssetPlugin) and [`ImagePlugin`].
+#[derive(Default)]
+pub struct ImageBitmapPlugin;
+
+impl Plugin for ImageBitmapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset_loader::<ImageBitmapLoader>();
+    }
+}
+
+/// Loader of `.bitmap` files: width and height as little endian `u32`, followed by RGBA pixels.
+#[derive(Default)]
+pub struct ImageBitmapLoader;
+
+impl AssetLoader for ImageBitmapLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let image = decode(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(image));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["bitmap"]
+    }
+}
+
+fn decode(bytes: &[u8]) -> Result<Image, bevy::asset::Error> {
+    let (Some(width), Some(height)) = (bytes.get(0..4), bytes.get(4..8)) else {
+        return Err(bevy::asset::Error::msg("bitmap header is truncated"));
+    };
+    let width = u32::from_le_bytes(width.try_into()?);
+    let height = u32::from_le_bytes(height.try_into()?);
+
+    let Some(size) = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|size| size.checked_mul(4))
+    else {
+        return Err(bevy::asset::Error::msg(format!(
+            "bitmap of {width}x{height} pixels is too large"
+        )));
+    };
+
+    let pixels = &bytes[8..];
+    if pixels.len() != size {
+        return Err(bevy::asset::Error::msg(format!(
+            "bitmap of {width}x{height} pixels has {} bytes of data",
+            pixels.len()
+        )));
+    }
+
+    Ok(Image::new(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        pixels.to_vec(),
+        TextureFormat::Rgba8UnormSrgb,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bitmap(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        bytes.extend_from_slice(pixels);
+        bytes
+    }
+
+    #[test]
+    fn decode_pixels() {
+        let pixels: Vec<u8> = (0..2 * 3 * 4).collect();
+        let image = decode(&bitmap(2, 3, &pixels)).unwrap();
+
+        assert_eq!(image.size(), Vec2::new(2.0, 3.0));
+        assert_eq!(image.data, pixels);
+        assert_eq!(
+            image.texture_descriptor.format,
+            TextureFormat::Rgba8UnormSrgb
+        );
+    }
+
+    #[test]
+    fn truncated_header() {
+        assert!(decode(&[1, 0, 0, 0, 1]).is_err());
+    }
+
+    #[test]
+    fn pixel_count_mismatch() {
+        assert!(decode(&bitmap(2, 2, &[0; 12])).is_err());
+        assert!(decode(&bitmap(2, 2, &[0; 20])).is_err());
+    }
+
+    #[test]
+    fn size_overflow() {
+        // Wrapped around, size of these would be zero and match missing pixels.
+        assert!(decode(&bitmap(1 << 31, 1 << 31, &[])).is_err());
+        assert!(decode(&bitmap(u32::MAX, u32::MAX, &[])).is_err());
+    }
+}
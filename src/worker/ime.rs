@@ -0,0 +1,119 @@
+//! Text input through the page, including IME composition.
+//!
+//! Worker never sees key presses of non-Latin scripts: they are composed by the IME,
+//! which only talks to a focused text element. While `Window::ime_enabled` is set
+//! page focuses a hidden text element over the canvas and forwards whatever is typed into it
+//! as `Ime` events, the same way winit reports them on native platforms.
+
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use bevy::window::Ime;
+
+use super::{post, take_messages, BridgeSchedules, BridgeSend, InputInject, Views};
+use crate::coords::LogicalPx;
+use crate::protocol::{HostMessage, ImeAction, WorkerMessage};
+
+/// Accept text input while window has IME enabled.
+#[derive(Default)]
+pub struct ImePlugin;
+
+impl Plugin for ImePlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.add_systems(schedules.input, receive_ime.in_set(InputInject))
+            .add_systems(schedules.send, request_ime.in_set(BridgeSend));
+    }
+}
+
+fn receive_ime(
+    views: Res<Views>,
+    mut ime: EventWriter<Ime>,
+    mut composing: Local<HashSet<Entity>>,
+) {
+    take_messages(|msg| match msg {
+        HostMessage::Ime { view, action, text } => {
+            let Some(window) = views.window(view) else {
+                return Ok(());
+            };
+
+            match action {
+                ImeAction::Preedit => {
+                    let cursor = (!text.is_empty()).then_some((text.len(), text.len()));
+                    if cursor.is_some() {
+                        composing.insert(window);
+                    } else {
+                        composing.remove(&window);
+                    }
+
+                    ime.send(Ime::Preedit {
+                        window,
+                        value: text,
+                        cursor,
+                    });
+                }
+                ImeAction::Commit => {
+                    // Winit clears preedit before committing, apps may rely on that.
+                    if composing.remove(&window) {
+                        ime.send(Ime::Preedit {
+                            window,
+                            value: String::new(),
+                            cursor: None,
+                        });
+                    }
+
+                    ime.send(Ime::Commit {
+                        window,
+                        value: text,
+                    });
+                }
+            }
+
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
+
+fn request_ime(
+    views: Res<Views>,
+    windows: Query<&Window>,
+    mut ime: EventWriter<Ime>,
+    mut sent: Local<HashMap<Entity, (bool, Vec2)>>,
+) {
+    for (view, entity) in views.iter() {
+        let Ok(window) = windows.get(entity) else {
+            continue;
+        };
+
+        let state = (window.ime_enabled, window.ime_position);
+        let previous = sent.insert(entity, state);
+        if previous == Some(state) {
+            continue;
+        }
+
+        // Text element only moves while it is focused, there is no point sending position otherwise.
+        let toggled = previous.map_or(window.ime_enabled, |(enabled, _)| {
+            enabled != window.ime_enabled
+        });
+        if !toggled && !window.ime_enabled {
+            continue;
+        }
+
+        let position = LogicalPx(window.ime_position).to_physical(window.scale_factor());
+        post(&WorkerMessage::SetIme {
+            view,
+            enabled: window.ime_enabled,
+            x: position.0.x,
+            y: position.0.y,
+        });
+
+        if toggled {
+            ime.send(if window.ime_enabled {
+                Ime::Enabled { window: entity }
+            } else {
+                Ime::Disabled { window: entity }
+            });
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Assets handed over by the page.
+//!
+//! Page can post file contents it got hold of (file picker, drag-and-drop, its own fetches)
+//! with [`HostMessage::AssetBytes`].
+//! They become loadable by asset server under `inmem://` prefix, e.g. `inmem://level.gltf`.
+//! Images page decoded itself go the same way, see [`image_bitmap`](super::image_bitmap).
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use bevy::asset::{AssetIo, AssetIoError, ChangeWatcher, FileType, LoadState, Metadata};
+use bevy::prelude::*;
+use bevy::utils::{BoxedFuture, HashMap};
+
+use super::asset_cache::{self, AssetCache, AssetCacheSettings, CachedAssetIo};
+use super::cache_storage::{CacheStorageSettings, CachedAssetReader, OfflineAssetCache};
+use super::{take_messages, BridgeReceive, BridgeSchedules};
+use crate::protocol::HostMessage;
+
+/// Asset path prefix for in-memory files.
+pub const SCHEME: &str = "inmem://";
+
+/// Files posted by the page, keyed by path without [`SCHEME`].
+#[derive(Resource, Clone, Default)]
+pub struct InMemoryAssets {
+    files: Arc<RwLock<HashMap<String, Arc<[u8]>>>>,
+}
+
+impl InMemoryAssets {
+    pub fn insert(&self, path: &str, bytes: impl Into<Arc<[u8]>>) {
+        self.files
+            .write()
+            .expect("lock is not poisoned")
+            .insert(path.to_owned(), bytes.into());
+    }
+
+    pub fn remove(&self, path: &str) {
+        self.files
+            .write()
+            .expect("lock is not poisoned")
+            .remove(path);
+    }
+
+    pub fn get(&self, path: &str) -> Option<Arc<[u8]>> {
+        self.files
+            .read()
+            .expect("lock is not poisoned")
+            .get(path)
+            .cloned()
+    }
+}
+
+/// Page posted a file.
+#[derive(Event, Debug, Clone)]
+pub struct AssetBytesReceived {
+    /// Full asset path, including [`SCHEME`].
+    pub path: String,
+}
+
+/// Asset source serving [`InMemoryAssets`] and passing everything else to another source.
+struct InMemoryAssetIo {
+    files: InMemoryAssets,
+    fallback: Box<dyn AssetIo>,
+}
+
+impl InMemoryAssetIo {
+    fn in_memory(path: &Path) -> Option<&str> {
+        // Paths are not URLs, double slash may get collapsed along the way.
+        let path = path.to_str()?;
+        path.strip_prefix(SCHEME)
+            .or_else(|| path.strip_prefix("inmem:/"))
+    }
+}
+
+impl AssetIo for InMemoryAssetIo {
+    fn load_path<'a>(&'a self, path: &'a Path) -> BoxedFuture<'a, Result<Vec<u8>, AssetIoError>> {
+        match Self::in_memory(path) {
+            Some(key) => {
+                let result = self
+                    .files
+                    .get(key)
+                    .map(|bytes| bytes.to_vec())
+                    .ok_or_else(|| AssetIoError::NotFound(path.to_owned()));
+
+                Box::pin(async move { result })
+            }
+            None => self.fallback.load_path(path),
+        }
+    }
+
+    fn read_directory(
+        &self,
+        path: &Path,
+    ) -> Result<Box<dyn Iterator<Item = PathBuf>>, AssetIoError> {
+        match Self::in_memory(path) {
+            Some(_) => Err(AssetIoError::NotFound(path.to_owned())),
+            None => self.fallback.read_directory(path),
+        }
+    }
+
+    fn get_metadata(&self, path: &Path) -> Result<Metadata, AssetIoError> {
+        match Self::in_memory(path) {
+            Some(key) if self.files.get(key).is_some() => Ok(Metadata::new(FileType::File)),
+            Some(_) => Err(AssetIoError::NotFound(path.to_owned())),
+            None => self.fallback.get_metadata(path),
+        }
+    }
+
+    fn watch_path_for_changes(
+        &self,
+        to_watch: &Path,
+        to_reload: Option<PathBuf>,
+    ) -> Result<(), AssetIoError> {
+        match Self::in_memory(to_watch) {
+            // Changes are picked up by `receive_bytes` instead.
+            Some(_) => Ok(()),
+            None => self.fallback.watch_path_for_changes(to_watch, to_reload),
+        }
+    }
+
+    fn watch_for_changes(&self, configuration: &ChangeWatcher) -> Result<(), AssetIoError> {
+        self.fallback.watch_for_changes(configuration)
+    }
+}
+
+/// Serve files posted by the page through asset server.
+///
+/// Must be added before [`AssetPlugin`], which is otherwise going to create asset server on its own.
+/// Loading a path before its contents arrive fails, but the load is retried once they do.
+///
+/// Since this plugin owns the asset server, it also sets up persistent [`AssetCache`]
+/// and [`OfflineAssetCache`] for everything else.
+/// Offline cache is consulted first, so there is little point in enabling both.
+#[derive(Default)]
+pub struct InMemoryAssetPlugin {
+    pub asset_plugin: AssetPlugin,
+    /// Keep fetched assets in IndexedDB, `None` disables caching.
+    pub cache: Option<AssetCacheSettings>,
+    /// Serve assets from Cache Storage without asking the server, `None` disables offline caching.
+    pub offline: Option<CacheStorageSettings>,
+}
+
+impl Plugin for InMemoryAssetPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+        let files = InMemoryAssets::default();
+
+        let mut fallback = self.asset_plugin.create_platform_default_asset_io();
+        if let Some(settings) = &self.cache {
+            let cache = AssetCache::new(settings.clone());
+            fallback = Box::new(CachedAssetIo::new(
+                cache.clone(),
+                &self.asset_plugin.asset_folder,
+                fallback,
+            ));
+            app.insert_resource(cache);
+        }
+        if let Some(settings) = &self.offline {
+            let cache = OfflineAssetCache::new(settings.clone());
+            fallback = Box::new(CachedAssetReader::new(
+                cache.clone(),
+                &self.asset_plugin.asset_folder,
+                fallback,
+            ));
+            app.insert_resource(cache);
+        }
+
+        let io = InMemoryAssetIo {
+            files: files.clone(),
+            fallback,
+        };
+
+        app.insert_resource(AssetServer::new(io))
+            .insert_resource(files)
+            .add_event::<AssetBytesReceived>()
+            .add_systems(
+                schedules.receive,
+                (receive_bytes, asset_cache::receive_clear).in_set(BridgeReceive),
+            );
+    }
+}
+
+fn receive_bytes(
+    files: Res<InMemoryAssets>,
+    asset_server: Res<AssetServer>,
+    mut received: EventWriter<AssetBytesReceived>,
+) {
+    take_messages(|msg| match msg {
+        HostMessage::AssetBytes { path, bytes } => {
+            let bytes = js_sys::Uint8Array::new(&bytes.into_inner()).to_vec();
+            files.insert(&path, bytes);
+
+            let path = format!("{SCHEME}{path}");
+            if asset_server.get_load_state(path.as_str()) != LoadState::NotLoaded {
+                asset_server.reload_asset(path.as_str());
+            }
+
+            received.send(AssetBytesReceived { path });
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
@@ -0,0 +1,230 @@
+//! Turn input forwarded by the page into Bevy input events.
+
+use bevy::input::mouse::{MouseButtonInput, MouseScrollUnit, MouseWheel};
+use bevy::input::touch::{TouchInput, TouchPhase};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy::window::CursorLeft;
+
+use super::{handled_sent_at, take_messages, BridgeSchedules, InputInject, Views};
+use crate::coords::PhysicalPx;
+use crate::protocol::{HostMessage, PointerAction, TouchAction, WheelMode};
+
+/// Feed pointer, touch and wheel events from page canvases into the app.
+///
+/// Every finger is a [`TouchInput`] event, while the first one also moves the cursor and presses left button.
+///
+/// Cursor position is stored on the window, so UI interaction and picking work as usual.
+#[derive(Default)]
+pub struct PointerInputPlugin;
+
+impl Plugin for PointerInputPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.init_resource::<InputTimestamps>()
+            .init_resource::<WheelSettings>()
+            .add_systems(
+                schedules.input,
+                (receive_pointer, receive_touch, receive_wheel).in_set(InputInject),
+            );
+    }
+}
+
+/// When page captured the latest input.
+///
+/// Times are reported by `Date.now()` on page side, compare them against the same clock.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct InputTimestamps {
+    /// Latest button press.
+    pub last_press: Option<f64>,
+}
+
+/// How wheel events from the page turn into [`MouseWheel`] events.
+///
+/// Wheel deltas measured in pixels or lines keep their unit,
+/// while page scrolls become pixels worth of window size.
+#[derive(Resource, Debug, Clone)]
+pub struct WheelSettings {
+    /// Lines scrolled per pixel of trackpad pinch.
+    ///
+    /// Pinch reports much smaller deltas than wheels do,
+    /// default makes it zoom at about the pace of a mouse wheel.
+    pub pinch_lines_per_pixel: f32,
+}
+
+impl Default for WheelSettings {
+    fn default() -> Self {
+        WheelSettings {
+            pinch_lines_per_pixel: 0.1,
+        }
+    }
+}
+
+fn receive_pointer(
+    views: Res<Views>,
+    mut timestamps: ResMut<InputTimestamps>,
+    pressed: Res<Input<MouseButton>>,
+    mut windows: Query<&mut Window>,
+    mut moved: EventWriter<CursorMoved>,
+    mut left: EventWriter<CursorLeft>,
+    mut buttons: EventWriter<MouseButtonInput>,
+) {
+    take_messages(|msg| match msg {
+        HostMessage::Pointer {
+            view,
+            action,
+            x,
+            y,
+            button,
+        } => {
+            // Input for views which aren't attached yet is of no use.
+            let Some(entity) = views.window(view) else {
+                return Ok(());
+            };
+            let Ok(mut window) = windows.get_mut(entity) else {
+                return Ok(());
+            };
+
+            let position = PhysicalPx(Vec2::new(x, y))
+                .to_logical(window.scale_factor())
+                .0;
+
+            match action {
+                PointerAction::Move => {
+                    window.set_cursor_position(Some(position));
+                    moved.send(CursorMoved {
+                        window: entity,
+                        position,
+                    });
+                }
+                PointerAction::Down | PointerAction::Up => {
+                    let state = if action == PointerAction::Down {
+                        timestamps.last_press = handled_sent_at();
+                        ButtonState::Pressed
+                    } else {
+                        ButtonState::Released
+                    };
+
+                    window.set_cursor_position(Some(position));
+                    buttons.send(MouseButtonInput {
+                        button: mouse_button(button),
+                        state,
+                        window: entity,
+                    });
+                }
+                PointerAction::Leave => {
+                    window.set_cursor_position(None);
+                    left.send(CursorLeft { window: entity });
+
+                    // Buttons released outside of the canvas never report it.
+                    buttons.send_batch(pressed.get_pressed().map(|&button| MouseButtonInput {
+                        button,
+                        state: ButtonState::Released,
+                        window: entity,
+                    }));
+                }
+            }
+
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
+
+fn receive_touch(views: Res<Views>, windows: Query<&Window>, mut touches: EventWriter<TouchInput>) {
+    take_messages(|msg| match msg {
+        HostMessage::Touch {
+            view,
+            id,
+            action,
+            x,
+            y,
+        } => {
+            let Some(window) = views
+                .window(view)
+                .and_then(|entity| windows.get(entity).ok())
+            else {
+                return Ok(());
+            };
+
+            touches.send(TouchInput {
+                phase: match action {
+                    TouchAction::Start => TouchPhase::Started,
+                    TouchAction::Move => TouchPhase::Moved,
+                    TouchAction::End => TouchPhase::Ended,
+                    TouchAction::Cancel => TouchPhase::Canceled,
+                },
+                position: PhysicalPx(Vec2::new(x, y))
+                    .to_logical(window.scale_factor())
+                    .0,
+                force: None,
+                id: id as u64,
+            });
+
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
+
+fn receive_wheel(
+    views: Res<Views>,
+    settings: Res<WheelSettings>,
+    windows: Query<&Window>,
+    mut wheel: EventWriter<MouseWheel>,
+) {
+    take_messages(|msg| match msg {
+        HostMessage::Wheel {
+            view,
+            dx,
+            dy,
+            mode,
+            pinch,
+        } => {
+            let Some(entity) = views.window(view) else {
+                return Ok(());
+            };
+            let Ok(window) = windows.get(entity) else {
+                return Ok(());
+            };
+
+            // DOM scrolls down on positive deltas, Bevy scrolls up.
+            let delta = -Vec2::new(dx, dy);
+            let (unit, delta) = match mode {
+                _ if pinch => (
+                    MouseScrollUnit::Line,
+                    delta * settings.pinch_lines_per_pixel,
+                ),
+                WheelMode::Pixel => (MouseScrollUnit::Pixel, delta),
+                WheelMode::Line => (MouseScrollUnit::Line, delta),
+                WheelMode::Page => {
+                    let size = Vec2::new(
+                        window.physical_width() as f32,
+                        window.physical_height() as f32,
+                    );
+                    (MouseScrollUnit::Pixel, delta * size)
+                }
+            };
+
+            wheel.send(MouseWheel {
+                unit,
+                x: delta.x,
+                y: delta.y,
+                window: entity,
+            });
+
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
+
+fn mouse_button(button: i16) -> MouseButton {
+    match button {
+        0 => MouseButton::Left,
+        1 => MouseButton::Middle,
+        2 => MouseButton::Right,
+        other => MouseButton::Other(other as u16),
+    }
+}
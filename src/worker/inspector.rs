@@ -0,0 +1,229 @@
+//! Entities and their components, streamed to the page for inspection and editing.
+//!
+//! Page sets a query with [`HostMessage::SetInspector`] and gets a snapshot of matching entities
+//! every so often until it clears it. Components travel as RON produced by reflection,
+//! edits come back in the same form and replace component's value.
+//! Only registered components with `ReflectComponent` data can be included or edited.
+
+use bevy::ecs::reflect::ReflectComponent;
+use bevy::prelude::*;
+use bevy::reflect::serde::{TypedReflectDeserializer, TypedReflectSerializer};
+use bevy::reflect::{TypeRegistration, TypeRegistry};
+use bevy::utils::HashSet;
+use serde::de::DeserializeSeed;
+
+use super::{post, take_messages, BridgeReceive, BridgeSchedules, BridgeSend};
+use crate::protocol::{HostMessage, InspectQuery, InspectedEntity, WorkerMessage};
+
+/// Answer page's inspector queries.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins).
+pub struct RemoteInspectorPlugin {
+    /// Time between snapshots.
+    pub interval_ms: f64,
+    /// Snapshot is cut off after this many entities.
+    pub max_entities: usize,
+}
+
+impl Default for RemoteInspectorPlugin {
+    fn default() -> Self {
+        RemoteInspectorPlugin {
+            interval_ms: 500.0,
+            max_entities: 1000,
+        }
+    }
+}
+
+impl Plugin for RemoteInspectorPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.insert_resource(Inspector {
+            interval_ms: self.interval_ms,
+            max_entities: self.max_entities,
+            query: None,
+            sent_at: None,
+        })
+        .add_systems(schedules.receive, receive.in_set(BridgeReceive))
+        .add_systems(schedules.send, publish.in_set(BridgeSend));
+    }
+}
+
+#[derive(Resource)]
+struct Inspector {
+    interval_ms: f64,
+    max_entities: usize,
+    query: Option<InspectQuery>,
+    sent_at: Option<f64>,
+}
+
+fn receive(world: &mut World) {
+    let messages = take_messages(|msg| match msg {
+        HostMessage::SetInspector(_) | HostMessage::InspectorEdit { .. } => Ok(msg),
+        msg => Err(msg),
+    });
+
+    for msg in messages {
+        match msg {
+            HostMessage::SetInspector(query) => {
+                if let Some(query) = &query {
+                    let registry = world.resource::<AppTypeRegistry>().read();
+                    for name in &query.components {
+                        if find(&registry, name).is_none() {
+                            warn!("inspector can't find component `{name}`");
+                        }
+                    }
+                }
+
+                let mut inspector = world.resource_mut::<Inspector>();
+                inspector.query = query;
+                // New query deserves an answer right away.
+                inspector.sent_at = None;
+            }
+            HostMessage::InspectorEdit {
+                entity,
+                component,
+                value,
+            } => {
+                if let Err(err) = edit(world, Entity::from_bits(entity), &component, &value) {
+                    warn!("inspector failed to edit `{component}`: {err}");
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn edit(world: &mut World, entity: Entity, component: &str, value: &str) -> Result<(), String> {
+    let registry = world.resource::<AppTypeRegistry>().clone();
+    let registry = registry.read();
+
+    let registration = registry
+        .get_with_name(component)
+        .ok_or("component is not registered")?;
+    let reflect_component = registration
+        .data::<ReflectComponent>()
+        .ok_or("component doesn't reflect `Component`")?;
+
+    let mut deserializer = ron::Deserializer::from_str(value).map_err(|err| err.to_string())?;
+    let value = TypedReflectDeserializer::new(registration, &registry)
+        .deserialize(&mut deserializer)
+        .map_err(|err| err.to_string())?;
+
+    let mut entity = world.get_entity_mut(entity).ok_or("entity doesn't exist")?;
+    reflect_component.apply(&mut entity, &*value);
+
+    Ok(())
+}
+
+fn publish(world: &mut World) {
+    let now = js_sys::Date::now();
+
+    let inspector = world.resource::<Inspector>();
+    let Some(query) = inspector.query.clone() else {
+        return;
+    };
+    let due = inspector
+        .sent_at
+        .map_or(true, |at| now - at >= inspector.interval_ms);
+    if !due {
+        return;
+    }
+    let max_entities = inspector.max_entities;
+
+    world.resource_mut::<Inspector>().sent_at = Some(now);
+
+    let registry = world.resource::<AppTypeRegistry>().clone();
+    let registry = registry.read();
+    let components = included_components(&registry, &query);
+
+    let matching: Vec<Entity> = world
+        .iter_entities()
+        .filter(|entity| match &query.name_filter {
+            Some(filter) => entity
+                .get::<Name>()
+                .map_or(false, |name| name.as_str().contains(filter.as_str())),
+            None => true,
+        })
+        .map(|entity| entity.id())
+        .collect();
+
+    // Ancestors of matching entities come along, so the tree stays connected.
+    let mut included = HashSet::new();
+    for mut entity in matching {
+        while included.insert(entity) {
+            match world.get::<Parent>(entity) {
+                Some(parent) => entity = parent.get(),
+                None => break,
+            }
+        }
+    }
+
+    // Hash set order changes between snapshots, so truncated ones would show different entities each time.
+    // Shallow entities go first, so ancestors of whatever is shown are never cut off.
+    let depth = |mut entity: Entity| {
+        let mut depth = 0;
+        while let Some(parent) = world.get::<Parent>(entity) {
+            entity = parent.get();
+            depth += 1;
+        }
+        depth
+    };
+    let mut included: Vec<_> = included.into_iter().collect();
+    included.sort_unstable_by_key(|&entity| (depth(entity), entity));
+
+    let entities = included
+        .into_iter()
+        .take(max_entities)
+        .filter_map(|entity| {
+            let entity = world.get_entity(entity)?;
+
+            let components = components
+                .iter()
+                .filter_map(|(name, reflect_component)| {
+                    let value = reflect_component.reflect(entity)?;
+                    let serializer = TypedReflectSerializer::new(value, &registry);
+                    let ron = ron::ser::to_string_pretty(&serializer, Default::default()).ok()?;
+                    Some((name.clone(), ron))
+                })
+                .collect();
+
+            Some(InspectedEntity {
+                entity: entity.id().to_bits(),
+                name: entity.get::<Name>().map(|name| name.as_str().to_owned()),
+                parent: entity.get::<Parent>().map(|parent| parent.get().to_bits()),
+                components,
+            })
+        })
+        .collect();
+
+    post(&WorkerMessage::InspectorSnapshot(entities));
+}
+
+/// Components the query asks for, transform always among them.
+fn included_components(
+    registry: &TypeRegistry,
+    query: &InspectQuery,
+) -> Vec<(String, ReflectComponent)> {
+    let requested = query
+        .components
+        .iter()
+        .filter_map(|name| find(registry, name));
+    let mut seen = HashSet::new();
+
+    std::iter::once(registry.get(std::any::TypeId::of::<Transform>()))
+        .flatten()
+        .chain(requested)
+        .filter(|registration| seen.insert(registration.type_id()))
+        .filter_map(|registration| {
+            let reflect_component = registration.data::<ReflectComponent>()?.clone();
+            Some((registration.type_name().to_owned(), reflect_component))
+        })
+        .collect()
+}
+
+fn find<'a>(registry: &'a TypeRegistry, name: &str) -> Option<&'a TypeRegistration> {
+    registry
+        .get_with_short_name(name)
+        .or_else(|| registry.get_with_name(name))
+}
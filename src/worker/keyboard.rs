@@ -0,0 +1,212 @@
+//! Keyboard input forwarded by the page, see [`HostMessage::Key`].
+//!
+//! Keys arrive as [`KeyboardInput`] events of primary view's window.
+//! Bevy's key codes stand for physical keys, so WASD bindings keep their places on any layout,
+//! but labels shown to the user should come from [`KeyboardLayout`]: on AZERTY `KeyCode::W` is labeled `z`.
+//!
+//! Layout is taken from `navigator.keyboard.getLayoutMap()` where browser has it,
+//! elsewhere it is filled in as user presses keys.
+
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use super::{take_messages, BridgeSchedules, InputInject, Views};
+use crate::protocol::{HostMessage, ViewId};
+
+/// Feed keyboard events from the page into the app and keep [`KeyboardLayout`] up to date.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins) for apps with a window.
+#[derive(Default)]
+pub struct KeyboardPlugin;
+
+impl Plugin for KeyboardPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.init_resource::<KeyboardLayout>()
+            .add_systems(schedules.input, receive_keys.in_set(InputInject));
+    }
+}
+
+/// Labels of physical keys in user's keyboard layout.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct KeyboardLayout {
+    labels: HashMap<String, String>,
+}
+
+impl KeyboardLayout {
+    /// What the key is labeled with, e.g. `z` for `KeyCode::W` on AZERTY.
+    ///
+    /// `None` until page reports the layout or user presses the key.
+    pub fn label(&self, key: KeyCode) -> Option<&str> {
+        let (code, _) = CODES.iter().find(|(_, key_code)| *key_code == key)?;
+        self.label_of_code(code)
+    }
+
+    /// Same as [`label`](Self::label), but for `KeyboardEvent.code` value.
+    pub fn label_of_code(&self, code: &str) -> Option<&str> {
+        self.labels.get(code).map(String::as_str)
+    }
+}
+
+fn receive_keys(
+    views: Res<Views>,
+    mut layout: ResMut<KeyboardLayout>,
+    mut keyboard: EventWriter<KeyboardInput>,
+) {
+    take_messages(|msg| match msg {
+        HostMessage::Key {
+            code,
+            key,
+            scan_code,
+            pressed,
+            plain,
+        } => {
+            // Printable keys pressed on their own tell what they are labeled with.
+            if pressed && plain && key.chars().count() == 1 && !layout.labels.contains_key(&code) {
+                layout.labels.insert(code.clone(), key);
+            }
+
+            let Some(window) = views
+                .window(ViewId::PRIMARY)
+                .or_else(|| views.iter().next().map(|(_, window)| window))
+            else {
+                return Ok(());
+            };
+
+            keyboard.send(KeyboardInput {
+                scan_code,
+                key_code: key_code(&code),
+                state: if pressed {
+                    ButtonState::Pressed
+                } else {
+                    ButtonState::Released
+                },
+                window,
+            });
+
+            Ok(())
+        }
+        HostMessage::KeyboardLayout(labels) => {
+            layout.labels.extend(labels);
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
+
+fn key_code(code: &str) -> Option<KeyCode> {
+    CODES
+        .iter()
+        .find(|(name, _)| *name == code)
+        .map(|(_, key_code)| *key_code)
+}
+
+/// `KeyboardEvent.code` values of keys Bevy has codes for.
+const CODES: &[(&str, KeyCode)] = &[
+    ("KeyA", KeyCode::A),
+    ("KeyB", KeyCode::B),
+    ("KeyC", KeyCode::C),
+    ("KeyD", KeyCode::D),
+    ("KeyE", KeyCode::E),
+    ("KeyF", KeyCode::F),
+    ("KeyG", KeyCode::G),
+    ("KeyH", KeyCode::H),
+    ("KeyI", KeyCode::I),
+    ("KeyJ", KeyCode::J),
+    ("KeyK", KeyCode::K),
+    ("KeyL", KeyCode::L),
+    ("KeyM", KeyCode::M),
+    ("KeyN", KeyCode::N),
+    ("KeyO", KeyCode::O),
+    ("KeyP", KeyCode::P),
+    ("KeyQ", KeyCode::Q),
+    ("KeyR", KeyCode::R),
+    ("KeyS", KeyCode::S),
+    ("KeyT", KeyCode::T),
+    ("KeyU", KeyCode::U),
+    ("KeyV", KeyCode::V),
+    ("KeyW", KeyCode::W),
+    ("KeyX", KeyCode::X),
+    ("KeyY", KeyCode::Y),
+    ("KeyZ", KeyCode::Z),
+    ("Digit0", KeyCode::Key0),
+    ("Digit1", KeyCode::Key1),
+    ("Digit2", KeyCode::Key2),
+    ("Digit3", KeyCode::Key3),
+    ("Digit4", KeyCode::Key4),
+    ("Digit5", KeyCode::Key5),
+    ("Digit6", KeyCode::Key6),
+    ("Digit7", KeyCode::Key7),
+    ("Digit8", KeyCode::Key8),
+    ("Digit9", KeyCode::Key9),
+    ("F1", KeyCode::F1),
+    ("F2", KeyCode::F2),
+    ("F3", KeyCode::F3),
+    ("F4", KeyCode::F4),
+    ("F5", KeyCode::F5),
+    ("F6", KeyCode::F6),
+    ("F7", KeyCode::F7),
+    ("F8", KeyCode::F8),
+    ("F9", KeyCode::F9),
+    ("F10", KeyCode::F10),
+    ("F11", KeyCode::F11),
+    ("F12", KeyCode::F12),
+    ("Escape", KeyCode::Escape),
+    ("Space", KeyCode::Space),
+    ("Enter", KeyCode::Return),
+    ("Backspace", KeyCode::Back),
+    ("Tab", KeyCode::Tab),
+    ("ArrowLeft", KeyCode::Left),
+    ("ArrowRight", KeyCode::Right),
+    ("ArrowUp", KeyCode::Up),
+    ("ArrowDown", KeyCode::Down),
+    ("Insert", KeyCode::Insert),
+    ("Delete", KeyCode::Delete),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("ShiftLeft", KeyCode::ShiftLeft),
+    ("ShiftRight", KeyCode::ShiftRight),
+    ("ControlLeft", KeyCode::ControlLeft),
+    ("ControlRight", KeyCode::ControlRight),
+    ("AltLeft", KeyCode::AltLeft),
+    ("AltRight", KeyCode::AltRight),
+    ("MetaLeft", KeyCode::SuperLeft),
+    ("MetaRight", KeyCode::SuperRight),
+    ("CapsLock", KeyCode::Capital),
+    ("Minus", KeyCode::Minus),
+    ("Equal", KeyCode::Equals),
+    ("BracketLeft", KeyCode::BracketLeft),
+    ("BracketRight", KeyCode::BracketRight),
+    ("Backslash", KeyCode::Backslash),
+    ("Semicolon", KeyCode::Semicolon),
+    ("Quote", KeyCode::Apostrophe),
+    ("Backquote", KeyCode::Grave),
+    ("Comma", KeyCode::Comma),
+    ("Period", KeyCode::Period),
+    ("Slash", KeyCode::Slash),
+    ("Numpad0", KeyCode::Numpad0),
+    ("Numpad1", KeyCode::Numpad1),
+    ("Numpad2", KeyCode::Numpad2),
+    ("Numpad3", KeyCode::Numpad3),
+    ("Numpad4", KeyCode::Numpad4),
+    ("Numpad5", KeyCode::Numpad5),
+    ("Numpad6", KeyCode::Numpad6),
+    ("Numpad7", KeyCode::Numpad7),
+    ("Numpad8", KeyCode::Numpad8),
+    ("Numpad9", KeyCode::Numpad9),
+    ("NumpadAdd", KeyCode::NumpadAdd),
+    ("NumpadSubtract", KeyCode::NumpadSubtract),
+    ("NumpadMultiply", KeyCode::NumpadMultiply),
+    ("NumpadDivide", KeyCode::NumpadDivide),
+    ("NumpadDecimal", KeyCode::NumpadDecimal),
+    ("NumpadEnter", KeyCode::NumpadEnter),
+    ("NumLock", KeyCode::Numlock),
+    ("ScrollLock", KeyCode::Scroll),
+    ("Pause", KeyCode::Pause),
+    ("PrintScreen", KeyCode::Snapshot),
+];
@@ -0,0 +1,179 @@
+//! Scene measuring end-to-end input latency through the bridge.
+//!
+//! Every button press flashes the whole canvas white for one frame
+//! and records how long it took from page capturing the press to the flash frame being submitted.
+//! Pointing a high-speed camera at the screen gives the remaining compositor and display latency.
+//! Press time comes from page's clock, worker compares it against its own clock synced to the page's,
+//! see [`page_now`].
+//!
+//! Worker has no access to audio, so page plays a click for every flash
+//! and reports when it reaches audio output, which ends up in [`AUDIO_LATENCY`].
+//!
+//! Scene only reacts while [`LATENCY_TEST`] toggle is enabled.
+//!
+//! Independently of the scene, page can probe latency of all input with [`HostMessage::SetLatencyProbe`]:
+//! worker reports ids of input messages once the frame handling them is submitted,
+//! and page measures the whole trip by its own clock, see `WorkerHandle::latency_report`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bevy::diagnostic::{Diagnostic, DiagnosticId, Diagnostics, RegisterDiagnostic};
+use bevy::prelude::*;
+
+use super::bridge_diagnostics::{page_now, ping};
+use super::features::{feature_enabled, LATENCY_TEST};
+use super::input::InputTimestamps;
+use super::{post, take_messages, AppId, BridgeReceive, BridgeSchedules};
+use crate::protocol::{CorrelationId, HostMessage, WorkerMessage};
+
+thread_local! {
+    // Input handled during current frame, for apps page is probing.
+    static PROBES: RefCell<HashMap<AppId, Vec<CorrelationId>>> = RefCell::new(HashMap::new());
+}
+
+/// Time from page capturing a button press to the app finishing the frame reacting to it, in ms.
+pub const INPUT_LATENCY: DiagnosticId =
+    DiagnosticId::from_u128(0x6f3d_2a91_c04e_4b7a_9d18_53e2_a7c6_01b4);
+/// Time from page capturing a button press to the click played for it reaching audio output, in ms.
+pub const AUDIO_LATENCY: DiagnosticId =
+    DiagnosticId::from_u128(0xb81c_4e07_3d5a_4f96_a2e8_7c19_05d3_6fa4);
+
+/// How often page is pinged to keep clocks in sync while scene is enabled.
+const CLOCK_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Flash the canvas and click on input, report [`INPUT_LATENCY`] and [`AUDIO_LATENCY`].
+#[derive(Default)]
+pub struct LatencyTestPlugin;
+
+impl Plugin for LatencyTestPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.register_diagnostic(
+            Diagnostic::new(INPUT_LATENCY, "input_latency", 60).with_suffix("ms"),
+        )
+        .register_diagnostic(Diagnostic::new(AUDIO_LATENCY, "audio_latency", 60).with_suffix("ms"))
+        .init_resource::<PendingFlash>()
+        .add_systems(Startup, spawn_flash)
+        .add_systems(
+            schedules.receive,
+            receive_click_latency.in_set(BridgeReceive),
+        )
+        .add_systems(
+            Update,
+            (flash, sync_clock).run_if(feature_enabled(LATENCY_TEST)),
+        )
+        .add_systems(Last, measure);
+    }
+}
+
+/// Press the flash frame is reacting to.
+#[derive(Resource, Default)]
+struct PendingFlash {
+    pressed_at: Option<f64>,
+}
+
+#[derive(Component)]
+struct Flash;
+
+fn spawn_flash(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            // Stay on top of everything else.
+            z_index: ZIndex::Global(i32::MAX),
+            ..default()
+        },
+        Flash,
+    ));
+}
+
+fn flash(
+    buttons: Res<Input<MouseButton>>,
+    timestamps: Res<InputTimestamps>,
+    mut pending: ResMut<PendingFlash>,
+    mut flash: Query<&mut BackgroundColor, With<Flash>>,
+) {
+    let pressed = buttons.get_just_pressed().next().is_some();
+
+    for mut color in &mut flash {
+        *color = if pressed { Color::WHITE } else { Color::NONE }.into();
+    }
+
+    if pressed {
+        pending.pressed_at = timestamps.last_press;
+    }
+}
+
+fn measure(mut pending: ResMut<PendingFlash>, mut diagnostics: Diagnostics) {
+    let Some(pressed_at) = pending.pressed_at.take() else {
+        return;
+    };
+
+    diagnostics.add_measurement(INPUT_LATENCY, || page_now() - pressed_at);
+    post(&WorkerMessage::LatencyClick { pressed_at });
+}
+
+fn sync_clock(time: Res<Time>, mut last_ping: Local<Option<Duration>>) {
+    let now = time.raw_elapsed();
+    if last_ping.map_or(true, |last| now - last >= CLOCK_SYNC_INTERVAL) {
+        *last_ping = Some(now);
+        ping();
+    }
+}
+
+fn receive_click_latency(mut diagnostics: Diagnostics) {
+    take_messages(|msg| match msg {
+        HostMessage::ClickLatency(latency_ms) => {
+            diagnostics.add_measurement(AUDIO_LATENCY, || latency_ms);
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
+
+/// Start or stop reporting input handled by the app, see [`HostMessage::SetLatencyProbe`].
+///
+/// [`HostMessage::SetLatencyProbe`]: crate::protocol::HostMessage::SetLatencyProbe
+pub(super) fn set_probe(app: AppId, enabled: bool) {
+    PROBES.with(|probes| {
+        let mut probes = probes.borrow_mut();
+        if enabled {
+            probes.entry(app).or_default();
+        } else {
+            probes.remove(&app);
+        }
+    });
+}
+
+/// App handled input message with given id.
+pub(super) fn handled_input(app: AppId, id: CorrelationId) {
+    PROBES.with(|probes| {
+        if let Some(handled) = probes.borrow_mut().get_mut(&app) {
+            handled.push(id);
+        }
+    });
+}
+
+/// App finished the frame, input it handled is on its way to the screen.
+pub(super) fn frame_submitted(app: AppId) {
+    let handled = PROBES.with(|probes| {
+        probes
+            .borrow_mut()
+            .get_mut(&app)
+            .map(std::mem::take)
+            .unwrap_or_default()
+    });
+
+    if !handled.is_empty() {
+        post(&WorkerMessage::InputPresented(handled));
+    }
+}
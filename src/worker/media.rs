@@ -0,0 +1,62 @@
+//! User's color scheme and motion preferences, forwarded by the page from `prefers-color-scheme`
+//! and `prefers-reduced-motion` media queries, see [`HostMessage::MediaPreferences`].
+//!
+//! Worker can't evaluate media queries by itself.
+//! Until page reports, preferences are assumed to be light scheme with motion allowed.
+
+use bevy::prelude::*;
+
+use super::{take_messages, BridgeReceive, BridgeSchedules};
+use crate::protocol::HostMessage;
+
+/// Keep [`MediaPreferences`] up to date.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins).
+#[derive(Default)]
+pub struct MediaPreferencesPlugin;
+
+impl Plugin for MediaPreferencesPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.init_resource::<MediaPreferences>()
+            .add_event::<MediaPreferencesChanged>()
+            .add_systems(schedules.receive, receive.in_set(BridgeReceive));
+    }
+}
+
+/// User's preferences as reported by the page.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MediaPreferences {
+    /// User prefers dark color scheme.
+    pub dark: bool,
+    /// User asked to minimize non-essential motion, e.g. camera shake or parallax.
+    pub reduced_motion: bool,
+}
+
+/// Preferences changed, holds the new ones.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct MediaPreferencesChanged(pub MediaPreferences);
+
+fn receive(
+    mut preferences: ResMut<MediaPreferences>,
+    mut changed: EventWriter<MediaPreferencesChanged>,
+) {
+    take_messages(|msg| match msg {
+        HostMessage::MediaPreferences {
+            dark,
+            reduced_motion,
+        } => {
+            let reported = MediaPreferences {
+                dark,
+                reduced_motion,
+            };
+            if *preferences != reported {
+                *preferences = reported;
+                changed.send(MediaPreferencesChanged(reported));
+            }
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
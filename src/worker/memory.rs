@@ -0,0 +1,131 @@
+//! Size of worker's wasm memory and number of loaded assets as Bevy diagnostics.
+//!
+//! Wasm memory only ever grows, so a tab left open for hours creeps towards the 4 GiB limit
+//! if something leaks. Diagnostics show the trend, and page is warned with
+//! [`WorkerMessage::MemoryWarning`] once memory goes past the threshold.
+
+use bevy::diagnostic::{Diagnostic, DiagnosticId, Diagnostics, RegisterDiagnostic};
+use bevy::prelude::*;
+use wasm_bindgen::prelude::JsCast;
+
+use super::post;
+use crate::protocol::{MemoryWarning, WorkerMessage};
+
+/// Size of wasm memory, in MiB.
+pub const WASM_MEMORY: DiagnosticId =
+    DiagnosticId::from_u128(0x6f3a_0c92_d41e_4b87_9e56_2a7d_c0b1_83f4);
+/// Growth of wasm memory since the previous sample, in MiB per minute.
+pub const WASM_MEMORY_GROWTH: DiagnosticId =
+    DiagnosticId::from_u128(0xb8d7_4e25_19fa_4c63_a20e_5d91_6e3c_07ab);
+/// Loaded images.
+pub const IMAGE_ASSETS: DiagnosticId =
+    DiagnosticId::from_u128(0x23c5_e8a1_7b04_4f9d_8c3e_f61a_94d2_5b70);
+/// Loaded meshes.
+pub const MESH_ASSETS: DiagnosticId =
+    DiagnosticId::from_u128(0xd190_6b3f_a5e2_47c8_b7f1_08c4_3a6d_e925);
+
+const MIB: f64 = 1024.0 * 1024.0;
+
+/// Sample memory and asset counts, and warn the page when memory grows too much.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins).
+pub struct MemoryDiagnosticsPlugin {
+    /// How often memory is sampled.
+    pub interval_secs: f64,
+    /// Memory size page is warned about, `None` to never warn.
+    ///
+    /// After a warning next one comes when memory grows by another quarter.
+    pub warn_above_bytes: Option<u64>,
+}
+
+impl Default for MemoryDiagnosticsPlugin {
+    fn default() -> Self {
+        MemoryDiagnosticsPlugin {
+            interval_secs: 1.0,
+            warn_above_bytes: Some(1024 * 1024 * 1024),
+        }
+    }
+}
+
+impl Plugin for MemoryDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_diagnostic(Diagnostic::new(WASM_MEMORY, "wasm_memory", 20).with_suffix("MiB"))
+            .register_diagnostic(
+                Diagnostic::new(WASM_MEMORY_GROWTH, "wasm_memory_growth", 20)
+                    .with_suffix("MiB/min"),
+            )
+            .register_diagnostic(Diagnostic::new(IMAGE_ASSETS, "image_assets", 20))
+            .register_diagnostic(Diagnostic::new(MESH_ASSETS, "mesh_assets", 20))
+            .insert_resource(MemorySampling {
+                interval_secs: self.interval_secs,
+                warn_above: self.warn_above_bytes,
+                previous: None,
+            })
+            .add_systems(Last, sample);
+    }
+}
+
+#[derive(Resource)]
+struct MemorySampling {
+    interval_secs: f64,
+    warn_above: Option<u64>,
+    // Time and memory size of the previous sample.
+    previous: Option<(f64, u64)>,
+}
+
+/// Current size of worker's wasm memory.
+pub fn wasm_memory_bytes() -> u64 {
+    let memory: js_sys::WebAssembly::Memory = wasm_bindgen::memory().unchecked_into();
+
+    // Buffer is shared with atomics, byte length is there all the same.
+    memory
+        .buffer()
+        .unchecked_into::<js_sys::ArrayBuffer>()
+        .byte_length() as u64
+}
+
+fn sample(
+    time: Res<Time>,
+    images: Option<Res<Assets<Image>>>,
+    meshes: Option<Res<Assets<Mesh>>>,
+    mut sampling: ResMut<MemorySampling>,
+    mut diagnostics: Diagnostics,
+) {
+    // Memory grows with real time, not with the app's own.
+    let now = time.raw_elapsed_seconds_f64();
+    if let Some((at, _)) = sampling.previous {
+        if now - at < sampling.interval_secs {
+            return;
+        }
+    }
+
+    let bytes = wasm_memory_bytes();
+    let images = images.map_or(0, |images| images.len());
+    let meshes = meshes.map_or(0, |meshes| meshes.len());
+
+    diagnostics.add_measurement(WASM_MEMORY, || bytes as f64 / MIB);
+    diagnostics.add_measurement(IMAGE_ASSETS, || images as f64);
+    diagnostics.add_measurement(MESH_ASSETS, || meshes as f64);
+    if let Some((at, previous)) = sampling.previous {
+        let growth = (bytes as f64 - previous as f64) / MIB / (now - at) * 60.0;
+        diagnostics.add_measurement(WASM_MEMORY_GROWTH, || growth);
+    }
+    sampling.previous = Some((now, bytes));
+
+    let Some(threshold) = sampling.warn_above.filter(|threshold| bytes > *threshold) else {
+        return;
+    };
+
+    warn!(
+        "wasm memory is at {:.0} MiB, past {:.0} MiB threshold",
+        bytes as f64 / MIB,
+        threshold as f64 / MIB
+    );
+    post(&WorkerMessage::MemoryWarning(MemoryWarning {
+        bytes,
+        threshold,
+        assets: (images + meshes) as u32,
+    }));
+
+    sampling.warn_above = Some(bytes + bytes / 4);
+}
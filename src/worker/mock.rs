@@ -0,0 +1,105 @@
+//! Stand-in for the page, for running worker where there is nobody to talk to.
+//!
+//! Worker creates its own canvas and goes through handshake and attach as if page sent them,
+//! while input and canvas resizes are made up by [`MockPagePlugin`].
+
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WebElement};
+use web_sys::OffscreenCanvas;
+
+use super::{receive, BridgeSchedules, Build, InputInject, PageState};
+use crate::protocol::{CapabilityReport, HostMessage, Transferable, ViewId};
+
+/// Canvas sizes mock page cycles through.
+const SIZES: [(u32, u32); 2] = [(1280, 720), (960, 540)];
+
+/// How often mock page resizes the canvas.
+const RESIZE_PERIOD_SECS: f32 = 5.0;
+
+/// Build app without waiting for the page.
+///
+/// Page reports no capabilities of its own, headless apps don't get a canvas.
+pub(super) fn start(build: Build) {
+    let mut messages = vec![HostMessage::Capabilities(CapabilityReport::default())];
+    if !matches!(build, Build::Headless(_)) {
+        let (width, height) = SIZES[0];
+        let canvas = OffscreenCanvas::new(width, height).expect("canvas creation succeeds");
+
+        messages.push(HostMessage::Attach {
+            view: ViewId::PRIMARY,
+            canvas: Transferable::new(canvas),
+        });
+    }
+
+    for msg in messages {
+        let (data, _) = msg.encode().expect("mock page messages to encode");
+        receive(&build, data);
+    }
+}
+
+/// Feed app synthetic input and canvas resizes.
+#[derive(Default)]
+pub struct MockPagePlugin;
+
+impl Plugin for MockPagePlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.insert_resource(PageState {
+            connected: false,
+            visible: true,
+        })
+        .add_systems(
+            schedules.input,
+            (move_cursor, resize_canvas).in_set(InputInject),
+        );
+    }
+}
+
+/// Drag cursor around a Lissajous curve.
+fn move_cursor(
+    time: Res<Time>,
+    mut windows: Query<(Entity, &mut Window), With<PrimaryWindow>>,
+    mut moved: EventWriter<CursorMoved>,
+) {
+    let Ok((entity, mut window)) = windows.get_single_mut() else {
+        return;
+    };
+
+    let t = time.elapsed_seconds();
+    let position = Vec2::new(
+        (0.5 + 0.4 * (t * 0.7).sin()) * window.width(),
+        (0.5 + 0.4 * (t * 1.1).cos()) * window.height(),
+    );
+
+    window.set_cursor_position(Some(position));
+    moved.send(CursorMoved {
+        window: entity,
+        position,
+    });
+}
+
+fn resize_canvas(
+    time: Res<Time>,
+    mut current: Local<usize>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Ok(mut window) = windows.get_single_mut() else {
+        return;
+    };
+
+    let index = (time.elapsed_seconds() / RESIZE_PERIOD_SECS) as usize % SIZES.len();
+    if index == *current {
+        return;
+    }
+    *current = index;
+
+    let (width, height) = SIZES[index];
+
+    if let WebElement::OffscreenCanvas(canvas) = &window.web_element {
+        canvas.set_width(width);
+        canvas.set_height(height);
+    }
+
+    window.resolution.set(width as f32, height as f32);
+}
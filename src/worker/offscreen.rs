@@ -0,0 +1,186 @@
+//! Stop rendering views scrolled out of sight.
+//!
+//! Page watches every canvas with an `IntersectionObserver` and reports when it leaves or enters the viewport,
+//! as well as how much of it is visible.
+//! Cameras rendering to a hidden view are deactivated until it is back,
+//! and once all views of the app are hidden, the app itself slows down unless told to keep simulating.
+//! Views which are only partly visible are rendered at a reduced rate, see [`VisibilityPolicy`]:
+//! app is updated less often, as long as none of its other views needs the full rate.
+//! Switching cameras off instead would leave the canvas without a picture in skipped frames.
+
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::utils::{HashMap, HashSet};
+use bevy::window::{PrimaryWindow, WindowRef};
+
+use super::{current_app, scheduler, take_messages, BridgeReceive, BridgeSchedules, Views};
+use crate::protocol::{HostMessage, ViewId};
+
+/// Pause rendering of views which are out of sight.
+#[derive(Default)]
+pub struct OffscreenPlugin {
+    /// Keep updating the app at full rate while all of its views are hidden.
+    ///
+    /// Rendering stops either way, this only matters for the simulation.
+    pub keep_simulating: bool,
+}
+
+impl Plugin for OffscreenPlugin {
+    fn build(&self, app: &mut App) {
+        scheduler::set_throttle_offscreen(current_app(), !self.keep_simulating);
+
+        let schedules = BridgeSchedules::of(app);
+
+        app.init_resource::<OffscreenViews>()
+            .init_resource::<VisibilityPolicy>()
+            .add_systems(
+                schedules.receive,
+                (receive_intersections, pause_cameras)
+                    .chain()
+                    .in_set(BridgeReceive),
+            );
+    }
+}
+
+/// How views are rendered depending on how much of their canvas is visible.
+///
+/// Change it at runtime to trade smoothness of partly visible views for battery.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct VisibilityPolicy {
+    /// Views with smaller part of canvas in sight are rendered at `reduced_fps`.
+    pub reduced_below: f32,
+    pub reduced_fps: u32,
+    /// Views with smaller part of canvas in sight are not rendered at all,
+    /// same as views out of sight.
+    pub suspended_below: f32,
+}
+
+impl Default for VisibilityPolicy {
+    fn default() -> Self {
+        VisibilityPolicy {
+            reduced_below: 0.5,
+            reduced_fps: 15,
+            suspended_below: 0.0,
+        }
+    }
+}
+
+/// How often view is rendered, see [`VisibilityPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderRate {
+    Full,
+    Reduced { fps: u32 },
+    Suspended,
+}
+
+/// Views which are scrolled out of sight and how much of the others is visible.
+#[derive(Resource, Debug, Default)]
+pub struct OffscreenViews {
+    hidden: HashSet<ViewId>,
+    ratios: HashMap<ViewId, f32>,
+}
+
+impl OffscreenViews {
+    pub fn is_hidden(&self, view: ViewId) -> bool {
+        self.hidden.contains(&view)
+    }
+
+    /// Part of view's canvas in sight, from 0 to 1.
+    ///
+    /// Views page didn't report on yet are considered fully visible.
+    pub fn ratio(&self, view: ViewId) -> f32 {
+        self.ratios.get(&view).copied().unwrap_or(1.0)
+    }
+
+    /// How often view is rendered under given policy.
+    pub fn render_rate(&self, view: ViewId, policy: &VisibilityPolicy) -> RenderRate {
+        let ratio = self.ratio(view);
+
+        if self.is_hidden(view) || ratio < policy.suspended_below {
+            RenderRate::Suspended
+        } else if ratio < policy.reduced_below {
+            RenderRate::Reduced {
+                fps: policy.reduced_fps,
+            }
+        } else {
+            RenderRate::Full
+        }
+    }
+}
+
+fn receive_intersections(mut offscreen: ResMut<OffscreenViews>) {
+    take_messages(|msg| match msg {
+        HostMessage::ViewIntersection {
+            view,
+            visible,
+            ratio,
+        } => {
+            if visible {
+                offscreen.hidden.remove(&view);
+            } else {
+                offscreen.hidden.insert(view);
+            }
+            offscreen.ratios.insert(view, ratio);
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
+
+fn pause_cameras(
+    views: Res<Views>,
+    offscreen: Res<OffscreenViews>,
+    policy: Res<VisibilityPolicy>,
+    primary: Query<Entity, With<PrimaryWindow>>,
+    mut cameras: Query<(Entity, &mut Camera)>,
+    mut paused: Local<HashSet<Entity>>,
+    mut capped_fps: Local<Option<u32>>,
+) {
+    let rates: HashMap<Entity, RenderRate> = views
+        .iter()
+        .map(|(view, window)| (window, offscreen.render_rate(view, &policy)))
+        .collect();
+
+    // Whole app slows down, so it is up to the fastest view still in sight.
+    let mut fps = None;
+    for rate in rates.values() {
+        match rate {
+            RenderRate::Full => {
+                fps = None;
+                break;
+            }
+            RenderRate::Reduced { fps: reduced } => {
+                fps = Some(fps.map_or(*reduced, |fps: u32| fps.max(*reduced)))
+            }
+            RenderRate::Suspended => (),
+        }
+    }
+    if *capped_fps != fps {
+        *capped_fps = fps;
+        scheduler::set_reduced_fps(current_app(), fps);
+    }
+
+    for (entity, mut camera) in &mut cameras {
+        let window = match &camera.target {
+            RenderTarget::Window(WindowRef::Primary) => primary.get_single().ok(),
+            RenderTarget::Window(WindowRef::Entity(window)) => Some(*window),
+            _ => None,
+        };
+        let rate = window
+            .and_then(|window| rates.get(&window).copied())
+            .unwrap_or(RenderRate::Full);
+
+        let hide = rate == RenderRate::Suspended;
+
+        if hide && camera.is_active {
+            camera.is_active = false;
+            paused.insert(entity);
+        } else if !hide && paused.remove(&entity) {
+            // Only cameras paused here are turned back on, app may have turned others off itself.
+            camera.is_active = true;
+        }
+    }
+
+    // Forget cameras which were despawned while paused.
+    paused.retain(|entity| cameras.contains(*entity));
+}
@@ -0,0 +1,472 @@
+//! Direct connections to other workers, set up by the page with
+//! [`WorkerMesh`](crate::host::mesh::WorkerMesh).
+//!
+//! Page creates a `MessageChannel` and hands one end to each worker,
+//! after that messages go straight between the two without touching the bridge.
+//! Peers exchange raw bytes or [`TransformSnapshot`]s.
+//! [`TransformSyncPlugin`] publishes transforms of [`Synced`] entities every frame on one side
+//! and applies them on the other, which is all it takes to move physics out of the render worker.
+//!
+//! Ports don't tell when the other end is gone, so closing side sends a notice before closing its port.
+//! Peers which said goodbye or can't be posted to anymore are forgotten with [`PeerDisconnected`].
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use js_sys::{Array, ArrayBuffer, Object, Reflect, Uint8Array};
+use wasm_bindgen::prelude::{Closure, JsCast, JsValue};
+use web_sys::{MessageEvent, MessagePort};
+
+use super::{
+    current_app, take_messages, wake_app, AppId, BridgeReceive, BridgeSchedules, BridgeSend,
+};
+use crate::protocol::HostMessage;
+
+thread_local! {
+    static CONNECTIONS: RefCell<HashMap<(AppId, String), Connection>> = RefCell::new(HashMap::new());
+    static INCOMING: RefCell<Vec<(AppId, String, PeerMessage)>> = RefCell::new(Vec::new());
+    // Ports peers announced closing on, connection can't be dropped from inside its own handler.
+    static CLOSED: RefCell<Vec<(AppId, String, MessagePort)>> = RefCell::new(Vec::new());
+}
+
+/// Surface peers page connects the app to as events.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins).
+#[derive(Default)]
+pub struct PeersPlugin;
+
+impl Plugin for PeersPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.init_resource::<Peers>()
+            .add_event::<PeerConnected>()
+            .add_event::<PeerReceived>()
+            .add_event::<PeerDisconnected>()
+            .add_systems(schedules.receive, receive.in_set(BridgeReceive))
+            .add_systems(schedules.send, flush.in_set(BridgeSend));
+    }
+}
+
+/// Message exchanged with a peer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PeerMessage {
+    Bytes(Vec<u8>),
+    Snapshot(TransformSnapshot),
+}
+
+/// Page connected the app to another worker.
+///
+/// Connecting to the same peer again replaces the previous connection.
+#[derive(Event, Debug, Clone)]
+pub struct PeerConnected {
+    pub peer: String,
+}
+
+/// Peer closed its end or can't be reached anymore, it is gone from [`Peers`].
+#[derive(Event, Debug, Clone)]
+pub struct PeerDisconnected {
+    pub peer: String,
+}
+
+/// Message received from a peer.
+#[derive(Event, Debug, Clone)]
+pub struct PeerReceived {
+    pub peer: String,
+    pub message: PeerMessage,
+}
+
+/// Access to connected peers, identified by names page gave them.
+#[derive(Resource, Debug, Default)]
+pub struct Peers {
+    connected: HashSet<String>,
+    outgoing: Vec<(String, PeerMessage)>,
+}
+
+impl Peers {
+    pub fn is_connected(&self, peer: &str) -> bool {
+        self.connected.contains(peer)
+    }
+
+    pub fn peers(&self) -> impl Iterator<Item = &str> + '_ {
+        self.connected.iter().map(String::as_str)
+    }
+
+    /// Queue message to be sent at the end of the frame.
+    ///
+    /// Messages for peers which aren't connected are dropped.
+    pub fn send(&mut self, peer: &str, message: PeerMessage) {
+        self.outgoing.push((peer.to_owned(), message));
+    }
+}
+
+/// Identity of entity shared with peers, entity ids themselves differ between worlds.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Synced(pub u32);
+
+/// Transforms of [`Synced`] entities at one point in time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TransformSnapshot {
+    pub entries: Vec<(Synced, Transform)>,
+}
+
+impl TransformSnapshot {
+    /// Id, translation, rotation and scale.
+    const ENTRY_SIZE: usize = 4 + 10 * 4;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.entries.len() * Self::ENTRY_SIZE);
+
+        for (Synced(id), transform) in &self.entries {
+            bytes.extend_from_slice(&id.to_le_bytes());
+
+            let floats = transform
+                .translation
+                .to_array()
+                .into_iter()
+                .chain(transform.rotation.to_array())
+                .chain(transform.scale.to_array());
+            for x in floats {
+                bytes.extend_from_slice(&x.to_le_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() % Self::ENTRY_SIZE != 0 {
+            return None;
+        }
+
+        let entries = bytes
+            .chunks_exact(Self::ENTRY_SIZE)
+            .map(|entry| {
+                let word = |i: usize| [entry[i], entry[i + 1], entry[i + 2], entry[i + 3]];
+                let float = |n: usize| f32::from_le_bytes(word(4 + n * 4));
+
+                let transform = Transform {
+                    translation: Vec3::new(float(0), float(1), float(2)),
+                    rotation: Quat::from_xyzw(float(3), float(4), float(5), float(6)),
+                    scale: Vec3::new(float(7), float(8), float(9)),
+                };
+
+                (Synced(u32::from_le_bytes(word(0))), transform)
+            })
+            .collect();
+
+        Some(TransformSnapshot { entries })
+    }
+}
+
+/// Which way [`TransformSyncPlugin`] moves transforms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncRole {
+    /// Send transforms of all [`Synced`] entities to the peer every frame.
+    Publish,
+    /// Overwrite transforms of [`Synced`] entities with the latest snapshot from the peer.
+    ///
+    /// Entities missing from either side are left alone.
+    Apply,
+}
+
+/// Keep transforms of [`Synced`] entities in step with a peer.
+///
+/// Can be added once per peer. Requires [`PeersPlugin`].
+pub struct TransformSyncPlugin {
+    pub peer: String,
+    pub role: SyncRole,
+}
+
+impl Plugin for TransformSyncPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+        let peer = self.peer.clone();
+
+        match self.role {
+            SyncRole::Publish => {
+                let publish = move |synced: Query<(&Synced, &Transform)>,
+                                    mut peers: ResMut<Peers>| {
+                    if !peers.is_connected(&peer) {
+                        return;
+                    }
+
+                    let entries = synced
+                        .iter()
+                        .map(|(synced, transform)| (*synced, *transform))
+                        .collect();
+                    peers.send(&peer, PeerMessage::Snapshot(TransformSnapshot { entries }));
+                };
+
+                app.add_systems(schedules.send, publish.before(BridgeSend));
+            }
+            SyncRole::Apply => {
+                let apply =
+                    move |mut received: EventReader<PeerReceived>,
+                          mut synced: Query<(&Synced, &mut Transform)>| {
+                        // Snapshots carry everything, so only the latest one matters.
+                        let latest = received
+                            .iter()
+                            .filter(|event| event.peer == peer)
+                            .filter_map(|event| match &event.message {
+                                PeerMessage::Snapshot(snapshot) => Some(snapshot),
+                                PeerMessage::Bytes(_) => None,
+                            })
+                            .last();
+                        let Some(snapshot) = latest else {
+                            return;
+                        };
+
+                        let transforms: HashMap<_, _> = snapshot.entries.iter().copied().collect();
+                        for (synced, mut transform) in &mut synced {
+                            if let Some(latest) = transforms.get(synced) {
+                                *transform = *latest;
+                            }
+                        }
+                    };
+
+                app.add_systems(schedules.receive, apply.after(BridgeReceive));
+            }
+        }
+    }
+
+    fn is_unique(&self) -> bool {
+        false
+    }
+}
+
+struct Connection {
+    port: MessagePort,
+    _onmessage: Closure<dyn FnMut(MessageEvent)>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // Other end may be gone already, nothing to do then.
+        let _ = self.port.post_message(&close_notice());
+        self.port.set_onmessage(None);
+        self.port.close();
+    }
+}
+
+/// Sent by the side closing its port as `{ closed: true }`.
+fn close_notice() -> JsValue {
+    let object = Object::new();
+    // Setting a property on a fresh plain object cannot fail.
+    let _ = Reflect::set(&object, &"closed".into(), &JsValue::TRUE);
+    object.into()
+}
+
+fn is_close_notice(data: &JsValue) -> bool {
+    data.is_object()
+        && Reflect::get(data, &"closed".into())
+            .ok()
+            .and_then(|closed| closed.as_bool())
+            .unwrap_or(false)
+}
+
+/// Forget connection to the peer, unless it was replaced by a newer one already.
+fn disconnect(app: AppId, peer: &str, port: &MessagePort) -> bool {
+    let connection = CONNECTIONS.with(|connections| {
+        let mut connections = connections.borrow_mut();
+        let key = (app, peer.to_owned());
+        let current = connections
+            .get(&key)
+            .map_or(false, |connection| connection.port == *port);
+        current.then(|| connections.remove(&key)).flatten()
+    });
+    let removed = connection.is_some();
+    drop(connection);
+    removed
+}
+
+/// Raw bytes travel as array buffers, snapshots as `{ snapshot: ArrayBuffer }`.
+fn decode(data: &JsValue) -> Option<PeerMessage> {
+    if let Some(buffer) = data.dyn_ref::<ArrayBuffer>() {
+        return Some(PeerMessage::Bytes(Uint8Array::new(buffer).to_vec()));
+    }
+
+    let snapshot: ArrayBuffer = Reflect::get(data, &"snapshot".into())
+        .ok()?
+        .dyn_into()
+        .ok()?;
+    TransformSnapshot::from_bytes(&Uint8Array::new(&snapshot).to_vec()).map(PeerMessage::Snapshot)
+}
+
+fn encode(message: &PeerMessage) -> (JsValue, ArrayBuffer) {
+    match message {
+        PeerMessage::Bytes(bytes) => {
+            let buffer = Uint8Array::from(&bytes[..]).buffer();
+            (buffer.clone().into(), buffer)
+        }
+        PeerMessage::Snapshot(snapshot) => {
+            let buffer = Uint8Array::from(&snapshot.to_bytes()[..]).buffer();
+            let object = Object::new();
+            // Setting a property on a fresh plain object cannot fail.
+            let _ = Reflect::set(&object, &"snapshot".into(), &buffer);
+            (object.into(), buffer)
+        }
+    }
+}
+
+fn receive(
+    mut peers: ResMut<Peers>,
+    mut connected: EventWriter<PeerConnected>,
+    mut received: EventWriter<PeerReceived>,
+    mut disconnected: EventWriter<PeerDisconnected>,
+) {
+    let app = current_app();
+
+    take_messages(|msg| match msg {
+        HostMessage::Peer { peer, port } => {
+            let port = port.into_inner();
+            let onmessage = {
+                let peer = peer.clone();
+                let port = port.clone();
+
+                Closure::wrap(Box::new(move |event: MessageEvent| {
+                    let data = event.data();
+                    if is_close_notice(&data) {
+                        CLOSED.with(|closed| {
+                            closed.borrow_mut().push((app, peer.clone(), port.clone()))
+                        });
+                        wake_app(app);
+                        return;
+                    }
+
+                    let Some(message) = decode(&data) else {
+                        warn!("received malformed message from peer `{peer}`");
+                        return;
+                    };
+
+                    INCOMING
+                        .with(|incoming| incoming.borrow_mut().push((app, peer.clone(), message)));
+                    wake_app(app);
+                }) as Box<dyn FnMut(MessageEvent)>)
+            };
+            port.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+
+            let connection = Connection {
+                port,
+                _onmessage: onmessage,
+            };
+            let previous = CONNECTIONS.with(|connections| {
+                connections
+                    .borrow_mut()
+                    .insert((app, peer.clone()), connection)
+            });
+            drop(previous);
+
+            peers.connected.insert(peer.clone());
+            connected.send(PeerConnected { peer });
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+
+    let closed = CLOSED.with(|closed| {
+        let mut closed = closed.borrow_mut();
+        let (ours, rest) = closed.drain(..).partition(|(id, ..)| *id == app);
+        *closed = rest;
+        ours
+    });
+    for (_, peer, port) in closed {
+        if disconnect(app, &peer, &port) && peers.connected.remove(&peer) {
+            disconnected.send(PeerDisconnected { peer });
+        }
+    }
+
+    let incoming = INCOMING.with(|incoming| {
+        let mut incoming = incoming.borrow_mut();
+        let (ours, rest) = incoming.drain(..).partition(|(id, ..)| *id == app);
+        *incoming = rest;
+        ours
+    });
+
+    received.send_batch(
+        incoming
+            .into_iter()
+            .map(|(_, peer, message): (AppId, String, PeerMessage)| PeerReceived { peer, message }),
+    );
+}
+
+fn flush(mut peers: ResMut<Peers>, mut disconnected: EventWriter<PeerDisconnected>) {
+    let app = current_app();
+
+    for (peer, message) in std::mem::take(&mut peers.outgoing) {
+        let failed = CONNECTIONS.with(|connections| {
+            let connections = connections.borrow();
+            let Some(connection) = connections.get(&(app, peer.clone())) else {
+                warn!("peer `{peer}` is not connected, message is dropped");
+                return None;
+            };
+
+            let (data, buffer) = encode(&message);
+            match connection
+                .port
+                .post_message_with_transferable(&data, &Array::of1(&buffer))
+            {
+                Ok(()) => None,
+                Err(err) => {
+                    warn!("failed to send message to peer `{peer}`, disconnecting: {err:?}");
+                    Some(connection.port.clone())
+                }
+            }
+        });
+
+        if let Some(port) = failed {
+            if disconnect(app, &peer, &port) && peers.connected.remove(&peer) {
+                disconnected.send(PeerDisconnected { peer });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_round_trip() {
+        let snapshot = TransformSnapshot {
+            entries: vec![
+                (Synced(1), Transform::from_xyz(1.0, -2.0, 3.5)),
+                (
+                    Synced(u32::MAX),
+                    Transform::from_rotation(Quat::from_rotation_z(0.5))
+                        .with_scale(Vec3::splat(2.0)),
+                ),
+            ],
+        };
+        let bytes = snapshot.to_bytes();
+
+        assert_eq!(bytes.len(), 2 * TransformSnapshot::ENTRY_SIZE);
+        assert_eq!(TransformSnapshot::from_bytes(&bytes), Some(snapshot));
+    }
+
+    #[test]
+    fn empty_snapshot() {
+        let bytes = TransformSnapshot::default().to_bytes();
+
+        assert!(bytes.is_empty());
+        assert_eq!(
+            TransformSnapshot::from_bytes(&bytes),
+            Some(TransformSnapshot::default())
+        );
+    }
+
+    #[test]
+    fn truncated_snapshot() {
+        let bytes = TransformSnapshot {
+            entries: vec![(Synced(1), Transform::IDENTITY)],
+        }
+        .to_bytes();
+
+        assert_eq!(
+            TransformSnapshot::from_bytes(&bytes[..bytes.len() - 1]),
+            None
+        );
+    }
+}
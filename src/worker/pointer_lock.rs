@@ -0,0 +1,124 @@
+//! Pointer lock for mouse look.
+//!
+//! Only the page can lock pointer to a canvas, so worker follows `CursorGrabMode` of its windows
+//! and asks page to lock or release the pointer.
+//! While locked, page forwards raw mouse movement, which arrives as [`MouseMotion`] events.
+
+use bevy::input::mouse::MouseMotion;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy::window::CursorGrabMode;
+
+use super::{post, take_messages, BridgeSchedules, BridgeSend, InputInject, Views};
+use crate::protocol::{HostMessage, WorkerMessage};
+
+/// Lock pointer to views whose window asks for cursor grab.
+///
+/// Both `Locked` and `Confined` grab modes lock the pointer, browsers have nothing in between.
+#[derive(Default)]
+pub struct PointerLockPlugin;
+
+impl Plugin for PointerLockPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.init_resource::<PointerLockState>()
+            .add_systems(schedules.input, receive_lock.in_set(InputInject))
+            .add_systems(schedules.send, request_lock.in_set(BridgeSend));
+    }
+}
+
+/// Window pointer is locked to, as reported by the page.
+///
+/// Lock can be lost at any moment, e.g. user pressing Escape,
+/// in which case window's grab mode is reset to `None`.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct PointerLockState {
+    pub window: Option<Entity>,
+}
+
+impl PointerLockState {
+    pub fn is_locked(&self) -> bool {
+        self.window.is_some()
+    }
+}
+
+fn receive_lock(
+    views: Res<Views>,
+    mut state: ResMut<PointerLockState>,
+    mut windows: Query<&mut Window>,
+    mut motion: EventWriter<MouseMotion>,
+) {
+    take_messages(|msg| match msg {
+        HostMessage::PointerMotion { view, dx, dy } => {
+            // Movement may still trickle in after worker learned lock is gone.
+            if state.window.is_some() && state.window == views.window(view) {
+                motion.send(MouseMotion {
+                    delta: Vec2::new(dx, dy),
+                });
+            }
+
+            Ok(())
+        }
+        HostMessage::PointerLockChanged { view, locked } => {
+            let Some(entity) = views.window(view) else {
+                return Ok(());
+            };
+
+            if locked {
+                state.window = Some(entity);
+            } else if state.window == Some(entity) {
+                state.window = None;
+            }
+
+            // Keep grab mode in line with reality, so lock taken away by browser is not requested again.
+            if let Ok(mut window) = windows.get_mut(entity) {
+                match (locked, window.cursor.grab_mode) {
+                    (true, CursorGrabMode::None) => {
+                        window.cursor.grab_mode = CursorGrabMode::Locked
+                    }
+                    (false, CursorGrabMode::Locked | CursorGrabMode::Confined) => {
+                        window.cursor.grab_mode = CursorGrabMode::None
+                    }
+                    _ => (),
+                }
+            }
+
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
+
+fn request_lock(
+    views: Res<Views>,
+    state: Res<PointerLockState>,
+    windows: Query<(Entity, &Window)>,
+    mut requested: Local<HashMap<Entity, CursorGrabMode>>,
+) {
+    for (entity, window) in &windows {
+        let mode = window.cursor.grab_mode;
+        let previous = requested
+            .insert(entity, mode)
+            .unwrap_or(CursorGrabMode::None);
+        if previous == mode {
+            continue;
+        }
+
+        let locked = state.window == Some(entity);
+
+        match mode {
+            CursorGrabMode::None if locked => post(&WorkerMessage::ExitPointerLock),
+            CursorGrabMode::Locked | CursorGrabMode::Confined if !locked => {
+                let view = views
+                    .iter()
+                    .find_map(|(view, window)| (window == entity).then_some(view));
+
+                if let Some(view) = view {
+                    post(&WorkerMessage::RequestPointerLock { view });
+                }
+            }
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! Vsync switched by the page, see [`HostMessage::SetVsync`].
+//!
+//! Request becomes `PresentMode::AutoVsync` or `PresentMode::AutoNoVsync` of every view window,
+//! which wgpu resolves against modes the surface offers.
+//! Browsers present canvases together with the rest of the page, so their surfaces may only offer FIFO.
+//! Render world asks surface of every window which modes it has,
+//! and worker reports the one which will actually be used with [`WorkerMessage::PresentMode`].
+//!
+//! Without vsync app stops waiting for page's animation frames in [`UpdateMode::AnimationFrame`],
+//! and updates at target frame rate instead, even if surface keeps presenting in sync.
+//!
+//! [`UpdateMode::AnimationFrame`]: crate::protocol::UpdateMode::AnimationFrame
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use bevy::prelude::*;
+use bevy::render::renderer::{render_system, RenderAdapter, RenderInstance};
+use bevy::render::{Extract, ExtractSchedule, Render, RenderApp, RenderSet};
+use bevy::utils::HashMap;
+use bevy::window::{PresentMode, WebElement};
+use web_sys::OffscreenCanvas;
+
+use super::{post, take_messages, BridgeReceive, BridgeSchedules, Views};
+use crate::protocol::{HostMessage, SurfacePresentMode, ViewId, WorkerMessage};
+
+/// Apply vsync requested by the page to view windows.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins) for apps with a window.
+#[derive(Default)]
+pub struct PresentModePlugin;
+
+impl Plugin for PresentModePlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.init_resource::<RequestedPresentMode>()
+            .init_resource::<SurfacePresentModes>()
+            .add_systems(
+                schedules.receive,
+                (receive_vsync, apply_present_mode, report_present_mode)
+                    .chain()
+                    .in_set(BridgeReceive),
+            );
+    }
+
+    // Render app is only there once `RenderPlugin` is built.
+    fn finish(&self, app: &mut App) {
+        let modes = app.world.resource::<SurfacePresentModes>().clone();
+
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        // Canvases aren't `Send`, but render world runs on the same thread as the app.
+        render_app
+            .insert_resource(modes)
+            .insert_non_send_resource(Unprobed::default())
+            .add_systems(ExtractSchedule, extract_canvases)
+            .add_systems(
+                Render,
+                probe_surfaces
+                    .in_set(RenderSet::Render)
+                    .before(render_system),
+            );
+    }
+}
+
+/// Present mode page asked for, `None` until it does.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RequestedPresentMode(pub Option<PresentMode>);
+
+impl RequestedPresentMode {
+    /// Whether app should present and update in sync with display refresh.
+    pub fn vsync(&self) -> bool {
+        self.0 != Some(PresentMode::AutoNoVsync)
+    }
+}
+
+/// Modes surface of each window offers, travelling from render world.
+#[derive(Resource, Clone, Default)]
+struct SurfacePresentModes(Arc<Mutex<HashMap<Entity, Vec<PresentMode>>>>);
+
+impl SurfacePresentModes {
+    // Modes are plain values, panic of the other side leaves nothing broken behind.
+    fn lock(&self) -> MutexGuard<'_, HashMap<Entity, Vec<PresentMode>>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Canvases of windows render world is yet to ask about.
+#[derive(Default)]
+struct Unprobed(Vec<(Entity, OffscreenCanvas)>);
+
+fn receive_vsync(mut requested_mode: ResMut<RequestedPresentMode>) {
+    let requests = take_messages(|msg| match msg {
+        HostMessage::SetVsync(vsync) => Ok(vsync),
+        msg => Err(msg),
+    });
+    let Some(&vsync) = requests.last() else {
+        return;
+    };
+
+    // Setting it marks the request changed even if it is the same, so page gets its answer.
+    requested_mode.0 = Some(if vsync {
+        PresentMode::AutoVsync
+    } else {
+        PresentMode::AutoNoVsync
+    });
+}
+
+// Views attached later get the mode too.
+fn apply_present_mode(
+    requested: Res<RequestedPresentMode>,
+    views: Res<Views>,
+    mut windows: Query<&mut Window>,
+) {
+    let Some(requested) = requested.0 else {
+        return;
+    };
+
+    for (_, entity) in views.iter() {
+        let Ok(mut window) = windows.get_mut(entity) else {
+            continue;
+        };
+        if window.present_mode != requested {
+            window.present_mode = requested;
+        }
+    }
+}
+
+/// Tell the page which mode primary view presents with, once its surface was asked.
+fn report_present_mode(
+    requested: Res<RequestedPresentMode>,
+    views: Res<Views>,
+    modes: Res<SurfacePresentModes>,
+    mut reported: Local<Option<SurfacePresentMode>>,
+) {
+    if requested.0.is_none() {
+        return;
+    }
+
+    let window = views
+        .window(ViewId::PRIMARY)
+        .or_else(|| views.iter().map(|(_, window)| window).next());
+    let resolved = window.and_then(|window| {
+        let modes = modes.lock();
+        let supported = modes.get(&window)?;
+        Some(resolve(requested.vsync(), supported))
+    });
+    let Some(resolved) = resolved else {
+        return;
+    };
+    if !requested.is_changed() && *reported == Some(resolved) {
+        return;
+    }
+
+    if !requested.vsync() && resolved.is_vsync() {
+        warn!("surface can't turn vsync off, presenting with {resolved:?}");
+    }
+    *reported = Some(resolved);
+    post(&WorkerMessage::PresentMode(resolved));
+}
+
+fn extract_canvases(
+    mut unprobed: NonSendMut<Unprobed>,
+    modes: Res<SurfacePresentModes>,
+    windows: Extract<Query<(Entity, &Window)>>,
+) {
+    let modes = modes.lock();
+
+    for (entity, window) in windows.iter() {
+        let WebElement::OffscreenCanvas(canvas) = &window.web_element else {
+            continue;
+        };
+        let pending = unprobed.0.iter().any(|(window, _)| *window == entity);
+        if !modes.contains_key(&entity) && !pending {
+            unprobed.0.push((entity, canvas.clone()));
+        }
+    }
+}
+
+/// Ask surfaces of new windows which present modes they offer.
+///
+/// Runs after windows are prepared, so the canvas already has the context window's own surface uses,
+/// and a surface made for asking shares it.
+fn probe_surfaces(
+    mut unprobed: NonSendMut<Unprobed>,
+    instance: Res<RenderInstance>,
+    adapter: Res<RenderAdapter>,
+    modes: Res<SurfacePresentModes>,
+) {
+    for (window, canvas) in unprobed.0.drain(..) {
+        let supported = match instance.create_surface_from_offscreen_canvas(canvas) {
+            Ok(surface) => surface
+                .get_capabilities(&adapter)
+                .present_modes
+                .into_iter()
+                .map(present_mode)
+                .collect(),
+            Err(err) => {
+                warn!("failed to ask surface for its present modes: {err}");
+                vec![PresentMode::Fifo]
+            }
+        };
+
+        modes.lock().insert(window, supported);
+    }
+}
+
+fn present_mode(mode: wgpu::PresentMode) -> PresentMode {
+    match mode {
+        wgpu::PresentMode::AutoVsync => PresentMode::AutoVsync,
+        wgpu::PresentMode::AutoNoVsync => PresentMode::AutoNoVsync,
+        wgpu::PresentMode::Fifo => PresentMode::Fifo,
+        wgpu::PresentMode::FifoRelaxed => PresentMode::FifoRelaxed,
+        wgpu::PresentMode::Immediate => PresentMode::Immediate,
+        wgpu::PresentMode::Mailbox => PresentMode::Mailbox,
+    }
+}
+
+/// Pick mode surface offers the same way wgpu does.
+fn resolve(vsync: bool, supported: &[PresentMode]) -> SurfacePresentMode {
+    let fallbacks: &[(PresentMode, SurfacePresentMode)] = if vsync {
+        &[
+            (PresentMode::FifoRelaxed, SurfacePresentMode::FifoRelaxed),
+            (PresentMode::Fifo, SurfacePresentMode::Fifo),
+        ]
+    } else {
+        &[
+            (PresentMode::Immediate, SurfacePresentMode::Immediate),
+            (PresentMode::Mailbox, SurfacePresentMode::Mailbox),
+            (PresentMode::Fifo, SurfacePresentMode::Fifo),
+        ]
+    };
+
+    fallbacks
+        .iter()
+        .find(|(mode, _)| supported.contains(mode))
+        .map_or(SurfacePresentMode::Fifo, |(_, resolved)| *resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_like_wgpu() {
+        let web = [PresentMode::Fifo];
+        assert_eq!(resolve(true, &web), SurfacePresentMode::Fifo);
+        assert_eq!(resolve(false, &web), SurfacePresentMode::Fifo);
+
+        let native = [
+            PresentMode::Fifo,
+            PresentMode::Mailbox,
+            PresentMode::Immediate,
+        ];
+        assert_eq!(resolve(true, &native), SurfacePresentMode::Fifo);
+        assert_eq!(resolve(false, &native), SurfacePresentMode::Immediate);
+    }
+}
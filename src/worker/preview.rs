@@ -0,0 +1,113 @@
+//! Previews of worker assets for the page.
+//!
+//! Page refers to assets by asset path, which means the same thing on both sides,
+//! and gets back an `ImageBitmap` it can draw e.g. as a DOM thumbnail.
+//! Asset server deduplicates loads by path, so assets worker already uses are not downloaded again.
+
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+use bevy::render::render_resource::TextureFormat;
+use wasm_bindgen::prelude::{JsCast, JsValue};
+use wasm_bindgen::Clamped;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{ImageBitmap, ImageData};
+
+use super::{post, scope, take_messages, BridgeReceive, BridgeSchedules, BridgeSend};
+use crate::protocol::{HostMessage, Transferable, WorkerMessage};
+
+/// Answer [`HostMessage::RequestAssetPreview`].
+#[derive(Default)]
+pub struct AssetPreviewPlugin;
+
+impl Plugin for AssetPreviewPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.init_resource::<PendingPreviews>()
+            .add_systems(schedules.receive, receive_requests.in_set(BridgeReceive))
+            .add_systems(schedules.send, send_previews.in_set(BridgeSend));
+    }
+}
+
+/// Requests waiting for their asset to load.
+#[derive(Resource, Default)]
+struct PendingPreviews {
+    requests: Vec<(u32, Handle<Image>)>,
+}
+
+fn receive_requests(asset_server: Res<AssetServer>, mut pending: ResMut<PendingPreviews>) {
+    take_messages(|msg| match msg {
+        HostMessage::RequestAssetPreview { request, path } => {
+            pending.requests.push((request, asset_server.load(path)));
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
+
+fn send_previews(
+    asset_server: Res<AssetServer>,
+    images: Res<Assets<Image>>,
+    mut pending: ResMut<PendingPreviews>,
+) {
+    pending.requests.retain(|(request, handle)| {
+        let pixels = match asset_server.get_load_state(handle) {
+            LoadState::Loaded => match images.get(handle) {
+                Some(image) => rgba(image),
+                // Asset is loaded, but is not an image.
+                None => Err("asset is not an image".to_owned()),
+            },
+            LoadState::Failed => Err("asset failed to load".to_owned()),
+            LoadState::Unloaded => Err("asset was unloaded".to_owned()),
+            LoadState::NotLoaded | LoadState::Loading => return true,
+        };
+
+        let request = *request;
+        wasm_bindgen_futures::spawn_local(async move {
+            let result = match pixels {
+                Ok((width, height, pixels)) => bitmap(width, height, &pixels)
+                    .await
+                    .map(Transferable::new)
+                    .map_err(|err| format!("failed to create bitmap: {err:?}")),
+                Err(err) => Err(err),
+            };
+
+            post(&WorkerMessage::AssetPreview { request, result });
+        });
+
+        false
+    });
+}
+
+/// Pixels of the top mip level as RGBA.
+fn rgba(image: &Image) -> Result<(u32, u32, Vec<u8>), String> {
+    let width = image.texture_descriptor.size.width;
+    let height = image.texture_descriptor.size.height;
+
+    let bgra = match image.texture_descriptor.format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => false,
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => true,
+        format => return Err(format!("previews of {format:?} textures are not supported")),
+    };
+
+    let Some(data) = image.data.get(..width as usize * height as usize * 4) else {
+        return Err("image data is truncated".to_owned());
+    };
+
+    let pixels = if bgra {
+        data.chunks_exact(4)
+            .flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+            .collect()
+    } else {
+        data.to_vec()
+    };
+
+    Ok((width, height, pixels))
+}
+
+async fn bitmap(width: u32, height: u32, pixels: &[u8]) -> Result<ImageBitmap, JsValue> {
+    let data = ImageData::new_with_u8_clamped_array_and_sh(Clamped(pixels), width, height)?;
+    let bitmap = JsFuture::from(scope().create_image_bitmap_with_image_data(&data)?).await?;
+
+    bitmap.dyn_into()
+}
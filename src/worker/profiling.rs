@@ -0,0 +1,197 @@
+//! Logging with schedules and selected spans reported as `performance.measure` entries.
+//!
+//! Browser profiler sees worker's frames as a wall of anonymous wasm calls.
+//! Measures put named bars over them in the worker's track of DevTools' Performance panel,
+//! e.g. `Update` or `bridge_receive pointer_move`.
+//! Every span could be measured, but with systems included there are thousands per frame,
+//! so only schedules and spans picked in [`ProfilingLogPlugin`] are.
+//!
+//! Schedules only have spans when Bevy is built with `trace`, enable crate's `trace` feature to get them.
+
+use bevy::log::tracing_subscriber::filter::filter_fn;
+use bevy::log::tracing_subscriber::layer::{Context, SubscriberExt};
+use bevy::log::tracing_subscriber::registry::LookupSpan;
+use bevy::log::tracing_subscriber::{EnvFilter, Layer, Registry};
+use bevy::log::Level;
+use bevy::prelude::*;
+use bevy::utils::tracing::field::{Field, Visit};
+use bevy::utils::tracing::{span, Subscriber};
+use wasm_bindgen::prelude::JsCast;
+use web_sys::{Performance, WorkerGlobalScope};
+
+/// Replacement for Bevy's `LogPlugin` which also reports spans to the browser profiler.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins) instead of `LogPlugin`, don't add both.
+pub struct ProfilingLogPlugin {
+    /// Filters logs using the [`EnvFilter`] format, same as in `LogPlugin`.
+    pub filter: String,
+    /// Filters out logs that are "less than" the given level.
+    pub level: Level,
+    /// Measure schedules and sub apps.
+    pub measure_schedules: bool,
+    /// Names of other spans to measure.
+    pub measured_spans: Vec<&'static str>,
+}
+
+impl Default for ProfilingLogPlugin {
+    fn default() -> Self {
+        let LogPlugin { filter, level } = LogPlugin::default();
+
+        ProfilingLogPlugin {
+            filter,
+            level,
+            measure_schedules: true,
+            measured_spans: vec!["frame", "bridge_receive", "bridge_send"],
+        }
+    }
+}
+
+impl Plugin for ProfilingLogPlugin {
+    fn build(&self, _app: &mut App) {
+        console_error_panic_hook::set_once();
+
+        let filter = EnvFilter::try_new(format!("{},{}", self.level, self.filter))
+            .unwrap_or_else(|_| EnvFilter::new(self.level.to_string()));
+
+        // Console layer marks and measures every span it sees, which drowns the ones picked here,
+        // so it only gets to see events.
+        let console = tracing_wasm::WASMLayer::new(
+            tracing_wasm::WASMLayerConfigBuilder::new()
+                .set_report_logs_in_timings(false)
+                .build(),
+        )
+        .with_filter(filter_fn(|metadata| metadata.is_event()));
+        let measures = PerformanceLayer {
+            schedules: self.measure_schedules,
+            spans: self.measured_spans.clone(),
+        };
+
+        let subscriber = Registry::default()
+            .with(filter)
+            .with(console)
+            .with(measures);
+
+        let logger_already_set = tracing_log::LogTracer::init().is_err();
+        let subscriber_already_set =
+            bevy::utils::tracing::subscriber::set_global_default(subscriber).is_err();
+
+        // Apps built after a restart find logger of the previous one, which is just as good.
+        if logger_already_set || subscriber_already_set {
+            debug!("global logger is already set, keeping it");
+        }
+    }
+}
+
+/// Label of a measured span.
+struct Measure(String);
+
+/// Tracing layer turning spans into `performance.measure` entries.
+struct PerformanceLayer {
+    schedules: bool,
+    spans: Vec<&'static str>,
+}
+
+impl PerformanceLayer {
+    fn label(&self, attrs: &span::Attributes) -> Option<String> {
+        let name = attrs.metadata().name();
+        let is_schedule = name == "schedule" || name == "sub app";
+        if !(is_schedule && self.schedules || self.spans.contains(&name)) {
+            return None;
+        }
+
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+
+        let label = match (is_schedule, fields.name, fields.kind) {
+            (true, Some(schedule), _) => schedule,
+            (false, _, Some(kind)) => format!("{name} {kind}"),
+            _ => name.to_owned(),
+        };
+
+        Some(label)
+    }
+}
+
+impl<S> Layer<S> for PerformanceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(label) = self.label(attrs) else {
+            return;
+        };
+
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Measure(label));
+        }
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if span.extensions().get::<Measure>().is_none() {
+            return;
+        }
+
+        if let Some(performance) = performance() {
+            let _ = performance.mark(&start_mark(id));
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let extensions = span.extensions();
+        let Some(Measure(label)) = extensions.get::<Measure>() else {
+            return;
+        };
+
+        if let Some(performance) = performance() {
+            let start = start_mark(id);
+            let _ = performance.measure_with_start_mark(label, &start);
+
+            // Profiler records entries as they are made, keeping them would only grow the timeline buffer.
+            performance.clear_marks_with_mark_name(&start);
+            performance.clear_measures_with_measure_name(label);
+        }
+    }
+}
+
+/// `name` and `kind` fields of a span, which make a readable label.
+#[derive(Default)]
+struct Fields {
+    name: Option<String>,
+    kind: Option<String>,
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "name" => self.name = Some(value.to_owned()),
+            "kind" => self.kind = Some(value.to_owned()),
+            _ => (),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "name" => self.name = Some(format!("{value:?}")),
+            "kind" => self.kind = Some(format!("{value:?}")),
+            _ => (),
+        }
+    }
+}
+
+fn start_mark(id: &span::Id) -> String {
+    format!("span {} start", id.into_u64())
+}
+
+/// Performance of whichever worker the span is on, app's own or one of the task pool threads.
+fn performance() -> Option<Performance> {
+    js_sys::global()
+        .dyn_into::<WorkerGlobalScope>()
+        .ok()?
+        .performance()
+}
@@ -0,0 +1,173 @@
+//! Phase items, pipelines and texture memory of the render world, as [`RenderStats`] resource.
+//!
+//! Browsers don't let native GPU profilers look inside a worker, so this is the quickest way
+//! to tell whether a scene is slow because of too many draws or too much texture data.
+//! Stats are gathered in render world after phases are sorted and batched,
+//! and show up in main world's resource on the next frame.
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use bevy::core_pipeline::core_2d::Transparent2d;
+use bevy::core_pipeline::core_3d::{AlphaMask3d, Opaque3d, Transparent3d};
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_phase::{PhaseItem, RenderPhase};
+use bevy::render::render_resource::{CachedPipelineState, PipelineCache};
+use bevy::render::renderer::render_system;
+use bevy::render::texture::GpuImage;
+use bevy::render::{Render, RenderApp, RenderSet};
+use bevy::ui::TransparentUi;
+
+use super::post;
+use crate::protocol::{RenderStats, WorkerMessage};
+
+/// Gather [`RenderStats`] every frame.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins) for apps with a window.
+pub struct RenderStatsPlugin {
+    /// Send stats to the page every interval.
+    pub forward_to_page: bool,
+    /// How often stats are sent to the page.
+    pub interval_secs: f64,
+}
+
+impl Default for RenderStatsPlugin {
+    fn default() -> Self {
+        RenderStatsPlugin {
+            forward_to_page: false,
+            interval_secs: 1.0,
+        }
+    }
+}
+
+impl Plugin for RenderStatsPlugin {
+    fn build(&self, _app: &mut App) {}
+
+    // Render app is only there once `RenderPlugin` is built.
+    fn finish(&self, app: &mut App) {
+        let shared = SharedRenderStats::default();
+
+        app.init_resource::<RenderStats>()
+            .insert_resource(shared.clone())
+            .insert_resource(Forwarding {
+                enabled: self.forward_to_page,
+                interval_secs: self.interval_secs,
+                last_sent: None,
+            })
+            .add_systems(Last, publish);
+
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.insert_resource(shared).add_systems(
+            Render,
+            gather.in_set(RenderSet::Render).before(render_system),
+        );
+    }
+}
+
+/// Stats travelling from render world.
+#[derive(Resource, Clone, Default)]
+struct SharedRenderStats(Arc<Mutex<RenderStats>>);
+
+impl SharedRenderStats {
+    // Stats are plain numbers, panic of the other side leaves nothing broken behind.
+    fn lock(&self) -> MutexGuard<'_, RenderStats> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[derive(Resource)]
+struct Forwarding {
+    enabled: bool,
+    interval_secs: f64,
+    last_sent: Option<f64>,
+}
+
+fn publish(
+    time: Res<Time>,
+    shared: Res<SharedRenderStats>,
+    mut stats: ResMut<RenderStats>,
+    mut forwarding: ResMut<Forwarding>,
+) {
+    let latest = *shared.lock();
+    if *stats != latest {
+        *stats = latest;
+    }
+
+    if !forwarding.enabled {
+        return;
+    }
+
+    // Intervals are in real time, paused or slowed down app still reports.
+    let now = time.raw_elapsed_seconds_f64();
+    if let Some(last_sent) = forwarding.last_sent {
+        if now - last_sent < forwarding.interval_secs {
+            return;
+        }
+    }
+
+    forwarding.last_sent = Some(now);
+    post(&WorkerMessage::RenderStats(latest));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn gather(
+    shared: Res<SharedRenderStats>,
+    pipelines: Res<PipelineCache>,
+    images: Res<RenderAssets<Image>>,
+    phases_2d: Query<&RenderPhase<Transparent2d>>,
+    opaque_3d: Query<&RenderPhase<Opaque3d>>,
+    alpha_mask_3d: Query<&RenderPhase<AlphaMask3d>>,
+    transparent_3d: Query<&RenderPhase<Transparent3d>>,
+    phases_ui: Query<&RenderPhase<TransparentUi>>,
+) {
+    fn items<I: PhaseItem>(phases: &Query<&RenderPhase<I>>) -> usize {
+        phases.iter().map(|phase| phase.items.len()).sum()
+    }
+
+    let phase_items = items(&phases_2d)
+        + items(&opaque_3d)
+        + items(&alpha_mask_3d)
+        + items(&transparent_3d)
+        + items(&phases_ui);
+
+    let pipelines = pipelines
+        .pipelines()
+        .filter(|pipeline| matches!(pipeline.state, CachedPipelineState::Ok(_)))
+        .count();
+
+    let mut textures = 0;
+    let mut texture_bytes = 0;
+    for (_, image) in images.iter() {
+        textures += 1;
+        texture_bytes += estimate_bytes(image);
+    }
+
+    *shared.lock() = RenderStats {
+        phase_items: phase_items as u32,
+        pipelines: pipelines as u32,
+        textures,
+        texture_bytes,
+    };
+}
+
+/// Size of image with all its mip levels, going by block size of its format.
+fn estimate_bytes(image: &GpuImage) -> u64 {
+    let info = image.texture_format.describe();
+    let (block_width, block_height) = info.block_dimensions;
+
+    let (mut width, mut height) = (image.size.x as u64, image.size.y as u64);
+    let mut bytes = 0;
+    for _ in 0..image.mip_level_count.max(1) {
+        let blocks_x = (width + block_width as u64 - 1) / block_width as u64;
+        let blocks_y = (height + block_height as u64 - 1) / block_height as u64;
+        bytes += blocks_x * blocks_y * info.block_size as u64;
+
+        width = (width / 2).max(1);
+        height = (height / 2).max(1);
+    }
+
+    bytes
+}
@@ -0,0 +1,295 @@
+//! Recording of messages app handles, and replaying them for deterministic debugging.
+//!
+//! While recording, every replayable message (see [`HostMessage::is_replayable`]) is kept
+//! together with the step it was handled at, counting from the start of recording.
+//! Apps with [`SimulationPlugin`](super::simulation::SimulationPlugin) count simulation steps,
+//! so messages land between the same steps no matter how many of them rendered frames happened to take;
+//! other apps count frames.
+//! Recording goes to the page as a JSON document and can be fed back into the same app, possibly in another browser.
+//! During replay time advances by exactly one step each frame and live replayable messages are dropped,
+//! so the app sees exactly what it saw while recording.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use js_sys::{Array, Reflect, Uint8Array, JSON};
+use wasm_bindgen::prelude::JsCast;
+
+use super::simulation::Simulation;
+use super::{
+    current_app, post, replace_replayable, take_messages, AppId, BridgeReceive, BridgeSchedules,
+};
+use crate::protocol::{HostMessage, Transferable, WorkerMessage};
+
+thread_local! {
+    static RECORDINGS: RefCell<HashMap<AppId, Recording>> = RefCell::new(HashMap::new());
+}
+
+/// Record and replay messages when page asks for it.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins).
+pub struct ReplayPlugin {
+    /// Time between frames during replay of apps without a simulation,
+    /// otherwise frames advance by the simulation's timestep.
+    pub timestep: Duration,
+}
+
+impl Default for ReplayPlugin {
+    fn default() -> Self {
+        ReplayPlugin {
+            timestep: Duration::from_secs_f64(1.0 / 60.0),
+        }
+    }
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.insert_resource(ReplayTimestep(self.timestep))
+            .add_systems(
+                schedules.receive,
+                (advance_recording, feed_replay).before(BridgeReceive),
+            )
+            .add_systems(schedules.receive, receive_commands.in_set(BridgeReceive));
+    }
+}
+
+/// Replay in progress.
+#[derive(Resource)]
+pub struct Replay {
+    // Simulation steps taken before replay, counted from the first frame it runs in.
+    start: Option<u64>,
+    step: u32,
+    steps: u32,
+    messages: VecDeque<(u32, HostMessage)>,
+}
+
+impl Replay {
+    /// Steps since replay started.
+    pub fn step(&self) -> u32 {
+        self.step
+    }
+
+    /// Steps the recording lasted.
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    /// Messages yet to be fed.
+    pub fn remaining(&self) -> usize {
+        self.messages.len()
+    }
+}
+
+#[derive(Resource)]
+struct ReplayTimestep(Duration);
+
+#[derive(Default)]
+struct Recording {
+    // Simulation steps taken before recording started.
+    start: u64,
+    step: u32,
+    // Step and message encoded as JSON.
+    messages: Vec<(u32, String)>,
+}
+
+impl Recording {
+    fn to_json(&self) -> String {
+        let messages: Vec<_> = self
+            .messages
+            .iter()
+            .map(|(step, msg)| format!(r#"{{"step":{step},"message":{msg}}}"#))
+            .collect();
+
+        format!(
+            r#"{{"steps":{},"messages":[{}]}}"#,
+            self.step,
+            messages.join(",")
+        )
+    }
+}
+
+/// Steps taken since `start`, simulation steps if there is a simulation.
+fn steps_since(simulation: Option<&Simulation>, start: u64) -> Option<u32> {
+    simulation.map(|simulation| simulation.steps().saturating_sub(start) as u32)
+}
+
+/// Message as JSON, if the app is recording and message is replayable.
+///
+/// Happens before message is handled, as handling consumes it.
+pub(super) fn encode(app: AppId, msg: &HostMessage) -> Option<String> {
+    if !msg.is_replayable() || !RECORDINGS.with(|recordings| recordings.borrow().contains_key(&app))
+    {
+        return None;
+    }
+
+    let (value, _) = msg.encode().ok()?;
+    JSON::stringify(&value).ok().map(String::from)
+}
+
+/// Keep message handled before the next step.
+pub(super) fn record(app: AppId, msg: String) {
+    RECORDINGS.with(|recordings| {
+        if let Some(recording) = recordings.borrow_mut().get_mut(&app) {
+            recording.messages.push((recording.step, msg));
+        }
+    });
+}
+
+fn decode(bytes: &[u8]) -> Result<Replay, String> {
+    let text = std::str::from_utf8(bytes).map_err(|err| err.to_string())?;
+    let json = JSON::parse(text).map_err(|_| "recording is not valid JSON".to_owned())?;
+
+    let steps = Reflect::get(&json, &"steps".into())
+        .ok()
+        .and_then(|steps| steps.as_f64())
+        .ok_or("recording has no step count")?;
+
+    let messages: Array = Reflect::get(&json, &"messages".into())
+        .ok()
+        .and_then(|messages| messages.dyn_into().ok())
+        .ok_or("recording has no messages")?;
+
+    let messages = messages
+        .iter()
+        .map(|entry| {
+            let step = Reflect::get(&entry, &"step".into())
+                .ok()
+                .and_then(|step| step.as_f64())
+                .ok_or("message has no step")?;
+            let msg = Reflect::get(&entry, &"message".into())
+                .ok()
+                .and_then(|msg| HostMessage::decode(&msg))
+                .ok_or("message is malformed")?;
+
+            Ok((step as u32, msg))
+        })
+        .collect::<Result<_, String>>()?;
+
+    Ok(Replay {
+        start: None,
+        step: 0,
+        steps: steps as u32,
+        messages,
+    })
+}
+
+fn advance_recording(simulation: Option<Res<Simulation>>) {
+    RECORDINGS.with(|recordings| {
+        if let Some(recording) = recordings.borrow_mut().get_mut(&current_app()) {
+            recording.step =
+                steps_since(simulation.as_deref(), recording.start).unwrap_or(recording.step + 1);
+        }
+    });
+}
+
+fn feed_replay(
+    mut commands: Commands,
+    replay: Option<ResMut<Replay>>,
+    mut simulation: Option<ResMut<Simulation>>,
+    mut strategy: ResMut<TimeUpdateStrategy>,
+) {
+    let Some(mut replay) = replay else {
+        return;
+    };
+
+    if replay.messages.is_empty() && replay.step >= replay.steps {
+        info!("replay finished after {} steps", replay.step);
+        *strategy = TimeUpdateStrategy::Automatic;
+        commands.remove_resource::<Replay>();
+        return;
+    }
+
+    // Frame replay started in has already taken steps at the real pace, so counting starts in the next one.
+    let step = match (&mut simulation, replay.start) {
+        (Some(simulation), None) => {
+            // Time left over from before would add a second step to some frame.
+            simulation.reset_accumulated();
+            replay.start = Some(simulation.steps());
+            0
+        }
+        (simulation, start) => {
+            steps_since(simulation.as_deref(), start.unwrap_or(0)).unwrap_or(replay.step + 1)
+        }
+    };
+    replay.step = step;
+
+    let due = replay
+        .messages
+        .iter()
+        .take_while(|(at, _)| *at <= step)
+        .count();
+    replace_replayable(
+        current_app(),
+        replay.messages.drain(..due).map(|(_, msg)| msg),
+    );
+}
+
+fn receive_commands(
+    mut commands: Commands,
+    timestep: Res<ReplayTimestep>,
+    simulation: Option<Res<Simulation>>,
+    mut strategy: ResMut<TimeUpdateStrategy>,
+) {
+    let app = current_app();
+
+    take_messages(|msg| match msg {
+        HostMessage::SetRecording(enabled) => {
+            RECORDINGS.with(|recordings| {
+                let mut recordings = recordings.borrow_mut();
+
+                match (enabled, recordings.contains_key(&app)) {
+                    (true, false) => {
+                        let start = simulation
+                            .as_ref()
+                            .map_or(0, |simulation| simulation.steps());
+                        recordings.insert(app, Recording { start, ..default() });
+                    }
+                    (false, true) => {
+                        recordings.remove(&app);
+                    }
+                    _ => (),
+                }
+            });
+            Ok(())
+        }
+        HostMessage::RequestRecording => {
+            let json = RECORDINGS.with(|recordings| {
+                recordings
+                    .borrow()
+                    .get(&app)
+                    .map_or_else(|| Recording::default().to_json(), Recording::to_json)
+            });
+            let buffer = Uint8Array::from(json.as_bytes()).buffer();
+
+            post(&WorkerMessage::Recording(Transferable::new(buffer)));
+            Ok(())
+        }
+        HostMessage::Replay(recording) => {
+            let bytes = Uint8Array::new(&recording.into_inner()).to_vec();
+
+            match decode(&bytes) {
+                Ok(replay) => {
+                    info!(
+                        "replaying {} messages over {} steps",
+                        replay.messages.len(),
+                        replay.steps
+                    );
+                    // One step per frame.
+                    let timestep = simulation
+                        .as_ref()
+                        .map_or(timestep.0, |simulation| simulation.timestep());
+                    *strategy = TimeUpdateStrategy::ManualDuration(timestep);
+                    commands.insert_resource(replay);
+                }
+                Err(err) => warn!("failed to start replay: {err}"),
+            }
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
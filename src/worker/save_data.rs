@@ -0,0 +1,245 @@
+//! Save data kept in Origin Private File System.
+//!
+//! Every slot is a file in a dedicated OPFS directory, written through `FileSystemSyncAccessHandle`,
+//! which is only available inside workers and is much faster than the async file API.
+//! Operations run in background one at a time, results come back as [`SlotSaved`] and [`SlotLoaded`] events.
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+
+use bevy::prelude::*;
+use js_sys::{Object, Reflect, Uint8Array};
+use wasm_bindgen::prelude::JsValue;
+
+use super::{call, call_async, wake};
+
+thread_local! {
+    static QUEUE: RefCell<VecDeque<Op>> = RefCell::new(VecDeque::new());
+    static RUNNING: Cell<bool> = Cell::new(false);
+    static OUTCOMES: RefCell<Vec<Outcome>> = RefCell::new(Vec::new());
+}
+
+/// Persist save slots to OPFS.
+pub struct SaveDataPlugin {
+    /// OPFS directory holding save slots.
+    pub directory: String,
+}
+
+impl Default for SaveDataPlugin {
+    fn default() -> Self {
+        SaveDataPlugin {
+            directory: "saves".to_owned(),
+        }
+    }
+}
+
+impl Plugin for SaveDataPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SaveData {
+            directory: self.directory.clone(),
+        })
+        .add_event::<SlotSaved>()
+        .add_event::<SlotLoaded>()
+        .add_systems(PreUpdate, deliver_outcomes);
+    }
+}
+
+/// Access to save slots.
+#[derive(Resource, Debug, Clone)]
+pub struct SaveData {
+    directory: String,
+}
+
+impl SaveData {
+    /// Overwrite slot with given contents, [`SlotSaved`] is sent once done.
+    pub fn save_slot(&self, name: &str, bytes: Vec<u8>) {
+        self.enqueue(Op::Save {
+            directory: self.directory.clone(),
+            name: name.to_owned(),
+            bytes,
+        });
+    }
+
+    /// Read slot contents, they arrive with [`SlotLoaded`].
+    pub fn load_slot(&self, name: &str) {
+        self.enqueue(Op::Load {
+            directory: self.directory.clone(),
+            name: name.to_owned(),
+        });
+    }
+
+    fn enqueue(&self, op: Op) {
+        QUEUE.with(|queue| queue.borrow_mut().push_back(op));
+
+        // Sync access handles lock the file, so operations must not overlap.
+        if !RUNNING.with(|running| running.replace(true)) {
+            wasm_bindgen_futures::spawn_local(run_queue());
+        }
+    }
+}
+
+/// Slot finished saving.
+#[derive(Event, Debug, Clone)]
+pub struct SlotSaved {
+    pub name: String,
+    pub result: Result<(), SaveError>,
+}
+
+/// Slot finished loading, contents are `None` if slot was never saved.
+#[derive(Event, Debug, Clone)]
+pub struct SlotLoaded {
+    pub name: String,
+    pub result: Result<Option<Vec<u8>>, SaveError>,
+}
+
+/// Failure to access OPFS.
+#[derive(Debug, Clone)]
+pub struct SaveError(String);
+
+impl Display for SaveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to access save data: {}", self.0)
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<JsValue> for SaveError {
+    fn from(value: JsValue) -> Self {
+        let message = match value.dyn_ref::<js_sys::Error>() {
+            Some(err) => err.message().into(),
+            None => value.as_string().unwrap_or_else(|| format!("{value:?}")),
+        };
+
+        SaveError(message)
+    }
+}
+
+enum Op {
+    Save {
+        directory: String,
+        name: String,
+        bytes: Vec<u8>,
+    },
+    Load {
+        directory: String,
+        name: String,
+    },
+}
+
+enum Outcome {
+    Saved(SlotSaved),
+    Loaded(SlotLoaded),
+}
+
+async fn run_queue() {
+    while let Some(op) = QUEUE.with(|queue| queue.borrow_mut().pop_front()) {
+        let outcome = match op {
+            Op::Save {
+                directory,
+                name,
+                bytes,
+            } => Outcome::Saved(SlotSaved {
+                result: save(&directory, &name, &bytes).await.map_err(Into::into),
+                name,
+            }),
+            Op::Load { directory, name } => Outcome::Loaded(SlotLoaded {
+                result: load(&directory, &name).await.map_err(Into::into),
+                name,
+            }),
+        };
+
+        OUTCOMES.with(|outcomes| outcomes.borrow_mut().push(outcome));
+        // Reactive app would otherwise not notice until something else wakes it.
+        wake();
+    }
+
+    RUNNING.with(|running| running.set(false));
+}
+
+async fn save(directory: &str, name: &str, bytes: &[u8]) -> Result<(), JsValue> {
+    let file = file_handle(directory, name, true)
+        .await?
+        .ok_or_else(|| JsValue::from_str("file was not created"))?;
+    let access = call_async(&file, "createSyncAccessHandle", &[]).await?;
+
+    let result = (|| {
+        call(&access, "truncate", &[0.into()])?;
+        call(
+            &access,
+            "write",
+            &[Uint8Array::from(bytes).into(), at_start().into()],
+        )?;
+        call(&access, "flush", &[])
+    })();
+
+    // Handle keeps the file locked until closed.
+    call(&access, "close", &[])?;
+    result.map(drop)
+}
+
+async fn load(directory: &str, name: &str) -> Result<Option<Vec<u8>>, JsValue> {
+    let Some(file) = file_handle(directory, name, false).await? else {
+        return Ok(None);
+    };
+    let access = call_async(&file, "createSyncAccessHandle", &[]).await?;
+
+    let result = (|| {
+        let size = call(&access, "getSize", &[])?.as_f64().unwrap_or_default();
+        let buffer = Uint8Array::new_with_length(size as u32);
+        call(&access, "read", &[buffer.clone().into(), at_start().into()])?;
+        Ok::<_, JsValue>(buffer.to_vec())
+    })();
+
+    call(&access, "close", &[])?;
+    result.map(Some)
+}
+
+/// Open file in save directory, `None` if it doesn't exist and `create` is not set.
+async fn file_handle(
+    directory: &str,
+    name: &str,
+    create: bool,
+) -> Result<Option<JsValue>, JsValue> {
+    let navigator = Reflect::get(&js_sys::global(), &"navigator".into())?;
+    let storage = Reflect::get(&navigator, &"storage".into())?;
+    let root = call_async(&storage, "getDirectory", &[]).await?;
+
+    let options = Object::new();
+    Reflect::set(&options, &"create".into(), &true.into())?;
+    let directory = call_async(
+        &root,
+        "getDirectoryHandle",
+        &[directory.into(), options.into()],
+    )
+    .await?;
+
+    let options = Object::new();
+    Reflect::set(&options, &"create".into(), &create.into())?;
+    match call_async(&directory, "getFileHandle", &[name.into(), options.into()]).await {
+        Ok(file) => Ok(Some(file)),
+        Err(err) if !create && error_name(&err).as_deref() == Some("NotFoundError") => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn at_start() -> Object {
+    let options = Object::new();
+    // Setting a field on a fresh object cannot fail.
+    let _ = Reflect::set(&options, &"at".into(), &0.into());
+    options
+}
+
+fn error_name(err: &JsValue) -> Option<String> {
+    Reflect::get(err, &"name".into()).ok()?.as_string()
+}
+
+fn deliver_outcomes(mut saved: EventWriter<SlotSaved>, mut loaded: EventWriter<SlotLoaded>) {
+    for outcome in OUTCOMES.with(|outcomes| std::mem::take(&mut *outcomes.borrow_mut())) {
+        match outcome {
+            Outcome::Saved(event) => saved.send(event),
+            Outcome::Loaded(event) => loaded.send(event),
+        }
+    }
+}
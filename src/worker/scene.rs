@@ -0,0 +1,131 @@
+//! Entities of the app as a `bevy_scene` file, for saving from the page and loading into a running app.
+//!
+//! Page asks with [`HostMessage::ExportScene`] and gets RON back in [`WorkerMessage::SceneExported`],
+//! scene files sent with [`HostMessage::ImportScene`] are spawned on top of what is already there.
+//! Only registered components reflecting `Component` make it into or out of a scene, resources are left out.
+
+use bevy::ecs::entity::EntityMap;
+use bevy::prelude::*;
+use bevy::scene::serde::SceneDeserializer;
+use bevy::utils::HashSet;
+use js_sys::Uint8Array;
+use serde::de::DeserializeSeed;
+
+use super::{post, take_messages, BridgeReceive, BridgeSchedules};
+use crate::protocol::{HostMessage, WorkerMessage};
+
+/// Answer page's scene exports and imports.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins).
+#[derive(Default)]
+pub struct SceneBridgePlugin;
+
+impl Plugin for SceneBridgePlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.add_systems(schedules.receive, receive.in_set(BridgeReceive));
+    }
+}
+
+fn receive(world: &mut World) {
+    let messages = take_messages(|msg| match msg {
+        HostMessage::ExportScene { .. } | HostMessage::ImportScene(_) => Ok(msg),
+        msg => Err(msg),
+    });
+
+    for msg in messages {
+        match msg {
+            HostMessage::ExportScene { components } => {
+                let result = export(world, components.as_deref());
+                if let Err(err) = &result {
+                    warn!("failed to export scene: {err}");
+                }
+
+                post(&WorkerMessage::SceneExported(result));
+            }
+            HostMessage::ImportScene(scene) => {
+                let bytes = Uint8Array::new(&scene.into_inner()).to_vec();
+                match import(world, &bytes) {
+                    Ok(count) => info!("imported scene with {count} entities"),
+                    Err(err) => warn!("failed to import scene: {err}"),
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Every entity of the world as RON, keeping only listed components if there is a list.
+fn export(world: &World, components: Option<&[String]>) -> Result<String, String> {
+    let registry = world.resource::<AppTypeRegistry>();
+
+    let mut scene = DynamicScene::from_world(world, registry);
+
+    if let Some(components) = components {
+        let registry = registry.read();
+        let allowed: HashSet<&str> = components
+            .iter()
+            .filter_map(|name| {
+                let registration = registry
+                    .get_with_short_name(name)
+                    .or_else(|| registry.get_with_name(name));
+                if registration.is_none() {
+                    warn!("scene export can't find component `{name}`");
+                }
+
+                registration.map(|registration| registration.type_name())
+            })
+            .collect();
+
+        for entity in &mut scene.entities {
+            entity
+                .components
+                .retain(|component| allowed.contains(component.type_name()));
+        }
+    }
+
+    scene.serialize_ron(registry).map_err(|err| err.to_string())
+}
+
+/// Listed entities as RON, with every reflected component.
+pub(super) fn export_entities(
+    world: &World,
+    entities: impl Iterator<Item = Entity>,
+) -> Result<String, String> {
+    let registry = world.resource::<AppTypeRegistry>();
+
+    let mut builder = DynamicSceneBuilder::from_world(world);
+    builder.extract_entities(entities);
+
+    builder
+        .build()
+        .serialize_ron(registry)
+        .map_err(|err| err.to_string())
+}
+
+/// Spawn entities of the scene, returns how many there were.
+///
+/// Entity references inside the scene, e.g. parents, are remapped to spawned entities.
+pub(super) fn import(world: &mut World, bytes: &[u8]) -> Result<usize, String> {
+    let registry = world.resource::<AppTypeRegistry>().clone();
+
+    let scene = {
+        let registry = registry.read();
+        let mut deserializer =
+            ron::de::Deserializer::from_bytes(bytes).map_err(|err| err.to_string())?;
+
+        SceneDeserializer {
+            type_registry: &registry,
+        }
+        .deserialize(&mut deserializer)
+        .map_err(|err| err.to_string())?
+    };
+
+    let mut entity_map = EntityMap::default();
+    scene
+        .write_to_world(world, &mut entity_map)
+        .map_err(|err| err.to_string())?;
+
+    Ok(scene.entities.len())
+}
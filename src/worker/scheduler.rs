@@ -0,0 +1,227 @@
+//! Sharing worker time between its apps.
+//!
+//! Every app has a budget share, set by the page. Apps are updated in order of their shares,
+//! and an app which takes more than its share of the time gets its frames spaced out,
+//! so a heavy background widget cannot starve the foreground view.
+//! Apps whose canvases are all scrolled out of sight are throttled regardless of their share,
+//! and ones with canvases only partly in sight are capped at a reduced rate, see [`offscreen`](super::offscreen).
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::protocol::{AppId, ViewId};
+
+/// Time worker spends updating apps before yielding to the event loop.
+const ROUND_BUDGET_MS: f64 = 12.0;
+
+/// Time between frames of apps nobody can see.
+const OFFSCREEN_INTERVAL_MS: f64 = 1000.0;
+
+thread_local! {
+    static SLOTS: RefCell<BTreeMap<AppId, Slot>> = RefCell::new(BTreeMap::new());
+}
+
+struct Slot {
+    share: f64,
+    /// Whether app slows down while it is off screen.
+    throttle_offscreen: bool,
+    /// Frame rate cap while app's canvases are only partly visible.
+    reduced_fps: Option<u32>,
+    /// Views which are scrolled out of sight.
+    hidden: HashSet<ViewId>,
+    /// Views page told about, app is on screen while any of them isn't hidden.
+    known: HashSet<ViewId>,
+    /// Smoothed duration of app updates.
+    update_ms: f64,
+}
+
+impl Default for Slot {
+    fn default() -> Self {
+        Slot {
+            share: 1.0,
+            throttle_offscreen: true,
+            reduced_fps: None,
+            hidden: HashSet::new(),
+            known: HashSet::new(),
+            update_ms: 0.0,
+        }
+    }
+}
+
+impl Slot {
+    fn on_screen(&self) -> bool {
+        self.known.is_empty() || self.known.len() > self.hidden.len()
+    }
+}
+
+fn with_slot<T>(app: AppId, f: impl FnOnce(&mut Slot) -> T) -> T {
+    SLOTS.with(|slots| f(slots.borrow_mut().entry(app).or_default()))
+}
+
+/// Set app's share of worker time, relative to other apps.
+pub(super) fn set_share(app: AppId, share: f64) {
+    with_slot(app, |slot| slot.share = share.max(f64::EPSILON));
+}
+
+/// Set whether app slows down while none of its canvases is visible.
+pub(super) fn set_throttle_offscreen(app: AppId, throttle: bool) {
+    with_slot(app, |slot| slot.throttle_offscreen = throttle);
+}
+
+/// Cap app's frame rate, or lift the cap with `None`.
+pub(super) fn set_reduced_fps(app: AppId, fps: Option<u32>) {
+    with_slot(app, |slot| slot.reduced_fps = fps);
+}
+
+/// Record whether view's canvas is visible on the page.
+///
+/// Returns `true` if this brought the app back on screen.
+pub(super) fn set_view_visible(app: AppId, view: ViewId, visible: bool) -> bool {
+    with_slot(app, |slot| {
+        let was_on_screen = slot.on_screen();
+
+        slot.known.insert(view);
+        if visible {
+            slot.hidden.remove(&view);
+        } else {
+            slot.hidden.insert(view);
+        }
+
+        !was_on_screen && slot.on_screen()
+    })
+}
+
+/// Due apps in the order they should be updated, largest share first.
+pub(super) fn order(mut due: Vec<AppId>) -> Vec<AppId> {
+    SLOTS.with(|slots| {
+        let slots = slots.borrow();
+        let share = |app: &AppId| slots.get(app).map_or(1.0, |slot| slot.share);
+
+        due.sort_by(|a, b| share(b).total_cmp(&share(a)));
+    });
+
+    due
+}
+
+/// Whether round which started at given time has time left for another app.
+pub(super) fn round_has_time(round_start: f64) -> bool {
+    js_sys::Date::now() - round_start < ROUND_BUDGET_MS
+}
+
+/// When app should be updated next.
+///
+/// `due_at` is what app's frame pacing asks for, it is pushed back if app uses more than its share.
+pub(super) fn next_due(app: AppId, update_ms: f64, due_at: f64, running: &[AppId]) -> f64 {
+    SLOTS.with(|slots| {
+        let mut slots = slots.borrow_mut();
+        let slot = slots.entry(app).or_default();
+
+        slot.update_ms = smooth(slot.update_ms, update_ms);
+
+        let now = js_sys::Date::now();
+        if !slot.on_screen() && slot.throttle_offscreen {
+            return due_at.max(now + OFFSCREEN_INTERVAL_MS);
+        }
+
+        let due_at = match slot.reduced_fps {
+            Some(fps) => capped(due_at, now, update_ms, fps),
+            None => due_at,
+        };
+
+        let (share, update_ms) = (slot.share, slot.update_ms);
+        let total: f64 = running
+            .iter()
+            .filter_map(|app| slots.get(app))
+            .filter(|slot| slot.on_screen())
+            .map(|slot| slot.share)
+            .sum();
+
+        spaced(due_at, now, update_ms, share, total)
+    })
+}
+
+/// Push `due_at` back so frames of app which took `update_ms` start no more than `fps` times a second.
+fn capped(due_at: f64, now: f64, update_ms: f64, fps: u32) -> f64 {
+    due_at.max(now - update_ms + 1000.0 / fps.max(1) as f64)
+}
+
+/// Running average of update durations, starting from the first one.
+fn smooth(average_ms: f64, update_ms: f64) -> f64 {
+    if average_ms == 0.0 {
+        update_ms
+    } else {
+        average_ms * 0.8 + update_ms * 0.2
+    }
+}
+
+/// Push `due_at` back so app taking `update_ms` doesn't exceed its `share` of `total` shares.
+fn spaced(due_at: f64, now: f64, update_ms: f64, share: f64, total: f64) -> f64 {
+    // App spending `update_ms` every `interval` takes `update_ms / interval` of the time,
+    // which must not exceed its share.
+    let min_interval = update_ms * total.max(share) / share;
+
+    due_at.max(now - update_ms + min_interval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smooth_starts_from_first_update() {
+        assert_eq!(smooth(0.0, 10.0), 10.0);
+        assert_eq!(smooth(10.0, 20.0), 12.0);
+    }
+
+    #[test]
+    fn single_app_runs_as_paced() {
+        // Update finished just now, only app there is may run again right away.
+        assert_eq!(spaced(100.0, 100.0, 5.0, 1.0, 1.0), 100.0);
+    }
+
+    #[test]
+    fn heavy_app_is_spaced_out() {
+        // Half of the time for 10ms updates means one every 20ms, counted from update start.
+        assert_eq!(spaced(100.0, 100.0, 10.0, 1.0, 2.0), 110.0);
+        // Pacing asking for later than that wins.
+        assert_eq!(spaced(150.0, 100.0, 10.0, 1.0, 2.0), 150.0);
+    }
+
+    #[test]
+    fn share_larger_than_total_is_not_limited() {
+        // App alone on screen with others hidden has all the time.
+        assert_eq!(spaced(100.0, 100.0, 10.0, 3.0, 1.0), 100.0);
+    }
+
+    #[test]
+    fn reduced_rate_caps_frames() {
+        // 10 fps means a frame every 100ms, counted from update start.
+        assert_eq!(capped(100.0, 105.0, 5.0, 10), 200.0);
+        // Pacing asking for later than that wins.
+        assert_eq!(capped(300.0, 105.0, 5.0, 10), 300.0);
+    }
+
+    #[test]
+    fn order_by_share() {
+        let (small, large, unknown) = (AppId(100), AppId(101), AppId(102));
+        set_share(small, 0.5);
+        set_share(large, 2.0);
+
+        assert_eq!(order(vec![small, unknown, large]), [large, unknown, small]);
+    }
+
+    #[test]
+    fn hidden_views_take_app_off_screen() {
+        let app = AppId(200);
+        let on_screen = || with_slot(app, |slot| slot.on_screen());
+
+        assert!(on_screen());
+        assert!(!set_view_visible(app, ViewId(0), true));
+        assert!(!set_view_visible(app, ViewId(1), false));
+        assert!(on_screen());
+        assert!(!set_view_visible(app, ViewId(0), false));
+        assert!(!on_screen());
+        assert!(set_view_visible(app, ViewId(1), true));
+        assert!(on_screen());
+    }
+}
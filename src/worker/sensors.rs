@@ -0,0 +1,67 @@
+//! Device orientation and motion forwarded by the page, see [`HostMessage::DeviceOrientation`]
+//! and [`HostMessage::DeviceMotion`].
+//!
+//! Page only starts forwarding them after `WorkerHandle::enable_motion_sensors`,
+//! which on iOS has to be called from a click or tap.
+//! Resources are inserted with the first reading, devices without sensors never get them.
+
+use bevy::prelude::*;
+
+use super::{take_messages, BridgeSchedules, InputInject};
+use crate::protocol::HostMessage;
+
+/// Keep [`DeviceOrientation`] and [`DeviceAcceleration`] up to date.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins).
+#[derive(Default)]
+pub struct MotionSensorsPlugin;
+
+impl Plugin for MotionSensorsPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.add_systems(schedules.input, receive.in_set(InputInject));
+    }
+}
+
+/// How the device is turned, in degrees as `deviceorientation` reports it.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct DeviceOrientation {
+    /// Rotation around Z axis, pointing out of the screen, from 0 to 360.
+    pub alpha: f32,
+    /// Front to back tilt around X axis, from -180 to 180.
+    pub beta: f32,
+    /// Left to right tilt around Y axis, from -90 to 90.
+    pub gamma: f32,
+}
+
+/// How the device accelerates, in m/s² along its axes as `devicemotion` reports them.
+///
+/// X points right and Y up along the screen in its natural orientation, Z out of the screen.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct DeviceAcceleration {
+    /// Acceleration user gives the device, `None` if device can't tell it from gravity.
+    pub acceleration: Option<Vec3>,
+    /// Acceleration with gravity, which is what tilt controls usually want.
+    pub with_gravity: Vec3,
+}
+
+fn receive(mut commands: Commands) {
+    take_messages(|msg| match msg {
+        HostMessage::DeviceOrientation { alpha, beta, gamma } => {
+            commands.insert_resource(DeviceOrientation { alpha, beta, gamma });
+            Ok(())
+        }
+        HostMessage::DeviceMotion {
+            acceleration,
+            with_gravity,
+        } => {
+            commands.insert_resource(DeviceAcceleration {
+                acceleration: acceleration.map(Vec3::from),
+                with_gravity: Vec3::from(with_gravity),
+            });
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
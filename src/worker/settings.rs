@@ -0,0 +1,146 @@
+//! User settings persisted by the page.
+//!
+//! `localStorage` is not available in workers, so settings resources are serialized
+//! and sent to the page with [`WorkerMessage::SaveSettings`], which stores them.
+//! Page sends everything it has stored with [`HostMessage::StoredSettings`] when worker starts.
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::{post, take_messages, BridgeReceive, BridgeSchedules, BridgeSend};
+use crate::protocol::{HostMessage, WorkerMessage};
+
+/// Settings page had stored, `None` until page sends them.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct StoredSettings {
+    pub entries: Option<HashMap<String, String>>,
+}
+
+/// Receive [`StoredSettings`] from the page.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins), page sends stored settings whether app uses them or not.
+#[derive(Default)]
+pub struct StoredSettingsPlugin;
+
+impl Plugin for StoredSettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.init_resource::<StoredSettings>()
+            .add_systems(schedules.receive, receive_stored.in_set(BridgeReceive));
+    }
+}
+
+/// Persist settings resources across visits.
+///
+/// Resources start out with default values and get overwritten once stored settings arrive,
+/// after that every change is sent to the page.
+/// Settings are stored as JSON, stored values which fail to deserialize are reported and ignored.
+///
+/// Relies on [`StoredSettingsPlugin`].
+#[derive(Default)]
+pub struct SettingsPlugin {
+    settings: Vec<Box<dyn Fn(&mut App) + Send + Sync>>,
+}
+
+impl SettingsPlugin {
+    /// Persist resource `T` under given key.
+    pub fn settings<T>(mut self, key: &'static str) -> Self
+    where
+        T: Resource + Serialize + DeserializeOwned + Default,
+    {
+        self.settings.push(Box::new(move |app| {
+            let schedules = BridgeSchedules::of(app);
+
+            app.init_resource::<T>()
+                .add_systems(
+                    schedules.receive,
+                    load_settings::<T>(key)
+                        .after(receive_stored)
+                        .in_set(BridgeReceive),
+                )
+                .add_systems(schedules.send, save_settings::<T>(key).in_set(BridgeSend));
+        }));
+        self
+    }
+}
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        for settings in &self.settings {
+            settings(app);
+        }
+    }
+}
+
+fn receive_stored(mut stored: ResMut<StoredSettings>) {
+    take_messages(|msg| match msg {
+        HostMessage::StoredSettings(entries) => {
+            stored.entries = Some(entries.into_iter().collect());
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
+
+fn load_settings<T>(key: &'static str) -> impl FnMut(Res<StoredSettings>, ResMut<T>)
+where
+    T: Resource + DeserializeOwned,
+{
+    move |stored, mut settings| {
+        if !stored.is_changed() {
+            return;
+        }
+
+        let Some(value) = stored.entries.as_ref().and_then(|entries| entries.get(key)) else {
+            return;
+        };
+
+        match serde_json::from_str(value) {
+            Ok(value) => *settings = value,
+            Err(err) => warn!("invalid stored settings `{key}`: {err}"),
+        }
+    }
+}
+
+fn save_settings<T>(
+    key: &'static str,
+) -> impl FnMut(Res<StoredSettings>, Res<T>, Local<Option<String>>)
+where
+    T: Resource + Serialize,
+{
+    move |stored, settings, mut saved| {
+        // Defaults would overwrite what page has stored.
+        let Some(entries) = &stored.entries else {
+            return;
+        };
+
+        if !settings.is_changed() {
+            return;
+        }
+
+        let value = match serde_json::to_string(&*settings) {
+            Ok(value) => value,
+            Err(err) => {
+                warn!("failed to serialize settings `{key}`: {err}");
+                return;
+            }
+        };
+
+        // Loading stored value marks resource as changed too, no need to send it back.
+        if saved.is_none() {
+            *saved = entries.get(key).cloned();
+        }
+        if saved.as_ref() == Some(&value) {
+            return;
+        }
+
+        post(&WorkerMessage::SaveSettings {
+            key: key.to_owned(),
+            value: value.clone(),
+        });
+        *saved = Some(value);
+    }
+}
@@ -0,0 +1,100 @@
+//! Worker shared between tabs of the same origin, see [`start_shared`](super::start_shared).
+//!
+//! Every tab connects over its own port and goes through the usual handshake,
+//! but there is only one app. Boot flags and capabilities of the first tab are the app's,
+//! those of the other tabs are kept in [`SharedTabs`].
+//! Views tabs attach are renumbered,
+//! so primary canvas of every tab after the first becomes another window of the same app.
+//! Messages about a view go to the tab owning it, and so does input coming from it.
+//! Answers to a tab's request go back to that tab, the rest goes to every tab.
+//!
+//! Closed tabs can't say goodbye, so worker pings every tab and forgets ones which stop answering.
+//! Subsystem channels and frame clock aren't used, everything travels over tab's port.
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use crate::protocol::{BootFlags, CapabilityReport, ViewId};
+
+#[cfg(not(feature = "mock-page"))]
+mod connection;
+
+// Mock page has no tabs to connect.
+#[cfg(not(feature = "mock-page"))]
+pub(super) use connection::{is_shared, listen, post};
+
+/// Tab connected to the worker, numbered in order of connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TabId(pub u32);
+
+/// Tabs connected to a shared worker and the views they attached.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins), stays empty unless worker is shared.
+/// Point a camera at [`Views::window`](super::Views::window) of every view tab attaches,
+/// so each tab shows its own picture of the shared world.
+#[derive(Default)]
+pub struct SharedTabsPlugin;
+
+impl Plugin for SharedTabsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SharedTabs>()
+            .add_event::<TabConnected>()
+            .add_event::<TabLeft>();
+
+        #[cfg(not(feature = "mock-page"))]
+        {
+            let schedules = super::BridgeSchedules::of(app);
+            app.add_systems(
+                schedules.receive,
+                connection::update_tabs.in_set(super::BridgeReceive),
+            );
+        }
+    }
+}
+
+/// Tabs connected to the worker, with views of the app each of them attached.
+#[derive(Resource, Debug, Default)]
+pub struct SharedTabs {
+    tabs: BTreeMap<TabId, TabInfo>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct TabInfo {
+    views: Vec<ViewId>,
+    boot_flags: Option<BootFlags>,
+    capabilities: Option<CapabilityReport>,
+}
+
+impl SharedTabs {
+    pub fn iter(&self) -> impl Iterator<Item = (TabId, &[ViewId])> + '_ {
+        self.tabs
+            .iter()
+            .map(|(tab, info)| (*tab, info.views.as_slice()))
+    }
+
+    /// Tab view belongs to.
+    pub fn owner(&self, view: ViewId) -> Option<TabId> {
+        self.tabs
+            .iter()
+            .find(|(_, info)| info.views.contains(&view))
+            .map(|(tab, _)| *tab)
+    }
+
+    /// Boot flags tab connected with, app itself runs with the first tab's ones.
+    pub fn boot_flags(&self, tab: TabId) -> Option<&BootFlags> {
+        self.tabs.get(&tab)?.boot_flags.as_ref()
+    }
+
+    /// Capabilities of the tab and the worker, app itself sees the first tab's ones.
+    pub fn capabilities(&self, tab: TabId) -> Option<&CapabilityReport> {
+        self.tabs.get(&tab)?.capabilities.as_ref()
+    }
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct TabConnected(pub TabId);
+
+/// Tab was closed or shut its connection down, its views are detached by then.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct TabLeft(pub TabId);
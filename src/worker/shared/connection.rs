@@ -0,0 +1,449 @@
+//! Tabs connected to a shared worker and routing of messages between them and the app.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use bevy::prelude::*;
+use js_sys::{Array, Reflect};
+use wasm_bindgen::prelude::{Closure, JsCast, JsValue};
+use web_sys::{MessageEvent, MessagePort};
+
+use super::{SharedTabs, TabConnected, TabId, TabInfo, TabLeft};
+use crate::protocol::{AppId, BootFlags, CapabilityReport, HostMessage, ViewId, WorkerMessage};
+use crate::worker::{
+    precise_now, ready_message, receive, scope, watch_gpu_devices, Build, PAGE_CONNECTED,
+};
+
+thread_local! {
+    // `None` unless worker is shared.
+    static TABS: RefCell<Option<Tabs>> = RefCell::new(None);
+}
+
+/// How often tabs are pinged.
+const HEARTBEAT_MS: i32 = 2000;
+
+/// How long tab may stay silent before it is considered closed.
+const HEARTBEAT_TIMEOUT_MS: f64 = 10_000.0;
+
+/// Requests answered to the tab which made them, with kinds of their answers.
+const REQUESTS: &[(&str, &str)] = &[
+    ("console_command", "console_output"),
+    ("export_scene", "scene_exported"),
+    ("take_snapshot", "snapshot"),
+    ("request_traffic_log", "traffic_log"),
+    ("request_recording", "recording"),
+];
+
+struct Tab {
+    port: MessagePort,
+    // When tab was last heard from.
+    seen_at: f64,
+    // Handshake of the tab, app only gets the first tab's one.
+    boot_flags: Option<BootFlags>,
+    capabilities: Option<CapabilityReport>,
+}
+
+#[derive(Default)]
+struct Tabs {
+    tabs: BTreeMap<TabId, Tab>,
+    next_tab: u32,
+    next_view: u32,
+    // Views as the app knows them, with the tab they belong to and tab's own id for them.
+    views: HashMap<ViewId, (TabId, ViewId, AppId)>,
+    // Tab which sent the latest message.
+    last: Option<TabId>,
+    // Tabs waiting for an answer, with its kind, oldest first.
+    asked: Vec<(TabId, &'static str)>,
+    // Whether app got its boot flags and capabilities.
+    handshake_done: bool,
+    // Sequence number of the latest heartbeat, they count down from `u32::MAX`
+    // to stay clear of the ones apps ping with.
+    heartbeat: u32,
+    // Connections and departures since app last looked.
+    changes: Vec<TabChange>,
+}
+
+impl Tabs {
+    /// View as the app knows it, allocated the first time tab mentions it.
+    fn app_view(&mut self, tab: TabId, local: ViewId, app: AppId) -> ViewId {
+        let known = self
+            .views
+            .iter()
+            .find(|(_, (owner, view, _))| *owner == tab && *view == local)
+            .map(|(view, _)| *view);
+        if let Some(view) = known {
+            return view;
+        }
+
+        // First primary view keeps its id, so the app is built as usual.
+        let view = if local == ViewId::PRIMARY && !self.views.contains_key(&ViewId::PRIMARY) {
+            ViewId::PRIMARY
+        } else {
+            self.next_view += 1;
+            ViewId(self.next_view)
+        };
+        self.views.insert(view, (tab, local, app));
+
+        view
+    }
+
+    /// Port of the tab which made the request this message answers, if any.
+    fn asker(&mut self, kind: &str) -> Option<&MessagePort> {
+        let index = self.asked.iter().position(|(_, answer)| *answer == kind)?;
+        let (tab, _) = self.asked.remove(index);
+        self.tabs.get(&tab).map(|tab| &tab.port)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TabChange {
+    Connected(TabId),
+    Left(TabId),
+}
+
+/// Whether worker was started with [`start_shared`](crate::worker::start_shared).
+pub(in crate::worker) fn is_shared() -> bool {
+    TABS.with(|tabs| tabs.borrow().is_some())
+}
+
+/// Take over tabs loader script collected while wasm was loading and accept new ones.
+pub(in crate::worker) fn listen(build: Build) {
+    TABS.with(|tabs| {
+        *tabs.borrow_mut() = Some(Tabs {
+            heartbeat: u32::MAX,
+            ..default()
+        })
+    });
+    watch_gpu_devices();
+
+    let build = Rc::new(build);
+    let scope: JsValue = js_sys::global().into();
+
+    let onconnect = {
+        let build = Rc::clone(&build);
+
+        Closure::wrap(Box::new(move |event: MessageEvent| {
+            if let Ok(port) = event.ports().get(0).dyn_into::<MessagePort>() {
+                connect(&build, port);
+            }
+        }) as Box<dyn FnMut(MessageEvent)>)
+    };
+    let _ = Reflect::set(&scope, &"onconnect".into(), onconnect.as_ref());
+    onconnect.forget();
+
+    let waiting = Reflect::get(&scope, &"bevySharedPorts".into())
+        .ok()
+        .and_then(|ports| ports.dyn_into::<Array>().ok());
+    for port in waiting.iter().flat_map(Array::iter) {
+        if let Ok(port) = port.dyn_into::<MessagePort>() {
+            connect(&build, port);
+        }
+    }
+
+    let heartbeat = Closure::wrap(Box::new(move || heartbeat(&build)) as Box<dyn FnMut()>);
+    if let Err(err) = scope().set_interval_with_callback_and_timeout_and_arguments_0(
+        heartbeat.as_ref().unchecked_ref(),
+        HEARTBEAT_MS,
+    ) {
+        warn!("failed to start heartbeat, closed tabs won't be noticed: {err:?}");
+    }
+    heartbeat.forget();
+}
+
+/// Forget tabs which stopped answering and ping the rest.
+fn heartbeat(build: &Build) {
+    let now = precise_now();
+    let (silent, ports, seq) = TABS.with(|tabs| {
+        let mut tabs = tabs.borrow_mut();
+        let tabs = tabs.as_mut().expect("worker to be shared");
+
+        let (silent, alive): (Vec<_>, Vec<_>) = tabs
+            .tabs
+            .iter()
+            .partition(|(_, tab)| now - tab.seen_at > HEARTBEAT_TIMEOUT_MS);
+        let silent: Vec<_> = silent.into_iter().map(|(id, _)| *id).collect();
+        let ports: Vec<_> = alive.into_iter().map(|(_, tab)| tab.port.clone()).collect();
+        tabs.heartbeat = tabs.heartbeat.wrapping_sub(1);
+
+        (silent, ports, tabs.heartbeat)
+    });
+
+    for tab in silent {
+        warn!("tab {} stopped answering, forgetting it", tab.0);
+        if let Some(port) = forget(build, tab) {
+            port.close();
+        }
+    }
+
+    let Ok((ping, _)) = WorkerMessage::Ping(seq).encode() else {
+        return;
+    };
+    for port in ports {
+        let _ = port.post_message(&ping);
+    }
+}
+
+fn connect(build: &Rc<Build>, port: MessagePort) {
+    let tab = TABS.with(|tabs| {
+        let mut tabs = tabs.borrow_mut();
+        let tabs = tabs.as_mut().expect("worker to be shared");
+
+        let tab = TabId(tabs.next_tab);
+        tabs.next_tab += 1;
+        tabs.tabs.insert(
+            tab,
+            Tab {
+                port: port.clone(),
+                seen_at: precise_now(),
+                boot_flags: None,
+                capabilities: None,
+            },
+        );
+        tabs.changes.push(TabChange::Connected(tab));
+
+        tab
+    });
+
+    let onmessage = {
+        let build = Rc::clone(build);
+
+        Closure::wrap(Box::new(move |event: MessageEvent| {
+            if let Some(data) = inbound(&build, tab, event.data()) {
+                PAGE_CONNECTED.with(|connected| connected.set(true));
+                receive(&build, data);
+            }
+        }) as Box<dyn FnMut(MessageEvent)>)
+    };
+    port.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+    onmessage.forget();
+
+    if let Err(err) = port.post_message(&ready_message()) {
+        warn!("failed to greet tab: {err:?}");
+    }
+}
+
+/// Translate tab's message for the app, `None` if it concerns only the tab.
+fn inbound(build: &Build, tab: TabId, data: JsValue) -> Option<JsValue> {
+    let kind = Reflect::get(&data, &"kind".into()).ok()?.as_string()?;
+    let app = AppId::read(&data);
+
+    match kind.as_str() {
+        // Tab's own channels would only split its messages from the rest, they are closed right away.
+        "ports" | "frame_clock" => {
+            match HostMessage::decode(&data) {
+                Some(HostMessage::Ports(ports)) => {
+                    for (_, port) in ports {
+                        port.into_inner().close();
+                    }
+                }
+                Some(HostMessage::FrameClock(port)) => port.into_inner().close(),
+                _ => (),
+            }
+            return None;
+        }
+        // Tab leaving doesn't stop the app for others, only the last one does.
+        "shutdown" if !leave(build, tab) => return None,
+        _ => (),
+    }
+
+    TABS.with(|tabs| {
+        let mut tabs = tabs.borrow_mut();
+        let tabs = tabs.as_mut()?;
+        tabs.tabs.get_mut(&tab)?.seen_at = precise_now();
+
+        let handshake = matches!(kind.as_str(), "pong" | "boot_flags" | "capabilities");
+        match handshake.then(|| HostMessage::decode(&data)).flatten() {
+            // Answers to heartbeats only prove the tab is still there.
+            Some(HostMessage::Pong { seq, .. }) if seq >= tabs.heartbeat => return None,
+            Some(HostMessage::BootFlags(flags)) => {
+                tabs.tabs.get_mut(&tab)?.boot_flags = Some(flags);
+                if tabs.handshake_done {
+                    return None;
+                }
+            }
+            Some(HostMessage::Capabilities(report)) => {
+                tabs.tabs.get_mut(&tab)?.capabilities = Some(report);
+                if tabs.handshake_done {
+                    return None;
+                }
+                // Capabilities are the last part of the handshake.
+                tabs.handshake_done = true;
+            }
+            _ => (),
+        }
+
+        if let Some((_, answer)) = REQUESTS.iter().find(|(request, _)| *request == kind) {
+            tabs.asked.push((tab, *answer));
+        }
+        tabs.last = Some(tab);
+
+        if let Some(local) = Reflect::get(&data, &"view".into()).ok()?.as_f64() {
+            let view = tabs.app_view(tab, ViewId(local as u32), app);
+            let _ = Reflect::set(&data, &"view".into(), &view.0.into());
+        }
+
+        Some(())
+    })?;
+
+    Some(data)
+}
+
+/// Let the tab go, returns `true` if it was the last one.
+fn leave(build: &Build, tab: TabId) -> bool {
+    // Last tab gets the shutdown confirmation as usual.
+    let last = TABS.with(|tabs| {
+        let tabs = tabs.borrow();
+        tabs.as_ref().map_or(true, |tabs| tabs.tabs.len() <= 1)
+    });
+    if last {
+        return true;
+    }
+
+    if let Some(port) = forget(build, tab) {
+        if let Ok((msg, _)) = WorkerMessage::ShutdownComplete.encode() {
+            let _ = port.post_message(&msg);
+        }
+        port.close();
+    }
+
+    false
+}
+
+/// Detach views of the tab and forget it, returns its port if it was connected.
+fn forget(build: &Build, tab: TabId) -> Option<MessagePort> {
+    let (port, detached) = TABS.with(|tabs| {
+        let mut tabs = tabs.borrow_mut();
+        let tabs = tabs.as_mut().expect("worker to be shared");
+
+        let port = tabs.tabs.remove(&tab).map(|tab| tab.port);
+        let detached: Vec<_> = tabs
+            .views
+            .iter()
+            .filter(|(_, (owner, _, _))| *owner == tab)
+            .map(|(view, (_, _, app))| (*view, *app))
+            .collect();
+        tabs.views.retain(|_, (owner, _, _)| *owner != tab);
+        tabs.asked.retain(|(asker, _)| *asker != tab);
+        if tabs.last == Some(tab) {
+            tabs.last = None;
+        }
+        if port.is_some() {
+            tabs.changes.push(TabChange::Left(tab));
+        }
+
+        (port, detached)
+    });
+
+    for (view, app) in detached {
+        if let Ok((msg, _)) = (HostMessage::Detach { view }).encode() {
+            app.stamp(&msg);
+            // Views are the app's own already, there is nothing to translate.
+            receive(build, msg);
+        }
+    }
+
+    let empty = TABS.with(|tabs| {
+        tabs.borrow()
+            .as_ref()
+            .map_or(true, |tabs| tabs.tabs.is_empty())
+    });
+    if empty {
+        PAGE_CONNECTED.with(|connected| connected.set(false));
+    }
+
+    port
+}
+
+/// Post message to tabs it concerns, see [module docs](super).
+pub(in crate::worker) fn post(value: &JsValue, transfer: &Array) -> Result<(), JsValue> {
+    TABS.with(|tabs| {
+        let mut tabs = tabs.borrow_mut();
+        let Some(tabs) = tabs.as_mut() else {
+            return Ok(());
+        };
+
+        let view = Reflect::get(value, &"view".into())?.as_f64();
+        if let Some(view) = view {
+            let Some((tab, local, _)) = tabs.views.get(&ViewId(view as u32)) else {
+                return Ok(());
+            };
+            Reflect::set(value, &"view".into(), &local.0.into())?;
+
+            return match tabs.tabs.get(tab) {
+                Some(tab) => tab.port.post_message_with_transferable(value, transfer),
+                None => Ok(()),
+            };
+        }
+
+        let kind = Reflect::get(value, &"kind".into())?.as_string();
+        if let Some(port) = kind.and_then(|kind| tabs.asker(&kind)) {
+            return port.post_message_with_transferable(value, transfer);
+        }
+
+        // Transferred objects can only go to one tab, the one which spoke last is likely the one waiting for them.
+        if transfer.length() > 0 {
+            let port = tabs
+                .last
+                .and_then(|tab| tabs.tabs.get(&tab))
+                .or_else(|| tabs.tabs.values().next())
+                .map(|tab| &tab.port);
+
+            return match port {
+                Some(port) => port.post_message_with_transferable(value, transfer),
+                None => Ok(()),
+            };
+        }
+
+        for tab in tabs.tabs.values() {
+            tab.port.post_message(value)?;
+        }
+
+        Ok(())
+    })
+}
+
+pub(super) fn update_tabs(
+    mut shared: ResMut<SharedTabs>,
+    mut connected: EventWriter<TabConnected>,
+    mut left: EventWriter<TabLeft>,
+) {
+    TABS.with(|tabs| {
+        let mut tabs = tabs.borrow_mut();
+        let Some(tabs) = tabs.as_mut() else {
+            return;
+        };
+
+        for change in tabs.changes.drain(..) {
+            match change {
+                TabChange::Connected(tab) => connected.send(TabConnected(tab)),
+                TabChange::Left(tab) => left.send(TabLeft(tab)),
+            }
+        }
+
+        let mut current: BTreeMap<TabId, TabInfo> = tabs
+            .tabs
+            .iter()
+            .map(|(id, tab)| {
+                let info = TabInfo {
+                    views: Vec::new(),
+                    boot_flags: tab.boot_flags.clone(),
+                    capabilities: tab.capabilities,
+                };
+                (*id, info)
+            })
+            .collect();
+        for (view, (tab, _, _)) in &tabs.views {
+            if let Some(info) = current.get_mut(tab) {
+                info.views.push(*view);
+            }
+        }
+        for info in current.values_mut() {
+            info.views.sort_by_key(|view| view.0);
+        }
+
+        if shared.tabs != current {
+            shared.tabs = current;
+        }
+    });
+}
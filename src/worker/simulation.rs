@@ -0,0 +1,255 @@
+//! Deterministic simulation stepped with a fixed timestep, independent of animation frames.
+//!
+//! Systems in [`SimulationUpdate`] run zero or more times per frame, each time advancing the world by exactly one step;
+//! leftover time is carried over to the next frame.
+//! Randomness comes from [`SimulationRng`] seeded by page's `seed` boot flag, so given the same input
+//! (e.g. a [replayed](super::replay) recording) simulation ends up in the same state on every machine.
+//! Transforms of [`Interpolated`] entities are blended between the last two steps for rendering,
+//! so motion stays smooth when frame rate doesn't match the timestep.
+
+use std::ops::Range;
+use std::time::Duration;
+
+use bevy::app::RunFixedUpdateLoop;
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::prelude::*;
+
+use super::boot::boot_flags;
+
+/// Step simulation with a fixed timestep.
+///
+/// Not part of [`DefaultPlugins`](super::DefaultPlugins), add it to apps which need determinism.
+pub struct SimulationPlugin {
+    /// Time simulation advances by in one step.
+    pub timestep: Duration,
+    /// Most steps taken in one frame.
+    ///
+    /// Time beyond that is dropped, so a long frame, e.g. after tab was hidden, doesn't snowball into longer ones.
+    pub max_steps: u32,
+}
+
+impl Default for SimulationPlugin {
+    fn default() -> Self {
+        SimulationPlugin {
+            timestep: Duration::from_secs_f64(1.0 / 60.0),
+            max_steps: 8,
+        }
+    }
+}
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        let seed = boot_flags().seed.unwrap_or_else(|| {
+            let word = || (js_sys::Math::random() * u32::MAX as f64) as u64;
+            (word() << 32) | word()
+        });
+        info!("simulation seed is {seed}, pass it as `seed` boot flag to reproduce the run");
+
+        app.init_schedule(SimulationUpdate)
+            .insert_resource(Simulation {
+                timestep: self.timestep,
+                max_steps: self.max_steps,
+                accumulated: Duration::ZERO,
+                steps: 0,
+            })
+            .insert_resource(SimulationRng::new(seed))
+            .add_systems(RunFixedUpdateLoop, run_simulation);
+    }
+}
+
+/// Schedule run once per simulation step.
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimulationUpdate;
+
+/// State of the fixed timestep.
+#[derive(Resource, Debug)]
+pub struct Simulation {
+    timestep: Duration,
+    max_steps: u32,
+    accumulated: Duration,
+    steps: u64,
+}
+
+impl Simulation {
+    pub fn timestep(&self) -> Duration {
+        self.timestep
+    }
+
+    /// Steps taken since the start.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Drop time carried over towards the next step, so the next one is a whole timestep away.
+    pub(super) fn reset_accumulated(&mut self) {
+        self.accumulated = Duration::ZERO;
+    }
+
+    /// How far the frame is between the last step and the next one, from 0 to 1.
+    pub fn alpha(&self) -> f32 {
+        self.accumulated.as_secs_f32() / self.timestep.as_secs_f32()
+    }
+}
+
+/// Random number generator of the simulation, seeded from boot flags.
+///
+/// SplitMix64, which is fast and good enough for gameplay, not for anything security related.
+#[derive(Resource, Debug, Clone)]
+pub struct SimulationRng {
+    seed: u64,
+    state: u64,
+}
+
+impl SimulationRng {
+    pub fn new(seed: u64) -> Self {
+        SimulationRng { seed, state: seed }
+    }
+
+    /// Seed generator started from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed number in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniformly distributed number in the range.
+    pub fn range(&mut self, range: Range<f32>) -> f32 {
+        range.start + (range.end - range.start) * self.next_f32()
+    }
+
+    /// Uniformly distributed integer below `n`, which must not be zero.
+    pub fn below(&mut self, n: u32) -> u32 {
+        (((self.next_u64() >> 32) * n as u64) >> 32) as u32
+    }
+}
+
+/// Render transform blended between the last two simulation steps.
+///
+/// Simulation sees and changes the real transform, everything outside of [`SimulationUpdate`]
+/// sees the blended one, so transforms of such entities should only be changed by the simulation.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Interpolated;
+
+/// Transforms of an [`Interpolated`] entity after the last two steps.
+#[derive(Component)]
+struct Steps {
+    previous: Transform,
+    current: Transform,
+}
+
+fn run_simulation(world: &mut World) {
+    let delta = world.resource::<Time>().delta();
+
+    let steps = {
+        let mut simulation = world.resource_mut::<Simulation>();
+        let timestep = simulation.timestep;
+
+        simulation.accumulated += delta;
+        let mut steps = 0;
+        while simulation.accumulated >= timestep && steps < simulation.max_steps {
+            simulation.accumulated -= timestep;
+            steps += 1;
+        }
+        if steps == simulation.max_steps {
+            simulation.accumulated = simulation.accumulated.min(timestep);
+        }
+
+        steps
+    };
+
+    let new: Vec<(Entity, Transform)> = world
+        .query_filtered::<(Entity, &Transform), (With<Interpolated>, Without<Steps>)>()
+        .iter(world)
+        .map(|(entity, transform)| (entity, *transform))
+        .collect();
+    for (entity, transform) in new {
+        world.entity_mut(entity).insert(Steps {
+            previous: transform,
+            current: transform,
+        });
+    }
+
+    let mut interpolated =
+        world.query_filtered::<(&mut Transform, &mut Steps), With<Interpolated>>();
+
+    // Simulation continues from where it left off, not from the blend.
+    for (mut transform, steps) in interpolated.iter_mut(world) {
+        *transform = steps.current;
+    }
+
+    for _ in 0..steps {
+        for (transform, mut steps) in interpolated.iter_mut(world) {
+            steps.previous = *transform;
+        }
+
+        world.run_schedule(SimulationUpdate);
+        world.resource_mut::<Simulation>().steps += 1;
+
+        for (transform, mut steps) in interpolated.iter_mut(world) {
+            steps.current = *transform;
+        }
+    }
+
+    let alpha = world.resource::<Simulation>().alpha();
+    for (mut transform, steps) in interpolated.iter_mut(world) {
+        let (previous, current) = (steps.previous, steps.current);
+
+        *transform = Transform {
+            translation: previous.translation.lerp(current.translation, alpha),
+            rotation: previous.rotation.slerp(current.rotation, alpha),
+            scale: previous.scale.lerp(current.scale, alpha),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splitmix64_reference_values() {
+        let mut rng = SimulationRng::new(0);
+
+        assert_eq!(rng.next_u64(), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(rng.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+        assert_eq!(rng.next_u64(), 0x06c4_5d18_8009_454f);
+        assert_eq!(rng.seed(), 0);
+    }
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = SimulationRng::new(42);
+        let mut b = SimulationRng::new(42);
+
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn numbers_stay_in_range() {
+        let mut rng = SimulationRng::new(7);
+
+        for _ in 0..1000 {
+            let x = rng.next_f32();
+            assert!((0.0..1.0).contains(&x));
+
+            let x = rng.range(-2.0..3.0);
+            assert!((-2.0..3.0).contains(&x));
+
+            assert!(rng.below(5) < 5);
+        }
+    }
+}
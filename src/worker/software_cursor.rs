@@ -0,0 +1,176 @@
+//! Cursor drawn by the app itself.
+//!
+//! Page can only show stock CSS cursors over the canvas, and none at all while pointer is locked.
+//! [`SoftwareCursorPlugin`] draws an image at pointer position instead, as a UI node of primary window.
+//!
+//! Drawn cursor trails behind the real pointer by at least a frame.
+//! When frames get slow enough for that to be noticeable, or app asks for precision,
+//! it is hidden and hardware cursor comes back, unless pointer is locked and there is no hardware cursor to show.
+
+use bevy::input::mouse::MouseMotion;
+use bevy::prelude::*;
+use bevy::window::{CursorGrabMode, PrimaryWindow};
+
+/// Draw cursor image over primary window instead of hardware cursor.
+pub struct SoftwareCursorPlugin {
+    /// Asset path of the cursor image.
+    pub image: String,
+    /// Point of the image at pointer position, in pixels from its top-left corner.
+    pub hotspot: Vec2,
+}
+
+impl SoftwareCursorPlugin {
+    pub fn new(image: &str) -> Self {
+        SoftwareCursorPlugin {
+            image: image.to_owned(),
+            hotspot: Vec2::ZERO,
+        }
+    }
+}
+
+impl Plugin for SoftwareCursorPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SoftwareCursor {
+            image: self.image.clone(),
+            hotspot: self.hotspot,
+            ..default()
+        })
+        .init_resource::<SoftwareCursorState>()
+        .add_systems(Startup, spawn_cursor)
+        .add_systems(Update, (track_pointer, switch_cursor, place_cursor).chain());
+    }
+}
+
+/// Settings of the drawn cursor, can be changed at runtime.
+#[derive(Resource, Debug, Clone)]
+pub struct SoftwareCursor {
+    /// Asset path of the cursor image, only read at startup.
+    pub image: String,
+    pub hotspot: Vec2,
+    /// Draw cursor at all, hardware cursor is used otherwise.
+    pub enabled: bool,
+    /// Use hardware cursor, e.g. while user picks text or drags precise handles.
+    pub prefer_hardware: bool,
+    /// Frame time above which hardware cursor is used, as drawn one visibly lags behind.
+    pub max_frame_ms: f32,
+}
+
+impl Default for SoftwareCursor {
+    fn default() -> Self {
+        SoftwareCursor {
+            image: String::new(),
+            hotspot: Vec2::ZERO,
+            enabled: true,
+            prefer_hardware: false,
+            max_frame_ms: 50.0,
+        }
+    }
+}
+
+/// What cursor is shown right now.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct SoftwareCursorState {
+    /// Drawn cursor is visible and hardware one is hidden.
+    pub active: bool,
+    /// Where cursor is drawn, in logical pixels of primary window.
+    ///
+    /// While pointer is locked it follows mouse movement, staying inside the window.
+    pub position: Option<Vec2>,
+    // Smoothed frame time.
+    frame_ms: f32,
+    // Hardware cursor was hidden by us and should be shown again.
+    hid_hardware: bool,
+}
+
+#[derive(Component)]
+struct CursorNode;
+
+fn spawn_cursor(mut commands: Commands, settings: Res<SoftwareCursor>, assets: Res<AssetServer>) {
+    commands.spawn((
+        CursorNode,
+        ImageBundle {
+            image: UiImage::new(assets.load(&settings.image)),
+            style: Style {
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            // Above everything else, including UI of the app.
+            z_index: ZIndex::Global(i32::MAX),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+}
+
+fn track_pointer(
+    time: Res<Time>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut motion: EventReader<MouseMotion>,
+    mut state: ResMut<SoftwareCursorState>,
+) {
+    const SMOOTHING: f32 = 0.1;
+
+    let frame_ms = time.delta_seconds() * 1000.0;
+    state.frame_ms += (frame_ms - state.frame_ms) * SMOOTHING;
+
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+
+    if window.cursor.grab_mode == CursorGrabMode::None {
+        motion.clear();
+        state.position = window.cursor_position();
+        return;
+    }
+
+    // Locked pointer stays in place, drawn cursor moves on its own.
+    let size = Vec2::new(window.width(), window.height());
+    let delta: Vec2 = motion.iter().map(|motion| motion.delta).sum();
+    let position = state.position.unwrap_or(size / 2.0) + delta;
+    state.position = Some(position.clamp(Vec2::ZERO, size));
+}
+
+fn switch_cursor(
+    settings: Res<SoftwareCursor>,
+    mut state: ResMut<SoftwareCursorState>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Ok(mut window) = windows.get_single_mut() else {
+        return;
+    };
+
+    let locked = window.cursor.grab_mode != CursorGrabMode::None;
+    let hardware = settings.prefer_hardware || state.frame_ms > settings.max_frame_ms;
+    state.active = settings.enabled && (locked || !hardware);
+
+    if state.active {
+        if window.cursor.visible {
+            window.cursor.visible = false;
+            state.hid_hardware = true;
+        }
+    } else if state.hid_hardware {
+        // Cursor app hid by itself stays hidden.
+        window.cursor.visible = true;
+        state.hid_hardware = false;
+    }
+}
+
+fn place_cursor(
+    settings: Res<SoftwareCursor>,
+    state: Res<SoftwareCursorState>,
+    ui_scale: Res<UiScale>,
+    mut nodes: Query<(&mut Style, &mut Visibility), With<CursorNode>>,
+) {
+    for (mut style, mut visibility) in &mut nodes {
+        let Some(position) = state.position.filter(|_| state.active) else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+
+        // Node positions are scaled together with the rest of UI, image is not.
+        let corner = position / ui_scale.scale as f32 - settings.hotspot;
+        style.left = Val::Px(corner.x);
+        style.top = Val::Px(corner.y);
+        *visibility = Visibility::Inherited;
+    }
+}
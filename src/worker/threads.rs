@@ -0,0 +1,336 @@
+//! Extra threads for the worker, backed by nested workers sharing its wasm memory.
+//!
+//! Threads are only spawned when the worker is built with `atomics` and `bulk-memory` target features
+//! (see README) and page is cross-origin isolated, since shared memory is off limits otherwise.
+//! Without them [`Threads`] runs work inline and reports a single thread.
+//!
+//! Bevy's task pools are compiled single threaded on wasm and can't be handed other threads,
+//! so work has to go to [`Threads`] explicitly, e.g. from a system collecting components into a buffer.
+//! [`ThreadTask`] is a future, async code like tasks spawned on `AsyncComputeTaskPool`
+//! can await it and leave the work itself to the threads.
+//!
+//! Nested workers load the same wasm-bindgen glue as the worker itself,
+//! which bootstrap script leaves in `self.bevyWorkerArtifacts`.
+//! Loading it runs binary's `main` on threads too, where [`start`](super::start) and friends
+//! see [`is_thread`] and return right away, leaving threads to [`Threads::spawn`] jobs.
+
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+use bevy::prelude::*;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+thread_local! {
+    // `None` until threads are spawned.
+    static COUNT: Cell<Option<u32>> = Cell::new(None);
+}
+
+/// Whether this is one of the threads rather than the worker itself.
+///
+/// Thread scripts set `self.bevyWorkerThread` before loading the module.
+pub fn is_thread() -> bool {
+    js_sys::Reflect::get(&js_sys::global(), &"bevyWorkerThread".into())
+        .map_or(false, |flag| flag.is_truthy())
+}
+
+/// Threads the worker runs on, its own included.
+///
+/// Spawns threads on the first call.
+pub fn thread_count() -> u32 {
+    COUNT.with(|count| match count.get() {
+        Some(threads) => threads,
+        None => {
+            let threads = 1 + pool::spawn();
+            count.set(Some(threads));
+            threads
+        }
+    })
+}
+
+/// Handle to worker's threads.
+///
+/// Inserted by [`ThreadsPlugin`], part of [`DefaultPlugins`](super::DefaultPlugins).
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct Threads {
+    count: u32,
+}
+
+impl Threads {
+    /// Threads the worker runs on, its own included.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Run `f` on another thread.
+    ///
+    /// Runs right away on the current thread if there are no other threads.
+    /// Threads take jobs in turns, so splitting work into [`count`](Self::count) pieces keeps all of them busy.
+    pub fn spawn<T: Send + 'static>(
+        &self,
+        f: impl FnOnce() -> T + Send + 'static,
+    ) -> ThreadTask<T> {
+        let result = Arc::new(Mutex::new(Outcome {
+            value: None,
+            waker: None,
+        }));
+        let job: Job = {
+            let result = Arc::clone(&result);
+            Box::new(move || {
+                let value = f();
+                let waker = {
+                    let mut outcome = lock(&result);
+                    outcome.value = Some(value);
+                    outcome.waker.take()
+                };
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+            })
+        };
+
+        if self.count > 1 {
+            pool::run(job);
+        } else {
+            job();
+        }
+
+        ThreadTask { result }
+    }
+}
+
+/// Result of work handed to [`Threads::spawn`].
+///
+/// Either poll it with [`try_take`](Self::try_take) from a system, or await it.
+pub struct ThreadTask<T> {
+    result: Arc<Mutex<Outcome<T>>>,
+}
+
+struct Outcome<T> {
+    value: Option<T>,
+    // Task awaiting the result.
+    waker: Option<Waker>,
+}
+
+impl<T> ThreadTask<T> {
+    /// Take the result, `None` while the job is still running.
+    ///
+    /// Result can only be taken once.
+    pub fn try_take(&self) -> Option<T> {
+        lock(&self.result).value.take()
+    }
+}
+
+impl<T> Future for ThreadTask<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut outcome = lock(&self.result);
+
+        match outcome.value.take() {
+            Some(value) => Poll::Ready(value),
+            None => {
+                outcome.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+// Job panicking on a thread takes the thread down with it, there is no half written result to worry about.
+fn lock<T>(result: &Mutex<Outcome<T>>) -> MutexGuard<'_, Outcome<T>> {
+    result.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Insert [`Threads`] resource, spawning threads if there are none yet.
+#[derive(Default)]
+pub struct ThreadsPlugin;
+
+impl Plugin for ThreadsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Threads {
+            count: thread_count(),
+        });
+    }
+}
+
+#[cfg(target_feature = "atomics")]
+mod pool {
+    use std::cell::{Cell, RefCell};
+
+    use js_sys::Reflect;
+    use wasm_bindgen::prelude::{wasm_bindgen, JsValue};
+    use web_sys::Worker;
+
+    use super::Job;
+
+    /// Browsers report up to 16 cores or so, there is no point in more threads than that.
+    const MAX_THREADS: u32 = 16;
+
+    thread_local! {
+        static WORKERS: RefCell<Vec<Worker>> = RefCell::new(Vec::new());
+        static NEXT: Cell<usize> = Cell::new(0);
+    }
+
+    /// Where worker's glue came from, as left by bootstrap script.
+    struct Artifacts {
+        js: String,
+        init: String,
+        module: bool,
+    }
+
+    impl Artifacts {
+        fn find(global: &JsValue) -> Option<Self> {
+            let artifacts = Reflect::get(global, &"bevyWorkerArtifacts".into()).ok()?;
+            let get = |key: &str| Reflect::get(&artifacts, &key.into()).ok();
+
+            let js = get("js")?.as_string()?;
+            // Thread scripts are served from `blob:` URLs, relative URLs don't resolve against those.
+            let js = super::super::scope()
+                .location()
+                .href()
+                .ok()
+                .and_then(|base| web_sys::Url::new_with_base(&js, &base).ok())
+                .map_or(js, |url| url.href());
+
+            Some(Artifacts {
+                js,
+                init: get("init")?.as_string()?,
+                module: get("module")?.as_bool()?,
+            })
+        }
+
+        /// Script waiting for module and memory, then running jobs it is sent pointers to.
+        fn script(&self) -> String {
+            let js = quote(&self.js);
+            let init = quote(&self.init);
+
+            if self.module {
+                format!(
+                    r#"self.bevyWorkerThread=true;let m;onmessage=async e=>{{if(!m){{m=import({js}).then(async x=>{{await x[{init}](e.data[0],e.data[1]);return x;}});return;}}(await m).bevy_worker_thread_entry(e.data);}};"#
+                )
+            } else {
+                format!(
+                    r#"self.bevyWorkerThread=true;importScripts({js});const w=self[{init}]??wasm_bindgen;let r;onmessage=async e=>{{if(!r){{r=w(e.data[0],e.data[1]);return;}}await r;w.bevy_worker_thread_entry(e.data);}};"#
+                )
+            }
+        }
+    }
+
+    fn quote(s: &str) -> String {
+        js_sys::JSON::stringify(&s.into())
+            .map(String::from)
+            .unwrap_or_else(|_| format!("{s:?}"))
+    }
+
+    /// Spawn threads, returns how many.
+    pub(super) fn spawn() -> u32 {
+        use js_sys::Array;
+        use web_sys::{Blob, BlobPropertyBag, Url, WorkerOptions, WorkerType};
+
+        let global = JsValue::from(js_sys::global());
+
+        if !super::super::capabilities::worker_capabilities().shared_memory() {
+            bevy::log::info!("worker can't share memory, running on a single thread");
+            return 0;
+        }
+
+        let Some(artifacts) = Artifacts::find(&global) else {
+            bevy::log::warn!(
+                "bootstrap script didn't leave artifact locations, running on a single thread"
+            );
+            return 0;
+        };
+
+        let cores = Reflect::get(&global, &"navigator".into())
+            .and_then(|navigator| Reflect::get(&navigator, &"hardwareConcurrency".into()))
+            .ok()
+            .and_then(|cores| cores.as_f64())
+            .map_or(1, |cores| cores as u32);
+        let count = cores.clamp(1, MAX_THREADS) - 1;
+
+        let parts = Array::of1(&artifacts.script().into());
+        let url = Blob::new_with_str_sequence_and_options(
+            &parts,
+            BlobPropertyBag::new().type_("text/javascript"),
+        )
+        .and_then(|blob| Url::create_object_url_with_blob(&blob));
+        let url = match url {
+            Ok(url) => url,
+            Err(err) => {
+                bevy::log::warn!("failed to create thread script: {err:?}");
+                return 0;
+            }
+        };
+
+        let init = Array::of2(&wasm_bindgen::module(), &wasm_bindgen::memory());
+        let mut options = WorkerOptions::new();
+        options.type_(match artifacts.module {
+            true => WorkerType::Module,
+            false => WorkerType::Classic,
+        });
+
+        WORKERS.with(|workers| {
+            let mut workers = workers.borrow_mut();
+
+            for _ in 0..count {
+                let spawned = Worker::new_with_options(&url, &options).and_then(|worker| {
+                    worker.post_message(&init)?;
+                    Ok(worker)
+                });
+
+                match spawned {
+                    Ok(worker) => workers.push(worker),
+                    Err(err) => {
+                        bevy::log::warn!("failed to spawn thread: {err:?}");
+                        break;
+                    }
+                }
+            }
+
+            workers.len() as u32
+        })
+    }
+
+    /// Hand job to the next thread in turn.
+    pub(super) fn run(job: Job) {
+        let ptr = Box::into_raw(Box::new(job));
+
+        let posted = WORKERS.with(|workers| {
+            let workers = workers.borrow();
+            let next = NEXT.with(|next| next.replace(next.get() + 1)) % workers.len();
+
+            workers[next].post_message(&JsValue::from(ptr as u32))
+        });
+
+        if posted.is_err() {
+            // SAFETY: pointer never made it to another thread.
+            let job = unsafe { Box::from_raw(ptr) };
+            job();
+        }
+    }
+
+    /// Entry point of thread workers, runs job posted by [`run`].
+    #[wasm_bindgen]
+    pub fn bevy_worker_thread_entry(ptr: u32) {
+        // SAFETY: pointer comes from `Box::into_raw` in `run` and is posted to exactly one thread.
+        let job = unsafe { Box::from_raw(ptr as *mut Job) };
+        job();
+    }
+}
+
+#[cfg(not(target_feature = "atomics"))]
+mod pool {
+    use super::Job;
+
+    pub(super) fn spawn() -> u32 {
+        0
+    }
+
+    pub(super) fn run(job: Job) {
+        job();
+    }
+}
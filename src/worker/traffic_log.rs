@@ -0,0 +1,77 @@
+//! Full log of bridge traffic for debugging.
+//!
+//! Unlike [`BridgeMetrics`](super::BridgeMetrics), which only remembers what went through lately,
+//! this records every message with its size and timestamps.
+//! Logging is off until page turns it on, and page asks for the log when it wants to export it.
+
+use std::cell::RefCell;
+
+use bevy::prelude::*;
+use wasm_bindgen::prelude::JsValue;
+
+use super::{post, take_messages, BridgeReceive, BridgeSchedules};
+use crate::protocol::{HostMessage, TrafficDirection, TrafficLog, WorkerMessage};
+
+thread_local! {
+    static LOG: RefCell<Option<TrafficLog>> = RefCell::new(None);
+}
+
+/// Log bridge traffic on worker side when page asks for it.
+pub struct TrafficLogPlugin {
+    /// How many latest messages to keep.
+    pub capacity: usize,
+}
+
+impl Default for TrafficLogPlugin {
+    fn default() -> Self {
+        TrafficLogPlugin { capacity: 1024 }
+    }
+}
+
+impl Plugin for TrafficLogPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.insert_resource(TrafficLogCapacity(self.capacity))
+            .add_systems(schedules.receive, receive_commands.in_set(BridgeReceive));
+    }
+}
+
+#[derive(Resource)]
+struct TrafficLogCapacity(usize);
+
+/// Log message if logging is on.
+pub(super) fn record(direction: TrafficDirection, kind: Option<&'static str>, msg: &JsValue) {
+    LOG.with(|log| {
+        if let Some(log) = &mut *log.borrow_mut() {
+            log.record(direction, kind, msg);
+        }
+    });
+}
+
+fn receive_commands(capacity: Res<TrafficLogCapacity>) {
+    take_messages(|msg| match msg {
+        HostMessage::SetTrafficLog(enabled) => {
+            LOG.with(|log| {
+                let mut log = log.borrow_mut();
+
+                match (enabled, log.is_some()) {
+                    (true, false) => *log = Some(TrafficLog::new(capacity.0)),
+                    (false, true) => *log = None,
+                    _ => (),
+                }
+            });
+            Ok(())
+        }
+        HostMessage::RequestTrafficLog => {
+            let json = LOG.with(|log| {
+                log.borrow()
+                    .as_ref()
+                    .map_or_else(|| "[]".to_owned(), TrafficLog::to_json)
+            });
+            post(&WorkerMessage::TrafficLog(json));
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
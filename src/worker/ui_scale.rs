@@ -0,0 +1,36 @@
+//! UI scale set by the page.
+//!
+//! Canvas resolution follows device pixel ratio already, this is an extra multiplier
+//! for users who need larger UI and text, see [`HostMessage::SetUiScale`].
+
+use bevy::prelude::*;
+
+use super::{take_messages, BridgeReceive, BridgeSchedules};
+use crate::protocol::HostMessage;
+
+/// Apply UI scale requested by the page to [`UiScale`].
+///
+/// Must be added after [`UiPlugin`](bevy::ui::UiPlugin).
+#[derive(Default)]
+pub struct UiScalePlugin;
+
+impl Plugin for UiScalePlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.add_systems(schedules.receive, receive_scale.in_set(BridgeReceive));
+    }
+}
+
+fn receive_scale(mut ui_scale: ResMut<UiScale>) {
+    take_messages(|msg| match msg {
+        HostMessage::SetUiScale(scale) => {
+            // Zero or negative scale collapses the whole UI.
+            if scale.is_finite() && scale > 0.0 {
+                ui_scale.scale = scale;
+            }
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+}
@@ -0,0 +1,179 @@
+//! State carried over when page upgrades the worker to a new build,
+//! see [`WorkerHandle::upgrade`](crate::host::WorkerHandle::upgrade).
+//!
+//! Old app writes entities marked [`Persistent`] out as a scene on [`HostMessage::TakeSnapshot`]
+//! and answers with [`WorkerMessage::Snapshot`].
+//! New app gets it in [`HostMessage::RestoreSnapshot`] and swaps persistent entities
+//! its startup spawned for the ones from the snapshot.
+//! Same as with scenes, only registered components reflecting `Component` are carried over,
+//! and entities referenced by persistent ones, e.g. children, have to be persistent as well.
+//!
+//! App torn down after losing its graphics device keeps persistent entities the same way:
+//! worker snapshots them before dropping the app and restores them into the app rebuilt on a fresh canvas.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use super::{post, scene, take_messages, BridgeReceive, BridgeSchedules};
+use crate::protocol::{AppId, HostMessage, WorkerMessage};
+
+thread_local! {
+    // Snapshots of apps torn down after losing their device, until they are rebuilt.
+    static KEPT: RefCell<HashMap<AppId, String>> = RefCell::new(HashMap::new());
+}
+
+/// Take and restore snapshots of persistent entities.
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins).
+#[derive(Default)]
+pub struct UpgradePlugin;
+
+impl Plugin for UpgradePlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.register_type::<Persistent>()
+            .add_systems(schedules.receive, receive.in_set(BridgeReceive));
+    }
+}
+
+/// Entity survives worker upgrades.
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct Persistent;
+
+fn receive(world: &mut World) {
+    let messages = take_messages(|msg| match msg {
+        HostMessage::TakeSnapshot | HostMessage::RestoreSnapshot(_) => Ok(msg),
+        msg => Err(msg),
+    });
+
+    for msg in messages {
+        match msg {
+            HostMessage::TakeSnapshot => {
+                let result = take_snapshot(world);
+                if let Err(err) = &result {
+                    warn!("failed to take snapshot: {err}");
+                }
+
+                post(&WorkerMessage::Snapshot(result));
+            }
+            HostMessage::RestoreSnapshot(snapshot) => match restore(world, &snapshot) {
+                Ok(count) => info!("restored {count} persistent entities"),
+                Err(err) => warn!("failed to restore snapshot: {err}"),
+            },
+            _ => (),
+        }
+    }
+}
+
+fn take_snapshot(world: &mut World) -> Result<String, String> {
+    let entities: Vec<_> = world
+        .query_filtered::<Entity, With<Persistent>>()
+        .iter(world)
+        .collect();
+
+    scene::export_entities(world, entities.into_iter())
+}
+
+/// Snapshot persistent entities of the app about to be torn down, see [`kept`].
+pub(super) fn keep(app: AppId, world: &mut World) {
+    match take_snapshot(world) {
+        Ok(snapshot) => {
+            KEPT.with(|kept| kept.borrow_mut().insert(app, snapshot));
+        }
+        Err(err) => warn!("app state is lost with the device, snapshot failed: {err}"),
+    }
+}
+
+/// Message restoring what was [`keep`]t for the app, to be handled once it is rebuilt.
+pub(super) fn kept(app: AppId) -> Option<HostMessage> {
+    let snapshot = KEPT.with(|kept| kept.borrow_mut().remove(&app))?;
+    Some(HostMessage::RestoreSnapshot(snapshot))
+}
+
+/// Replace persistent entities with ones from the snapshot, returns how many there were.
+fn restore(world: &mut World, snapshot: &str) -> Result<usize, String> {
+    let existing: Vec<_> = world
+        .query_filtered::<Entity, With<Persistent>>()
+        .iter(world)
+        .collect();
+
+    // Children may be gone together with their parent already.
+    for entity in existing {
+        if let Some(entity) = world.get_entity_mut(entity) {
+            entity.despawn_recursive();
+        }
+    }
+
+    scene::import(world, snapshot.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world() -> World {
+        let registry = AppTypeRegistry::default();
+        {
+            let mut registry = registry.write();
+            registry.register::<Persistent>();
+            registry.register::<Transform>();
+            registry.register::<Vec3>();
+            registry.register::<Quat>();
+        }
+
+        let mut world = World::new();
+        world.insert_resource(registry);
+        world
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let mut old = world();
+        old.spawn((Persistent, Transform::from_xyz(1.0, 2.0, 3.0)));
+        old.spawn(Transform::from_xyz(4.0, 5.0, 6.0));
+        let snapshot = take_snapshot(&mut old).unwrap();
+
+        // Startup of the new app spawned its own persistent entity, which snapshot replaces.
+        let mut new = world();
+        new.spawn((Persistent, Transform::default()));
+
+        assert_eq!(restore(&mut new, &snapshot), Ok(1));
+
+        let transforms: Vec<_> = new
+            .query_filtered::<&Transform, With<Persistent>>()
+            .iter(&new)
+            .copied()
+            .collect();
+        assert_eq!(transforms, [Transform::from_xyz(1.0, 2.0, 3.0)]);
+    }
+
+    #[test]
+    fn kept_across_device_loss() {
+        let app = AppId(7);
+        let mut lost = world();
+        lost.spawn((Persistent, Transform::from_xyz(1.0, 2.0, 3.0)));
+        keep(app, &mut lost);
+        drop(lost);
+
+        let Some(HostMessage::RestoreSnapshot(snapshot)) = kept(app) else {
+            panic!("snapshot to be kept");
+        };
+        // Snapshot is only restored once.
+        assert!(kept(app).is_none());
+
+        let mut rebuilt = world();
+        rebuilt.spawn((Persistent, Transform::default()));
+        assert_eq!(restore(&mut rebuilt, &snapshot), Ok(1));
+
+        let transforms: Vec<_> = rebuilt
+            .query_filtered::<&Transform, With<Persistent>>()
+            .iter(&rebuilt)
+            .copied()
+            .collect();
+        assert_eq!(transforms, [Transform::from_xyz(1.0, 2.0, 3.0)]);
+    }
+}
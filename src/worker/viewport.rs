@@ -0,0 +1,231 @@
+//! How the picture fits the canvas when its size changes, e.g. as phone is rotated.
+//!
+//! Canvas drawing buffer follows the page, see `WorkerBuilder::responsive_canvas`,
+//! so aspect ratio of the window can change at any time.
+//! By default cameras simply render to the whole window, apps designed for a fixed resolution
+//! pick a different [`ViewportPolicy`] to keep their proportions instead.
+
+use bevy::prelude::*;
+use bevy::render::camera::{CameraUpdateSystem, RenderTarget, ScalingMode, Viewport};
+use bevy::window::{PrimaryWindow, WindowRef};
+
+/// Fit camera viewports into their windows according to [`ViewportPolicy`].
+///
+/// Part of [`DefaultPlugins`](super::DefaultPlugins) for apps with a window.
+#[derive(Default)]
+pub struct ViewportPlugin {
+    pub policy: ViewportPolicy,
+}
+
+impl Plugin for ViewportPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.policy)
+            .add_systems(PostUpdate, apply_viewport_policy.before(CameraUpdateSystem));
+    }
+}
+
+/// How cameras rendering to a window fit into it, can be changed at runtime.
+///
+/// Applies to every camera targeting a window, cameras with a viewport of their own
+/// should stay with [`Stretch`](ViewportPolicy::Stretch).
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ViewportPolicy {
+    /// Render to the whole window, whatever its proportions.
+    #[default]
+    Stretch,
+    /// Keep aspect ratio of given resolution, scaling it as large as window allows.
+    ///
+    /// Rest of the window is filled with camera's clear color.
+    Letterbox { width: u32, height: u32 },
+    /// Same as [`Letterbox`](ViewportPolicy::Letterbox), but only scale by whole numbers,
+    /// so pixel art stays crisp.
+    IntegerScale { width: u32, height: u32 },
+}
+
+impl ViewportPolicy {
+    /// Viewport position and size inside window of given physical size.
+    fn fit(self, window: UVec2) -> Option<(UVec2, UVec2)> {
+        let (resolution, integer) = match self {
+            ViewportPolicy::Stretch => return None,
+            ViewportPolicy::Letterbox { width, height } => (UVec2::new(width, height), false),
+            ViewportPolicy::IntegerScale { width, height } => (UVec2::new(width, height), true),
+        };
+        if resolution.cmpeq(UVec2::ZERO).any() || window.cmpeq(UVec2::ZERO).any() {
+            return None;
+        }
+
+        let scale = (window.as_vec2() / resolution.as_vec2()).min_element();
+        // Windows smaller than the resolution still get the largest picture which fits.
+        let scale = if integer && scale >= 1.0 {
+            scale.floor()
+        } else {
+            scale
+        };
+
+        let size = (resolution.as_vec2() * scale)
+            .round()
+            .as_uvec2()
+            .clamp(UVec2::ONE, window);
+        let position = (window - size) / 2;
+
+        Some((position, size))
+    }
+
+    fn resolution(self) -> Option<Vec2> {
+        match self {
+            ViewportPolicy::Stretch => None,
+            ViewportPolicy::Letterbox { width, height }
+            | ViewportPolicy::IntegerScale { width, height } => {
+                Some(UVec2::new(width, height).as_vec2())
+            }
+        }
+    }
+}
+
+/// Scaling mode camera had before [`ViewportPolicy`] took over it,
+/// restored once policy goes back to [`Stretch`](ViewportPolicy::Stretch).
+#[derive(Component)]
+struct Fitted {
+    scaling_mode: Option<ScalingMode>,
+}
+
+fn apply_viewport_policy(
+    mut commands: Commands,
+    policy: Res<ViewportPolicy>,
+    primary: Query<Entity, With<PrimaryWindow>>,
+    windows: Query<&Window>,
+    mut cameras: Query<(
+        Entity,
+        &mut Camera,
+        Option<&mut OrthographicProjection>,
+        Option<&Fitted>,
+    )>,
+) {
+    for (entity, mut camera, projection, fitted) in &mut cameras {
+        let window = match &camera.target {
+            RenderTarget::Window(WindowRef::Primary) => primary.get_single().ok(),
+            RenderTarget::Window(WindowRef::Entity(window)) => Some(*window),
+            _ => None,
+        };
+        let Some(window) = window.and_then(|window| windows.get(window).ok()) else {
+            continue;
+        };
+        let window_size = UVec2::new(window.physical_width(), window.physical_height());
+
+        let Some((position, size)) = policy.fit(window_size) else {
+            if let Some(fitted) = fitted {
+                camera.viewport = None;
+                if let (Some(mut projection), Some(scaling_mode)) =
+                    (projection, fitted.scaling_mode)
+                {
+                    projection.scaling_mode = scaling_mode;
+                }
+                commands.entity(entity).remove::<Fitted>();
+            }
+            continue;
+        };
+
+        if fitted.is_none() {
+            commands.entity(entity).insert(Fitted {
+                scaling_mode: projection
+                    .as_ref()
+                    .map(|projection| projection.scaling_mode),
+            });
+        }
+
+        // Avoid touching components every frame, that would recompute projections needlessly.
+        let viewport = camera.viewport.as_ref();
+        if viewport.map(|viewport| (viewport.physical_position, viewport.physical_size))
+            != Some((position, size))
+        {
+            let depth = viewport.map_or(0.0..1.0, |viewport| viewport.depth.clone());
+            camera.viewport = Some(Viewport {
+                physical_position: position,
+                physical_size: size,
+                depth,
+            });
+        }
+
+        if let (Some(mut projection), Some(resolution)) = (projection, policy.resolution()) {
+            let fixed = matches!(
+                projection.scaling_mode,
+                ScalingMode::Fixed { width, height } if Vec2::new(width, height) == resolution
+            );
+            if !fixed {
+                projection.scaling_mode = ScalingMode::Fixed {
+                    width: resolution.x,
+                    height: resolution.y,
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stretch_has_no_viewport() {
+        assert_eq!(ViewportPolicy::Stretch.fit(UVec2::new(800, 600)), None);
+    }
+
+    #[test]
+    fn letterbox_keeps_aspect_ratio() {
+        let policy = ViewportPolicy::Letterbox {
+            width: 320,
+            height: 180,
+        };
+
+        // Wider window gets bars on the sides, taller one above and below.
+        assert_eq!(
+            policy.fit(UVec2::new(1000, 360)),
+            Some((UVec2::new(180, 0), UVec2::new(640, 360)))
+        );
+        assert_eq!(
+            policy.fit(UVec2::new(640, 1000)),
+            Some((UVec2::new(0, 320), UVec2::new(640, 360)))
+        );
+    }
+
+    #[test]
+    fn integer_scale_rounds_down() {
+        let policy = ViewportPolicy::IntegerScale {
+            width: 320,
+            height: 180,
+        };
+
+        assert_eq!(
+            policy.fit(UVec2::new(700, 600)),
+            Some((UVec2::new(30, 120), UVec2::new(640, 360)))
+        );
+    }
+
+    #[test]
+    fn integer_scale_shrinks_into_small_windows() {
+        let policy = ViewportPolicy::IntegerScale {
+            width: 320,
+            height: 180,
+        };
+
+        assert_eq!(
+            policy.fit(UVec2::new(160, 180)),
+            Some((UVec2::new(0, 45), UVec2::new(160, 90)))
+        );
+    }
+
+    #[test]
+    fn degenerate_sizes() {
+        let policy = ViewportPolicy::Letterbox {
+            width: 0,
+            height: 180,
+        };
+        assert_eq!(policy.fit(UVec2::new(800, 600)), None);
+
+        let policy = ViewportPolicy::Letterbox {
+            width: 320,
+            height: 180,
+        };
+        assert_eq!(policy.fit(UVec2::new(0, 600)), None);
+    }
+}
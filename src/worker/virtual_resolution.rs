@@ -0,0 +1,335 @@
+//! Rendering at a fixed internal resolution, upscaled to the canvas, e.g. 640x360 for pixel art.
+//!
+//! Cameras rendering to a window are redirected into a texture of [`VirtualResolution`] size,
+//! which an extra camera then draws over the whole window keeping its aspect ratio.
+//! Resolution and filter can come from the config file like any other section:
+//! `ConfigPlugin::default().section::<VirtualResolution>("virtual_resolution")`.
+//!
+//! Picture keeps its aspect ratio by itself, so `ViewportPolicy` should be left to stretch.
+//! Removing [`VirtualResolution`] resource points cameras back to their windows.
+//! Cursor positions are still in window coordinates, see [`VirtualResolution::to_virtual`].
+//!
+//! UI rendered into the texture doesn't react to the pointer, since bevy_ui only looks for it
+//! in windows cameras render to. Interactive UI needs a camera of its own marked with [`NativeResolution`].
+//! `DomAnchor` positions are mapped from virtual pixels into the window.
+
+use bevy::prelude::*;
+use bevy::render::camera::{CameraUpdateSystem, RenderTarget, ScalingMode};
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
+use bevy::render::texture::{BevyDefault, ImageSampler};
+use bevy::render::view::RenderLayers;
+use bevy::utils::HashMap;
+use bevy::window::{PrimaryWindow, WindowRef};
+use serde::Deserialize;
+
+/// Render layer of upscaled pictures, app's own cameras shouldn't render it.
+pub const UPSCALE_LAYER: u8 = RenderLayers::TOTAL_LAYERS as u8 - 1;
+
+/// Order of cameras drawing upscaled pictures to windows.
+///
+/// Cameras marked with [`NativeResolution`] should come after it and keep what is already in the window,
+/// e.g. with `ClearColorConfig::None`.
+pub const UPSCALE_ORDER: isize = 1000;
+
+/// Render app at [`VirtualResolution`] and upscale it to the canvas.
+///
+/// Not part of [`DefaultPlugins`](super::DefaultPlugins), add it when app needs a fixed resolution.
+#[derive(Default)]
+pub struct VirtualResolutionPlugin {
+    pub resolution: VirtualResolution,
+}
+
+impl Plugin for VirtualResolutionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.resolution)
+            .init_resource::<VirtualCanvases>()
+            .add_systems(
+                PostUpdate,
+                (redirect_cameras, update_canvases)
+                    .chain()
+                    .before(CameraUpdateSystem),
+            );
+    }
+}
+
+/// Size app is rendered at, can be changed at runtime.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct VirtualResolution {
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub filter: ScalingFilter,
+}
+
+impl Default for VirtualResolution {
+    fn default() -> Self {
+        VirtualResolution {
+            width: 640,
+            height: 360,
+            filter: ScalingFilter::Nearest,
+        }
+    }
+}
+
+impl VirtualResolution {
+    fn size(&self) -> UVec2 {
+        UVec2::new(self.width, self.height).max(UVec2::ONE)
+    }
+
+    // Picture is scaled as large as it fits, bars are left around it.
+    fn scaling_mode(&self) -> ScalingMode {
+        let size = self.size().as_vec2();
+        ScalingMode::AutoMin {
+            min_width: size.x,
+            min_height: size.y,
+        }
+    }
+
+    /// Scale of the picture in the window and offset of its top-left corner, in logical pixels.
+    fn fit(&self, window: &Window) -> (f32, Vec2) {
+        let size = self.size().as_vec2();
+        let window_size = Vec2::new(window.width(), window.height());
+        let scale = (window_size / size).min_element();
+        let offset = (window_size - size * scale) / 2.0;
+
+        (scale, offset)
+    }
+
+    /// Convert position in the window, e.g. cursor position, into virtual pixels.
+    ///
+    /// `None` if position falls onto bars around the picture.
+    pub fn to_virtual(&self, window: &Window, position: Vec2) -> Option<Vec2> {
+        let (scale, offset) = self.fit(window);
+
+        let position = (position - offset) / scale;
+        let inside =
+            position.cmpge(Vec2::ZERO).all() && position.cmplt(self.size().as_vec2()).all();
+        inside.then_some(position)
+    }
+
+    /// Convert position in virtual pixels into logical pixels of the window.
+    pub fn to_window(&self, window: &Window, position: Vec2) -> Vec2 {
+        let (scale, offset) = self.fit(window);
+        offset + position * scale
+    }
+}
+
+/// How virtual pixels are stretched over the canvas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScalingFilter {
+    /// Keep pixels sharp, for pixel art.
+    #[default]
+    Nearest,
+    /// Blend neighbouring pixels, for smooth pictures rendered at a lower resolution.
+    Linear,
+}
+
+impl ScalingFilter {
+    fn sampler(self) -> ImageSampler {
+        match self {
+            ScalingFilter::Nearest => ImageSampler::nearest(),
+            ScalingFilter::Linear => ImageSampler::linear(),
+        }
+    }
+}
+
+/// Camera which keeps rendering to its window at full resolution, e.g. for crisp UI.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct NativeResolution;
+
+/// Textures app is rendered into, one per window.
+#[derive(Resource, Debug, Default)]
+pub struct VirtualCanvases {
+    canvases: HashMap<Entity, VirtualCanvas>,
+}
+
+impl VirtualCanvases {
+    /// Texture cameras rendering to the window are redirected to.
+    pub fn image(&self, window: Entity) -> Option<&Handle<Image>> {
+        self.canvases.get(&window).map(|canvas| &canvas.image)
+    }
+}
+
+#[derive(Debug)]
+struct VirtualCanvas {
+    image: Handle<Image>,
+    // Camera and sprite drawing the texture to the window.
+    upscale: [Entity; 2],
+}
+
+/// Window camera rendered to before being redirected.
+#[derive(Component)]
+pub(super) struct Redirected(pub(super) Entity);
+
+/// Camera drawing a virtual canvas to its window.
+#[derive(Component)]
+pub(super) struct Upscale;
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn redirect_cameras(
+    mut commands: Commands,
+    resolution: Option<Res<VirtualResolution>>,
+    mut canvases: ResMut<VirtualCanvases>,
+    mut images: ResMut<Assets<Image>>,
+    primary: Query<Entity, With<PrimaryWindow>>,
+    windows: Query<(), With<Window>>,
+    mut cameras: Query<
+        (Entity, &mut Camera, Option<&Redirected>),
+        (Without<Upscale>, Without<NativeResolution>),
+    >,
+    ui: Query<(), With<Node>>,
+    native: Query<(), With<NativeResolution>>,
+    mut warned: Local<bool>,
+) {
+    let Some(resolution) = resolution else {
+        for (entity, mut camera, redirected) in &mut cameras {
+            if let Some(&Redirected(window)) = redirected {
+                camera.target = RenderTarget::Window(WindowRef::Entity(window));
+                commands.entity(entity).remove::<Redirected>();
+            }
+        }
+        for (_, canvas) in canvases.canvases.drain() {
+            for entity in canvas.upscale {
+                commands.entity(entity).despawn();
+            }
+        }
+        return;
+    };
+
+    for (entity, mut camera, _) in &mut cameras {
+        let window = match &camera.target {
+            RenderTarget::Window(WindowRef::Primary) => primary.get_single().ok(),
+            RenderTarget::Window(WindowRef::Entity(window)) => Some(*window),
+            _ => None,
+        };
+        let Some(window) = window.filter(|window| windows.contains(*window)) else {
+            continue;
+        };
+
+        let canvas = canvases
+            .canvases
+            .entry(window)
+            .or_insert_with(|| spawn_canvas(&mut commands, &mut images, &resolution, window));
+
+        camera.target = RenderTarget::Image(canvas.image.clone());
+        commands.entity(entity).insert(Redirected(window));
+    }
+
+    if !*warned && !canvases.canvases.is_empty() && !ui.is_empty() && native.is_empty() {
+        warn!("UI rendered at virtual resolution doesn't react to the pointer, render it with a `NativeResolution` camera");
+        *warned = true;
+    }
+
+    // Windows close together with their views.
+    canvases.canvases.retain(|window, canvas| {
+        let open = windows.contains(*window);
+        if !open {
+            for entity in canvas.upscale {
+                commands.entity(entity).despawn();
+            }
+        }
+        open
+    });
+}
+
+fn spawn_canvas(
+    commands: &mut Commands,
+    images: &mut Assets<Image>,
+    resolution: &VirtualResolution,
+    window: Entity,
+) -> VirtualCanvas {
+    let size = resolution.size();
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::bevy_default(),
+    );
+    image.texture_descriptor.usage |= TextureUsages::RENDER_ATTACHMENT;
+    image.sampler_descriptor = resolution.filter.sampler();
+    let image = images.add(image);
+
+    let camera = commands
+        .spawn((
+            Camera2dBundle {
+                camera: Camera {
+                    target: RenderTarget::Window(WindowRef::Entity(window)),
+                    order: UPSCALE_ORDER,
+                    ..default()
+                },
+                camera_2d: Camera2d {
+                    clear_color: ClearColorConfig::Custom(Color::BLACK),
+                },
+                projection: OrthographicProjection {
+                    scaling_mode: resolution.scaling_mode(),
+                    ..default()
+                },
+                ..default()
+            },
+            UiCameraConfig { show_ui: false },
+            RenderLayers::layer(UPSCALE_LAYER),
+            Upscale,
+        ))
+        .id();
+    let sprite = commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(size.as_vec2()),
+                    ..default()
+                },
+                texture: image.clone(),
+                ..default()
+            },
+            RenderLayers::layer(UPSCALE_LAYER),
+        ))
+        .id();
+
+    VirtualCanvas {
+        image,
+        upscale: [camera, sprite],
+    }
+}
+
+fn update_canvases(
+    resolution: Option<Res<VirtualResolution>>,
+    canvases: Res<VirtualCanvases>,
+    mut images: ResMut<Assets<Image>>,
+    mut cameras: Query<&mut OrthographicProjection, With<Upscale>>,
+    mut sprites: Query<&mut Sprite>,
+) {
+    // Modifying the image uploads it anew, so only touch things once resolution changes.
+    let Some(resolution) = resolution.filter(|resolution| resolution.is_changed()) else {
+        return;
+    };
+    let size = resolution.size();
+
+    for canvas in canvases.canvases.values() {
+        let [camera, sprite] = canvas.upscale;
+
+        if let Ok(mut projection) = cameras.get_mut(camera) {
+            projection.scaling_mode = resolution.scaling_mode();
+        }
+        if let Ok(mut sprite) = sprites.get_mut(sprite) {
+            sprite.custom_size = Some(size.as_vec2());
+        }
+
+        let Some(image) = images.get_mut(&canvas.image) else {
+            continue;
+        };
+        let extent = image.texture_descriptor.size;
+        if (extent.width, extent.height) != (size.x, size.y) {
+            image.resize(Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            });
+        }
+        image.sampler_descriptor = resolution.filter.sampler();
+    }
+}
@@ -0,0 +1,204 @@
+//! WebRTC data channels established by the page.
+//!
+//! `RTCPeerConnection` doesn't exist in workers, so signaling and connection setup stay on the page.
+//! Once a data channel is open, page hands over a `MessagePort` relaying its messages,
+//! which worker reads directly, without going through the bridge.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use js_sys::{Array, ArrayBuffer, Uint8Array};
+use wasm_bindgen::prelude::{Closure, JsCast};
+use web_sys::{MessageEvent, MessagePort};
+
+use super::{
+    current_app, take_messages, wake_app, AppId, BridgeReceive, BridgeSchedules, BridgeSend,
+};
+use crate::protocol::HostMessage;
+
+thread_local! {
+    static CHANNELS: RefCell<HashMap<(AppId, String), Channel>> = RefCell::new(HashMap::new());
+    static INCOMING: RefCell<Vec<(AppId, String, DataChannelMessage)>> = RefCell::new(Vec::new());
+}
+
+/// Surface data channels page attaches as events.
+#[derive(Default)]
+pub struct WebRtcPlugin;
+
+impl Plugin for WebRtcPlugin {
+    fn build(&self, app: &mut App) {
+        let schedules = BridgeSchedules::of(app);
+
+        app.init_resource::<DataChannels>()
+            .add_event::<DataChannelOpened>()
+            .add_event::<DataChannelReceived>()
+            .add_event::<DataChannelClosed>()
+            .add_systems(schedules.receive, receive.in_set(BridgeReceive))
+            .add_systems(schedules.send, flush.in_set(BridgeSend));
+    }
+}
+
+/// Message going through a data channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataChannelMessage {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+/// Page attached an open data channel.
+#[derive(Event, Debug, Clone)]
+pub struct DataChannelOpened {
+    pub label: String,
+}
+
+/// Message received over a data channel.
+#[derive(Event, Debug, Clone)]
+pub struct DataChannelReceived {
+    pub label: String,
+    pub message: DataChannelMessage,
+}
+
+/// Data channel was closed, it is gone from [`DataChannels`].
+#[derive(Event, Debug, Clone)]
+pub struct DataChannelClosed {
+    pub label: String,
+}
+
+/// Access to attached data channels, identified by their labels.
+#[derive(Resource, Debug, Default)]
+pub struct DataChannels {
+    open: HashSet<String>,
+    outgoing: Vec<(String, DataChannelMessage)>,
+}
+
+impl DataChannels {
+    pub fn is_open(&self, label: &str) -> bool {
+        self.open.contains(label)
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = &str> + '_ {
+        self.open.iter().map(String::as_str)
+    }
+
+    /// Queue message to be sent at the end of the frame.
+    ///
+    /// Messages for channels which aren't open are dropped.
+    pub fn send(&mut self, label: &str, message: DataChannelMessage) {
+        self.outgoing.push((label.to_owned(), message));
+    }
+}
+
+struct Channel {
+    port: MessagePort,
+    _onmessage: Closure<dyn FnMut(MessageEvent)>,
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        self.port.set_onmessage(None);
+        self.port.close();
+    }
+}
+
+fn receive(
+    mut channels: ResMut<DataChannels>,
+    mut opened: EventWriter<DataChannelOpened>,
+    mut received: EventWriter<DataChannelReceived>,
+    mut closed: EventWriter<DataChannelClosed>,
+) {
+    let app = current_app();
+
+    take_messages(|msg| match msg {
+        HostMessage::DataChannel { label, port } => {
+            let port = port.into_inner();
+            let onmessage = {
+                let label = label.clone();
+
+                Closure::wrap(Box::new(move |event: MessageEvent| {
+                    let data = event.data();
+
+                    let message = if let Some(text) = data.as_string() {
+                        DataChannelMessage::Text(text)
+                    } else if let Some(buffer) = data.dyn_ref::<ArrayBuffer>() {
+                        DataChannelMessage::Binary(Uint8Array::new(buffer).to_vec())
+                    } else {
+                        return;
+                    };
+
+                    INCOMING
+                        .with(|incoming| incoming.borrow_mut().push((app, label.clone(), message)));
+                    wake_app(app);
+                }) as Box<dyn FnMut(MessageEvent)>)
+            };
+            port.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+
+            let channel = Channel {
+                port,
+                _onmessage: onmessage,
+            };
+            // Channel reopened under the same label replaces the old one.
+            let previous = CHANNELS
+                .with(|channels| channels.borrow_mut().insert((app, label.clone()), channel));
+            drop(previous);
+
+            channels.open.insert(label.clone());
+            opened.send(DataChannelOpened { label });
+            Ok(())
+        }
+        HostMessage::DataChannelClosed { label } => {
+            let channel =
+                CHANNELS.with(|channels| channels.borrow_mut().remove(&(app, label.clone())));
+            drop(channel);
+
+            if channels.open.remove(&label) {
+                closed.send(DataChannelClosed { label });
+            }
+            Ok(())
+        }
+        msg => Err(msg),
+    });
+
+    let incoming = INCOMING.with(|incoming| {
+        let mut incoming = incoming.borrow_mut();
+        let (ours, rest) = incoming.drain(..).partition(|(id, ..)| *id == app);
+        *incoming = rest;
+        ours
+    });
+
+    received.send_batch(incoming.into_iter().map(
+        |(_, label, message): (AppId, String, DataChannelMessage)| DataChannelReceived {
+            label,
+            message,
+        },
+    ));
+}
+
+fn flush(mut channels: ResMut<DataChannels>) {
+    let app = current_app();
+
+    for (label, message) in std::mem::take(&mut channels.outgoing) {
+        CHANNELS.with(|channels| {
+            let channels = channels.borrow();
+            let Some(channel) = channels.get(&(app, label.clone())) else {
+                warn!("data channel `{label}` is not open, message is dropped");
+                return;
+            };
+
+            let result = match message {
+                DataChannelMessage::Text(text) => channel.port.post_message(&text.into()),
+                DataChannelMessage::Binary(bytes) => {
+                    let buffer = Uint8Array::from(&bytes[..]).buffer();
+                    channel
+                        .port
+                        .post_message_with_transferable(&buffer, &Array::of1(&buffer))
+                }
+            };
+
+            if let Err(err) = result {
+                warn!("failed to send message over data channel `{label}`: {err:?}");
+            }
+        });
+    }
+}
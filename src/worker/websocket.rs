@@ -0,0 +1,334 @@
+//! WebSocket connection opened by the worker itself.
+//!
+//! Dedicated workers have `WebSocket` of their own, so networking doesn't need to go through the page.
+//! Socket callbacks queue what they see, app picks it up as events next frame,
+//! while messages app sends are queued in [`WebSocket`] resource and go out at the end of the frame.
+//! Lost connection is reestablished with exponential backoff.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+
+use bevy::prelude::*;
+use js_sys::{ArrayBuffer, Uint8Array};
+use wasm_bindgen::prelude::{Closure, JsCast, JsValue};
+use web_sys::{BinaryType, MessageEvent};
+
+use super::{current_app, scope, wake_app, AppId, BridgeReceive, BridgeSchedules, BridgeSend};
+
+thread_local! {
+    static CONNECTIONS: RefCell<HashMap<AppId, Connection>> = RefCell::new(HashMap::new());
+}
+
+/// Keep a WebSocket connection to the server open.
+pub struct WebSocketPlugin {
+    /// Server to connect to, `ws://` or `wss://`.
+    pub url: String,
+    /// Delay before the first reconnection attempt, doubled with every failed one.
+    pub initial_backoff_ms: f64,
+    /// Longest delay between reconnection attempts.
+    pub max_backoff_ms: f64,
+}
+
+impl WebSocketPlugin {
+    pub fn new(url: &str) -> Self {
+        WebSocketPlugin {
+            url: url.to_owned(),
+            initial_backoff_ms: 500.0,
+            max_backoff_ms: 30_000.0,
+        }
+    }
+}
+
+impl Plugin for WebSocketPlugin {
+    fn build(&self, app: &mut App) {
+        let id = current_app();
+        let connection = Connection {
+            url: self.url.clone(),
+            initial_backoff_ms: self.initial_backoff_ms,
+            max_backoff_ms: self.max_backoff_ms,
+            socket: None,
+            failed_attempts: 0,
+            retry: None,
+            incoming: Vec::new(),
+            _callbacks: Vec::new(),
+        };
+
+        // Connection of the app built before, e.g. one which lost its graphics context, goes away.
+        let previous =
+            CONNECTIONS.with(|connections| connections.borrow_mut().insert(id, connection));
+        if let Some(previous) = previous {
+            if let Some(timer) = previous.retry {
+                scope().clear_timeout_with_handle(timer);
+            }
+            if let Some(socket) = &previous.socket {
+                // Callbacks are dropped with the connection, they must not be invoked afterwards.
+                socket.set_onopen(None);
+                socket.set_onmessage(None);
+                socket.set_onclose(None);
+                if is_alive(socket) {
+                    let _ = socket.close();
+                }
+            }
+        }
+
+        connect(id);
+
+        let schedules = BridgeSchedules::of(app);
+
+        app.insert_resource(WebSocket {
+            state: ConnectionState::Connecting,
+            outgoing: VecDeque::new(),
+        })
+        .add_event::<WebSocketReceived>()
+        .add_event::<WebSocketStateChanged>()
+        .add_systems(schedules.receive, receive.in_set(BridgeReceive))
+        .add_systems(schedules.send, flush.in_set(BridgeSend));
+    }
+}
+
+/// State of the connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Connection is being established, possibly after losing the previous one.
+    Connecting,
+    Open,
+    /// Connection is lost, next attempt is made after a delay.
+    Closed,
+}
+
+/// Message going through the socket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebSocketMessage {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+/// Message received from the server.
+#[derive(Event, Debug, Clone)]
+pub struct WebSocketReceived {
+    pub message: WebSocketMessage,
+}
+
+/// Connection was established or lost.
+#[derive(Event, Debug, Clone)]
+pub struct WebSocketStateChanged {
+    pub state: ConnectionState,
+}
+
+/// Access to the connection.
+#[derive(Resource, Debug)]
+pub struct WebSocket {
+    state: ConnectionState,
+    outgoing: VecDeque<WebSocketMessage>,
+}
+
+impl WebSocket {
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    /// Queue message to be sent at the end of the frame.
+    ///
+    /// Messages sent while connection is down wait until it is back.
+    pub fn send(&mut self, message: WebSocketMessage) {
+        self.outgoing.push_back(message);
+    }
+
+    pub fn send_text(&mut self, text: &str) {
+        self.send(WebSocketMessage::Text(text.to_owned()));
+    }
+
+    pub fn send_binary(&mut self, bytes: Vec<u8>) {
+        self.send(WebSocketMessage::Binary(bytes));
+    }
+}
+
+struct Connection {
+    url: String,
+    initial_backoff_ms: f64,
+    max_backoff_ms: f64,
+    socket: Option<web_sys::WebSocket>,
+    failed_attempts: u32,
+    // Timer of the pending reconnection attempt.
+    retry: Option<i32>,
+    incoming: Vec<Incoming>,
+    // Dropped together with the socket they listen to.
+    _callbacks: Vec<Closure<dyn FnMut(JsValue)>>,
+}
+
+enum Incoming {
+    State(ConnectionState),
+    Message(WebSocketMessage),
+}
+
+fn connect(app: AppId) {
+    let url = with_connection(app, |connection| {
+        if let Some(timer) = connection.retry.take() {
+            scope().clear_timeout_with_handle(timer);
+        }
+        // Socket which is still open or opening is kept rather than replaced.
+        let alive = connection.socket.as_ref().map_or(false, is_alive);
+        (!alive).then(|| connection.url.clone())
+    });
+    let Some(url) = url.flatten() else {
+        return;
+    };
+
+    let socket = match web_sys::WebSocket::new(&url) {
+        Ok(socket) => socket,
+        Err(err) => {
+            warn!("failed to open websocket to {url}: {err:?}");
+            closed(app);
+            return;
+        }
+    };
+    socket.set_binary_type(BinaryType::Arraybuffer);
+
+    let onopen = Closure::wrap(Box::new(move |_: JsValue| {
+        with_connection(app, |connection| {
+            connection.failed_attempts = 0;
+            connection
+                .incoming
+                .push(Incoming::State(ConnectionState::Open));
+        });
+        wake_app(app);
+    }) as Box<dyn FnMut(JsValue)>);
+
+    let onmessage = Closure::wrap(Box::new(move |event: JsValue| {
+        let data = event.unchecked_into::<MessageEvent>().data();
+
+        let message = if let Some(text) = data.as_string() {
+            WebSocketMessage::Text(text)
+        } else if let Some(buffer) = data.dyn_ref::<ArrayBuffer>() {
+            WebSocketMessage::Binary(Uint8Array::new(buffer).to_vec())
+        } else {
+            return;
+        };
+
+        with_connection(app, |connection| {
+            connection.incoming.push(Incoming::Message(message));
+        });
+        wake_app(app);
+    }) as Box<dyn FnMut(JsValue)>);
+
+    // Errors are always followed by `close`, which is where reconnection happens.
+    let closing = socket.clone();
+    let onclose = Closure::wrap(Box::new(move |_: JsValue| {
+        // Socket which was replaced in the meantime has nothing to reconnect.
+        let current = with_connection(app, |connection| {
+            connection.socket.as_ref().map(AsRef::<JsValue>::as_ref) == Some(closing.as_ref())
+        });
+        if current == Some(true) {
+            closed(app);
+        }
+    }) as Box<dyn FnMut(JsValue)>);
+
+    socket.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+    socket.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+    socket.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+
+    with_connection(app, |connection| {
+        connection.socket = Some(socket);
+        connection._callbacks = vec![onopen, onmessage, onclose];
+    });
+}
+
+/// Report lost connection and try again after a while.
+fn closed(app: AppId) {
+    let delay_ms = with_connection(app, |connection| {
+        let delay_ms = connection.initial_backoff_ms * 2f64.powi(connection.failed_attempts as i32);
+        connection.failed_attempts = connection.failed_attempts.saturating_add(1);
+        connection
+            .incoming
+            .push(Incoming::State(ConnectionState::Closed));
+
+        delay_ms.min(connection.max_backoff_ms)
+    });
+    let Some(delay_ms) = delay_ms else {
+        return;
+    };
+    wake_app(app);
+
+    let retry = Closure::once_into_js(move || {
+        with_connection(app, |connection| {
+            connection.retry = None;
+            connection
+                .incoming
+                .push(Incoming::State(ConnectionState::Connecting));
+        });
+        connect(app);
+    });
+
+    match scope().set_timeout_with_callback_and_timeout_and_arguments_0(
+        retry.unchecked_ref(),
+        delay_ms as i32,
+    ) {
+        Ok(timer) => {
+            with_connection(app, |connection| {
+                // Only one attempt is ever pending.
+                if let Some(previous) = connection.retry.replace(timer) {
+                    scope().clear_timeout_with_handle(previous);
+                }
+            });
+        }
+        Err(err) => warn!("failed to schedule websocket reconnection: {err:?}"),
+    }
+}
+
+/// Whether socket is open or still opening.
+fn is_alive(socket: &web_sys::WebSocket) -> bool {
+    matches!(
+        socket.ready_state(),
+        web_sys::WebSocket::CONNECTING | web_sys::WebSocket::OPEN
+    )
+}
+
+fn with_connection<T>(app: AppId, f: impl FnOnce(&mut Connection) -> T) -> Option<T> {
+    CONNECTIONS.with(|connections| connections.borrow_mut().get_mut(&app).map(f))
+}
+
+fn receive(
+    mut socket: ResMut<WebSocket>,
+    mut received: EventWriter<WebSocketReceived>,
+    mut changed: EventWriter<WebSocketStateChanged>,
+) {
+    let incoming = with_connection(current_app(), |connection| {
+        std::mem::take(&mut connection.incoming)
+    });
+
+    for incoming in incoming.into_iter().flatten() {
+        match incoming {
+            Incoming::State(state) => {
+                socket.state = state;
+                changed.send(WebSocketStateChanged { state });
+            }
+            Incoming::Message(message) => received.send(WebSocketReceived { message }),
+        }
+    }
+}
+
+fn flush(mut socket: ResMut<WebSocket>) {
+    if socket.state != ConnectionState::Open || socket.outgoing.is_empty() {
+        return;
+    }
+
+    with_connection(current_app(), |connection| {
+        let Some(ws) = &connection.socket else {
+            return;
+        };
+
+        while let Some(message) = socket.outgoing.pop_front() {
+            let result = match &message {
+                WebSocketMessage::Text(text) => ws.send_with_str(text),
+                WebSocketMessage::Binary(bytes) => ws.send_with_u8_array(bytes),
+            };
+
+            // Socket closed under our feet, keep the message for the next connection.
+            if let Err(err) = result {
+                warn!("failed to send websocket message: {err:?}");
+                socket.outgoing.push_front(message);
+                break;
+            }
+        }
+    });
+}
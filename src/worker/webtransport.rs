@@ -0,0 +1,387 @@
+//! Experimental WebTransport connection opened by the worker.
+//!
+//! Unlike WebSocket, WebTransport has unreliable datagrams and independent streams,
+//! so a lost packet doesn't hold back everything sent after it.
+//! The API is only exposed to workers on Chromium so far and `web-sys` has no stable bindings for it,
+//! everything goes through reflection.
+//!
+//! Datagrams and incoming unidirectional streams arrive as events, streams are read to the end first.
+//! Outgoing data is queued in [`WebTransport`] resource and goes out at the end of the frame.
+//! There is no reconnection: once closed, connection stays closed.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+
+use bevy::prelude::*;
+use js_sys::{Array, Function, Promise, Reflect, Uint8Array};
+use wasm_bindgen::prelude::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use super::{
+    call, call_async, current_app, wake_app, AppId, BridgeReceive, BridgeSchedules, BridgeSend,
+};
+
+thread_local! {
+    static CONNECTIONS: RefCell<HashMap<AppId, Connection>> = RefCell::new(HashMap::new());
+}
+
+/// Open WebTransport session to the server.
+pub struct WebTransportPlugin {
+    /// Server to connect to, must be `https://`.
+    pub url: String,
+}
+
+impl WebTransportPlugin {
+    pub fn new(url: &str) -> Self {
+        WebTransportPlugin {
+            url: url.to_owned(),
+        }
+    }
+}
+
+impl Plugin for WebTransportPlugin {
+    fn build(&self, app: &mut App) {
+        let id = current_app();
+
+        // Session of the app built before, e.g. one which lost its graphics context, goes away,
+        // tasks still reading it notice they are stale by the session number.
+        let session = CONNECTIONS.with(|connections| {
+            let mut connections = connections.borrow_mut();
+            let session = connections
+                .get(&id)
+                .map_or(0, |previous| previous.session.wrapping_add(1));
+            let previous = connections.insert(
+                id,
+                Connection {
+                    session,
+                    ..default()
+                },
+            );
+            if let Some(transport) = previous.and_then(|previous| previous.transport) {
+                let _ = call(&transport, "close", &[]);
+            }
+            session
+        });
+
+        wasm_bindgen_futures::spawn_local(run(id, session, self.url.clone()));
+
+        let schedules = BridgeSchedules::of(app);
+
+        app.insert_resource(WebTransport {
+            state: TransportState::Connecting,
+            datagrams: VecDeque::new(),
+            streams: VecDeque::new(),
+        })
+        .add_event::<DatagramReceived>()
+        .add_event::<StreamReceived>()
+        .add_event::<TransportStateChanged>()
+        .add_systems(schedules.receive, receive.in_set(BridgeReceive))
+        .add_systems(schedules.send, flush.in_set(BridgeSend));
+    }
+}
+
+/// State of the session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransportState {
+    Connecting,
+    Open,
+    /// Session is over, with reason reported by the browser.
+    Closed(String),
+}
+
+/// Datagram received from the server.
+#[derive(Event, Debug, Clone)]
+pub struct DatagramReceived {
+    pub bytes: Vec<u8>,
+}
+
+/// Unidirectional stream opened by the server, read to its end.
+#[derive(Event, Debug, Clone)]
+pub struct StreamReceived {
+    pub bytes: Vec<u8>,
+}
+
+/// Session was established or closed.
+#[derive(Event, Debug, Clone)]
+pub struct TransportStateChanged {
+    pub state: TransportState,
+}
+
+/// Access to the session.
+#[derive(Resource, Debug)]
+pub struct WebTransport {
+    state: TransportState,
+    datagrams: VecDeque<Vec<u8>>,
+    streams: VecDeque<Vec<u8>>,
+}
+
+impl WebTransport {
+    pub fn state(&self) -> &TransportState {
+        &self.state
+    }
+
+    /// Queue datagram to be sent at the end of the frame.
+    ///
+    /// Datagrams may be lost or arrive out of order, and so may be the ones sent before session is open.
+    pub fn send_datagram(&mut self, bytes: Vec<u8>) {
+        self.datagrams.push_back(bytes);
+    }
+
+    /// Send bytes over a fresh unidirectional stream, which is closed afterwards.
+    ///
+    /// Unlike datagrams, streams are reliable.
+    pub fn send_stream(&mut self, bytes: Vec<u8>) {
+        self.streams.push_back(bytes);
+    }
+}
+
+#[derive(Default)]
+struct Connection {
+    // Bumped every time plugin opens a new session for the app.
+    session: u32,
+    transport: Option<JsValue>,
+    datagram_writer: Option<JsValue>,
+    incoming: Vec<Incoming>,
+}
+
+enum Incoming {
+    State(TransportState),
+    Datagram(Vec<u8>),
+    Stream(Vec<u8>),
+}
+
+/// Pass what session saw to the app, `false` if session was replaced since.
+fn push(app: AppId, session: u32, incoming: Incoming) -> bool {
+    let current = with_session(app, session, |connection| {
+        connection.incoming.push(incoming);
+    });
+    if current.is_some() {
+        wake_app(app);
+    }
+    current.is_some()
+}
+
+fn with_session<T>(app: AppId, session: u32, f: impl FnOnce(&mut Connection) -> T) -> Option<T> {
+    CONNECTIONS.with(|connections| {
+        connections
+            .borrow_mut()
+            .get_mut(&app)
+            .filter(|connection| connection.session == session)
+            .map(f)
+    })
+}
+
+async fn run(app: AppId, session: u32, url: String) {
+    let transport = match open(&url).await {
+        Ok(transport) => transport,
+        Err(err) => {
+            warn!("failed to open webtransport session to {url}: {err:?}");
+            push(
+                app,
+                session,
+                Incoming::State(TransportState::Closed(describe(&err))),
+            );
+            return;
+        }
+    };
+
+    let writer = Reflect::get(&transport, &"datagrams".into())
+        .and_then(|datagrams| Reflect::get(&datagrams, &"writable".into()))
+        .and_then(|writable| call(&writable, "getWriter", &[]));
+
+    let current = with_session(app, session, |connection| {
+        connection.transport = Some(transport.clone());
+        connection.datagram_writer = writer.ok();
+    });
+    // App was rebuilt while session was opening, the new one has its own.
+    if current.is_none() {
+        let _ = call(&transport, "close", &[]);
+        return;
+    }
+    push(app, session, Incoming::State(TransportState::Open));
+
+    wasm_bindgen_futures::spawn_local(read_datagrams(app, session, transport.clone()));
+    wasm_bindgen_futures::spawn_local(read_streams(app, session, transport.clone()));
+
+    let closed = Reflect::get(&transport, &"closed".into()).and_then(JsCast::dyn_into::<Promise>);
+    let reason = match closed {
+        Ok(closed) => match JsFuture::from(closed).await {
+            Ok(info) => Reflect::get(&info, &"reason".into())
+                .ok()
+                .and_then(|reason| reason.as_string())
+                .unwrap_or_default(),
+            Err(err) => describe(&err),
+        },
+        Err(err) => describe(&err),
+    };
+
+    push(
+        app,
+        session,
+        Incoming::State(TransportState::Closed(reason)),
+    );
+}
+
+async fn open(url: &str) -> Result<JsValue, JsValue> {
+    let constructor: Function = Reflect::get(&js_sys::global(), &"WebTransport".into())?
+        .dyn_into()
+        .map_err(|_| JsValue::from_str("WebTransport is not supported"))?;
+    let transport = Reflect::construct(&constructor, &Array::of1(&url.into()))?;
+
+    let ready: Promise = Reflect::get(&transport, &"ready".into())?.dyn_into()?;
+    JsFuture::from(ready).await?;
+
+    Ok(transport)
+}
+
+async fn read_datagrams(app: AppId, session: u32, transport: JsValue) {
+    let result = async {
+        let datagrams = Reflect::get(&transport, &"datagrams".into())?;
+        let readable = Reflect::get(&datagrams, &"readable".into())?;
+        let reader = call(&readable, "getReader", &[])?;
+
+        while let Some(chunk) = read(&reader).await? {
+            if !push(app, session, Incoming::Datagram(chunk)) {
+                break;
+            }
+        }
+
+        Ok::<_, JsValue>(())
+    };
+
+    // Reading fails once session is closed, which is reported separately.
+    let _ = result.await;
+}
+
+async fn read_streams(app: AppId, session: u32, transport: JsValue) {
+    let result = async {
+        let streams = Reflect::get(&transport, &"incomingUnidirectionalStreams".into())?;
+        let reader = call(&streams, "getReader", &[])?;
+
+        while let Some(stream) = read_value(&reader).await? {
+            if with_session(app, session, |_| ()).is_none() {
+                break;
+            }
+            wasm_bindgen_futures::spawn_local(async move {
+                match read_to_end(&stream).await {
+                    Ok(bytes) => {
+                        push(app, session, Incoming::Stream(bytes));
+                    }
+                    Err(err) => warn!("failed to read webtransport stream: {err:?}"),
+                }
+            });
+        }
+
+        Ok::<_, JsValue>(())
+    };
+
+    let _ = result.await;
+}
+
+async fn read_to_end(stream: &JsValue) -> Result<Vec<u8>, JsValue> {
+    let reader = call(stream, "getReader", &[])?;
+    let mut bytes = Vec::new();
+
+    while let Some(chunk) = read(&reader).await? {
+        bytes.extend(chunk);
+    }
+
+    Ok(bytes)
+}
+
+/// Next chunk of bytes from stream reader, `None` once stream is done.
+async fn read(reader: &JsValue) -> Result<Option<Vec<u8>>, JsValue> {
+    let value = read_value(reader).await?;
+    Ok(value.map(|value| value.unchecked_into::<Uint8Array>().to_vec()))
+}
+
+async fn read_value(reader: &JsValue) -> Result<Option<JsValue>, JsValue> {
+    let result = call_async(reader, "read", &[]).await?;
+
+    if Reflect::get(&result, &"done".into())?.is_truthy() {
+        return Ok(None);
+    }
+
+    Reflect::get(&result, &"value".into()).map(Some)
+}
+
+async fn send_stream(transport: JsValue, bytes: Vec<u8>) -> Result<(), JsValue> {
+    let stream = call_async(&transport, "createUnidirectionalStream", &[]).await?;
+    let writer = call(&stream, "getWriter", &[])?;
+
+    call_async(&writer, "write", &[Uint8Array::from(&bytes[..]).into()]).await?;
+    call_async(&writer, "close", &[]).await.map(drop)
+}
+
+fn receive(
+    mut transport: ResMut<WebTransport>,
+    mut datagrams: EventWriter<DatagramReceived>,
+    mut streams: EventWriter<StreamReceived>,
+    mut changed: EventWriter<TransportStateChanged>,
+) {
+    let incoming = CONNECTIONS.with(|connections| {
+        connections
+            .borrow_mut()
+            .get_mut(&current_app())
+            .map(|connection| std::mem::take(&mut connection.incoming))
+            .unwrap_or_default()
+    });
+
+    for incoming in incoming {
+        match incoming {
+            Incoming::State(state) => {
+                transport.state = state.clone();
+                changed.send(TransportStateChanged { state });
+            }
+            Incoming::Datagram(bytes) => datagrams.send(DatagramReceived { bytes }),
+            Incoming::Stream(bytes) => streams.send(StreamReceived { bytes }),
+        }
+    }
+}
+
+fn flush(mut transport: ResMut<WebTransport>) {
+    if transport.state != TransportState::Open {
+        return;
+    }
+
+    let (session, writer) = CONNECTIONS.with(|connections| {
+        connections
+            .borrow()
+            .get(&current_app())
+            .map(|connection| {
+                (
+                    connection.transport.clone(),
+                    connection.datagram_writer.clone(),
+                )
+            })
+            .unwrap_or_default()
+    });
+
+    if let Some(writer) = writer {
+        for bytes in transport.datagrams.drain(..) {
+            // Writes resolve once datagram is queued, there is nothing to wait for.
+            if let Err(err) = call(&writer, "write", &[Uint8Array::from(&bytes[..]).into()]) {
+                warn!("failed to send webtransport datagram: {err:?}");
+            }
+        }
+    }
+
+    if let Some(session) = session {
+        for bytes in transport.streams.drain(..) {
+            let session = session.clone();
+
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Err(err) = send_stream(session, bytes).await {
+                    warn!("failed to send webtransport stream: {err:?}");
+                }
+            });
+        }
+    }
+}
+
+fn describe(err: &JsValue) -> String {
+    Reflect::get(err, &"message".into())
+        .ok()
+        .and_then(|message| message.as_string())
+        .unwrap_or_else(|| format!("{err:?}"))
+}
@@ -0,0 +1,450 @@
+//! Build helpers, run with `cargo xtask <command>`.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+const USAGE: &str = "\
+Usage: cargo xtask bootstrap [options]
+
+Generate worker bootstrap script with artifact locations baked in,
+for sites that forbid creating scripts at runtime.
+Serve it next to worker artifacts and spawn worker with `WorkerBuilder::hosted_bootstrap`.
+
+Options:
+    --js <url>       URL of wasm-bindgen JS glue [default: ./bevy_worker.js]
+    --wasm <url>     URL of wasm module [default: ./bevy_worker_bg.wasm]
+    --init <name>    Function initializing wasm module [default: depends on flavor]
+    --module         Generate bootstrap for module worker
+    --out <path>     Where to write the script [default: dist/worker_bootstrap.js]
+
+Usage: cargo xtask smoke [options]
+
+Build the page with trunk, serve it and open it with `?smoke` in every browser,
+waiting for each to report whether worker booted and rendered a frame.
+Browser executables can be overridden with CHROME and FIREFOX environment variables.
+
+Options:
+    --browser <name>   chrome, firefox or safari, can be repeated [default: chrome and firefox]
+    --dist <path>      Where to build the page [default: dist-smoke]
+    --no-build         Serve what is already built
+    --timeout <secs>   How long each browser gets [default: 60]
+";
+
+/// Fetch of wasm module reporting download progress to the page, same one loader scripts use.
+const FETCH_WASM: &str = include_str!("../../loader/fetch_wasm.js");
+
+struct Bootstrap {
+    js: String,
+    wasm: String,
+    init: Option<String>,
+    module: bool,
+    out: PathBuf,
+}
+
+impl Bootstrap {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut bootstrap = Bootstrap {
+            js: "./bevy_worker.js".to_owned(),
+            wasm: "./bevy_worker_bg.wasm".to_owned(),
+            init: None,
+            module: false,
+            out: PathBuf::from("dist/worker_bootstrap.js"),
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+
+            match arg.as_str() {
+                "--js" => bootstrap.js = value()?,
+                "--wasm" => bootstrap.wasm = value()?,
+                "--init" => bootstrap.init = Some(value()?),
+                "--module" => bootstrap.module = true,
+                "--out" => bootstrap.out = value()?.into(),
+                _ => return Err(format!("unknown option {arg}")),
+            }
+        }
+
+        Ok(bootstrap)
+    }
+
+    /// Same script host module creates at runtime, minus the blob.
+    fn script(&self) -> String {
+        let js = quote(&self.js);
+        let wasm = quote(&self.wasm);
+
+        if self.module {
+            let init = quote(self.init.as_deref().unwrap_or("default"));
+
+            format!(
+                "{FETCH_WASM}self.bevyWorkerArtifacts={{js:{js},init:{init},module:true}};\
+                 import({js}).then(m=>m[{init}](bevyFetchWasm({wasm},msg=>postMessage(msg)))).catch(e=>postMessage({{kind:\"error\",message:String(e)}}));\n"
+            )
+        } else {
+            let init = self.init.as_deref().unwrap_or("wasm_bindgen");
+            let init_str = quote(init);
+
+            format!(
+                "{FETCH_WASM}self.bevyWorkerArtifacts={{js:{js},init:{init_str},module:false}};\
+                 importScripts({js});{init}(bevyFetchWasm({wasm},msg=>postMessage(msg))).catch(e=>postMessage({{kind:\"error\",message:String(e)}}));\n"
+            )
+        }
+    }
+
+    fn run(self) -> Result<(), String> {
+        use base64::Engine;
+        use sha2::{Digest, Sha384};
+
+        let script = self.script();
+
+        if let Some(dir) = self.out.parent() {
+            std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        std::fs::write(&self.out, &script).map_err(|err| err.to_string())?;
+
+        let hash = base64::engine::general_purpose::STANDARD.encode(Sha384::digest(&script));
+
+        println!("wrote {}", self.out.display());
+        println!("integrity: sha384-{hash}");
+
+        Ok(())
+    }
+}
+
+struct Smoke {
+    browsers: Vec<Browser>,
+    dist: PathBuf,
+    build: bool,
+    timeout: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Browser {
+    Chrome,
+    Firefox,
+    /// Not headless and can't be closed from here, only runs on macOS.
+    Safari,
+}
+
+impl Browser {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "chrome" => Some(Browser::Chrome),
+            "firefox" => Some(Browser::Firefox),
+            "safari" => Some(Browser::Safari),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Browser::Chrome => "chrome",
+            Browser::Firefox => "firefox",
+            Browser::Safari => "safari",
+        }
+    }
+
+    fn launch(self, url: &str) -> Result<Child, String> {
+        let env_or = |var: &str, default: &str| std::env::var(var).unwrap_or(default.to_owned());
+        let profile = std::env::temp_dir().join(format!("xtask-smoke-{}", self.name()));
+
+        let mut command = match self {
+            Browser::Chrome => {
+                let mut command = Command::new(env_or("CHROME", "google-chrome"));
+                command
+                    .arg("--headless=new")
+                    .arg("--no-first-run")
+                    .arg("--no-default-browser-check")
+                    .arg("--enable-unsafe-webgpu")
+                    .arg(format!("--user-data-dir={}", profile.display()));
+                command
+            }
+            Browser::Firefox => {
+                std::fs::create_dir_all(&profile).map_err(|err| err.to_string())?;
+
+                let mut command = Command::new(env_or("FIREFOX", "firefox"));
+                command
+                    .arg("--headless")
+                    .arg("--no-remote")
+                    .arg("--profile")
+                    .arg(&profile);
+                command
+            }
+            Browser::Safari => {
+                let mut command = Command::new("open");
+                command.arg("-a").arg("Safari");
+                command
+            }
+        };
+
+        command
+            .arg(url)
+            .spawn()
+            .map_err(|err| format!("failed to launch {}: {err}", self.name()))
+    }
+}
+
+impl Smoke {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut smoke = Smoke {
+            browsers: Vec::new(),
+            dist: PathBuf::from("dist-smoke"),
+            build: true,
+            timeout: Duration::from_secs(60),
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+
+            match arg.as_str() {
+                "--browser" => {
+                    let name = value()?;
+                    let browser =
+                        Browser::from_name(&name).ok_or(format!("unknown browser {name}"))?;
+                    smoke.browsers.push(browser);
+                }
+                "--dist" => smoke.dist = value()?.into(),
+                "--no-build" => smoke.build = false,
+                "--timeout" => {
+                    let secs = value()?;
+                    let secs = secs
+                        .parse()
+                        .map_err(|_| format!("invalid timeout {secs}"))?;
+                    smoke.timeout = Duration::from_secs(secs);
+                }
+                _ => return Err(format!("unknown option {arg}")),
+            }
+        }
+
+        if smoke.browsers.is_empty() {
+            smoke.browsers = vec![Browser::Chrome, Browser::Firefox];
+        }
+
+        Ok(smoke)
+    }
+
+    fn run(self) -> Result<(), String> {
+        if self.build {
+            let status = Command::new("trunk")
+                .args(["build", "--release", "--dist"])
+                .arg(&self.dist)
+                .status()
+                .map_err(|err| format!("failed to run trunk: {err}"))?;
+
+            if !status.success() {
+                return Err("trunk build failed".to_owned());
+            }
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").map_err(|err| err.to_string())?;
+        let port = listener.local_addr().map_err(|err| err.to_string())?.port();
+        let (reports, received) = mpsc::channel();
+
+        let dist = self.dist.clone();
+        std::thread::spawn(move || serve(listener, &dist, reports));
+
+        let url = format!("http://127.0.0.1:{port}/?smoke");
+        let mut failed = false;
+
+        for browser in self.browsers {
+            // Late report from the previous browser would be mistaken for this one's.
+            while received.try_recv().is_ok() {}
+
+            let outcome = run_browser(browser, &url, &received, self.timeout);
+            let status = match &outcome {
+                Ok(report) => json_field(report, "status").unwrap_or("malformed"),
+                Err(_) => "error",
+            };
+
+            // Safari without `OffscreenCanvas` is expected to take the fallback path.
+            let ok = status == "pass" || (status == "fallback" && browser == Browser::Safari);
+            failed |= !ok;
+
+            match &outcome {
+                Ok(report) => println!("{:<8} {status:<9} {report}", browser.name()),
+                Err(err) => println!("{:<8} {status:<9} {err}", browser.name()),
+            }
+        }
+
+        if failed {
+            Err("smoke test failed".to_owned())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn run_browser(
+    browser: Browser,
+    url: &str,
+    received: &Receiver<String>,
+    timeout: Duration,
+) -> Result<String, String> {
+    let mut child = browser.launch(url)?;
+    let report = received
+        .recv_timeout(timeout)
+        .map_err(|_| format!("no report within {}s", timeout.as_secs()));
+
+    let _ = child.kill();
+    let _ = child.wait();
+
+    report
+}
+
+/// Serve built page and forward reports it posts.
+fn serve(listener: TcpListener, dist: &Path, reports: Sender<String>) {
+    for stream in listener.incoming().flatten() {
+        if let Err(err) = handle(stream, dist, &reports) {
+            eprintln!("request failed: {err}");
+        }
+    }
+}
+
+fn handle(stream: TcpStream, dist: &Path, reports: &Sender<String>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_owned();
+    let target = parts.next().unwrap_or("/").to_owned();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    let path = target.split(['?', '#']).next().unwrap_or("/");
+
+    if method == "POST" && path == "/report" {
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+        let _ = reports.send(String::from_utf8_lossy(&body).into_owned());
+
+        return stream.write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n");
+    }
+
+    let relative = match path.trim_start_matches('/') {
+        "" => "index.html",
+        path => path,
+    };
+    if relative.split('/').any(|part| part == "..") {
+        return stream.write_all(b"HTTP/1.1 403 Forbidden\r\nConnection: close\r\n\r\n");
+    }
+
+    match std::fs::read(dist.join(relative)) {
+        Ok(body) => {
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                content_type(relative),
+                body.len(),
+            );
+            stream.write_all(header.as_bytes())?;
+            stream.write_all(&body)
+        }
+        Err(_) => stream.write_all(b"HTTP/1.1 404 Not Found\r\nConnection: close\r\n\r\n"),
+    }
+}
+
+fn content_type(path: &str) -> &'static str {
+    match path.rsplit('.').next() {
+        Some("html") => "text/html",
+        Some("js") => "text/javascript",
+        Some("wasm") => "application/wasm",
+        Some("css") => "text/css",
+        Some("json") => "application/json",
+        Some("png") => "image/png",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Value of top-level string field in a flat enough JSON document.
+fn json_field<'a>(json: &'a str, name: &str) -> Option<&'a str> {
+    let key = format!("\"{name}\":\"");
+    let start = json.find(&key)? + key.len();
+    let end = json[start..].find('"')?;
+    Some(&json[start..start + end])
+}
+
+/// Quote string as JS literal.
+fn quote(s: &str) -> String {
+    let mut r = String::with_capacity(s.len() + 2);
+    r.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => r.push_str("\\\""),
+            '\\' => r.push_str("\\\\"),
+            '\n' => r.push_str("\\n"),
+            '\r' => r.push_str("\\r"),
+            c if c.is_control() => r.push_str(&format!("\\u{:04x}", c as u32)),
+            c => r.push(c),
+        }
+    }
+    r.push('"');
+    r
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+
+    let result = match args.next().as_deref() {
+        Some("bootstrap") => Bootstrap::parse(args).and_then(Bootstrap::run),
+        Some("smoke") => Smoke::parse(args).and_then(Smoke::run),
+        _ => Err(USAGE.to_owned()),
+    };
+
+    if let Err(err) = result {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_plain() {
+        assert_eq!(quote("./bevy_worker.js"), r#""./bevy_worker.js""#);
+        assert_eq!(quote(""), r#""""#);
+    }
+
+    #[test]
+    fn quote_escapes() {
+        assert_eq!(quote(r#"a"b"#), r#""a\"b""#);
+        assert_eq!(quote(r"a\b"), r#""a\\b""#);
+        assert_eq!(quote("a\nb\rc"), r#""a\nb\rc""#);
+        assert_eq!(quote("a\tb\u{0}"), r#""a\u0009b\u0000""#);
+    }
+
+    #[test]
+    fn quote_keeps_unicode() {
+        assert_eq!(quote("ассеты/画像.png"), r#""ассеты/画像.png""#);
+    }
+
+    #[test]
+    fn script_embeds_quoted_artifacts() {
+        let bootstrap = Bootstrap::parse(
+            ["--js", "a\"b.js", "--wasm", "c.wasm", "--module"]
+                .into_iter()
+                .map(String::from),
+        )
+        .unwrap();
+        let script = bootstrap.script();
+
+        assert!(script.contains(r#"import("a\"b.js")"#));
+        assert!(script.contains(r#"m["default"](bevyFetchWasm("c.wasm",msg=>postMessage(msg)))"#));
+    }
+}